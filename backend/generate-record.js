@@ -3,16 +3,16 @@ const crypto = require("crypto");
 function makeRecord(size) {
   for (let i = 0; i < size; i += 1) {
     let type = "";
-    let amount = "";
+    let amount = 0;
     let wallet_name = "";
     let buyer_public_key = "";
     let buyer_view_key = "";
     let buyer_address = "";
     let escrow_public_key = "";
     let escrow_view_key = "";
-    let session_id = "";
-    let payment_transaction_id = "";
-    let settlement_transaction_id = "";
+    let session_id = null;
+    let payment_transaction_id = null;
+    let settlement_transaction_id = null;
 
     const statusId = Math.floor(Math.random() * 6);
     wallet_name = makeRandomString(64);
@@ -54,7 +54,7 @@ function makeRecord(size) {
         break;
     }
     console.log(
-      `INSERT INTO orders VALUES('${i}','${type}','${wallet_name}','${amount}','${buyer_public_key}','${buyer_view_key}','${buyer_address}','${escrow_public_key}','${escrow_view_key}','${session_id}','${payment_transaction_id}','${settlement_transaction_id}');`
+      `INSERT INTO orders VALUES('${i}','${type}','${wallet_name}',${amount},'${buyer_public_key}','${buyer_view_key}','${buyer_address}','${escrow_public_key}','${escrow_view_key}',${sqlText(session_id)},${sqlText(payment_transaction_id)},${sqlText(settlement_transaction_id)},'',2,'','',NULL,NULL,NULL,0,NULL,'','0','0',NULL,NULL,NULL,NULL,NULL,0,NULL,1,CURRENT_TIMESTAMP,CURRENT_TIMESTAMP);`
     );
   }
 }
@@ -64,7 +64,11 @@ function makeRandomString(length) {
 }
 
 function makeRandomAmount() {
-  return Math.floor(Math.random() * 10000).toString();
+  return Math.floor(Math.random() * 10000);
+}
+
+function sqlText(value) {
+  return value === null ? "NULL" : `'${value}'`;
 }
 
 makeRecord(50);
@@ -0,0 +1,8 @@
+CREATE TABLE order_events(
+  id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+  order_id TEXT NOT NULL REFERENCES orders(order_id),
+  event TEXT NOT NULL,
+  detail TEXT NOT NULL,
+  created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+CREATE INDEX order_events_order_id ON order_events(order_id);
@@ -0,0 +1 @@
+ALTER TABLE orders ADD COLUMN extra_view_keys TEXT NOT NULL DEFAULT '';
@@ -0,0 +1,20 @@
+DROP TABLE session_signers;
+DROP TABLE order_participants;
+CREATE TABLE orders_backup(
+  order_id TEXT PRIMARY KEY NOT NULL,
+  status TEXT NOT NULL,
+  wallet_name TEXT NOT NULL,
+  amount TEXT NOT NULL,
+  buyer_public_key TEXT NOT NULL,
+  buyer_view_key TEXT NOT NULL,
+  buyer_address TEXT NOT NULL,
+  escrow_public_key TEXT NOT NULL,
+  escrow_view_key TEXT NOT NULL,
+  session_id TEXT NOT NULL,
+  payment_transaction_id TEXT NOT NULL,
+  settlement_transaction_id TEXT NOT NULL,
+  extra_view_keys TEXT NOT NULL DEFAULT ''
+);
+INSERT INTO orders_backup SELECT order_id, status, wallet_name, amount, buyer_public_key, buyer_view_key, buyer_address, escrow_public_key, escrow_view_key, session_id, payment_transaction_id, settlement_transaction_id, extra_view_keys FROM orders;
+DROP TABLE orders;
+ALTER TABLE orders_backup RENAME TO orders;
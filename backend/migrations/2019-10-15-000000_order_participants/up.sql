@@ -0,0 +1,28 @@
+ALTER TABLE orders ADD COLUMN required_signatures INTEGER NOT NULL DEFAULT 2;
+CREATE TABLE order_participants(
+  id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+  order_id TEXT NOT NULL REFERENCES orders(order_id),
+  role TEXT NOT NULL,
+  public_key TEXT NOT NULL,
+  view_key TEXT NOT NULL
+);
+CREATE INDEX order_participants_order_id ON order_participants(order_id);
+-- Existing orders are 2-of-3 over the merchant, buyer and escrow keys, in that order
+INSERT INTO order_participants(order_id, role, public_key, view_key)
+  SELECT order_id, 'Buyer', buyer_public_key, buyer_view_key FROM orders;
+INSERT INTO order_participants(order_id, role, public_key, view_key)
+  SELECT order_id, 'Escrow', escrow_public_key, escrow_view_key FROM orders;
+CREATE TABLE session_signers(
+  id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+  session_id TEXT NOT NULL,
+  order_id TEXT NOT NULL REFERENCES orders(order_id),
+  role TEXT NOT NULL,
+  public_key TEXT NOT NULL,
+  commitment TEXT NOT NULL DEFAULT '',
+  nonce TEXT NOT NULL DEFAULT '',
+  partial_signature TEXT NOT NULL DEFAULT ''
+);
+CREATE INDEX session_signers_session_id ON session_signers(session_id);
+-- Signers of sessions opened before this migration are unknown, unsettled orders start a new session
+UPDATE orders SET session_id = '', settlement_transaction_id = ''
+  WHERE status IN ('Delivering', 'Refunding');
@@ -0,0 +1,2 @@
+-- Empty for orders created before this migration, run `cargo run -- migrate-wallets` to fill it in
+ALTER TABLE orders ADD COLUMN merchant_public_key TEXT NOT NULL DEFAULT '';
@@ -0,0 +1,2 @@
+-- Empty when the merchant share goes to the payout address of the order wallet
+ALTER TABLE orders ADD COLUMN payout_address TEXT NOT NULL DEFAULT '';
@@ -0,0 +1,2 @@
+-- Empty while refunds and deposit returns go to the buyer address given with the order
+ALTER TABLE orders ADD COLUMN refund_address TEXT NOT NULL DEFAULT '';
@@ -0,0 +1,15 @@
+-- Agreed split of a Splitting or Split order, empty otherwise
+ALTER TABLE orders ADD COLUMN split_merchant_amount TEXT NOT NULL DEFAULT '';
+ALTER TABLE orders ADD COLUMN split_buyer_amount TEXT NOT NULL DEFAULT '';
+CREATE TABLE split_proposals(
+  id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+  order_id TEXT NOT NULL REFERENCES orders(order_id),
+  proposed_by TEXT NOT NULL,
+  merchant_amount TEXT NOT NULL,
+  buyer_amount TEXT NOT NULL,
+  signature TEXT NOT NULL,
+  status TEXT NOT NULL,
+  acceptance_signature TEXT NOT NULL DEFAULT '',
+  created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+CREATE INDEX split_proposals_order_id ON split_proposals(order_id);
@@ -0,0 +1,12 @@
+-- Escrow decisions on disputed orders, the settlement of the order follows the latest ruling
+CREATE TABLE rulings(
+  id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+  order_id TEXT NOT NULL REFERENCES orders(order_id),
+  escrow_public_key TEXT NOT NULL,
+  decision TEXT NOT NULL,
+  merchant_amount TEXT NOT NULL,
+  buyer_amount TEXT NOT NULL,
+  signature TEXT NOT NULL,
+  created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+CREATE INDEX rulings_order_id ON rulings(order_id);
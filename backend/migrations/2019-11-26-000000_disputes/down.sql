@@ -0,0 +1,3 @@
+DROP TABLE dispute_messages;
+DROP TABLE dispute_evidence;
+DROP TABLE dispute_cases;
@@ -0,0 +1,28 @@
+CREATE TABLE dispute_cases(
+  id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+  order_id TEXT NOT NULL REFERENCES orders(order_id),
+  opened_by TEXT NOT NULL,
+  status TEXT NOT NULL,
+  created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+CREATE INDEX dispute_cases_order_id ON dispute_cases(order_id);
+-- Files are stored on disk under their SHA-256 digest, identical files share one copy
+CREATE TABLE dispute_evidence(
+  id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+  case_id INTEGER NOT NULL REFERENCES dispute_cases(id),
+  submitted_by TEXT NOT NULL,
+  file_name TEXT NOT NULL,
+  digest TEXT NOT NULL,
+  size INTEGER NOT NULL,
+  created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+CREATE INDEX dispute_evidence_case_id ON dispute_evidence(case_id);
+CREATE TABLE dispute_messages(
+  id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+  case_id INTEGER NOT NULL REFERENCES dispute_cases(id),
+  reply_to INTEGER REFERENCES dispute_messages(id),
+  author TEXT NOT NULL,
+  body TEXT NOT NULL,
+  created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+CREATE INDEX dispute_messages_case_id ON dispute_messages(case_id);
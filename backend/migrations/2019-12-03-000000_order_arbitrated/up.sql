@@ -0,0 +1,2 @@
+-- Whether the settlement of the order follows an escrow ruling, which is charged the dispute fees
+ALTER TABLE orders ADD COLUMN arbitrated BOOLEAN NOT NULL DEFAULT 0;
@@ -0,0 +1,17 @@
+CREATE TABLE escrows(
+  id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+  name TEXT NOT NULL,
+  public_key TEXT NOT NULL UNIQUE,
+  view_key TEXT NOT NULL,
+  -- Fee schedule, amounts in base units or basis points such as 150bps
+  fee_address TEXT NOT NULL DEFAULT '',
+  fee TEXT NOT NULL DEFAULT '0',
+  dispute_fee TEXT NOT NULL DEFAULT '0',
+  status TEXT NOT NULL,
+  created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+-- Escrow of the order in the registry and its fee schedule when the order was created
+ALTER TABLE orders ADD COLUMN escrow_id INTEGER REFERENCES escrows(id);
+ALTER TABLE orders ADD COLUMN escrow_fee_address TEXT NOT NULL DEFAULT '';
+ALTER TABLE orders ADD COLUMN escrow_fee TEXT NOT NULL DEFAULT '0';
+ALTER TABLE orders ADD COLUMN escrow_dispute_fee TEXT NOT NULL DEFAULT '0';
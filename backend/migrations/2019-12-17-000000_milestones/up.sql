@@ -0,0 +1,18 @@
+CREATE TABLE milestones(
+  id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+  order_id TEXT NOT NULL REFERENCES orders(order_id),
+  sequence INTEGER NOT NULL,
+  amount TEXT NOT NULL,
+  status TEXT NOT NULL,
+  settlement_transaction_id TEXT NOT NULL DEFAULT '',
+  created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  UNIQUE(order_id, sequence)
+);
+-- Output of the order's multi-sig address still to be settled, the payment until a milestone is
+-- released. Empty for the payment transaction and the order amount.
+ALTER TABLE orders ADD COLUMN funding_transaction_id TEXT NOT NULL DEFAULT '';
+ALTER TABLE orders ADD COLUMN funding_amount TEXT NOT NULL DEFAULT '';
+-- Tranche of the milestone being released and the merchant key of the multi-sig address
+-- receiving the rest of the funds
+ALTER TABLE orders ADD COLUMN milestone_amount TEXT NOT NULL DEFAULT '';
+ALTER TABLE orders ADD COLUMN change_public_key TEXT NOT NULL DEFAULT '';
@@ -0,0 +1,10 @@
+CREATE TABLE checkouts(
+  checkout_id TEXT PRIMARY KEY NOT NULL,
+  status TEXT NOT NULL,
+  payment_transaction_id TEXT NOT NULL DEFAULT '',
+  created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+-- Checkout paying for the order, empty for orders paid on their own
+ALTER TABLE orders ADD COLUMN checkout_id TEXT NOT NULL DEFAULT '';
+-- Output of the payment transaction funding the order's multi-sig address
+ALTER TABLE orders ADD COLUMN payment_output_index INTEGER NOT NULL DEFAULT 0;
@@ -0,0 +1,36 @@
+CREATE TABLE orders_backup(
+  order_id TEXT PRIMARY KEY NOT NULL,
+  status TEXT NOT NULL,
+  wallet_name TEXT NOT NULL,
+  amount TEXT NOT NULL,
+  buyer_public_key TEXT NOT NULL,
+  buyer_view_key TEXT NOT NULL,
+  buyer_address TEXT NOT NULL,
+  escrow_public_key TEXT NOT NULL,
+  escrow_view_key TEXT NOT NULL,
+  session_id TEXT NOT NULL,
+  payment_transaction_id TEXT NOT NULL,
+  settlement_transaction_id TEXT NOT NULL,
+  extra_view_keys TEXT NOT NULL DEFAULT '',
+  required_signatures INTEGER NOT NULL DEFAULT 2,
+  merchant_public_key TEXT NOT NULL DEFAULT '',
+  payout_address TEXT NOT NULL DEFAULT '',
+  refund_address TEXT NOT NULL DEFAULT '',
+  split_merchant_amount TEXT NOT NULL DEFAULT '',
+  split_buyer_amount TEXT NOT NULL DEFAULT '',
+  arbitrated BOOLEAN NOT NULL DEFAULT 0,
+  escrow_id INTEGER REFERENCES escrows(id),
+  escrow_fee_address TEXT NOT NULL DEFAULT '',
+  escrow_fee TEXT NOT NULL DEFAULT '0',
+  escrow_dispute_fee TEXT NOT NULL DEFAULT '0',
+  funding_transaction_id TEXT NOT NULL DEFAULT '',
+  funding_amount TEXT NOT NULL DEFAULT '',
+  milestone_amount TEXT NOT NULL DEFAULT '',
+  change_public_key TEXT NOT NULL DEFAULT '',
+  checkout_id TEXT NOT NULL DEFAULT '',
+  payment_output_index INTEGER NOT NULL DEFAULT 0
+);
+INSERT INTO orders_backup SELECT order_id, status, wallet_name, amount, buyer_public_key, buyer_view_key, buyer_address, escrow_public_key, escrow_view_key, session_id, payment_transaction_id, settlement_transaction_id, extra_view_keys, required_signatures, merchant_public_key, payout_address, refund_address, split_merchant_amount, split_buyer_amount, arbitrated, escrow_id, escrow_fee_address, escrow_fee, escrow_dispute_fee, funding_transaction_id, funding_amount, milestone_amount, change_public_key, checkout_id, payment_output_index FROM orders;
+DROP TABLE orders;
+ALTER TABLE orders_backup RENAME TO orders;
+DROP TABLE settlement_batches;
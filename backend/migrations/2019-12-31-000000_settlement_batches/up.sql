@@ -0,0 +1,10 @@
+CREATE TABLE settlement_batches(
+  id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+  status TEXT NOT NULL,
+  transaction_id TEXT NOT NULL,
+  -- Hex of the SCALE encoded transaction signed by the sessions of every order of the batch
+  encoded_transaction TEXT NOT NULL,
+  created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+-- Batch settling the order, NULL for orders settled on their own
+ALTER TABLE orders ADD COLUMN batch_id INTEGER REFERENCES settlement_batches(id);
@@ -0,0 +1,14 @@
+-- Nonces issued by /auth/challenge, each verified at most once
+CREATE TABLE auth_challenges(
+  nonce TEXT PRIMARY KEY NOT NULL,
+  public_key TEXT NOT NULL,
+  -- Unix timestamp after which the challenge is rejected
+  expires_at BIGINT NOT NULL
+);
+-- Session tokens issued by /auth/verify to the holder of a co-signer key
+CREATE TABLE auth_sessions(
+  token TEXT PRIMARY KEY NOT NULL,
+  public_key TEXT NOT NULL,
+  expires_at BIGINT NOT NULL,
+  created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
@@ -0,0 +1,22 @@
+CREATE TABLE merchants(
+  id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+  name TEXT NOT NULL UNIQUE,
+  -- Settings of the merchant, empty to use a wallet per order and no treasury. The default
+  -- merchant takes the settings left empty from the configuration.
+  wallet_name TEXT NOT NULL DEFAULT '',
+  payout_address TEXT NOT NULL DEFAULT '',
+  treasury_address TEXT NOT NULL DEFAULT '',
+  created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+CREATE TABLE merchant_api_keys(
+  id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+  merchant_id INTEGER NOT NULL REFERENCES merchants(id),
+  name TEXT NOT NULL,
+  -- Hex of the SHA-256 digest of the key, the key itself is only shown when it is created
+  key_digest TEXT NOT NULL UNIQUE,
+  created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+-- Merchant which ran the backend before merchants were added, owning the existing orders
+INSERT INTO merchants (id, name) VALUES (1, 'default');
+ALTER TABLE orders ADD COLUMN merchant_id INTEGER NOT NULL DEFAULT 1;
+ALTER TABLE settlement_batches ADD COLUMN merchant_id INTEGER NOT NULL DEFAULT 1;
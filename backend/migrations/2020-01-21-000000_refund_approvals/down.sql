@@ -0,0 +1,4 @@
+-- Refunds and splits still waiting for approval go back to awaiting the merchant response
+UPDATE orders SET status = 'PendingResponse'
+  WHERE status IN ('RefundPendingApproval', 'SplitPendingApproval');
+DROP TABLE refund_approvals;
@@ -0,0 +1,8 @@
+-- Staff accounts which signed off the pending refund of an order
+CREATE TABLE refund_approvals(
+  id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+  order_id TEXT NOT NULL REFERENCES orders(order_id),
+  staff_name TEXT NOT NULL,
+  created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  UNIQUE(order_id, staff_name)
+);
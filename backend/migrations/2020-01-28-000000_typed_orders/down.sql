@@ -0,0 +1,80 @@
+CREATE TABLE split_proposals_backup(
+  id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+  order_id TEXT NOT NULL REFERENCES orders(order_id),
+  proposed_by TEXT NOT NULL,
+  merchant_amount TEXT NOT NULL,
+  buyer_amount TEXT NOT NULL,
+  signature TEXT NOT NULL,
+  status TEXT NOT NULL,
+  acceptance_signature TEXT NOT NULL DEFAULT '',
+  created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+INSERT INTO split_proposals_backup SELECT id, order_id, proposed_by, CAST(merchant_amount AS TEXT), CAST(buyer_amount AS TEXT), signature, status, acceptance_signature, created_at FROM split_proposals;
+DROP TABLE split_proposals;
+ALTER TABLE split_proposals_backup RENAME TO split_proposals;
+CREATE INDEX split_proposals_order_id ON split_proposals(order_id);
+CREATE TABLE milestones_backup(
+  id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+  order_id TEXT NOT NULL REFERENCES orders(order_id),
+  sequence INTEGER NOT NULL,
+  amount TEXT NOT NULL,
+  status TEXT NOT NULL,
+  settlement_transaction_id TEXT NOT NULL DEFAULT '',
+  created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  UNIQUE(order_id, sequence)
+);
+INSERT INTO milestones_backup SELECT id, order_id, sequence, CAST(amount AS TEXT), status, settlement_transaction_id, created_at FROM milestones;
+DROP TABLE milestones;
+ALTER TABLE milestones_backup RENAME TO milestones;
+CREATE TABLE orders_backup(
+  order_id TEXT PRIMARY KEY NOT NULL,
+  status TEXT NOT NULL,
+  wallet_name TEXT NOT NULL,
+  amount TEXT NOT NULL,
+  buyer_public_key TEXT NOT NULL,
+  buyer_view_key TEXT NOT NULL,
+  buyer_address TEXT NOT NULL,
+  escrow_public_key TEXT NOT NULL,
+  escrow_view_key TEXT NOT NULL,
+  session_id TEXT NOT NULL,
+  payment_transaction_id TEXT NOT NULL,
+  settlement_transaction_id TEXT NOT NULL,
+  extra_view_keys TEXT NOT NULL DEFAULT '',
+  required_signatures INTEGER NOT NULL DEFAULT 2,
+  merchant_public_key TEXT NOT NULL DEFAULT '',
+  payout_address TEXT NOT NULL DEFAULT '',
+  refund_address TEXT NOT NULL DEFAULT '',
+  split_merchant_amount TEXT NOT NULL DEFAULT '',
+  split_buyer_amount TEXT NOT NULL DEFAULT '',
+  arbitrated BOOLEAN NOT NULL DEFAULT 0,
+  escrow_id INTEGER REFERENCES escrows(id),
+  escrow_fee_address TEXT NOT NULL DEFAULT '',
+  escrow_fee TEXT NOT NULL DEFAULT '0',
+  escrow_dispute_fee TEXT NOT NULL DEFAULT '0',
+  funding_transaction_id TEXT NOT NULL DEFAULT '',
+  funding_amount TEXT NOT NULL DEFAULT '',
+  milestone_amount TEXT NOT NULL DEFAULT '',
+  change_public_key TEXT NOT NULL DEFAULT '',
+  checkout_id TEXT NOT NULL DEFAULT '',
+  payment_output_index INTEGER NOT NULL DEFAULT 0,
+  batch_id INTEGER REFERENCES settlement_batches(id),
+  merchant_id INTEGER NOT NULL DEFAULT 1
+);
+INSERT INTO orders_backup SELECT order_id, status, wallet_name, CAST(amount AS TEXT), buyer_public_key, buyer_view_key, buyer_address, escrow_public_key, escrow_view_key, COALESCE(session_id, ''), COALESCE(payment_transaction_id, ''), COALESCE(settlement_transaction_id, ''), extra_view_keys, required_signatures, merchant_public_key, payout_address, COALESCE(refund_address, ''), COALESCE(CAST(split_merchant_amount AS TEXT), ''), COALESCE(CAST(split_buyer_amount AS TEXT), ''), arbitrated, escrow_id, escrow_fee_address, CASE WHEN escrow_fee_basis_points > 0 THEN escrow_fee_basis_points || 'bps' ELSE CAST(escrow_fee AS TEXT) END, CASE WHEN escrow_dispute_fee_basis_points > 0 THEN escrow_dispute_fee_basis_points || 'bps' ELSE CAST(escrow_dispute_fee AS TEXT) END, COALESCE(funding_transaction_id, ''), COALESCE(CAST(funding_amount AS TEXT), ''), COALESCE(CAST(milestone_amount AS TEXT), ''), COALESCE(change_public_key, ''), COALESCE(checkout_id, ''), payment_output_index, batch_id, merchant_id FROM orders;
+DROP TABLE orders;
+ALTER TABLE orders_backup RENAME TO orders;
+CREATE TABLE escrows_backup(
+  id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+  name TEXT NOT NULL,
+  public_key TEXT NOT NULL UNIQUE,
+  view_key TEXT NOT NULL,
+  -- Fee schedule, amounts in base units or basis points such as 150bps
+  fee_address TEXT NOT NULL DEFAULT '',
+  fee TEXT NOT NULL DEFAULT '0',
+  dispute_fee TEXT NOT NULL DEFAULT '0',
+  status TEXT NOT NULL,
+  created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+INSERT INTO escrows_backup SELECT id, name, public_key, view_key, fee_address, CASE WHEN fee_basis_points > 0 THEN fee_basis_points || 'bps' ELSE CAST(fee AS TEXT) END, CASE WHEN dispute_fee_basis_points > 0 THEN dispute_fee_basis_points || 'bps' ELSE CAST(dispute_fee AS TEXT) END, status, created_at FROM escrows;
+DROP TABLE escrows;
+ALTER TABLE escrows_backup RENAME TO escrows;
@@ -0,0 +1,106 @@
+-- Fee schedules are a flat amount in base units or basis points of the settled amount, the
+-- other one being zero
+CREATE TABLE escrows_backup(
+  id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+  name TEXT NOT NULL,
+  public_key TEXT NOT NULL UNIQUE,
+  view_key TEXT NOT NULL,
+  fee_address TEXT NOT NULL DEFAULT '',
+  fee BIGINT NOT NULL DEFAULT 0 CHECK (fee >= 0),
+  fee_basis_points INTEGER NOT NULL DEFAULT 0 CHECK (fee_basis_points BETWEEN 0 AND 10000),
+  dispute_fee BIGINT NOT NULL DEFAULT 0 CHECK (dispute_fee >= 0),
+  dispute_fee_basis_points INTEGER NOT NULL DEFAULT 0 CHECK (dispute_fee_basis_points BETWEEN 0 AND 10000),
+  status TEXT NOT NULL,
+  created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  CHECK (fee = 0 OR fee_basis_points = 0),
+  CHECK (dispute_fee = 0 OR dispute_fee_basis_points = 0)
+);
+INSERT INTO escrows_backup SELECT id, name, public_key, view_key, fee_address, CASE WHEN fee LIKE '%bps' THEN 0 ELSE CAST(fee AS INTEGER) END, CASE WHEN fee LIKE '%bps' THEN CAST(substr(fee, 1, length(fee) - 3) AS INTEGER) ELSE 0 END, CASE WHEN dispute_fee LIKE '%bps' THEN 0 ELSE CAST(dispute_fee AS INTEGER) END, CASE WHEN dispute_fee LIKE '%bps' THEN CAST(substr(dispute_fee, 1, length(dispute_fee) - 3) AS INTEGER) ELSE 0 END, status, created_at FROM escrows;
+DROP TABLE escrows;
+ALTER TABLE escrows_backup RENAME TO escrows;
+-- Amounts in base units, NULL for the fields not set yet and timestamps
+CREATE TABLE orders_backup(
+  order_id TEXT PRIMARY KEY NOT NULL,
+  status TEXT NOT NULL CHECK (status IN (
+    'PendingPayment', 'PendingResponse', 'Delivering', 'Refunding', 'Completed', 'Refunded',
+    'Splitting', 'Split', 'Disputed', 'ReleasingMilestone', 'RefundPendingApproval',
+    'SplitPendingApproval'
+  )),
+  wallet_name TEXT NOT NULL,
+  amount BIGINT NOT NULL CHECK (amount >= 0),
+  buyer_public_key TEXT NOT NULL,
+  buyer_view_key TEXT NOT NULL,
+  buyer_address TEXT NOT NULL,
+  escrow_public_key TEXT NOT NULL,
+  escrow_view_key TEXT NOT NULL,
+  session_id TEXT,
+  payment_transaction_id TEXT,
+  settlement_transaction_id TEXT,
+  extra_view_keys TEXT NOT NULL DEFAULT '',
+  required_signatures INTEGER NOT NULL DEFAULT 2,
+  merchant_public_key TEXT NOT NULL DEFAULT '',
+  payout_address TEXT NOT NULL DEFAULT '',
+  refund_address TEXT,
+  split_merchant_amount BIGINT CHECK (split_merchant_amount >= 0),
+  split_buyer_amount BIGINT CHECK (split_buyer_amount >= 0),
+  arbitrated BOOLEAN NOT NULL DEFAULT 0,
+  escrow_id INTEGER REFERENCES escrows(id),
+  escrow_fee_address TEXT NOT NULL DEFAULT '',
+  escrow_fee BIGINT NOT NULL DEFAULT 0 CHECK (escrow_fee >= 0),
+  escrow_fee_basis_points INTEGER NOT NULL DEFAULT 0 CHECK (escrow_fee_basis_points BETWEEN 0 AND 10000),
+  escrow_dispute_fee BIGINT NOT NULL DEFAULT 0 CHECK (escrow_dispute_fee >= 0),
+  escrow_dispute_fee_basis_points INTEGER NOT NULL DEFAULT 0 CHECK (escrow_dispute_fee_basis_points BETWEEN 0 AND 10000),
+  funding_transaction_id TEXT,
+  funding_amount BIGINT CHECK (funding_amount >= 0),
+  milestone_amount BIGINT CHECK (milestone_amount >= 0),
+  change_public_key TEXT,
+  checkout_id TEXT,
+  payment_output_index INTEGER NOT NULL DEFAULT 0,
+  batch_id INTEGER REFERENCES settlement_batches(id),
+  merchant_id INTEGER NOT NULL DEFAULT 1 REFERENCES merchants(id),
+  created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  CHECK (escrow_fee = 0 OR escrow_fee_basis_points = 0),
+  CHECK (escrow_dispute_fee = 0 OR escrow_dispute_fee_basis_points = 0)
+);
+-- Existing orders take their timestamps from their first and last events, when they have any
+INSERT INTO orders_backup SELECT order_id, status, wallet_name, CAST(amount AS INTEGER), buyer_public_key, buyer_view_key, buyer_address, escrow_public_key, escrow_view_key, NULLIF(session_id, ''), NULLIF(payment_transaction_id, ''), NULLIF(settlement_transaction_id, ''), extra_view_keys, required_signatures, merchant_public_key, payout_address, NULLIF(refund_address, ''), CAST(NULLIF(split_merchant_amount, '') AS INTEGER), CAST(NULLIF(split_buyer_amount, '') AS INTEGER), arbitrated, escrow_id, escrow_fee_address, CASE WHEN escrow_fee LIKE '%bps' THEN 0 ELSE CAST(escrow_fee AS INTEGER) END, CASE WHEN escrow_fee LIKE '%bps' THEN CAST(substr(escrow_fee, 1, length(escrow_fee) - 3) AS INTEGER) ELSE 0 END, CASE WHEN escrow_dispute_fee LIKE '%bps' THEN 0 ELSE CAST(escrow_dispute_fee AS INTEGER) END, CASE WHEN escrow_dispute_fee LIKE '%bps' THEN CAST(substr(escrow_dispute_fee, 1, length(escrow_dispute_fee) - 3) AS INTEGER) ELSE 0 END, NULLIF(funding_transaction_id, ''), CAST(NULLIF(funding_amount, '') AS INTEGER), CAST(NULLIF(milestone_amount, '') AS INTEGER), NULLIF(change_public_key, ''), NULLIF(checkout_id, ''), payment_output_index, batch_id, merchant_id,
+  COALESCE((SELECT MIN(created_at) FROM order_events WHERE order_events.order_id = orders.order_id), CURRENT_TIMESTAMP),
+  COALESCE((SELECT MAX(created_at) FROM order_events WHERE order_events.order_id = orders.order_id), CURRENT_TIMESTAMP)
+FROM orders;
+DROP TABLE orders;
+ALTER TABLE orders_backup RENAME TO orders;
+CREATE INDEX orders_status ON orders(status);
+CREATE INDEX orders_buyer_public_key ON orders(buyer_public_key);
+CREATE TRIGGER orders_updated_at AFTER UPDATE ON orders FOR EACH ROW WHEN NEW.updated_at = OLD.updated_at
+BEGIN
+  UPDATE orders SET updated_at = CURRENT_TIMESTAMP WHERE order_id = NEW.order_id;
+END;
+CREATE TABLE milestones_backup(
+  id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+  order_id TEXT NOT NULL REFERENCES orders(order_id),
+  sequence INTEGER NOT NULL,
+  amount BIGINT NOT NULL CHECK (amount > 0),
+  status TEXT NOT NULL,
+  settlement_transaction_id TEXT NOT NULL DEFAULT '',
+  created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  UNIQUE(order_id, sequence)
+);
+INSERT INTO milestones_backup SELECT id, order_id, sequence, CAST(amount AS INTEGER), status, settlement_transaction_id, created_at FROM milestones;
+DROP TABLE milestones;
+ALTER TABLE milestones_backup RENAME TO milestones;
+CREATE TABLE split_proposals_backup(
+  id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+  order_id TEXT NOT NULL REFERENCES orders(order_id),
+  proposed_by TEXT NOT NULL,
+  merchant_amount BIGINT NOT NULL CHECK (merchant_amount >= 0),
+  buyer_amount BIGINT NOT NULL CHECK (buyer_amount >= 0),
+  signature TEXT NOT NULL,
+  status TEXT NOT NULL,
+  acceptance_signature TEXT NOT NULL DEFAULT '',
+  created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+INSERT INTO split_proposals_backup SELECT id, order_id, proposed_by, CAST(merchant_amount AS INTEGER), CAST(buyer_amount AS INTEGER), signature, status, acceptance_signature, created_at FROM split_proposals;
+DROP TABLE split_proposals;
+ALTER TABLE split_proposals_backup RENAME TO split_proposals;
+CREATE INDEX split_proposals_order_id ON split_proposals(order_id);
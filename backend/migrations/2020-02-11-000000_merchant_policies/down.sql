@@ -0,0 +1,11 @@
+CREATE TABLE merchants_backup(
+  id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+  name TEXT NOT NULL UNIQUE,
+  wallet_name TEXT NOT NULL DEFAULT '',
+  payout_address TEXT NOT NULL DEFAULT '',
+  treasury_address TEXT NOT NULL DEFAULT '',
+  created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+INSERT INTO merchants_backup SELECT id, name, wallet_name, payout_address, treasury_address, created_at FROM merchants;
+DROP TABLE merchants;
+ALTER TABLE merchants_backup RENAME TO merchants;
@@ -0,0 +1,12 @@
+-- Policies of the merchant, left empty or NULL the default merchant takes them from the
+-- configuration and the other merchants go without. Platform fees are a flat amount in base units
+-- or basis points of the settled amount, the other one being zero.
+ALTER TABLE merchants ADD COLUMN auditor_view_keys TEXT NOT NULL DEFAULT '';
+ALTER TABLE merchants ADD COLUMN escrow_access_on_refund BOOLEAN;
+ALTER TABLE merchants ADD COLUMN platform_fee_address TEXT NOT NULL DEFAULT '';
+ALTER TABLE merchants ADD COLUMN platform_fee BIGINT NOT NULL DEFAULT 0 CHECK (platform_fee >= 0);
+ALTER TABLE merchants ADD COLUMN platform_fee_basis_points INTEGER NOT NULL DEFAULT 0 CHECK (platform_fee_basis_points BETWEEN 0 AND 10000 AND (platform_fee = 0 OR platform_fee_basis_points = 0));
+ALTER TABLE merchants ADD COLUMN platform_dispute_fee BIGINT NOT NULL DEFAULT 0 CHECK (platform_dispute_fee >= 0);
+ALTER TABLE merchants ADD COLUMN platform_dispute_fee_basis_points INTEGER NOT NULL DEFAULT 0 CHECK (platform_dispute_fee_basis_points BETWEEN 0 AND 10000 AND (platform_dispute_fee = 0 OR platform_dispute_fee_basis_points = 0));
+ALTER TABLE merchants ADD COLUMN refund_approval_threshold BIGINT CHECK (refund_approval_threshold >= 0);
+ALTER TABLE merchants ADD COLUMN require_registered_escrow BOOLEAN;
@@ -0,0 +1,14 @@
+CREATE TABLE rulings_backup(
+  id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+  order_id TEXT NOT NULL REFERENCES orders(order_id),
+  escrow_public_key TEXT NOT NULL,
+  decision TEXT NOT NULL,
+  merchant_amount TEXT NOT NULL,
+  buyer_amount TEXT NOT NULL,
+  signature TEXT NOT NULL,
+  created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+INSERT INTO rulings_backup SELECT id, order_id, escrow_public_key, decision, merchant_amount, buyer_amount, signature, created_at FROM rulings;
+DROP TABLE rulings;
+ALTER TABLE rulings_backup RENAME TO rulings;
+CREATE INDEX rulings_order_id ON rulings(order_id);
@@ -0,0 +1,66 @@
+CREATE TABLE orders_backup(
+  order_id TEXT PRIMARY KEY NOT NULL,
+  status TEXT NOT NULL CHECK (status IN (
+    'PendingPayment', 'PendingResponse', 'Delivering', 'Refunding', 'Completed', 'Refunded',
+    'Splitting', 'Split', 'Disputed', 'ReleasingMilestone', 'RefundPendingApproval',
+    'SplitPendingApproval'
+  )),
+  wallet_name TEXT NOT NULL,
+  amount BIGINT NOT NULL CHECK (amount >= 0),
+  buyer_public_key TEXT NOT NULL,
+  buyer_view_key TEXT NOT NULL,
+  buyer_address TEXT NOT NULL,
+  escrow_public_key TEXT NOT NULL,
+  escrow_view_key TEXT NOT NULL,
+  session_id TEXT,
+  payment_transaction_id TEXT,
+  settlement_transaction_id TEXT,
+  extra_view_keys TEXT NOT NULL DEFAULT '',
+  required_signatures INTEGER NOT NULL DEFAULT 2,
+  merchant_public_key TEXT NOT NULL DEFAULT '',
+  payout_address TEXT NOT NULL DEFAULT '',
+  refund_address TEXT,
+  split_merchant_amount BIGINT CHECK (split_merchant_amount >= 0),
+  split_buyer_amount BIGINT CHECK (split_buyer_amount >= 0),
+  arbitrated BOOLEAN NOT NULL DEFAULT 0,
+  escrow_id INTEGER REFERENCES escrows(id),
+  escrow_fee_address TEXT NOT NULL DEFAULT '',
+  escrow_fee BIGINT NOT NULL DEFAULT 0 CHECK (escrow_fee >= 0),
+  escrow_fee_basis_points INTEGER NOT NULL DEFAULT 0 CHECK (escrow_fee_basis_points BETWEEN 0 AND 10000),
+  escrow_dispute_fee BIGINT NOT NULL DEFAULT 0 CHECK (escrow_dispute_fee >= 0),
+  escrow_dispute_fee_basis_points INTEGER NOT NULL DEFAULT 0 CHECK (escrow_dispute_fee_basis_points BETWEEN 0 AND 10000),
+  funding_transaction_id TEXT,
+  funding_amount BIGINT CHECK (funding_amount >= 0),
+  milestone_amount BIGINT CHECK (milestone_amount >= 0),
+  change_public_key TEXT,
+  checkout_id TEXT,
+  payment_output_index INTEGER NOT NULL DEFAULT 0,
+  batch_id INTEGER REFERENCES settlement_batches(id),
+  merchant_id INTEGER NOT NULL DEFAULT 1 REFERENCES merchants(id),
+  created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  encoded_settlement_transaction TEXT,
+  CHECK (escrow_fee = 0 OR escrow_fee_basis_points = 0),
+  CHECK (escrow_dispute_fee = 0 OR escrow_dispute_fee_basis_points = 0)
+);
+INSERT INTO orders_backup SELECT order_id, status, wallet_name, amount, buyer_public_key, buyer_view_key, buyer_address, escrow_public_key, escrow_view_key, session_id, payment_transaction_id, settlement_transaction_id, extra_view_keys, required_signatures, merchant_public_key, payout_address, refund_address, split_merchant_amount, split_buyer_amount, arbitrated, escrow_id, escrow_fee_address, escrow_fee, escrow_fee_basis_points, escrow_dispute_fee, escrow_dispute_fee_basis_points, funding_transaction_id, funding_amount, milestone_amount, change_public_key, checkout_id, payment_output_index, batch_id, merchant_id, created_at, updated_at, encoded_settlement_transaction FROM orders;
+DROP TABLE orders;
+ALTER TABLE orders_backup RENAME TO orders;
+CREATE INDEX orders_status ON orders(status);
+CREATE INDEX orders_buyer_public_key ON orders(buyer_public_key);
+CREATE TRIGGER orders_updated_at AFTER UPDATE ON orders FOR EACH ROW WHEN NEW.updated_at = OLD.updated_at
+BEGIN
+  UPDATE orders SET updated_at = CURRENT_TIMESTAMP WHERE order_id = NEW.order_id;
+END;
+CREATE TABLE settlement_batches_backup(
+  id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+  status TEXT NOT NULL,
+  transaction_id TEXT NOT NULL,
+  -- Hex of the SCALE encoded transaction signed by the sessions of every order of the batch
+  encoded_transaction TEXT NOT NULL,
+  created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  merchant_id INTEGER NOT NULL DEFAULT 1
+);
+INSERT INTO settlement_batches_backup SELECT id, status, transaction_id, encoded_transaction, created_at, merchant_id FROM settlement_batches;
+DROP TABLE settlement_batches;
+ALTER TABLE settlement_batches_backup RENAME TO settlement_batches;
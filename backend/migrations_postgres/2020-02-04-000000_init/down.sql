@@ -0,0 +1,20 @@
+DROP TABLE auth_sessions;
+DROP TABLE auth_challenges;
+DROP TABLE refund_approvals;
+DROP TABLE milestones;
+DROP TABLE dispute_messages;
+DROP TABLE dispute_evidence;
+DROP TABLE dispute_cases;
+DROP TABLE rulings;
+DROP TABLE split_proposals;
+DROP TABLE session_signers;
+DROP TABLE order_participants;
+DROP TABLE order_events;
+DROP TABLE orders;
+DROP FUNCTION orders_updated_at();
+DROP TABLE checkouts;
+DROP TABLE settlement_batches;
+DROP TABLE escrows;
+DROP TABLE merchant_api_keys;
+DROP TABLE merchants;
+DROP FUNCTION utc_timestamp();
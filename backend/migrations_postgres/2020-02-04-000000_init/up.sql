@@ -0,0 +1,221 @@
+-- Schema of the SQLite migrations up to typed_orders, for deployments starting on PostgreSQL.
+-- Timestamps are UTC, the untyped ones text in the format of SQLite's CURRENT_TIMESTAMP so both
+-- backends compare and return them alike.
+CREATE FUNCTION utc_timestamp() RETURNS TEXT AS $$
+  SELECT to_char(now() AT TIME ZONE 'UTC', 'YYYY-MM-DD HH24:MI:SS')
+$$ LANGUAGE SQL STABLE;
+
+CREATE TABLE merchants(
+  id SERIAL PRIMARY KEY,
+  name TEXT NOT NULL UNIQUE,
+  -- Settings of the merchant, empty to use a wallet per order and no treasury. The default
+  -- merchant takes the settings left empty from the configuration.
+  wallet_name TEXT NOT NULL DEFAULT '',
+  payout_address TEXT NOT NULL DEFAULT '',
+  treasury_address TEXT NOT NULL DEFAULT '',
+  created_at TEXT NOT NULL DEFAULT utc_timestamp()
+);
+-- Merchant owning the orders created without an API key
+INSERT INTO merchants (name) VALUES ('default');
+CREATE TABLE merchant_api_keys(
+  id SERIAL PRIMARY KEY,
+  merchant_id INTEGER NOT NULL REFERENCES merchants(id),
+  name TEXT NOT NULL,
+  -- Hex of the SHA-256 digest of the key, the key itself is only shown when it is created
+  key_digest TEXT NOT NULL UNIQUE,
+  created_at TEXT NOT NULL DEFAULT utc_timestamp()
+);
+CREATE TABLE escrows(
+  id SERIAL PRIMARY KEY,
+  name TEXT NOT NULL,
+  public_key TEXT NOT NULL UNIQUE,
+  view_key TEXT NOT NULL,
+  -- Fee schedules are a flat amount in base units or basis points of the settled amount, the
+  -- other one being zero
+  fee_address TEXT NOT NULL DEFAULT '',
+  fee BIGINT NOT NULL DEFAULT 0 CHECK (fee >= 0),
+  fee_basis_points INTEGER NOT NULL DEFAULT 0 CHECK (fee_basis_points BETWEEN 0 AND 10000),
+  dispute_fee BIGINT NOT NULL DEFAULT 0 CHECK (dispute_fee >= 0),
+  dispute_fee_basis_points INTEGER NOT NULL DEFAULT 0 CHECK (dispute_fee_basis_points BETWEEN 0 AND 10000),
+  status TEXT NOT NULL,
+  created_at TEXT NOT NULL DEFAULT utc_timestamp(),
+  CHECK (fee = 0 OR fee_basis_points = 0),
+  CHECK (dispute_fee = 0 OR dispute_fee_basis_points = 0)
+);
+CREATE TABLE settlement_batches(
+  id SERIAL PRIMARY KEY,
+  status TEXT NOT NULL,
+  transaction_id TEXT NOT NULL,
+  -- Hex of the SCALE encoded transaction signed by the sessions of every order of the batch
+  encoded_transaction TEXT NOT NULL,
+  created_at TEXT NOT NULL DEFAULT utc_timestamp(),
+  merchant_id INTEGER NOT NULL DEFAULT 1 REFERENCES merchants(id)
+);
+CREATE TABLE checkouts(
+  checkout_id TEXT PRIMARY KEY,
+  status TEXT NOT NULL,
+  payment_transaction_id TEXT NOT NULL DEFAULT '',
+  created_at TEXT NOT NULL DEFAULT utc_timestamp()
+);
+-- Amounts in base units, NULL for the fields not set yet
+CREATE TABLE orders(
+  order_id TEXT PRIMARY KEY,
+  status TEXT NOT NULL CHECK (status IN (
+    'PendingPayment', 'PendingResponse', 'Delivering', 'Refunding', 'Completed', 'Refunded',
+    'Splitting', 'Split', 'Disputed', 'ReleasingMilestone', 'RefundPendingApproval',
+    'SplitPendingApproval'
+  )),
+  wallet_name TEXT NOT NULL,
+  amount BIGINT NOT NULL CHECK (amount >= 0),
+  buyer_public_key TEXT NOT NULL,
+  buyer_view_key TEXT NOT NULL,
+  buyer_address TEXT NOT NULL,
+  escrow_public_key TEXT NOT NULL,
+  escrow_view_key TEXT NOT NULL,
+  session_id TEXT,
+  payment_transaction_id TEXT,
+  settlement_transaction_id TEXT,
+  extra_view_keys TEXT NOT NULL DEFAULT '',
+  required_signatures INTEGER NOT NULL DEFAULT 2,
+  merchant_public_key TEXT NOT NULL DEFAULT '',
+  payout_address TEXT NOT NULL DEFAULT '',
+  refund_address TEXT,
+  split_merchant_amount BIGINT CHECK (split_merchant_amount >= 0),
+  split_buyer_amount BIGINT CHECK (split_buyer_amount >= 0),
+  arbitrated BOOLEAN NOT NULL DEFAULT FALSE,
+  escrow_id INTEGER REFERENCES escrows(id),
+  escrow_fee_address TEXT NOT NULL DEFAULT '',
+  escrow_fee BIGINT NOT NULL DEFAULT 0 CHECK (escrow_fee >= 0),
+  escrow_fee_basis_points INTEGER NOT NULL DEFAULT 0 CHECK (escrow_fee_basis_points BETWEEN 0 AND 10000),
+  escrow_dispute_fee BIGINT NOT NULL DEFAULT 0 CHECK (escrow_dispute_fee >= 0),
+  escrow_dispute_fee_basis_points INTEGER NOT NULL DEFAULT 0 CHECK (escrow_dispute_fee_basis_points BETWEEN 0 AND 10000),
+  funding_transaction_id TEXT,
+  funding_amount BIGINT CHECK (funding_amount >= 0),
+  milestone_amount BIGINT CHECK (milestone_amount >= 0),
+  change_public_key TEXT,
+  checkout_id TEXT,
+  payment_output_index INTEGER NOT NULL DEFAULT 0,
+  batch_id INTEGER REFERENCES settlement_batches(id),
+  merchant_id INTEGER NOT NULL DEFAULT 1 REFERENCES merchants(id),
+  created_at TIMESTAMP NOT NULL DEFAULT (now() AT TIME ZONE 'UTC'),
+  updated_at TIMESTAMP NOT NULL DEFAULT (now() AT TIME ZONE 'UTC'),
+  CHECK (escrow_fee = 0 OR escrow_fee_basis_points = 0),
+  CHECK (escrow_dispute_fee = 0 OR escrow_dispute_fee_basis_points = 0)
+);
+CREATE INDEX orders_status ON orders(status);
+CREATE INDEX orders_buyer_public_key ON orders(buyer_public_key);
+CREATE FUNCTION orders_updated_at() RETURNS TRIGGER AS $$
+BEGIN
+  NEW.updated_at := now() AT TIME ZONE 'UTC';
+  RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+CREATE TRIGGER orders_updated_at BEFORE UPDATE ON orders FOR EACH ROW
+  WHEN (NEW.updated_at = OLD.updated_at)
+  EXECUTE PROCEDURE orders_updated_at();
+CREATE TABLE order_events(
+  id SERIAL PRIMARY KEY,
+  order_id TEXT NOT NULL REFERENCES orders(order_id),
+  event TEXT NOT NULL,
+  detail TEXT NOT NULL,
+  created_at TEXT NOT NULL DEFAULT utc_timestamp()
+);
+CREATE INDEX order_events_order_id ON order_events(order_id);
+CREATE TABLE order_participants(
+  id SERIAL PRIMARY KEY,
+  order_id TEXT NOT NULL REFERENCES orders(order_id),
+  role TEXT NOT NULL,
+  public_key TEXT NOT NULL,
+  view_key TEXT NOT NULL
+);
+CREATE INDEX order_participants_order_id ON order_participants(order_id);
+CREATE TABLE session_signers(
+  id SERIAL PRIMARY KEY,
+  session_id TEXT NOT NULL,
+  order_id TEXT NOT NULL REFERENCES orders(order_id),
+  role TEXT NOT NULL,
+  public_key TEXT NOT NULL,
+  commitment TEXT NOT NULL DEFAULT '',
+  nonce TEXT NOT NULL DEFAULT '',
+  partial_signature TEXT NOT NULL DEFAULT ''
+);
+CREATE INDEX session_signers_session_id ON session_signers(session_id);
+CREATE TABLE split_proposals(
+  id SERIAL PRIMARY KEY,
+  order_id TEXT NOT NULL REFERENCES orders(order_id),
+  proposed_by TEXT NOT NULL,
+  merchant_amount BIGINT NOT NULL CHECK (merchant_amount >= 0),
+  buyer_amount BIGINT NOT NULL CHECK (buyer_amount >= 0),
+  signature TEXT NOT NULL,
+  status TEXT NOT NULL,
+  acceptance_signature TEXT NOT NULL DEFAULT '',
+  created_at TEXT NOT NULL DEFAULT utc_timestamp()
+);
+CREATE INDEX split_proposals_order_id ON split_proposals(order_id);
+CREATE TABLE rulings(
+  id SERIAL PRIMARY KEY,
+  order_id TEXT NOT NULL REFERENCES orders(order_id),
+  escrow_public_key TEXT NOT NULL,
+  decision TEXT NOT NULL,
+  merchant_amount TEXT NOT NULL,
+  buyer_amount TEXT NOT NULL,
+  signature TEXT NOT NULL,
+  created_at TEXT NOT NULL DEFAULT utc_timestamp()
+);
+CREATE INDEX rulings_order_id ON rulings(order_id);
+CREATE TABLE dispute_cases(
+  id SERIAL PRIMARY KEY,
+  order_id TEXT NOT NULL REFERENCES orders(order_id),
+  opened_by TEXT NOT NULL,
+  status TEXT NOT NULL,
+  created_at TEXT NOT NULL DEFAULT utc_timestamp()
+);
+CREATE INDEX dispute_cases_order_id ON dispute_cases(order_id);
+CREATE TABLE dispute_evidence(
+  id SERIAL PRIMARY KEY,
+  case_id INTEGER NOT NULL REFERENCES dispute_cases(id),
+  submitted_by TEXT NOT NULL,
+  file_name TEXT NOT NULL,
+  digest TEXT NOT NULL,
+  size INTEGER NOT NULL,
+  created_at TEXT NOT NULL DEFAULT utc_timestamp()
+);
+CREATE INDEX dispute_evidence_case_id ON dispute_evidence(case_id);
+CREATE TABLE dispute_messages(
+  id SERIAL PRIMARY KEY,
+  case_id INTEGER NOT NULL REFERENCES dispute_cases(id),
+  reply_to INTEGER REFERENCES dispute_messages(id),
+  author TEXT NOT NULL,
+  body TEXT NOT NULL,
+  created_at TEXT NOT NULL DEFAULT utc_timestamp()
+);
+CREATE INDEX dispute_messages_case_id ON dispute_messages(case_id);
+CREATE TABLE milestones(
+  id SERIAL PRIMARY KEY,
+  order_id TEXT NOT NULL REFERENCES orders(order_id),
+  sequence INTEGER NOT NULL,
+  amount BIGINT NOT NULL CHECK (amount > 0),
+  status TEXT NOT NULL,
+  settlement_transaction_id TEXT NOT NULL DEFAULT '',
+  created_at TEXT NOT NULL DEFAULT utc_timestamp(),
+  UNIQUE(order_id, sequence)
+);
+CREATE TABLE refund_approvals(
+  id SERIAL PRIMARY KEY,
+  order_id TEXT NOT NULL REFERENCES orders(order_id),
+  staff_name TEXT NOT NULL,
+  created_at TEXT NOT NULL DEFAULT utc_timestamp(),
+  UNIQUE(order_id, staff_name)
+);
+CREATE TABLE auth_challenges(
+  nonce TEXT PRIMARY KEY,
+  public_key TEXT NOT NULL,
+  -- Unix timestamp after which the challenge is rejected
+  expires_at BIGINT NOT NULL
+);
+CREATE TABLE auth_sessions(
+  token TEXT PRIMARY KEY,
+  public_key TEXT NOT NULL,
+  expires_at BIGINT NOT NULL,
+  created_at TEXT NOT NULL DEFAULT utc_timestamp()
+);
@@ -0,0 +1,9 @@
+ALTER TABLE merchants DROP COLUMN require_registered_escrow;
+ALTER TABLE merchants DROP COLUMN refund_approval_threshold;
+ALTER TABLE merchants DROP COLUMN platform_dispute_fee_basis_points;
+ALTER TABLE merchants DROP COLUMN platform_dispute_fee;
+ALTER TABLE merchants DROP COLUMN platform_fee_basis_points;
+ALTER TABLE merchants DROP COLUMN platform_fee;
+ALTER TABLE merchants DROP COLUMN platform_fee_address;
+ALTER TABLE merchants DROP COLUMN escrow_access_on_refund;
+ALTER TABLE merchants DROP COLUMN auditor_view_keys;
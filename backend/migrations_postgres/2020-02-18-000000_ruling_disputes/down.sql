@@ -0,0 +1 @@
+ALTER TABLE rulings DROP COLUMN dispute_id;
@@ -0,0 +1,3 @@
+-- Dispute case opened by the escalation a ruling decides, the escrow signs the ruling for that case
+-- so that it cannot be replayed on a later escalation. Left NULL for the rulings made before.
+ALTER TABLE rulings ADD COLUMN dispute_id INTEGER REFERENCES dispute_cases(id);
@@ -0,0 +1,4 @@
+-- Transaction the signing session of an order signs, hex of its SCALE encoding. The session
+-- broadcasts exactly this transaction, whatever the merchant settings or the fee policy became
+-- since it started. NULL when no session is running.
+ALTER TABLE orders ADD COLUMN encoded_settlement_transaction TEXT;
@@ -0,0 +1,2 @@
+ALTER TABLE settlement_batches DROP COLUMN network_fee;
+ALTER TABLE orders DROP COLUMN settlement_network_fee;
@@ -0,0 +1,6 @@
+-- Network fee in base units paid by the transaction of the order's signing session, and by the
+-- transaction of a batch. It is computed once from the fee policy when the transaction is built
+-- and reused until the transaction is broadcast. NULL when no session is running, and for the
+-- sessions and batches started before.
+ALTER TABLE orders ADD COLUMN settlement_network_fee BIGINT CHECK (settlement_network_fee >= 0);
+ALTER TABLE settlement_batches ADD COLUMN network_fee BIGINT CHECK (network_fee >= 0);
@@ -0,0 +1,122 @@
+use chain_core::init::coin::Coin;
+
+use crate::config::RefundApprovalConfig;
+use crate::milestones::funding_amount;
+use crate::models::{Order, RefundApproval};
+
+/// Whether refunding an order needs the sign-off of several staff accounts. Refunds following an
+/// escrow ruling are decided by the escrow and need none.
+pub fn requires_refund_approval(record: &Order, config: &RefundApprovalConfig) -> bool {
+    match config.threshold {
+        Some(threshold) => {
+            !record.arbitrated && u64::from(funding_amount(record)) > u64::from(threshold)
+        }
+        None => false,
+    }
+}
+
+/// Whether a split paying the buyer the given share needs the same sign-off as a refund of that
+/// share, the merchant giving up that much of the order. Splits ruled by an escrow need none.
+pub fn requires_split_approval(
+    record: &Order,
+    buyer_amount: Coin,
+    config: &RefundApprovalConfig,
+) -> bool {
+    match config.threshold {
+        Some(threshold) => !record.arbitrated && u64::from(buyer_amount) > u64::from(threshold),
+        None => false,
+    }
+}
+
+/// Whether enough distinct staff accounts approved the refund of an order
+pub fn is_refund_approved(approvals: &[RefundApproval], config: &RefundApprovalConfig) -> bool {
+    let mut staff_names: Vec<&str> = approvals
+        .iter()
+        .map(|approval| approval.staff_name.as_str())
+        .collect();
+    staff_names.sort();
+    staff_names.dedup();
+    staff_names.len() >= config.required_approvals
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDateTime;
+
+    use crate::models::OrderStatus;
+
+    const THRESHOLD: u64 = 50_0000_0000;
+
+    fn config() -> RefundApprovalConfig {
+        RefundApprovalConfig {
+            threshold: Some(Coin::new(THRESHOLD).unwrap()),
+            required_approvals: 2,
+        }
+    }
+
+    fn order(amount: u64) -> Order {
+        Order {
+            order_id: "1".to_owned(),
+            status: OrderStatus::Delivering,
+            wallet_name: String::new(),
+            amount: amount as i64,
+            buyer_public_key: String::new(),
+            buyer_view_key: String::new(),
+            buyer_address: String::new(),
+            escrow_public_key: String::new(),
+            escrow_view_key: String::new(),
+            session_id: None,
+            payment_transaction_id: None,
+            settlement_transaction_id: None,
+            extra_view_keys: String::new(),
+            required_signatures: 2,
+            merchant_public_key: String::new(),
+            payout_address: String::new(),
+            refund_address: None,
+            split_merchant_amount: None,
+            split_buyer_amount: None,
+            arbitrated: false,
+            escrow_id: None,
+            escrow_fee_address: String::new(),
+            escrow_fee: 0,
+            escrow_fee_basis_points: 0,
+            escrow_dispute_fee: 0,
+            escrow_dispute_fee_basis_points: 0,
+            funding_transaction_id: None,
+            funding_amount: None,
+            milestone_amount: None,
+            change_public_key: None,
+            checkout_id: None,
+            payment_output_index: 0,
+            batch_id: None,
+            merchant_id: 1,
+            created_at: NaiveDateTime::from_timestamp(0, 0),
+            updated_at: NaiveDateTime::from_timestamp(0, 0),
+            encoded_settlement_transaction: None,
+            settlement_network_fee: None,
+        }
+    }
+
+    #[test]
+    fn refund_at_threshold_needs_no_approval() {
+        assert!(!requires_refund_approval(&order(THRESHOLD), &config()));
+        assert!(requires_refund_approval(&order(THRESHOLD + 1), &config()));
+    }
+
+    #[test]
+    fn split_at_threshold_needs_no_approval() {
+        let record = order(2 * THRESHOLD);
+        let buyer_amount = |amount| Coin::new(amount).unwrap();
+        assert!(!requires_split_approval(
+            &record,
+            buyer_amount(THRESHOLD),
+            &config()
+        ));
+        assert!(requires_split_approval(
+            &record,
+            buyer_amount(THRESHOLD + 1),
+            &config()
+        ));
+    }
+}
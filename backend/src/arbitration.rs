@@ -0,0 +1,66 @@
+use chain_core::init::coin::Coin;
+
+use crate::models::{Order, OrderStatus, RulingDecision};
+use crate::split::parse_split;
+
+/// Whether the settlement of an order can be escalated to its escrow
+pub fn can_escalate(record: &Order) -> bool {
+    !record.escrow_public_key.is_empty()
+        && (record.status == OrderStatus::PendingResponse
+            || record.status == OrderStatus::RefundPendingApproval
+            || record.status == OrderStatus::SplitPendingApproval
+            || record.status.is_settling())
+}
+
+/// Parses a ruling into its decision and the shares of the settlement in base units. Release and
+/// refund rulings follow the usual delivery and refund settlements and carry no shares.
+pub fn parse_ruling(
+    record: &Order,
+    decision: &str,
+    merchant_amount: Option<&str>,
+    buyer_amount: Option<&str>,
+) -> Result<(RulingDecision, Option<(Coin, Coin)>), &'static str> {
+    let decision = decision
+        .parse::<RulingDecision>()
+        .map_err(|_| "Invalid Ruling Decision")?;
+    match (decision, merchant_amount, buyer_amount) {
+        (RulingDecision::Split, Some(merchant_amount), Some(buyer_amount)) => {
+            let shares = parse_split(record, merchant_amount, buyer_amount)?;
+            Ok((decision, Some(shares)))
+        }
+        (RulingDecision::Split, _, _) => Err("Invalid Split Amount"),
+        _ => Ok((decision, None)),
+    }
+}
+
+/// Settlement status of an order after a ruling
+pub fn ruling_status(decision: RulingDecision) -> OrderStatus {
+    match decision {
+        RulingDecision::Release => OrderStatus::Delivering,
+        RulingDecision::Refund => OrderStatus::Refunding,
+        RulingDecision::Split => OrderStatus::Splitting,
+    }
+}
+
+/// Message the escrow signs to rule on the dispute case of an order, with the shares of a split in
+/// base units. The case ties the signature to one escalation, so that it cannot be replayed when
+/// the order is escalated again.
+pub fn ruling_message(
+    order_id: &str,
+    dispute_id: i32,
+    decision: RulingDecision,
+    shares: Option<(Coin, Coin)>,
+) -> String {
+    match (decision, shares) {
+        (RulingDecision::Release, _) => format!("ruling:{}:{}:Release", order_id, dispute_id),
+        (RulingDecision::Refund, _) => format!("ruling:{}:{}:Refund", order_id, dispute_id),
+        (RulingDecision::Split, Some((merchant_amount, buyer_amount))) => format!(
+            "ruling:{}:{}:Split:{}:{}",
+            order_id,
+            dispute_id,
+            u64::from(merchant_amount),
+            u64::from(buyer_amount)
+        ),
+        (RulingDecision::Split, None) => format!("ruling:{}:{}:Split", order_id, dispute_id),
+    }
+}
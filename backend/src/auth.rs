@@ -0,0 +1,676 @@
+use actix_web::dev::Payload;
+use actix_web::error::PayloadError;
+use actix_web::{web, Error as AWError, FromRequest, HttpRequest, HttpResponse};
+use futures::future::{self, Either, Future};
+use futures::sync::mpsc;
+use futures::Stream;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+use uuid::Uuid;
+
+use crate::config::{AuthConfig, MerchantConfig};
+use crate::db;
+use crate::merchants::DEFAULT_MERCHANT_ID;
+use crate::models::{Order, OrderParticipant, ParticipantRole};
+use crate::signing::verify_key_signature;
+use crate::Pool;
+
+/// Number of recorded nonces above which the expired ones are dropped
+const NONCE_PRUNE_THRESHOLD: usize = 1024;
+
+/// Caller of a route. Merchant staff authenticate with an API key, given as `X-API-Key` or as a
+/// bearer token. Buyers, escrows and other co-signers prove possession of their key by signing
+/// the request, see `request_message`, or with the bearer token of a session opened by signing a
+/// challenge, see `challenge_message`.
+///
+/// A signed request is verified once its body is read, so the identity has to be extracted before
+/// the body of the request.
+#[derive(Debug, Clone)]
+pub enum Identity {
+    Staff(Staff),
+    /// Hex encoded public key the caller proved possession of
+    Key(String),
+}
+impl Identity {
+    /// Whether the caller holds the given public key
+    pub fn holds_key(&self, public_key: &str) -> bool {
+        match self {
+            Identity::Key(key) => !public_key.is_empty() && key == public_key,
+            Identity::Staff(_) => false,
+        }
+    }
+
+    /// Whether the caller may act for the merchant or one of the co-signers of an order
+    pub fn is_order_party(&self, record: &Order, participants: &[OrderParticipant]) -> bool {
+        match self {
+            Identity::Staff(staff) => staff.merchant_id == record.merchant_id,
+            Identity::Key(_) => participants
+                .iter()
+                .any(|participant| self.holds_key(&participant.public_key)),
+        }
+    }
+
+    /// Whether the caller may act as the given party of an order, the merchant being represented
+    /// by its staff
+    pub fn acts_as(
+        &self,
+        record: &Order,
+        participants: &[OrderParticipant],
+        role: ParticipantRole,
+    ) -> bool {
+        match (self, role) {
+            (Identity::Staff(staff), ParticipantRole::Merchant) => {
+                staff.merchant_id == record.merchant_id
+            }
+            (Identity::Staff(_), _) | (_, ParticipantRole::Merchant) => false,
+            (Identity::Key(_), _) => participants.iter().any(|participant| {
+                participant.role == role && self.holds_key(&participant.public_key)
+            }),
+        }
+    }
+
+    /// Role in which the caller takes part in an order, the merchant being represented by its staff
+    pub fn party_role(
+        &self,
+        record: &Order,
+        participants: &[OrderParticipant],
+    ) -> Option<ParticipantRole> {
+        match self {
+            Identity::Staff(staff) if staff.merchant_id == record.merchant_id => {
+                Some(ParticipantRole::Merchant)
+            }
+            Identity::Staff(_) => None,
+            Identity::Key(_) => participants
+                .iter()
+                .find(|participant| self.holds_key(&participant.public_key))
+                .map(|participant| participant.role),
+        }
+    }
+
+    /// Whether the caller may submit the payment of an order, as its buyer or merchant staff
+    pub fn may_pay(&self, record: &Order, participants: &[OrderParticipant]) -> bool {
+        self.acts_as(record, participants, ParticipantRole::Buyer)
+            || self.acts_as(record, participants, ParticipantRole::Merchant)
+    }
+}
+impl FromRequest for Identity {
+    type Config = ();
+    type Error = AWError;
+    type Future = Box<dyn Future<Item = Self, Error = Self::Error>>;
+
+    fn from_request(req: &HttpRequest, payload: &mut Payload) -> Self::Future {
+        let credentials = web::Data::<MerchantConfig>::extract(req)
+            .and_then(|config| authenticate(req, &config.auth).map_err(unauthorized));
+        let (api_key, bearer) = match credentials {
+            Ok(Credentials::Identity(identity)) => return Box::new(future::ok(identity)),
+            Ok(Credentials::Signed(request)) => {
+                return Box::new(verify_signed_request(req, payload, request))
+            }
+            Ok(Credentials::ApiKey(api_key)) => (api_key, false),
+            Ok(Credentials::BearerToken(token)) => (token, true),
+            Err(err) => return Box::new(future::err(err)),
+        };
+        let pool = match web::Data::<Pool>::extract(req) {
+            Ok(pool) => pool,
+            Err(err) => return Box::new(future::err(err)),
+        };
+        let session_pool = pool.clone();
+
+        // API keys of the merchants are looked up first, other bearer tokens are session tokens
+        Box::new(
+            db::execute_get_merchant_api_key(pool, api_key_digest(&api_key)).and_then(
+                move |merchant_api_key| match merchant_api_key {
+                    Some(merchant_api_key) => Either::A(future::ok(Identity::Staff(Staff {
+                        merchant_id: merchant_api_key.merchant_id,
+                        name: merchant_api_key.name,
+                    }))),
+                    None if !bearer => Either::A(future::err(unauthorized("Invalid API Key"))),
+                    None => Either::B(
+                        db::execute_get_auth_session(session_pool, api_key).and_then(|session| {
+                            match session {
+                                Some(ref session) if session.expires_at > unix_now() => {
+                                    Ok(Identity::Key(session.public_key.clone()))
+                                }
+                                _ => Err(unauthorized("Invalid Session Token")),
+                            }
+                        }),
+                    ),
+                },
+            ),
+        )
+    }
+}
+
+/// Staff account of a merchant, extracted by the routes restricted to merchant staff
+#[derive(Debug, Clone)]
+pub struct Staff {
+    pub merchant_id: i32,
+    /// Name of the staff account
+    pub name: String,
+}
+impl FromRequest for Staff {
+    type Config = ();
+    type Error = AWError;
+    type Future = Box<dyn Future<Item = Self, Error = Self::Error>>;
+
+    fn from_request(req: &HttpRequest, payload: &mut Payload) -> Self::Future {
+        Box::new(Identity::from_request(req, payload).and_then(|identity| {
+            match identity {
+                Identity::Staff(staff) => Ok(staff),
+                Identity::Key(_) => Err(AWError::from(
+                    HttpResponse::Forbidden()
+                        .reason("Merchant Staff Only")
+                        .finish(),
+                )),
+            }
+        }))
+    }
+}
+
+enum Credentials {
+    Identity(Identity),
+    /// API key which is not one of the configured staff keys, looked up among the merchant keys
+    ApiKey(String),
+    /// Bearer token which is not one of the configured staff keys, looked up among the merchant
+    /// keys then among the key holder sessions
+    BearerToken(String),
+    /// Request signed by a key holder, verified with the digest of its body
+    Signed(SignedRequest),
+}
+
+struct SignedRequest {
+    public_key: String,
+    method: String,
+    path: String,
+    timestamp: u64,
+    nonce: String,
+    signature: String,
+}
+
+fn authenticate(req: &HttpRequest, config: &AuthConfig) -> Result<Credentials, &'static str> {
+    // The configured staff keys belong to the default merchant
+    let staff = |api_key: &str| {
+        config
+            .staff_api_keys
+            .iter()
+            .find(|(_, key)| constant_time_eq(key, api_key))
+            .map(|(name, _)| {
+                Credentials::Identity(Identity::Staff(Staff {
+                    merchant_id: DEFAULT_MERCHANT_ID,
+                    name: name.clone(),
+                }))
+            })
+    };
+    if let Some(api_key) = header(req, "X-API-Key") {
+        return Ok(staff(api_key).unwrap_or_else(|| Credentials::ApiKey(api_key.to_owned())));
+    }
+    if let Some(token) = bearer_token(req) {
+        return Ok(staff(token).unwrap_or_else(|| Credentials::BearerToken(token.to_owned())));
+    }
+
+    let public_key = header(req, "X-Public-Key").ok_or("Missing Credentials")?;
+    let timestamp = header(req, "X-Timestamp")
+        .and_then(|timestamp| timestamp.parse::<u64>().ok())
+        .ok_or("Invalid Timestamp")?;
+    let nonce = header(req, "X-Nonce")
+        .filter(|nonce| is_valid_nonce(nonce))
+        .ok_or("Invalid Nonce")?;
+    let signature = header(req, "X-Signature").ok_or("Missing Signature")?;
+
+    let now = unix_now() as u64;
+    let age = if now > timestamp {
+        now - timestamp
+    } else {
+        timestamp - now
+    };
+    if age > config.max_signature_age.as_secs() {
+        return Err("Signature Expired");
+    }
+
+    let path = req
+        .uri()
+        .path_and_query()
+        .map(|path| path.as_str())
+        .unwrap_or_else(|| req.path());
+    Ok(Credentials::Signed(SignedRequest {
+        public_key: public_key.to_owned(),
+        method: req.method().as_str().to_owned(),
+        path: path.to_owned(),
+        timestamp,
+        nonce: nonce.to_owned(),
+        signature: signature.to_owned(),
+    }))
+}
+
+/// Reads the body of a signed request and verifies the signature over its digest, then hands the
+/// body over to the extractors of the route
+fn verify_signed_request(
+    req: &HttpRequest,
+    payload: &mut Payload,
+    request: SignedRequest,
+) -> impl Future<Item = Identity, Error = AWError> {
+    let data = web::Data::<MerchantConfig>::extract(req)
+        .and_then(|config| web::Data::<NonceCache>::extract(req).map(|nonces| (config, nonces)));
+    let (config, nonces) = match data {
+        Ok(data) => data,
+        Err(err) => return Either::A(future::err(err)),
+    };
+
+    let body = payload.take();
+    let (sender, receiver) = mpsc::unbounded();
+    *payload = Payload::Stream(Box::new(
+        receiver.map_err(|_| PayloadError::Incomplete(None)),
+    ));
+
+    // No route accepts a body larger than the evidence files
+    let limit = config.disputes.max_evidence_size;
+    let max_age = config.auth.max_signature_age.as_secs();
+    Either::B(
+        body.map_err(AWError::from)
+            .fold(web::BytesMut::new(), move |mut body, chunk| {
+                if body.len() + chunk.len() > limit {
+                    return Err(AWError::from(
+                        HttpResponse::PayloadTooLarge()
+                            .reason("Payload Too Large")
+                            .finish(),
+                    ));
+                }
+                body.extend_from_slice(&chunk);
+                Ok(body)
+            })
+            .and_then(move |body| {
+                let body = body.freeze();
+                let digest = hex::encode(Sha256::digest(&body));
+                // The body is forwarded before the signature is checked, the extractors of the
+                // route are not polled any further when it is rejected
+                if !body.is_empty() {
+                    let _ = sender.unbounded_send(body);
+                }
+
+                let message = request_message(
+                    &request.method,
+                    &request.path,
+                    request.timestamp,
+                    &request.nonce,
+                    &digest,
+                );
+                if !verify_key_signature(&request.public_key, &message, &request.signature) {
+                    return Err(unauthorized("Invalid Signature"));
+                }
+                if !nonces.register(
+                    &request.public_key,
+                    &request.nonce,
+                    request.timestamp + max_age,
+                ) {
+                    return Err(unauthorized("Nonce Already Used"));
+                }
+                Ok(Identity::Key(request.public_key))
+            }),
+    )
+}
+
+/// Message signed by a key holder to authenticate a request sent at the given Unix timestamp,
+/// with a nonce used once and the hex encoded SHA-256 digest of the body, e.g.
+/// `GET /order?order_id=1 1577836800 5f2b9c0e8d7a4b61 e3b0c442...b855`
+pub fn request_message(
+    method: &str,
+    path: &str,
+    timestamp: u64,
+    nonce: &str,
+    body_digest: &str,
+) -> String {
+    format!(
+        "{} {} {} {} {}",
+        method, path, timestamp, nonce, body_digest
+    )
+}
+
+/// Message signed by a key holder to open a session with the nonce of a challenge
+pub fn challenge_message(nonce: &str) -> String {
+    format!("auth-challenge:{}", nonce)
+}
+
+/// Nonces of the signed requests accepted within the signature age, shared by every worker
+#[derive(Default)]
+pub struct NonceCache {
+    /// Expiry Unix timestamp of each nonce, by public key and nonce
+    nonces: Mutex<HashMap<String, u64>>,
+}
+
+impl NonceCache {
+    /// Records the nonce of a request of the key until the given Unix timestamp, returning false
+    /// if the key already used it in a request which has not expired
+    pub fn register(&self, public_key: &str, nonce: &str, expires_at: u64) -> bool {
+        let now = unix_now() as u64;
+        let key = format!("{}:{}", public_key, nonce);
+        let mut nonces = self.nonces.lock().unwrap();
+        if !nonces.contains_key(&key) && nonces.len() >= NONCE_PRUNE_THRESHOLD {
+            nonces.retain(|_, current| *current >= now);
+        }
+
+        match nonces.get(&key) {
+            Some(current) if *current >= now => false,
+            _ => {
+                nonces.insert(key, expires_at);
+                true
+            }
+        }
+    }
+}
+
+/// Nonces of signed requests are 16 to 64 hex characters
+fn is_valid_nonce(nonce: &str) -> bool {
+    nonce.len() >= 16 && nonce.len() <= 64 && nonce.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+/// Hex encoded SHA-256 digest under which the API key of a merchant is stored
+pub fn api_key_digest(api_key: &str) -> String {
+    hex::encode(Sha256::digest(api_key.as_bytes()))
+}
+
+/// Compares two keys in a time which does not depend on where they differ, through their digests
+/// so that their lengths are not compared either
+fn constant_time_eq(key: &str, other: &str) -> bool {
+    let (key, other) = (
+        Sha256::digest(key.as_bytes()),
+        Sha256::digest(other.as_bytes()),
+    );
+    key.iter()
+        .zip(other.iter())
+        .fold(0u8, |difference, (a, b)| difference | (a ^ b))
+        == 0
+}
+
+/// Random hex encoded 32 bytes, used for challenge nonces, session tokens and API keys
+pub fn random_token() -> String {
+    let mut bytes = Uuid::new_v4().as_bytes().to_vec();
+    bytes.extend_from_slice(Uuid::new_v4().as_bytes());
+    hex::encode(bytes)
+}
+
+pub fn unix_now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs() as i64
+}
+
+pub fn unauthorized(reason: &'static str) -> AWError {
+    AWError::from(HttpResponse::Unauthorized().reason(reason).finish())
+}
+
+fn header<'a>(req: &'a HttpRequest, name: &str) -> Option<&'a str> {
+    req.headers()
+        .get(name)
+        .and_then(|value| value.to_str().ok())
+        .filter(|value| !value.is_empty())
+}
+
+fn bearer_token(req: &HttpRequest) -> Option<&str> {
+    header(req, "Authorization")
+        .filter(|value| value.starts_with("Bearer "))
+        .map(|value| value["Bearer ".len()..].trim())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::http::StatusCode;
+    use actix_web::test::{self, TestRequest};
+    use actix_web::App;
+    use chrono::NaiveDateTime;
+    use secp256k1::schnorrsig::schnorr_sign;
+    use secp256k1::{Message, PublicKey as SecpPublicKey, Secp256k1, SecretKey};
+
+    use crate::checkouts::is_checkout_buyer;
+    use crate::models::OrderStatus;
+    use crate::rate_limit::{OrderClient, RateLimits};
+
+    const STAFF_API_KEY: &str = "test-staff-key";
+
+    fn config() -> MerchantConfig {
+        let mut config = MerchantConfig::from_env();
+        config.auth.staff_api_keys = vec![("admin".to_owned(), STAFF_API_KEY.to_owned())];
+        config
+    }
+
+    fn key_pair(seed: u8) -> (SecretKey, String) {
+        let secret_key = SecretKey::from_slice(&[seed; 32]).unwrap();
+        let public_key = SecpPublicKey::from_secret_key(&Secp256k1::new(), &secret_key);
+        (secret_key, hex::encode(&public_key.serialize()[..]))
+    }
+
+    fn sign(secret_key: &SecretKey, message: &str) -> String {
+        let message = Message::from_slice(&Sha256::digest(message.as_bytes())).unwrap();
+        let signature = schnorr_sign(&Secp256k1::new(), &message, secret_key);
+        hex::encode(&signature.serialize_default()[..])
+    }
+
+    /// Request to the path signed by the key with the given nonce over the given body
+    fn signed_request(seed: u8, path: &str, nonce: &str, body: &str) -> TestRequest {
+        let (secret_key, public_key) = key_pair(seed);
+        let timestamp = unix_now() as u64;
+        let digest = hex::encode(Sha256::digest(body.as_bytes()));
+        let message = request_message("POST", path, timestamp, nonce, &digest);
+        TestRequest::post()
+            .uri(path)
+            .header("X-Public-Key", public_key)
+            .header("X-Timestamp", timestamp.to_string())
+            .header("X-Nonce", nonce)
+            .header("X-Signature", sign(&secret_key, &message))
+    }
+
+    fn order(merchant_id: i32, buyer_public_key: &str) -> Order {
+        Order {
+            order_id: "1".to_owned(),
+            status: OrderStatus::PendingPayment,
+            wallet_name: String::new(),
+            amount: 100_0000_0000,
+            buyer_public_key: buyer_public_key.to_owned(),
+            buyer_view_key: String::new(),
+            buyer_address: String::new(),
+            escrow_public_key: String::new(),
+            escrow_view_key: String::new(),
+            session_id: None,
+            payment_transaction_id: None,
+            settlement_transaction_id: None,
+            extra_view_keys: String::new(),
+            required_signatures: 2,
+            merchant_public_key: String::new(),
+            payout_address: String::new(),
+            refund_address: None,
+            split_merchant_amount: None,
+            split_buyer_amount: None,
+            arbitrated: false,
+            escrow_id: None,
+            escrow_fee_address: String::new(),
+            escrow_fee: 0,
+            escrow_fee_basis_points: 0,
+            escrow_dispute_fee: 0,
+            escrow_dispute_fee_basis_points: 0,
+            funding_transaction_id: None,
+            funding_amount: None,
+            milestone_amount: None,
+            change_public_key: None,
+            checkout_id: None,
+            payment_output_index: 0,
+            batch_id: None,
+            merchant_id,
+            created_at: NaiveDateTime::from_timestamp(0, 0),
+            updated_at: NaiveDateTime::from_timestamp(0, 0),
+            encoded_settlement_transaction: None,
+            settlement_network_fee: None,
+        }
+    }
+
+    fn participant(role: ParticipantRole, public_key: &str) -> OrderParticipant {
+        OrderParticipant {
+            id: 0,
+            order_id: "1".to_owned(),
+            role,
+            public_key: public_key.to_owned(),
+            view_key: String::new(),
+        }
+    }
+
+    fn staff(merchant_id: i32) -> Identity {
+        Identity::Staff(Staff {
+            merchant_id,
+            name: "admin".to_owned(),
+        })
+    }
+
+    /// Status of a request to a route extracting the given caller, with the state of the server
+    macro_rules! call {
+        ($caller:ty, $req:expr) => {{
+            let config = config();
+            let rate_limits = web::Data::new(RateLimits::new(&config.rate_limits));
+            let mut app = test::init_service(
+                App::new()
+                    .data(config)
+                    .register_data(web::Data::new(NonceCache::default()))
+                    .register_data(rate_limits)
+                    .route(
+                        "/order/new",
+                        web::post().to(|_: $caller, _: String| HttpResponse::Ok()),
+                    )
+                    .route(
+                        "/order/payment-proof",
+                        web::post().to(|_: $caller, _: String| HttpResponse::Ok()),
+                    )
+                    .route(
+                        "/checkout/new",
+                        web::post().to(|_: $caller, _: String| HttpResponse::Ok()),
+                    )
+                    .route("/checkout", web::get().to(|_: $caller| HttpResponse::Ok())),
+            );
+            test::call_service(&mut app, $req.to_request()).status()
+        }};
+    }
+
+    #[test]
+    fn order_creation_needs_staff() {
+        let path = "/order/new";
+        assert_eq!(
+            call!(OrderClient, TestRequest::post().uri(path)),
+            StatusCode::UNAUTHORIZED
+        );
+        assert_eq!(
+            call!(
+                OrderClient,
+                signed_request(1, path, "00112233445566778899", "order_id=1")
+                    .set_payload("order_id=1")
+            ),
+            StatusCode::FORBIDDEN
+        );
+        assert_eq!(
+            call!(
+                OrderClient,
+                TestRequest::post()
+                    .uri(path)
+                    .header("X-API-Key", STAFF_API_KEY)
+            ),
+            StatusCode::OK
+        );
+    }
+
+    #[test]
+    fn payment_proof_needs_credentials() {
+        let path = "/order/payment-proof";
+        assert_eq!(
+            call!(Identity, TestRequest::post().uri(path)),
+            StatusCode::UNAUTHORIZED
+        );
+        assert_eq!(
+            call!(
+                Identity,
+                signed_request(1, path, "00112233445566778899", "order_id=1")
+                    .set_payload("order_id=1")
+            ),
+            StatusCode::OK
+        );
+    }
+
+    #[test]
+    fn payment_proof_needs_buyer_or_merchant_staff() {
+        let (_, buyer) = key_pair(1);
+        let (_, escrow) = key_pair(2);
+        let record = order(1, &buyer);
+        let participants = vec![
+            participant(ParticipantRole::Buyer, &buyer),
+            participant(ParticipantRole::Escrow, &escrow),
+        ];
+
+        assert!(Identity::Key(buyer).may_pay(&record, &participants));
+        assert!(staff(1).may_pay(&record, &participants));
+        assert!(!Identity::Key(escrow).may_pay(&record, &participants));
+        assert!(!staff(2).may_pay(&record, &participants));
+    }
+
+    #[test]
+    fn signed_request_covers_body_and_nonce() {
+        let path = "/order/payment-proof";
+        assert_eq!(
+            call!(
+                Identity,
+                signed_request(1, path, "00112233445566778899", "order_id=1")
+                    .set_payload("order_id=2")
+            ),
+            StatusCode::UNAUTHORIZED
+        );
+        assert_eq!(
+            call!(
+                Identity,
+                signed_request(1, path, "0011", "order_id=1").set_payload("order_id=1")
+            ),
+            StatusCode::UNAUTHORIZED
+        );
+
+        let nonces = NonceCache::default();
+        let expires_at = unix_now() as u64 + 300;
+        assert!(nonces.register("key", "00112233445566778899", expires_at));
+        assert!(!nonces.register("key", "00112233445566778899", expires_at));
+        assert!(nonces.register("other", "00112233445566778899", expires_at));
+    }
+
+    #[test]
+    fn checkout_needs_credentials() {
+        assert_eq!(
+            call!(Identity, TestRequest::post().uri("/checkout/new")),
+            StatusCode::UNAUTHORIZED
+        );
+        assert_eq!(
+            call!(Identity, TestRequest::get().uri("/checkout?checkout_id=1")),
+            StatusCode::UNAUTHORIZED
+        );
+    }
+
+    #[test]
+    fn checkout_needs_buyer_of_every_order() {
+        let (_, buyer) = key_pair(1);
+        let (_, other_buyer) = key_pair(2);
+        let mut other_order = order(1, &other_buyer);
+        other_order.order_id = "2".to_owned();
+        let mut other_participant = participant(ParticipantRole::Buyer, &other_buyer);
+        other_participant.order_id = "2".to_owned();
+        let orders = vec![order(1, &buyer), other_order];
+        let participants = vec![
+            participant(ParticipantRole::Buyer, &buyer),
+            other_participant,
+        ];
+
+        assert!(is_checkout_buyer(
+            &Identity::Key(buyer.clone()),
+            &orders[..1],
+            &participants
+        ));
+        assert!(!is_checkout_buyer(
+            &Identity::Key(buyer),
+            &orders,
+            &participants
+        ));
+        assert!(!is_checkout_buyer(&staff(1), &orders[..1], &participants));
+    }
+}
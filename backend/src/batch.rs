@@ -0,0 +1,193 @@
+use parity_scale_codec::{Decode, Encode};
+
+use chain_core::init::coin::Coin;
+use chain_core::tx::data::output::TxOut;
+use chain_core::tx::data::Tx;
+
+use crate::fees::UnsignedSettlement;
+use crate::models::{Order, OrderStatus, SessionSigner};
+
+/// Status an order settled by a batch ends up in, `None` for orders which cannot be batched.
+/// Milestone releases are settled on their own since the remaining funds have to be the first
+/// output of their transaction.
+pub fn settled_status(status: OrderStatus) -> Option<OrderStatus> {
+    match status {
+        OrderStatus::Delivering => Some(OrderStatus::Completed),
+        OrderStatus::Refunding => Some(OrderStatus::Refunded),
+        OrderStatus::Splitting => Some(OrderStatus::Split),
+        _ => None,
+    }
+}
+
+/// Merges the settlements of several orders into one settlement spending the input of every
+/// order. Outputs paying the same address are aggregated and every view key of the settlements
+/// keeps its access. The network fee is left to be deducted once for the merged transaction, from
+/// the merchant payouts only.
+pub fn merge_settlements(
+    settlements: Vec<UnsignedSettlement>,
+) -> Result<UnsignedSettlement, &'static str> {
+    let mut merged = UnsignedSettlement {
+        inputs: vec![],
+        amount: Coin::zero(),
+        change: None,
+        payouts: vec![],
+        buyer_payouts: vec![],
+        fees: vec![],
+        access_policies: vec![],
+        batched: true,
+    };
+    for settlement in settlements {
+        // The change of a milestone release has to be the first output of its transaction
+        if settlement.change.is_some() {
+            return Err("Order Cannot Be Batched");
+        }
+        merged.inputs.extend(settlement.inputs);
+        merged.amount =
+            (merged.amount + settlement.amount).map_err(|_| "Invalid Settlement Amount")?;
+        for output in settlement.payouts {
+            merge_output(&mut merged.payouts, output)?;
+        }
+        for output in settlement.buyer_payouts {
+            merge_output(&mut merged.buyer_payouts, output)?;
+        }
+        for output in settlement.fees {
+            merge_output(&mut merged.fees, output)?;
+        }
+        for policy in settlement.access_policies {
+            if !merged
+                .access_policies
+                .iter()
+                .any(|merged| merged.view_key == policy.view_key)
+            {
+                merged.access_policies.push(policy);
+            }
+        }
+    }
+    Ok(merged)
+}
+
+fn merge_output(outputs: &mut Vec<TxOut>, output: TxOut) -> Result<(), &'static str> {
+    match outputs
+        .iter_mut()
+        .find(|merged| merged.address == output.address && merged.valid_from == output.valid_from)
+    {
+        Some(merged) => {
+            merged.value = (merged.value + output.value).map_err(|_| "Invalid Settlement Amount")?
+        }
+        None => outputs.push(output),
+    }
+    Ok(())
+}
+
+/// Whether every co-signer of the order's signing session has submitted its partial signature
+pub fn is_session_signed(record: &Order, signers: &[SessionSigner]) -> bool {
+    let mut session_signers = signers
+        .iter()
+        .filter(|signer| Some(&signer.session_id) == record.session_id.as_ref())
+        .peekable();
+    record.session_id.is_some()
+        && session_signers.peek().is_some()
+        && session_signers.all(|signer| !signer.partial_signature.is_empty())
+}
+
+/// Hex of the SCALE encoded batch transaction, as stored with the batch
+pub fn encode_transaction(transaction: &Tx) -> String {
+    hex::encode(transaction.encode())
+}
+
+pub fn decode_transaction(encoded_transaction: &str) -> Tx {
+    let encoded_transaction = hex::decode(encoded_transaction).unwrap();
+    Tx::decode(&mut encoded_transaction.as_slice()).expect("decode_transaction error")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fees::{deduct_fees, unsigned_transaction};
+    use chain_core::tx::data::address::ExtendedAddr;
+    use chain_core::tx::data::input::TxoPointer;
+    use chain_core::tx::fee::{LinearFee, Milli};
+
+    fn output(address: u8, value: u64) -> TxOut {
+        TxOut::new(
+            ExtendedAddr::OrTree([address; 32]),
+            Coin::new(value).unwrap(),
+        )
+    }
+
+    /// Settlement of an order funded with `amount`, paying the merchant and a fee of 10
+    fn settlement(input: u8, amount: u64) -> UnsignedSettlement {
+        let fees = vec![output(9, 10)];
+        let (payouts, buyer_payouts) = deduct_fees(vec![output(1, amount)], vec![], &fees).unwrap();
+        UnsignedSettlement {
+            inputs: vec![TxoPointer::new([input; 32], 0)],
+            amount: Coin::new(amount).unwrap(),
+            change: None,
+            payouts,
+            buyer_payouts,
+            fees,
+            access_policies: vec![],
+            batched: false,
+        }
+    }
+
+    /// Settlement of an order funded with `amount`, refunding the buyer with the given address
+    fn refund(input: u8, buyer: u8, amount: u64) -> UnsignedSettlement {
+        UnsignedSettlement {
+            payouts: vec![],
+            buyer_payouts: vec![output(buyer, amount)],
+            fees: vec![],
+            ..settlement(input, amount)
+        }
+    }
+
+    #[test]
+    fn batch_pays_one_network_fee() {
+        let merged = merge_settlements(vec![settlement(1, 1000), settlement(2, 500)]).unwrap();
+        let fee_policy = LinearFee::new(Milli::new(7, 0), Milli::new(0, 0));
+        let (transaction, network_fee) = unsigned_transaction(42, merged, &fee_policy).unwrap();
+
+        assert_eq!(u64::from(network_fee), 7);
+        assert_eq!(transaction.inputs.len(), 2);
+        let values: Vec<u64> = transaction
+            .outputs
+            .iter()
+            .map(|output| u64::from(output.value))
+            .collect();
+        assert_eq!(values, vec![1500 - 20 - 7, 20]);
+    }
+
+    #[test]
+    fn milestone_release_is_not_batched() {
+        let mut release = settlement(2, 500);
+        release.change = Some(output(3, 100));
+        assert_eq!(
+            merge_settlements(vec![settlement(1, 1000), release]).err(),
+            Some("Order Cannot Be Batched")
+        );
+    }
+
+    #[test]
+    fn batch_fee_is_paid_by_the_merchant() {
+        let fee_policy = LinearFee::new(Milli::new(7, 0), Milli::new(0, 0));
+        let merged = merge_settlements(vec![refund(1, 2, 300), settlement(3, 500)]).unwrap();
+        let (transaction, _) = unsigned_transaction(42, merged, &fee_policy).unwrap();
+
+        let values: Vec<u64> = transaction
+            .outputs
+            .iter()
+            .map(|output| u64::from(output.value))
+            .collect();
+        assert_eq!(values, vec![500 - 10 - 7, 300, 10]);
+    }
+
+    #[test]
+    fn batch_fee_is_not_paid_by_buyers() {
+        let fee_policy = LinearFee::new(Milli::new(7, 0), Milli::new(0, 0));
+        let merged = merge_settlements(vec![refund(1, 2, 300), refund(3, 4, 500)]).unwrap();
+        assert_eq!(
+            unsigned_transaction(42, merged, &fee_policy).err(),
+            Some("Network Fee Exceeds Merchant Payouts")
+        );
+    }
+}
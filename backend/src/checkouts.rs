@@ -0,0 +1,61 @@
+use chain_core::init::address::CroAddress;
+use chain_core::init::coin::Coin;
+use chain_core::tx::data::Tx;
+
+use crate::auth::Identity;
+use crate::models::{to_coin, Order, OrderParticipant, ParticipantRole};
+
+/// Parses the comma separated IDs of the orders paid by a checkout
+pub fn parse_order_ids(order_ids: &str) -> Result<Vec<String>, &'static str> {
+    let mut parsed: Vec<String> = vec![];
+    for order_id in order_ids.split(',').map(str::trim) {
+        if order_id.is_empty() || parsed.iter().any(|parsed| parsed == order_id) {
+            return Err("Invalid Order IDs");
+        }
+        parsed.push(order_id.to_string());
+    }
+    Ok(parsed)
+}
+
+/// Whether the caller holds the buyer key of every order of a checkout, which has a single buyer
+pub fn is_checkout_buyer(
+    identity: &Identity,
+    orders: &[Order],
+    participants: &[OrderParticipant],
+) -> bool {
+    !orders.is_empty()
+        && orders.iter().all(|record| {
+            let order_participants: Vec<OrderParticipant> = participants
+                .iter()
+                .filter(|participant| participant.order_id == record.order_id)
+                .cloned()
+                .collect();
+            identity.acts_as(record, &order_participants, ParticipantRole::Buyer)
+        })
+}
+
+/// Index of the output of a payment transaction funding an order's multi-sig address with the
+/// order amount
+pub fn payment_output(
+    tx: &Tx,
+    multisig_address: &str,
+    record: &Order,
+) -> Result<usize, &'static str> {
+    let index = tx
+        .outputs
+        .iter()
+        .position(|output| output.address.to_cro().unwrap() == multisig_address)
+        .ok_or("Incorrect Transaction Output Address")?;
+    if tx.outputs[index].value != to_coin(record.amount) {
+        return Err("Incorrect Transaction Output Amount");
+    }
+    Ok(index)
+}
+
+/// Amount paid by a checkout, the total of its orders
+pub fn checkout_amount(orders: &[Order]) -> Coin {
+    orders
+        .iter()
+        .try_fold(Coin::zero(), |total, record| total + to_coin(record.amount))
+        .unwrap()
+}
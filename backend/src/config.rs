@@ -0,0 +1,439 @@
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::Duration;
+
+use chain_core::init::address::CroAddress;
+use chain_core::init::coin::Coin;
+use chain_core::tx::data::address::ExtendedAddr;
+use client_common::PublicKey;
+
+/// Settings of the merchant running this backend, loaded from the environment
+#[derive(Clone)]
+pub struct MerchantConfig {
+    pub network: Network,
+    /// Long-lived wallet holding the merchant keys of every new order of the default merchant.
+    /// When unset each order gets a wallet of its own.
+    pub wallet_name: Option<String>,
+    /// Address receiving the default merchant share of settlements, unless set per order. When
+    /// unset the payout address of the order wallet is used.
+    pub payout_address: Option<ExtendedAddr>,
+    pub access_policy: AccessPolicyConfig,
+    pub sweep: SweepConfig,
+    /// Whether new orders only accept escrows of the registry which have not been revoked
+    pub require_registered_escrow: bool,
+    /// Whether settlements of several orders can be signed and broadcast as one transaction
+    pub batch_settlement: bool,
+    pub disputes: DisputeConfig,
+    pub fees: FeeConfig,
+    pub auth: AuthConfig,
+    pub refund_approval: RefundApprovalConfig,
+    pub rate_limits: RateLimitConfig,
+}
+
+/// Chain network the backend runs against
+#[derive(Clone, Copy, PartialEq)]
+pub enum Network {
+    Devnet,
+    Testnet,
+    Mainnet,
+}
+impl Network {
+    /// Human readable part of the bech32 transfer addresses of the network
+    pub fn address_prefix(self) -> &'static str {
+        match self {
+            Network::Devnet => "dcro",
+            Network::Testnet => "tcro",
+            Network::Mainnet => "cro",
+        }
+    }
+}
+impl FromStr for Network {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "devnet" => Network::Devnet,
+            "testnet" => Network::Testnet,
+            "mainnet" => Network::Mainnet,
+            _ => return Err(()),
+        })
+    }
+}
+
+/// Which view keys are granted access to the settlement transactions of an order
+#[derive(Clone)]
+pub struct AccessPolicyConfig {
+    /// View keys added to every settlement transaction, e.g. compliance auditors
+    pub auditor_view_keys: Vec<PublicKey>,
+    /// Whether the escrow view key is added to refund transactions
+    pub escrow_access_on_refund: bool,
+}
+
+/// Collection of the merchant payouts into a treasury address
+#[derive(Clone)]
+pub struct SweepConfig {
+    /// Address receiving the swept funds of the default merchant, sweeping is disabled when unset
+    pub treasury_address: Option<ExtendedAddr>,
+    /// Maximum number of outputs spent by one sweep transaction
+    pub batch_size: usize,
+    /// Interval of the background sweep job, sweeping only happens on demand when unset
+    pub interval: Option<Duration>,
+    /// Amount set aside for the fee of each sweep transaction, the unused part is returned to the
+    /// treasury address as change
+    pub fee_allowance: Coin,
+}
+
+/// Storage of the evidence attached to dispute cases
+#[derive(Clone)]
+pub struct DisputeConfig {
+    /// Directory holding the evidence files, named by the SHA-256 digest of their content
+    pub evidence_dir: PathBuf,
+    /// Maximum size in bytes of one evidence file
+    pub max_evidence_size: usize,
+}
+
+/// Credentials accepted from the API callers
+#[derive(Clone)]
+pub struct AuthConfig {
+    /// API keys of the staff accounts of the default merchant, by account name
+    pub staff_api_keys: Vec<(String, String)>,
+    /// How far the timestamp of a signed request may be from the server time
+    pub max_signature_age: Duration,
+    /// How long the nonce of an authentication challenge can be signed
+    pub challenge_ttl: Duration,
+    /// Lifetime of the sessions opened by signing a challenge
+    pub session_ttl: Duration,
+}
+
+/// Sign-off needed from merchant staff before a refund is signed
+#[derive(Clone)]
+pub struct RefundApprovalConfig {
+    /// Refunds of more than this amount need approval, no refund needs approval when unset
+    pub threshold: Option<Coin>,
+    /// Distinct staff accounts approving such a refund, the one requesting it included
+    pub required_approvals: usize,
+}
+
+/// Limits on the routes which are expensive to serve, every limit is disabled when unset
+#[derive(Clone)]
+pub struct RateLimitConfig {
+    /// Orders created by one client IP
+    pub order_per_ip: Option<RateLimit>,
+    /// Orders created by one staff account
+    pub order_per_key: Option<RateLimit>,
+    /// Requests to the signing routes from one client IP
+    pub signing_per_ip: Option<RateLimit>,
+    /// Requests to the signing routes with one API key, session or signing key
+    pub signing_per_key: Option<RateLimit>,
+    /// Reverse proxies in front of the server, each adding the address it received the request
+    /// from to `X-Forwarded-For`. The client IP is the one added by the outermost of them, the
+    /// peer address when there are none.
+    pub trusted_proxies: usize,
+    /// Orders of one buyer key which can be pending payment at the same time
+    pub max_pending_orders_per_buyer: Option<usize>,
+}
+
+/// Number of requests accepted in each window of time, given as `<requests>/<seconds>`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RateLimit {
+    pub requests: u32,
+    pub window: Duration,
+}
+impl FromStr for RateLimit {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let separator = s.find('/').ok_or(())?;
+        let requests = s[..separator].parse::<u32>().map_err(|_| ())?;
+        let seconds = s[separator + 1..].parse::<u64>().map_err(|_| ())?;
+        if requests == 0 || seconds == 0 {
+            return Err(());
+        }
+        Ok(RateLimit {
+            requests,
+            window: Duration::from_secs(seconds),
+        })
+    }
+}
+
+/// Fee outputs added to settlement transactions
+#[derive(Clone)]
+pub struct FeeConfig {
+    /// Fee of the platform on every order of the merchant
+    pub platform: Option<FeeRecipient>,
+}
+
+/// Address receiving a fee and the fee charged on each settlement path
+#[derive(Clone)]
+pub struct FeeRecipient {
+    pub address: ExtendedAddr,
+    /// Fee on deliveries, refunds and splits agreed by the parties
+    pub normal: FeeRate,
+    /// Fee on settlements following an escrow ruling
+    pub dispute: FeeRate,
+}
+
+/// Flat amount in base units or basis points of the order amount
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FeeRate {
+    Flat(Coin),
+    BasisPoints(u64),
+}
+impl FeeRate {
+    /// Fee charged on an order of the given amount, rounded down
+    pub fn fee(self, amount: Coin) -> Coin {
+        match self {
+            FeeRate::Flat(fee) => fee,
+            FeeRate::BasisPoints(basis_points) => {
+                let fee = u128::from(u64::from(amount)) * u128::from(basis_points) / 10_000;
+                Coin::new(fee as u64).unwrap()
+            }
+        }
+    }
+    /// Fee rate of a flat amount and basis points column pair, one of them being zero
+    pub fn from_columns(amount: i64, basis_points: i32) -> FeeRate {
+        if basis_points > 0 {
+            FeeRate::BasisPoints(basis_points as u64)
+        } else {
+            FeeRate::Flat(Coin::new(amount as u64).unwrap())
+        }
+    }
+    /// Flat amount and basis points columns of the fee rate
+    pub fn to_columns(self) -> (i64, i32) {
+        match self {
+            FeeRate::Flat(fee) => (u64::from(fee) as i64, 0),
+            FeeRate::BasisPoints(basis_points) => (0, basis_points as i32),
+        }
+    }
+}
+impl FromStr for FeeRate {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.ends_with("bps") {
+            let basis_points = s[..s.len() - 3].parse::<u64>().map_err(|_| ())?;
+            if basis_points > 10_000 {
+                return Err(());
+            }
+            Ok(FeeRate::BasisPoints(basis_points))
+        } else {
+            Coin::from_str(s).map(FeeRate::Flat).map_err(|_| ())
+        }
+    }
+}
+
+impl MerchantConfig {
+    pub fn from_env() -> MerchantConfig {
+        let network = std::env::var("CHAIN_NETWORK")
+            .map(|value| Network::from_str(&value).expect("Invalid CHAIN_NETWORK"))
+            .unwrap_or(Network::Devnet);
+        let wallet_name = std::env::var("MERCHANT_WALLET_NAME")
+            .ok()
+            .filter(|wallet_name| !wallet_name.is_empty());
+        let payout_address = std::env::var("MERCHANT_PAYOUT_ADDRESS")
+            .ok()
+            .filter(|address| !address.is_empty())
+            .map(|address| {
+                parse_address(&address, network).expect("Invalid MERCHANT_PAYOUT_ADDRESS")
+            });
+        let auditor_view_keys = std::env::var("AUDITOR_VIEW_KEYS").unwrap_or_default();
+        let auditor_view_keys =
+            parse_view_keys(&auditor_view_keys).expect("Invalid AUDITOR_VIEW_KEYS");
+        let escrow_access_on_refund = std::env::var("ESCROW_ACCESS_ON_REFUND")
+            .map(|value| value.parse().expect("Invalid ESCROW_ACCESS_ON_REFUND"))
+            .unwrap_or(true);
+        let treasury_address = std::env::var("TREASURY_ADDRESS")
+            .ok()
+            .filter(|address| !address.is_empty())
+            .map(|address| parse_address(&address, network).expect("Invalid TREASURY_ADDRESS"));
+        let batch_size = std::env::var("SWEEP_BATCH_SIZE")
+            .map(|value| value.parse::<usize>().expect("Invalid SWEEP_BATCH_SIZE"))
+            .unwrap_or(16);
+        assert!(batch_size > 0, "Invalid SWEEP_BATCH_SIZE");
+        let interval = std::env::var("SWEEP_INTERVAL_SECS")
+            .ok()
+            .filter(|value| !value.is_empty())
+            .map(|value| value.parse::<u64>().expect("Invalid SWEEP_INTERVAL_SECS"))
+            .filter(|seconds| *seconds > 0)
+            .map(Duration::from_secs);
+        let fee_allowance = std::env::var("SWEEP_FEE_ALLOWANCE")
+            .map(|value| Coin::from_str(&value).expect("Invalid SWEEP_FEE_ALLOWANCE"))
+            .unwrap_or_else(|_| Coin::from(1_0000_0000));
+        let evidence_dir = std::env::var("EVIDENCE_DIR")
+            .ok()
+            .filter(|value| !value.is_empty())
+            .map(PathBuf::from)
+            .unwrap_or_else(|| PathBuf::from("evidence"));
+        let max_evidence_size = std::env::var("EVIDENCE_MAX_SIZE")
+            .map(|value| value.parse::<usize>().expect("Invalid EVIDENCE_MAX_SIZE"))
+            .unwrap_or(10 * 1024 * 1024);
+
+        let platform_fee_address = std::env::var("PLATFORM_FEE_ADDRESS")
+            .ok()
+            .filter(|address| !address.is_empty())
+            .map(|address| parse_address(&address, network).expect("Invalid PLATFORM_FEE_ADDRESS"));
+        let platform_fee = std::env::var("PLATFORM_FEE")
+            .ok()
+            .filter(|value| !value.is_empty())
+            .map(|value| FeeRate::from_str(&value).expect("Invalid PLATFORM_FEE"))
+            .unwrap_or(FeeRate::Flat(Coin::zero()));
+        let platform_dispute_fee = std::env::var("PLATFORM_DISPUTE_FEE")
+            .ok()
+            .filter(|value| !value.is_empty())
+            .map(|value| FeeRate::from_str(&value).expect("Invalid PLATFORM_DISPUTE_FEE"))
+            .unwrap_or(platform_fee);
+        let require_registered_escrow = std::env::var("REQUIRE_REGISTERED_ESCROW")
+            .map(|value| value.parse().expect("Invalid REQUIRE_REGISTERED_ESCROW"))
+            .unwrap_or(false);
+        let batch_settlement = std::env::var("BATCH_SETTLEMENT")
+            .map(|value| value.parse().expect("Invalid BATCH_SETTLEMENT"))
+            .unwrap_or(false);
+        let staff_api_keys = std::env::var("STAFF_API_KEYS").unwrap_or_default();
+        let staff_api_keys = parse_staff_api_keys(&staff_api_keys).expect("Invalid STAFF_API_KEYS");
+        let max_signature_age = std::env::var("AUTH_MAX_SIGNATURE_AGE_SECS")
+            .map(|value| {
+                value
+                    .parse::<u64>()
+                    .expect("Invalid AUTH_MAX_SIGNATURE_AGE_SECS")
+            })
+            .map(Duration::from_secs)
+            .unwrap_or_else(|_| Duration::from_secs(300));
+        let challenge_ttl = std::env::var("AUTH_CHALLENGE_TTL_SECS")
+            .map(|value| {
+                value
+                    .parse::<u64>()
+                    .expect("Invalid AUTH_CHALLENGE_TTL_SECS")
+            })
+            .map(Duration::from_secs)
+            .unwrap_or_else(|_| Duration::from_secs(300));
+        let session_ttl = std::env::var("AUTH_SESSION_TTL_SECS")
+            .map(|value| value.parse::<u64>().expect("Invalid AUTH_SESSION_TTL_SECS"))
+            .map(Duration::from_secs)
+            .unwrap_or_else(|_| Duration::from_secs(900));
+        let refund_approval_threshold = std::env::var("REFUND_APPROVAL_THRESHOLD")
+            .ok()
+            .filter(|value| !value.is_empty())
+            .map(|value| Coin::from_str(&value).expect("Invalid REFUND_APPROVAL_THRESHOLD"));
+        let required_approvals = std::env::var("REFUND_APPROVALS_REQUIRED")
+            .map(|value| {
+                value
+                    .parse::<usize>()
+                    .expect("Invalid REFUND_APPROVALS_REQUIRED")
+            })
+            .unwrap_or(2);
+        assert!(required_approvals > 1, "Invalid REFUND_APPROVALS_REQUIRED");
+        let rate_limit = |name: &str| {
+            std::env::var(name)
+                .ok()
+                .filter(|value| !value.is_empty())
+                .map(|value| {
+                    RateLimit::from_str(&value).unwrap_or_else(|_| panic!("Invalid {}", name))
+                })
+        };
+        let trusted_proxies = std::env::var("RATE_LIMIT_TRUSTED_PROXIES")
+            .map(|value| {
+                value
+                    .parse::<usize>()
+                    .expect("Invalid RATE_LIMIT_TRUSTED_PROXIES")
+            })
+            .unwrap_or(0);
+        let max_pending_orders_per_buyer = std::env::var("MAX_PENDING_ORDERS_PER_BUYER")
+            .ok()
+            .filter(|value| !value.is_empty())
+            .map(|value| {
+                value
+                    .parse::<usize>()
+                    .expect("Invalid MAX_PENDING_ORDERS_PER_BUYER")
+            });
+
+        MerchantConfig {
+            network,
+            wallet_name,
+            payout_address,
+            access_policy: AccessPolicyConfig {
+                auditor_view_keys,
+                escrow_access_on_refund,
+            },
+            sweep: SweepConfig {
+                treasury_address,
+                batch_size,
+                interval,
+                fee_allowance,
+            },
+            require_registered_escrow,
+            batch_settlement,
+            disputes: DisputeConfig {
+                evidence_dir,
+                max_evidence_size,
+            },
+            fees: FeeConfig {
+                platform: platform_fee_address.map(|address| FeeRecipient {
+                    address,
+                    normal: platform_fee,
+                    dispute: platform_dispute_fee,
+                }),
+            },
+            auth: AuthConfig {
+                staff_api_keys,
+                max_signature_age,
+                challenge_ttl,
+                session_ttl,
+            },
+            refund_approval: RefundApprovalConfig {
+                threshold: refund_approval_threshold,
+                required_approvals,
+            },
+            rate_limits: RateLimitConfig {
+                order_per_ip: rate_limit("ORDER_RATE_LIMIT_PER_IP"),
+                order_per_key: rate_limit("ORDER_RATE_LIMIT_PER_KEY"),
+                signing_per_ip: rate_limit("SIGNING_RATE_LIMIT_PER_IP"),
+                signing_per_key: rate_limit("SIGNING_RATE_LIMIT_PER_KEY"),
+                trusted_proxies,
+                max_pending_orders_per_buyer,
+            },
+        }
+    }
+}
+
+/// Parses a comma separated list of `name:api_key` entries of the merchant staff accounts, empty
+/// entries are ignored. Errors do not echo the entries, which hold secrets.
+pub fn parse_staff_api_keys(staff_api_keys: &str) -> Result<Vec<(String, String)>, ()> {
+    let mut parsed: Vec<(String, String)> = vec![];
+    for entry in staff_api_keys
+        .split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+    {
+        let separator = entry.find(':').ok_or(())?;
+        let (name, api_key) = (&entry[..separator], &entry[separator + 1..]);
+        if name.is_empty()
+            || api_key.is_empty()
+            || parsed
+                .iter()
+                .any(|(other_name, other_key)| other_name == name || other_key == api_key)
+        {
+            return Err(());
+        }
+        parsed.push((name.to_owned(), api_key.to_owned()));
+    }
+    Ok(parsed)
+}
+
+/// Parses a comma separated list of hex encoded view keys, empty entries are ignored
+pub fn parse_view_keys(view_keys: &str) -> Result<Vec<PublicKey>, String> {
+    view_keys
+        .split(',')
+        .map(str::trim)
+        .filter(|view_key| !view_key.is_empty())
+        .map(|view_key| PublicKey::from_str(view_key).map_err(|_| view_key.to_owned()))
+        .collect()
+}
+
+/// Parses a bech32 transfer address, rejecting addresses of other networks
+pub fn parse_address(address: &str, network: Network) -> Result<ExtendedAddr, ()> {
+    let prefix = address.rfind('1').map(|separator| &address[..separator]);
+    if prefix != Some(network.address_prefix()) {
+        return Err(());
+    }
+    ExtendedAddr::from_cro(address).map_err(|_| ())
+}
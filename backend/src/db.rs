@@ -5,9 +5,30 @@ use diesel::r2d2::{self, ConnectionManager};
 use failure::Error;
 use futures::Future;
 
-use crate::models::{Order, OrderStatus};
+use crate::models::{
+    parse_base_units, AuthChallenge, AuthSession, BatchSettlement, BatchStatus, Checkout,
+    CheckoutStatus, DisputeCase, DisputeEvidence, DisputeMessage, DisputeStatus, Escrow,
+    EscrowStatus, Merchant, MerchantApiKey, MerchantPolicies, Milestone, MilestoneStatus,
+    NewAuthSession, NewCheckout, NewDisputeCase, NewDisputeEvidence, NewDisputeMessage, NewEscrow,
+    NewMerchant, NewMerchantApiKey, NewMilestone, NewOrder, NewOrderEvent, NewOrderParticipant,
+    NewRefundApproval, NewRuling, NewSessionSigner, NewSettlementBatch, NewSplitProposal, Order,
+    OrderEvent, OrderEventKind, OrderParticipant, OrderStatus, ParticipantRole, RefundApproval,
+    Ruling, SessionSigner, SettlementBatch, SplitProposal, SplitProposalStatus,
+};
+use crate::order_query::{Cursor, CursorValue, OrderQuery, SortField};
 
-type Pool = r2d2::Pool<ConnectionManager<SqliteConnection>>;
+#[cfg(all(feature = "sqlite", feature = "postgres"))]
+compile_error!("features `sqlite` and `postgres` are mutually exclusive");
+#[cfg(not(any(feature = "sqlite", feature = "postgres")))]
+compile_error!("either feature `sqlite` or `postgres` must be enabled");
+
+/// Connection of the database backend selected by the `sqlite` or `postgres` feature
+#[cfg(feature = "sqlite")]
+pub type DbConnection = SqliteConnection;
+#[cfg(feature = "postgres")]
+pub type DbConnection = PgConnection;
+
+pub type Pool = r2d2::Pool<ConnectionManager<DbConnection>>;
 
 pub fn execute_is_order_exist(
     pool: web::Data<Pool>,
@@ -17,17 +38,24 @@ pub fn execute_is_order_exist(
 }
 pub fn execute_register_order(
     pool: web::Data<Pool>,
-    order: Order,
+    order: NewOrder,
+    participants: Vec<NewOrderParticipant>,
+    milestones: Vec<NewMilestone>,
+    max_pending_orders: Option<usize>,
 ) -> impl Future<Item = bool, Error = AWError> {
-    web::block(move || register_order(pool, order)).from_err()
+    web::block(move || register_order(pool, order, participants, milestones, max_pending_orders))
+        .from_err()
 }
 pub fn execute_store_payment_transaction_id(
     pool: web::Data<Pool>,
     order_id: String,
     payment_transaction_id: String,
+    payment_output_index: i32,
 ) -> impl Future<Item = bool, Error = AWError> {
-    web::block(move || store_payment_transaction_id(pool, order_id, payment_transaction_id))
-        .from_err()
+    web::block(move || {
+        store_payment_transaction_id(pool, order_id, payment_transaction_id, payment_output_index)
+    })
+    .from_err()
 }
 pub fn execute_get_order_by_id(
     pool: web::Data<Pool>,
@@ -42,25 +70,447 @@ pub fn execute_update_order_status(
 ) -> impl Future<Item = bool, Error = AWError> {
     web::block(move || update_order_status(pool, order_id, status)).from_err()
 }
-pub fn execute_store_exchanged_data(
+pub fn execute_start_settlement(
+    pool: web::Data<Pool>,
+    order_id: String,
+    status: OrderStatus,
+) -> impl Future<Item = bool, Error = AWError> {
+    web::block(move || start_settlement(pool, order_id, status)).from_err()
+}
+pub fn execute_request_refund_approval(
+    pool: web::Data<Pool>,
+    approval: NewRefundApproval,
+) -> impl Future<Item = bool, Error = AWError> {
+    web::block(move || request_refund_approval(pool, approval)).from_err()
+}
+pub fn execute_approve_refund(
+    pool: web::Data<Pool>,
+    approval: NewRefundApproval,
+    required_approvals: usize,
+) -> impl Future<Item = Vec<RefundApproval>, Error = AWError> {
+    web::block(move || approve_refund(pool, approval, required_approvals)).from_err()
+}
+pub fn execute_get_refund_approvals(
+    pool: web::Data<Pool>,
+    order_id: String,
+) -> impl Future<Item = Vec<RefundApproval>, Error = AWError> {
+    web::block(move || get_refund_approvals(pool, order_id)).from_err()
+}
+pub fn execute_get_order_participants(
+    pool: web::Data<Pool>,
+    order_id: String,
+) -> impl Future<Item = Vec<OrderParticipant>, Error = AWError> {
+    web::block(move || get_order_participants(pool, order_id)).from_err()
+}
+pub fn execute_start_signing_session(
     pool: web::Data<Pool>,
     order_id: String,
     session_id: String,
     settlement_transaction_id: String,
+    encoded_settlement_transaction: String,
+    settlement_network_fee: Option<i64>,
+    signers: Vec<NewSessionSigner>,
+) -> impl Future<Item = bool, Error = AWError> {
+    web::block(move || {
+        start_signing_session(
+            pool,
+            order_id,
+            session_id,
+            settlement_transaction_id,
+            encoded_settlement_transaction,
+            settlement_network_fee,
+            signers,
+        )
+    })
+    .from_err()
+}
+pub fn execute_get_session_signers(
+    pool: web::Data<Pool>,
+    session_id: String,
+) -> impl Future<Item = Vec<SessionSigner>, Error = AWError> {
+    web::block(move || get_session_signers(pool, session_id)).from_err()
+}
+pub fn execute_store_signer_commitment(
+    pool: web::Data<Pool>,
+    session_id: String,
+    public_key: String,
+    commitment: String,
+) -> impl Future<Item = bool, Error = AWError> {
+    web::block(move || store_signer_commitment(pool, session_id, public_key, commitment)).from_err()
+}
+pub fn execute_store_signer_nonce(
+    pool: web::Data<Pool>,
+    session_id: String,
+    public_key: String,
+    nonce: String,
+) -> impl Future<Item = bool, Error = AWError> {
+    web::block(move || store_signer_nonce(pool, session_id, public_key, nonce)).from_err()
+}
+pub fn execute_store_signer_partial_signature(
+    pool: web::Data<Pool>,
+    session_id: String,
+    public_key: String,
+    partial_signature: String,
+) -> impl Future<Item = bool, Error = AWError> {
+    web::block(move || {
+        store_signer_partial_signature(pool, session_id, public_key, partial_signature)
+    })
+    .from_err()
+}
+pub fn execute_store_merchant_public_key(
+    pool: web::Data<Pool>,
+    order_id: String,
+    merchant_public_key: String,
+) -> impl Future<Item = bool, Error = AWError> {
+    web::block(move || store_merchant_public_key(pool, order_id, merchant_public_key)).from_err()
+}
+pub fn execute_change_refund_address(
+    pool: web::Data<Pool>,
+    order_id: String,
+    refund_address: String,
+    detail: String,
+) -> impl Future<Item = bool, Error = AWError> {
+    web::block(move || change_refund_address(pool, order_id, refund_address, detail)).from_err()
+}
+pub fn execute_propose_split(
+    pool: web::Data<Pool>,
+    proposal: NewSplitProposal,
+) -> impl Future<Item = SplitProposal, Error = AWError> {
+    web::block(move || propose_split(pool, proposal)).from_err()
+}
+pub fn execute_get_proposed_split(
+    pool: web::Data<Pool>,
+    order_id: String,
+) -> impl Future<Item = Option<SplitProposal>, Error = AWError> {
+    web::block(move || get_proposed_split(pool, order_id)).from_err()
+}
+pub fn execute_get_latest_split(
+    pool: web::Data<Pool>,
+    order_id: String,
+) -> impl Future<Item = Option<SplitProposal>, Error = AWError> {
+    web::block(move || get_latest_split(pool, order_id)).from_err()
+}
+pub fn execute_accept_split(
+    pool: web::Data<Pool>,
+    proposal: SplitProposal,
+    acceptance_signature: String,
+    status: OrderStatus,
+) -> impl Future<Item = bool, Error = AWError> {
+    web::block(move || accept_split(pool, proposal, acceptance_signature, status)).from_err()
+}
+pub fn execute_escalate_order(
+    pool: web::Data<Pool>,
+    order_id: String,
+    opened_by: ParticipantRole,
+    detail: String,
+) -> impl Future<Item = DisputeCase, Error = AWError> {
+    web::block(move || escalate_order(pool, order_id, opened_by, detail)).from_err()
+}
+pub fn execute_record_ruling(
+    pool: web::Data<Pool>,
+    ruling: NewRuling,
+    status: OrderStatus,
+) -> impl Future<Item = Ruling, Error = AWError> {
+    web::block(move || record_ruling(pool, ruling, status)).from_err()
+}
+pub fn execute_get_latest_ruling(
+    pool: web::Data<Pool>,
+    order_id: String,
+) -> impl Future<Item = Option<Ruling>, Error = AWError> {
+    web::block(move || get_latest_ruling(pool, order_id)).from_err()
+}
+pub fn execute_open_dispute(
+    pool: web::Data<Pool>,
+    dispute: NewDisputeCase,
+    author_message: Option<String>,
+) -> impl Future<Item = DisputeCase, Error = AWError> {
+    web::block(move || open_dispute(pool, dispute, author_message)).from_err()
+}
+pub fn execute_get_active_dispute(
+    pool: web::Data<Pool>,
+    order_id: String,
+) -> impl Future<Item = Option<DisputeCase>, Error = AWError> {
+    web::block(move || get_active_dispute(pool, order_id)).from_err()
+}
+pub fn execute_get_dispute(
+    pool: web::Data<Pool>,
+    case_id: i32,
+) -> impl Future<Item = Option<DisputeCase>, Error = AWError> {
+    web::block(move || get_dispute(pool, case_id)).from_err()
+}
+pub fn execute_get_order_disputes(
+    pool: web::Data<Pool>,
+    order_id: String,
+) -> impl Future<Item = Vec<DisputeCase>, Error = AWError> {
+    web::block(move || get_order_disputes(pool, order_id)).from_err()
+}
+pub fn execute_get_dispute_log(
+    pool: web::Data<Pool>,
+    case_id: i32,
+) -> impl Future<Item = (Vec<DisputeEvidence>, Vec<DisputeMessage>), Error = AWError> {
+    web::block(move || get_dispute_log(pool, case_id)).from_err()
+}
+pub fn execute_get_evidence_orders(
+    pool: web::Data<Pool>,
+    digest: String,
+) -> impl Future<Item = (Vec<Order>, Vec<OrderParticipant>), Error = AWError> {
+    web::block(move || get_evidence_orders(pool, digest)).from_err()
+}
+pub fn execute_add_dispute_message(
+    pool: web::Data<Pool>,
+    message: NewDisputeMessage,
+) -> impl Future<Item = Option<DisputeMessage>, Error = AWError> {
+    web::block(move || add_dispute_message(pool, message)).from_err()
+}
+pub fn execute_add_dispute_evidence(
+    pool: web::Data<Pool>,
+    evidence: NewDisputeEvidence,
+) -> impl Future<Item = DisputeEvidence, Error = AWError> {
+    web::block(move || add_dispute_evidence(pool, evidence)).from_err()
+}
+pub fn execute_update_dispute_status(
+    pool: web::Data<Pool>,
+    case_id: i32,
+    status: DisputeStatus,
+) -> impl Future<Item = bool, Error = AWError> {
+    web::block(move || update_dispute_status(pool, case_id, status)).from_err()
+}
+pub fn execute_get_escrows(
+    pool: web::Data<Pool>,
+) -> impl Future<Item = Vec<Escrow>, Error = AWError> {
+    web::block(move || get_escrows(pool)).from_err()
+}
+pub fn execute_register_escrow(
+    pool: web::Data<Pool>,
+    escrow: NewEscrow,
+) -> impl Future<Item = Escrow, Error = AWError> {
+    web::block(move || register_escrow(pool, escrow)).from_err()
+}
+pub fn execute_revoke_escrow(
+    pool: web::Data<Pool>,
+    escrow_id: i32,
+) -> impl Future<Item = bool, Error = AWError> {
+    web::block(move || revoke_escrow(pool, escrow_id)).from_err()
+}
+pub fn execute_get_merchant(
+    pool: web::Data<Pool>,
+    merchant_id: i32,
+) -> impl Future<Item = Option<Merchant>, Error = AWError> {
+    web::block(move || get_merchant(pool, merchant_id)).from_err()
+}
+pub fn execute_get_merchants(
+    pool: web::Data<Pool>,
+) -> impl Future<Item = Vec<Merchant>, Error = AWError> {
+    web::block(move || get_merchants(pool)).from_err()
+}
+pub fn execute_register_merchant(
+    pool: web::Data<Pool>,
+    merchant: NewMerchant,
+) -> impl Future<Item = Merchant, Error = AWError> {
+    web::block(move || register_merchant(pool, merchant)).from_err()
+}
+pub fn execute_set_merchant_policies(
+    pool: web::Data<Pool>,
+    merchant_id: i32,
+    policies: MerchantPolicies,
+) -> impl Future<Item = Merchant, Error = AWError> {
+    web::block(move || set_merchant_policies(pool, merchant_id, policies)).from_err()
+}
+pub fn execute_get_merchant_api_key(
+    pool: web::Data<Pool>,
+    key_digest: String,
+) -> impl Future<Item = Option<MerchantApiKey>, Error = AWError> {
+    web::block(move || get_merchant_api_key(pool, key_digest)).from_err()
+}
+pub fn execute_count_merchant_api_keys(
+    pool: web::Data<Pool>,
+) -> impl Future<Item = i64, Error = AWError> {
+    web::block(move || count_merchant_api_keys(pool)).from_err()
+}
+pub fn execute_add_merchant_api_key(
+    pool: web::Data<Pool>,
+    api_key: NewMerchantApiKey,
+) -> impl Future<Item = MerchantApiKey, Error = AWError> {
+    web::block(move || add_merchant_api_key(pool, api_key)).from_err()
+}
+pub fn execute_revoke_merchant_api_key(
+    pool: web::Data<Pool>,
+    api_key_id: i32,
+) -> impl Future<Item = bool, Error = AWError> {
+    web::block(move || revoke_merchant_api_key(pool, api_key_id)).from_err()
+}
+pub fn execute_get_milestones(
+    pool: web::Data<Pool>,
+    order_id: String,
+) -> impl Future<Item = Vec<Milestone>, Error = AWError> {
+    web::block(move || get_milestones(pool, order_id)).from_err()
+}
+pub fn execute_start_milestone_release(
+    pool: web::Data<Pool>,
+    order_id: String,
+    milestone_amount: i64,
+    change_public_key: String,
 ) -> impl Future<Item = bool, Error = AWError> {
-    web::block(move || store_submit_data(pool, order_id, session_id, settlement_transaction_id))
+    web::block(move || start_milestone_release(pool, order_id, milestone_amount, change_public_key))
         .from_err()
 }
+pub fn execute_release_milestone(
+    pool: web::Data<Pool>,
+    record: Order,
+    remaining_amount: Option<i64>,
+) -> impl Future<Item = bool, Error = AWError> {
+    web::block(move || release_milestone(pool, record, remaining_amount)).from_err()
+}
+pub fn execute_get_checkout(
+    pool: web::Data<Pool>,
+    checkout_id: String,
+) -> impl Future<Item = Option<Checkout>, Error = AWError> {
+    web::block(move || get_checkout(pool, checkout_id)).from_err()
+}
+pub fn execute_create_checkout(
+    pool: web::Data<Pool>,
+    checkout: NewCheckout,
+    order_ids: Vec<String>,
+) -> impl Future<Item = Checkout, Error = AWError> {
+    web::block(move || create_checkout(pool, checkout, order_ids)).from_err()
+}
+pub fn execute_store_checkout_payment(
+    pool: web::Data<Pool>,
+    checkout_id: String,
+    payment_transaction_id: String,
+    payment_outputs: Vec<(String, i32)>,
+) -> impl Future<Item = bool, Error = AWError> {
+    web::block(move || {
+        store_checkout_payment(pool, checkout_id, payment_transaction_id, payment_outputs)
+    })
+    .from_err()
+}
+pub fn execute_get_orders_by_ids(
+    pool: web::Data<Pool>,
+    order_ids: Vec<String>,
+) -> impl Future<Item = Vec<Order>, Error = AWError> {
+    web::block(move || get_orders_by_ids(pool, order_ids)).from_err()
+}
+pub fn execute_get_participants_of_orders(
+    pool: web::Data<Pool>,
+    order_ids: Vec<String>,
+) -> impl Future<Item = Vec<OrderParticipant>, Error = AWError> {
+    web::block(move || get_participants_of_orders(pool, order_ids)).from_err()
+}
+pub fn execute_get_checkout_orders(
+    pool: web::Data<Pool>,
+    checkout_id: String,
+) -> impl Future<Item = Vec<Order>, Error = AWError> {
+    web::block(move || get_checkout_orders(pool, checkout_id)).from_err()
+}
+pub fn execute_create_batch(
+    pool: web::Data<Pool>,
+    batch: NewSettlementBatch,
+    order_ids: Vec<String>,
+) -> impl Future<Item = SettlementBatch, Error = AWError> {
+    web::block(move || create_batch(pool, batch, order_ids)).from_err()
+}
+pub fn execute_get_batch(
+    pool: web::Data<Pool>,
+    batch_id: i32,
+) -> impl Future<Item = Option<SettlementBatch>, Error = AWError> {
+    web::block(move || get_batch(pool, batch_id)).from_err()
+}
+pub fn execute_get_batch_settlement(
+    pool: web::Data<Pool>,
+    batch_id: i32,
+) -> impl Future<Item = Option<BatchSettlement>, Error = AWError> {
+    web::block(move || get_batch_settlement(pool, batch_id)).from_err()
+}
+pub fn execute_complete_batch(
+    pool: web::Data<Pool>,
+    batch_id: i32,
+    settled: Vec<(String, OrderStatus)>,
+) -> impl Future<Item = bool, Error = AWError> {
+    web::block(move || complete_batch(pool, batch_id, settled)).from_err()
+}
+pub fn execute_cancel_batch(
+    pool: web::Data<Pool>,
+    batch_id: i32,
+) -> impl Future<Item = bool, Error = AWError> {
+    web::block(move || cancel_batch(pool, batch_id)).from_err()
+}
+pub fn execute_is_participant_key(
+    pool: web::Data<Pool>,
+    public_key: String,
+) -> impl Future<Item = bool, Error = AWError> {
+    web::block(move || is_participant_key(pool, public_key)).from_err()
+}
+pub fn execute_create_auth_challenge(
+    pool: web::Data<Pool>,
+    challenge: AuthChallenge,
+    now: i64,
+) -> impl Future<Item = bool, Error = AWError> {
+    web::block(move || create_auth_challenge(pool, challenge, now)).from_err()
+}
+pub fn execute_take_auth_challenge(
+    pool: web::Data<Pool>,
+    nonce: String,
+) -> impl Future<Item = Option<AuthChallenge>, Error = AWError> {
+    web::block(move || take_auth_challenge(pool, nonce)).from_err()
+}
+pub fn execute_create_auth_session(
+    pool: web::Data<Pool>,
+    session: NewAuthSession,
+    now: i64,
+) -> impl Future<Item = bool, Error = AWError> {
+    web::block(move || create_auth_session(pool, session, now)).from_err()
+}
+pub fn execute_get_auth_session(
+    pool: web::Data<Pool>,
+    token: String,
+) -> impl Future<Item = Option<AuthSession>, Error = AWError> {
+    web::block(move || get_auth_session(pool, token)).from_err()
+}
 pub fn execute_get_orders_by_status(
     pool: web::Data<Pool>,
+    merchant_id: i32,
     status_list: Vec<OrderStatus>,
 ) -> impl Future<Item = Vec<Order>, Error = AWError> {
-    web::block(move || get_orders_by_status(pool, status_list)).from_err()
+    web::block(move || get_orders_by_status(pool, merchant_id, status_list)).from_err()
+}
+pub fn execute_query_orders(
+    pool: web::Data<Pool>,
+    merchant_id: i32,
+    query: OrderQuery,
+) -> impl Future<Item = (Vec<Order>, bool), Error = AWError> {
+    web::block(move || query_orders(pool, merchant_id, query)).from_err()
+}
+pub fn execute_count_pending_orders(
+    pool: web::Data<Pool>,
+    buyer_public_key: String,
+) -> impl Future<Item = i64, Error = AWError> {
+    web::block(move || count_pending_orders(pool, buyer_public_key)).from_err()
+}
+pub fn execute_get_wallet_names(
+    pool: web::Data<Pool>,
+    merchant_id: i32,
+) -> impl Future<Item = Vec<String>, Error = AWError> {
+    web::block(move || get_wallet_names(pool, merchant_id)).from_err()
+}
+pub fn execute_get_order_events(
+    pool: web::Data<Pool>,
+    order_id: String,
+) -> impl Future<Item = Vec<OrderEvent>, Error = AWError> {
+    web::block(move || get_order_events(pool, order_id)).from_err()
+}
+pub fn execute_record_order_event(
+    pool: web::Data<Pool>,
+    order_id: String,
+    event: OrderEventKind,
+    detail: String,
+) -> impl Future<Item = bool, Error = AWError> {
+    web::block(move || record_order_event(pool, order_id, event, detail)).from_err()
 }
 
 fn is_order_exist(pool: web::Data<Pool>, id: String) -> Result<bool, Error> {
     use crate::schema::orders::dsl::*;
-    let conn: &SqliteConnection = &pool.get().unwrap();
+    let conn: &DbConnection = &pool.get().unwrap();
 
     let result = orders.filter(order_id.eq(&id)).first::<Order>(conn);
     match result {
@@ -72,27 +522,84 @@ fn is_order_exist(pool: web::Data<Pool>, id: String) -> Result<bool, Error> {
     }
 }
 
-fn register_order(pool: web::Data<Pool>, order: Order) -> Result<bool, Error> {
-    use crate::schema::orders;
-    let conn: &SqliteConnection = &pool.get().unwrap();
+/// Registers an order unless its buyer already has `max_pending_orders` orders pending payment,
+/// returning whether it was registered
+fn register_order(
+    pool: web::Data<Pool>,
+    order: NewOrder,
+    participants: Vec<NewOrderParticipant>,
+    new_milestones: Vec<NewMilestone>,
+    max_pending_orders: Option<usize>,
+) -> Result<bool, Error> {
+    use crate::schema::{milestones, order_participants, orders};
+    let conn: &DbConnection = &pool.get().unwrap();
 
-    diesel::insert_into(orders::table)
-        .values(&order)
-        .execute(conn)
-        .expect("Error saving new post");
-    Ok(true)
+    let result = buyer_transaction(conn, &order.buyer_public_key, || {
+        if let Some(max_pending_orders) = max_pending_orders {
+            let pending = pending_order_count(conn, &order.buyer_public_key)?;
+            if pending as usize >= max_pending_orders {
+                return Ok(false);
+            }
+        }
+        diesel::insert_into(orders::table)
+            .values(&order)
+            .execute(conn)?;
+        diesel::insert_into(order_participants::table)
+            .values(&participants)
+            .execute(conn)?;
+        diesel::insert_into(milestones::table)
+            .values(&new_milestones)
+            .execute(conn)?;
+        Ok(true)
+    })
+    .expect("Error saving new post");
+    Ok(result)
+}
+
+/// Runs a transaction counting and adding the orders of a buyer, one buyer transaction at a time
+/// so that concurrent orders cannot exceed the pending orders cap. SQLite takes its write lock
+/// up front, PostgreSQL a lock on the buyer key held until the end of the transaction.
+#[cfg(feature = "sqlite")]
+fn buyer_transaction<T, F>(
+    conn: &DbConnection,
+    _buyer_public_key: &str,
+    f: F,
+) -> Result<T, diesel::result::Error>
+where
+    F: FnOnce() -> Result<T, diesel::result::Error>,
+{
+    conn.immediate_transaction(f)
+}
+
+#[cfg(feature = "postgres")]
+fn buyer_transaction<T, F>(
+    conn: &DbConnection,
+    buyer_public_key: &str,
+    f: F,
+) -> Result<T, diesel::result::Error>
+where
+    F: FnOnce() -> Result<T, diesel::result::Error>,
+{
+    conn.transaction(|| {
+        diesel::sql_query("SELECT pg_advisory_xact_lock(hashtext($1))")
+            .bind::<diesel::sql_types::Text, _>(buyer_public_key)
+            .execute(conn)?;
+        f()
+    })
 }
 
 fn store_payment_transaction_id(
     pool: web::Data<Pool>,
     affected_order_id: String,
     transaction_id: String,
+    output_index: i32,
 ) -> Result<bool, Error> {
     use crate::schema::orders::dsl::*;
-    let conn: &SqliteConnection = &pool.get().unwrap();
+    let conn: &DbConnection = &pool.get().unwrap();
     diesel::update(orders.filter(order_id.eq(&affected_order_id)))
         .set((
             payment_transaction_id.eq(&transaction_id),
+            payment_output_index.eq(output_index),
             status.eq(OrderStatus::PendingResponse),
         ))
         .execute(conn)
@@ -100,27 +607,112 @@ fn store_payment_transaction_id(
     Ok(true)
 }
 
-fn store_submit_data(
+fn start_signing_session(
     pool: web::Data<Pool>,
     affected_order_id: String,
     new_session_id: String,
     new_settlement_transaction_id: String,
+    new_encoded_settlement_transaction: String,
+    new_settlement_network_fee: Option<i64>,
+    signers: Vec<NewSessionSigner>,
 ) -> Result<bool, Error> {
     use crate::schema::orders::dsl::*;
-    let conn: &SqliteConnection = &pool.get().unwrap();
-    diesel::update(orders.filter(order_id.eq(&affected_order_id)))
-        .set((
-            session_id.eq(&new_session_id),
-            settlement_transaction_id.eq(&new_settlement_transaction_id),
-        ))
-        .execute(conn)
-        .expect("store_submit_data error");
+    use crate::schema::session_signers;
+    let conn: &DbConnection = &pool.get().unwrap();
+
+    conn.transaction::<_, diesel::result::Error, _>(|| {
+        // The session signs and broadcasts exactly the transaction it started with
+        diesel::update(orders.filter(order_id.eq(&affected_order_id)))
+            .set((
+                session_id.eq(&new_session_id),
+                settlement_transaction_id.eq(&new_settlement_transaction_id),
+                encoded_settlement_transaction.eq(&new_encoded_settlement_transaction),
+                settlement_network_fee.eq(new_settlement_network_fee),
+            ))
+            .execute(conn)?;
+        diesel::insert_into(session_signers::table)
+            .values(&signers)
+            .execute(conn)?;
+        Ok(())
+    })
+    .expect("start_signing_session error");
+    Ok(true)
+}
+
+fn get_session_signers(
+    pool: web::Data<Pool>,
+    affected_session_id: String,
+) -> Result<Vec<SessionSigner>, Error> {
+    use crate::schema::session_signers::dsl::*;
+    let conn: &DbConnection = &pool.get().unwrap();
+    let result = session_signers
+        .filter(session_id.eq(&affected_session_id))
+        .order(id)
+        .load::<SessionSigner>(conn)
+        .expect("get_session_signers error");
+    Ok(result)
+}
+
+fn store_signer_commitment(
+    pool: web::Data<Pool>,
+    affected_session_id: String,
+    signer_public_key: String,
+    new_commitment: String,
+) -> Result<bool, Error> {
+    use crate::schema::session_signers::dsl::*;
+    let conn: &DbConnection = &pool.get().unwrap();
+    diesel::update(
+        session_signers
+            .filter(session_id.eq(&affected_session_id))
+            .filter(public_key.eq(&signer_public_key)),
+    )
+    .set(commitment.eq(&new_commitment))
+    .execute(conn)
+    .expect("store_signer_commitment error");
+    Ok(true)
+}
+
+fn store_signer_nonce(
+    pool: web::Data<Pool>,
+    affected_session_id: String,
+    signer_public_key: String,
+    new_nonce: String,
+) -> Result<bool, Error> {
+    use crate::schema::session_signers::dsl::*;
+    let conn: &DbConnection = &pool.get().unwrap();
+    diesel::update(
+        session_signers
+            .filter(session_id.eq(&affected_session_id))
+            .filter(public_key.eq(&signer_public_key)),
+    )
+    .set(nonce.eq(&new_nonce))
+    .execute(conn)
+    .expect("store_signer_nonce error");
+    Ok(true)
+}
+
+fn store_signer_partial_signature(
+    pool: web::Data<Pool>,
+    affected_session_id: String,
+    signer_public_key: String,
+    new_partial_signature: String,
+) -> Result<bool, Error> {
+    use crate::schema::session_signers::dsl::*;
+    let conn: &DbConnection = &pool.get().unwrap();
+    diesel::update(
+        session_signers
+            .filter(session_id.eq(&affected_session_id))
+            .filter(public_key.eq(&signer_public_key)),
+    )
+    .set(partial_signature.eq(&new_partial_signature))
+    .execute(conn)
+    .expect("store_signer_partial_signature error");
     Ok(true)
 }
 
 fn get_order_by_id(pool: web::Data<Pool>, id: String) -> Result<Order, Error> {
     use crate::schema::orders::dsl::*;
-    let conn: &SqliteConnection = &pool.get().unwrap();
+    let conn: &DbConnection = &pool.get().unwrap();
     let result = orders
         .filter(order_id.eq(&id))
         .first::<Order>(conn)
@@ -134,7 +726,7 @@ fn update_order_status(
     new_status: OrderStatus,
 ) -> Result<bool, Error> {
     use crate::schema::orders::dsl::*;
-    let conn: &SqliteConnection = &pool.get().unwrap();
+    let conn: &DbConnection = &pool.get().unwrap();
     diesel::update(orders.filter(order_id.eq(&affected_order_id)))
         .set(status.eq(new_status))
         .execute(conn)
@@ -142,15 +734,1722 @@ fn update_order_status(
     Ok(true)
 }
 
-fn get_orders_by_status(
+fn start_settlement(
     pool: web::Data<Pool>,
-    order_status: Vec<OrderStatus>,
-) -> Result<Vec<Order>, Error> {
+    affected_order_id: String,
+    new_status: OrderStatus,
+) -> Result<bool, Error> {
     use crate::schema::orders::dsl::*;
-    let conn: &SqliteConnection = &pool.get().unwrap();
-    let result = orders
-        .filter(status.eq_any(order_status))
-        .load::<Order>(conn)
-        .expect("Error loading orders");
+    let conn: &DbConnection = &pool.get().unwrap();
+    // An order whose settlement starts over leaves its batch
+    diesel::update(orders.filter(order_id.eq(&affected_order_id)))
+        .set((
+            status.eq(new_status),
+            session_id.eq(None::<String>),
+            settlement_transaction_id.eq(None::<String>),
+            encoded_settlement_transaction.eq(None::<String>),
+            settlement_network_fee.eq(None::<i64>),
+            batch_id.eq(None::<i32>),
+        ))
+        .execute(conn)
+        .expect("start_settlement error");
+    Ok(true)
+}
+
+fn request_refund_approval(
+    pool: web::Data<Pool>,
+    approval: NewRefundApproval,
+) -> Result<bool, Error> {
+    use crate::schema::orders::dsl::*;
+    use crate::schema::refund_approvals;
+    let conn: &DbConnection = &pool.get().unwrap();
+
+    conn.transaction::<_, diesel::result::Error, _>(|| {
+        // Approvals of an earlier refund request do not count towards this one
+        diesel::delete(
+            refund_approvals::table.filter(refund_approvals::order_id.eq(&approval.order_id)),
+        )
+        .execute(conn)?;
+        diesel::insert_into(refund_approvals::table)
+            .values(&approval)
+            .execute(conn)?;
+        // Any signing session of a previous settlement is abandoned
+        diesel::update(orders.filter(order_id.eq(&approval.order_id)))
+            .set((
+                status.eq(OrderStatus::RefundPendingApproval),
+                session_id.eq(None::<String>),
+                settlement_transaction_id.eq(None::<String>),
+                encoded_settlement_transaction.eq(None::<String>),
+                settlement_network_fee.eq(None::<i64>),
+                batch_id.eq(None::<i32>),
+            ))
+            .execute(conn)?;
+        Ok(())
+    })
+    .expect("request_refund_approval error");
+    Ok(true)
+}
+
+fn approve_refund(
+    pool: web::Data<Pool>,
+    approval: NewRefundApproval,
+    required_approvals: usize,
+) -> Result<Vec<RefundApproval>, Error> {
+    use crate::schema::orders::dsl::*;
+    use crate::schema::refund_approvals;
+    let conn: &DbConnection = &pool.get().unwrap();
+
+    let result = conn
+        .transaction::<_, diesel::result::Error, _>(|| {
+            diesel::insert_into(refund_approvals::table)
+                .values(&approval)
+                .execute(conn)?;
+            let approvals = refund_approvals::table
+                .filter(refund_approvals::order_id.eq(&approval.order_id))
+                .order(refund_approvals::id)
+                .load::<RefundApproval>(conn)?;
+            // The refund or split settlement starts with the last approval needed
+            if approvals.len() >= required_approvals {
+                diesel::update(
+                    orders
+                        .filter(order_id.eq(&approval.order_id))
+                        .filter(status.eq(OrderStatus::RefundPendingApproval)),
+                )
+                .set(status.eq(OrderStatus::Refunding))
+                .execute(conn)?;
+                diesel::update(
+                    orders
+                        .filter(order_id.eq(&approval.order_id))
+                        .filter(status.eq(OrderStatus::SplitPendingApproval)),
+                )
+                .set(status.eq(OrderStatus::Splitting))
+                .execute(conn)?;
+            }
+            Ok(approvals)
+        })
+        .expect("approve_refund error");
+    Ok(result)
+}
+
+fn get_refund_approvals(
+    pool: web::Data<Pool>,
+    affected_order_id: String,
+) -> Result<Vec<RefundApproval>, Error> {
+    use crate::schema::refund_approvals::dsl::*;
+    let conn: &DbConnection = &pool.get().unwrap();
+    let result = refund_approvals
+        .filter(order_id.eq(&affected_order_id))
+        .order(id)
+        .load::<RefundApproval>(conn)
+        .expect("get_refund_approvals error");
+    Ok(result)
+}
+
+fn get_order_participants(
+    pool: web::Data<Pool>,
+    affected_order_id: String,
+) -> Result<Vec<OrderParticipant>, Error> {
+    use crate::schema::order_participants::dsl::*;
+    let conn: &DbConnection = &pool.get().unwrap();
+    let result = order_participants
+        .filter(order_id.eq(&affected_order_id))
+        .order(id)
+        .load::<OrderParticipant>(conn)
+        .expect("get_order_participants error");
     Ok(result)
 }
+
+fn store_merchant_public_key(
+    pool: web::Data<Pool>,
+    affected_order_id: String,
+    new_merchant_public_key: String,
+) -> Result<bool, Error> {
+    use crate::schema::orders::dsl::*;
+    let conn: &DbConnection = &pool.get().unwrap();
+    diesel::update(orders.filter(order_id.eq(&affected_order_id)))
+        .set(merchant_public_key.eq(&new_merchant_public_key))
+        .execute(conn)
+        .expect("store_merchant_public_key error");
+    Ok(true)
+}
+
+fn change_refund_address(
+    pool: web::Data<Pool>,
+    affected_order_id: String,
+    new_refund_address: String,
+    order_event_detail: String,
+) -> Result<bool, Error> {
+    use crate::schema::order_events;
+    use crate::schema::orders::dsl::*;
+    let conn: &DbConnection = &pool.get().unwrap();
+
+    conn.transaction::<_, diesel::result::Error, _>(|| {
+        diesel::update(orders.filter(order_id.eq(&affected_order_id)))
+            .set(refund_address.eq(&new_refund_address))
+            .execute(conn)?;
+        diesel::insert_into(order_events::table)
+            .values(&NewOrderEvent {
+                order_id: affected_order_id.clone(),
+                event: OrderEventKind::RefundAddressChanged,
+                detail: order_event_detail,
+            })
+            .execute(conn)?;
+        Ok(())
+    })
+    .expect("change_refund_address error");
+    Ok(true)
+}
+
+fn propose_split(
+    pool: web::Data<Pool>,
+    proposal: NewSplitProposal,
+) -> Result<SplitProposal, Error> {
+    use crate::schema::split_proposals::dsl::*;
+    let conn: &DbConnection = &pool.get().unwrap();
+
+    let result = conn
+        .transaction::<_, diesel::result::Error, _>(|| {
+            // A new proposal replaces any proposal still waiting for the counterparty
+            diesel::update(
+                split_proposals
+                    .filter(order_id.eq(&proposal.order_id))
+                    .filter(status.eq(SplitProposalStatus::Proposed)),
+            )
+            .set(status.eq(SplitProposalStatus::Superseded))
+            .execute(conn)?;
+            diesel::insert_into(split_proposals)
+                .values(&proposal)
+                .execute(conn)?;
+            split_proposals
+                .filter(order_id.eq(&proposal.order_id))
+                .order(id.desc())
+                .first::<SplitProposal>(conn)
+        })
+        .expect("propose_split error");
+    Ok(result)
+}
+
+fn get_proposed_split(
+    pool: web::Data<Pool>,
+    affected_order_id: String,
+) -> Result<Option<SplitProposal>, Error> {
+    use crate::schema::split_proposals::dsl::*;
+    let conn: &DbConnection = &pool.get().unwrap();
+    let result = split_proposals
+        .filter(order_id.eq(&affected_order_id))
+        .filter(status.eq(SplitProposalStatus::Proposed))
+        .first::<SplitProposal>(conn)
+        .optional()
+        .expect("get_proposed_split error");
+    Ok(result)
+}
+
+fn get_latest_split(
+    pool: web::Data<Pool>,
+    affected_order_id: String,
+) -> Result<Option<SplitProposal>, Error> {
+    use crate::schema::split_proposals::dsl::*;
+    let conn: &DbConnection = &pool.get().unwrap();
+    let result = split_proposals
+        .filter(order_id.eq(&affected_order_id))
+        .order(id.desc())
+        .first::<SplitProposal>(conn)
+        .optional()
+        .expect("get_latest_split error");
+    Ok(result)
+}
+
+fn accept_split(
+    pool: web::Data<Pool>,
+    proposal: SplitProposal,
+    new_acceptance_signature: String,
+    new_status: OrderStatus,
+) -> Result<bool, Error> {
+    use crate::schema::{orders, refund_approvals, split_proposals};
+    let conn: &DbConnection = &pool.get().unwrap();
+
+    conn.transaction::<_, diesel::result::Error, _>(|| {
+        diesel::update(split_proposals::table.find(proposal.id))
+            .set((
+                split_proposals::status.eq(SplitProposalStatus::Accepted),
+                split_proposals::acceptance_signature.eq(&new_acceptance_signature),
+            ))
+            .execute(conn)?;
+        // Approvals of an earlier refund or split do not count towards this one
+        diesel::delete(
+            refund_approvals::table.filter(refund_approvals::order_id.eq(&proposal.order_id)),
+        )
+        .execute(conn)?;
+        // Any signing session of a previous settlement is abandoned
+        diesel::update(orders::table.find(&proposal.order_id))
+            .set((
+                orders::status.eq(new_status),
+                orders::split_merchant_amount.eq(proposal.merchant_amount),
+                orders::split_buyer_amount.eq(proposal.buyer_amount),
+                orders::session_id.eq(None::<String>),
+                orders::settlement_transaction_id.eq(None::<String>),
+                orders::encoded_settlement_transaction.eq(None::<String>),
+                orders::settlement_network_fee.eq(None::<i64>),
+                orders::batch_id.eq(None::<i32>),
+            ))
+            .execute(conn)?;
+        Ok(())
+    })
+    .expect("accept_split error");
+    Ok(true)
+}
+
+fn escalate_order(
+    pool: web::Data<Pool>,
+    affected_order_id: String,
+    escalated_by: ParticipantRole,
+    order_event_detail: String,
+) -> Result<DisputeCase, Error> {
+    use crate::schema::{dispute_cases, order_events, orders};
+    let conn: &DbConnection = &pool.get().unwrap();
+
+    let result = conn
+        .transaction::<_, diesel::result::Error, _>(|| {
+            // Any signing session of a previous settlement is abandoned
+            diesel::update(orders::table.find(&affected_order_id))
+                .set((
+                    orders::status.eq(OrderStatus::Disputed),
+                    orders::session_id.eq(None::<String>),
+                    orders::settlement_transaction_id.eq(None::<String>),
+                    orders::encoded_settlement_transaction.eq(None::<String>),
+                    orders::settlement_network_fee.eq(None::<i64>),
+                    orders::batch_id.eq(None::<i32>),
+                ))
+                .execute(conn)?;
+            diesel::insert_into(order_events::table)
+                .values(&NewOrderEvent {
+                    order_id: affected_order_id.clone(),
+                    event: OrderEventKind::Escalated,
+                    detail: order_event_detail,
+                })
+                .execute(conn)?;
+            // A case already ruled on is closed, the escalation is decided on a case of its own
+            diesel::update(
+                dispute_cases::table
+                    .filter(dispute_cases::order_id.eq(&affected_order_id))
+                    .filter(dispute_cases::status.eq(DisputeStatus::Ruled)),
+            )
+            .set(dispute_cases::status.eq(DisputeStatus::Closed))
+            .execute(conn)?;
+            let active = dispute_cases::table
+                .filter(dispute_cases::order_id.eq(&affected_order_id))
+                .filter(dispute_cases::status.ne(DisputeStatus::Closed))
+                .first::<DisputeCase>(conn)
+                .optional()?;
+            if let Some(case) = active {
+                return Ok(case);
+            }
+            diesel::insert_into(dispute_cases::table)
+                .values(&NewDisputeCase {
+                    order_id: affected_order_id.clone(),
+                    opened_by: escalated_by,
+                    status: DisputeStatus::Open,
+                })
+                .execute(conn)?;
+            dispute_cases::table
+                .filter(dispute_cases::order_id.eq(&affected_order_id))
+                .order(dispute_cases::id.desc())
+                .first::<DisputeCase>(conn)
+        })
+        .expect("escalate_order error");
+    Ok(result)
+}
+
+fn record_ruling(
+    pool: web::Data<Pool>,
+    ruling: NewRuling,
+    new_status: OrderStatus,
+) -> Result<Ruling, Error> {
+    use crate::schema::{dispute_cases, order_events, orders, rulings};
+    let conn: &DbConnection = &pool.get().unwrap();
+
+    let result = conn
+        .transaction::<_, diesel::result::Error, _>(|| {
+            diesel::insert_into(rulings::table)
+                .values(&ruling)
+                .execute(conn)?;
+            diesel::update(orders::table.find(&ruling.order_id))
+                .set((
+                    orders::status.eq(new_status),
+                    orders::split_merchant_amount.eq(parse_base_units(&ruling.merchant_amount)),
+                    orders::split_buyer_amount.eq(parse_base_units(&ruling.buyer_amount)),
+                    orders::arbitrated.eq(true),
+                ))
+                .execute(conn)?;
+            if let Some(case_id) = ruling.dispute_id {
+                diesel::update(dispute_cases::table.find(case_id))
+                    .set(dispute_cases::status.eq(DisputeStatus::Ruled))
+                    .execute(conn)?;
+            }
+            diesel::insert_into(order_events::table)
+                .values(&NewOrderEvent {
+                    order_id: ruling.order_id.clone(),
+                    event: OrderEventKind::Ruled,
+                    detail: format!("{:?} by {}", ruling.decision, ruling.escrow_public_key),
+                })
+                .execute(conn)?;
+            rulings::table
+                .filter(rulings::order_id.eq(&ruling.order_id))
+                .order(rulings::id.desc())
+                .first::<Ruling>(conn)
+        })
+        .expect("record_ruling error");
+    Ok(result)
+}
+
+fn get_latest_ruling(
+    pool: web::Data<Pool>,
+    affected_order_id: String,
+) -> Result<Option<Ruling>, Error> {
+    use crate::schema::rulings::dsl::*;
+    let conn: &DbConnection = &pool.get().unwrap();
+    let result = rulings
+        .filter(order_id.eq(&affected_order_id))
+        .order(id.desc())
+        .first::<Ruling>(conn)
+        .optional()
+        .expect("get_latest_ruling error");
+    Ok(result)
+}
+
+fn open_dispute(
+    pool: web::Data<Pool>,
+    dispute: NewDisputeCase,
+    author_message: Option<String>,
+) -> Result<DisputeCase, Error> {
+    use crate::schema::{dispute_cases, dispute_messages};
+    let conn: &DbConnection = &pool.get().unwrap();
+
+    let result = conn
+        .transaction::<_, diesel::result::Error, _>(|| {
+            diesel::insert_into(dispute_cases::table)
+                .values(&dispute)
+                .execute(conn)?;
+            let case = dispute_cases::table
+                .filter(dispute_cases::order_id.eq(&dispute.order_id))
+                .order(dispute_cases::id.desc())
+                .first::<DisputeCase>(conn)?;
+            if let Some(body) = author_message {
+                diesel::insert_into(dispute_messages::table)
+                    .values(&NewDisputeMessage {
+                        case_id: case.id,
+                        reply_to: None,
+                        author: dispute.opened_by,
+                        body,
+                    })
+                    .execute(conn)?;
+            }
+            Ok(case)
+        })
+        .expect("open_dispute error");
+    Ok(result)
+}
+
+fn get_active_dispute(
+    pool: web::Data<Pool>,
+    affected_order_id: String,
+) -> Result<Option<DisputeCase>, Error> {
+    use crate::schema::dispute_cases::dsl::*;
+    let conn: &DbConnection = &pool.get().unwrap();
+    let result = dispute_cases
+        .filter(order_id.eq(&affected_order_id))
+        .filter(status.ne(DisputeStatus::Closed))
+        .first::<DisputeCase>(conn)
+        .optional()
+        .expect("get_active_dispute error");
+    Ok(result)
+}
+
+fn get_dispute(pool: web::Data<Pool>, affected_case_id: i32) -> Result<Option<DisputeCase>, Error> {
+    use crate::schema::dispute_cases::dsl::*;
+    let conn: &DbConnection = &pool.get().unwrap();
+    let result = dispute_cases
+        .find(affected_case_id)
+        .first::<DisputeCase>(conn)
+        .optional()
+        .expect("get_dispute error");
+    Ok(result)
+}
+
+fn get_order_disputes(
+    pool: web::Data<Pool>,
+    affected_order_id: String,
+) -> Result<Vec<DisputeCase>, Error> {
+    use crate::schema::dispute_cases::dsl::*;
+    let conn: &DbConnection = &pool.get().unwrap();
+    let result = dispute_cases
+        .filter(order_id.eq(&affected_order_id))
+        .order(id)
+        .load::<DisputeCase>(conn)
+        .expect("get_order_disputes error");
+    Ok(result)
+}
+
+fn get_dispute_log(
+    pool: web::Data<Pool>,
+    affected_case_id: i32,
+) -> Result<(Vec<DisputeEvidence>, Vec<DisputeMessage>), Error> {
+    use crate::schema::{dispute_evidence, dispute_messages};
+    let conn: &DbConnection = &pool.get().unwrap();
+    let evidence = dispute_evidence::table
+        .filter(dispute_evidence::case_id.eq(affected_case_id))
+        .order(dispute_evidence::id)
+        .load::<DisputeEvidence>(conn)
+        .expect("get_dispute_log error");
+    let messages = dispute_messages::table
+        .filter(dispute_messages::case_id.eq(affected_case_id))
+        .order(dispute_messages::id)
+        .load::<DisputeMessage>(conn)
+        .expect("get_dispute_log error");
+    Ok((evidence, messages))
+}
+
+fn get_evidence_orders(
+    pool: web::Data<Pool>,
+    affected_digest: String,
+) -> Result<(Vec<Order>, Vec<OrderParticipant>), Error> {
+    use crate::schema::{dispute_cases, dispute_evidence, order_participants, orders};
+    let conn: &DbConnection = &pool.get().unwrap();
+    let affected_order_ids = dispute_evidence::table
+        .inner_join(dispute_cases::table)
+        .filter(dispute_evidence::digest.eq(&affected_digest))
+        .select(dispute_cases::order_id)
+        .load::<String>(conn)
+        .expect("get_evidence_orders error");
+    let affected_orders = orders::table
+        .filter(orders::order_id.eq_any(&affected_order_ids))
+        .load::<Order>(conn)
+        .expect("get_evidence_orders error");
+    let participants = order_participants::table
+        .filter(order_participants::order_id.eq_any(&affected_order_ids))
+        .order(order_participants::id)
+        .load::<OrderParticipant>(conn)
+        .expect("get_evidence_orders error");
+    Ok((affected_orders, participants))
+}
+
+fn add_dispute_message(
+    pool: web::Data<Pool>,
+    message: NewDisputeMessage,
+) -> Result<Option<DisputeMessage>, Error> {
+    use crate::schema::dispute_messages::dsl::*;
+    let conn: &DbConnection = &pool.get().unwrap();
+
+    let result = conn
+        .transaction::<_, diesel::result::Error, _>(|| {
+            // Replies have to stay within the thread of the same case
+            if let Some(parent_id) = message.reply_to {
+                let parent = dispute_messages
+                    .find(parent_id)
+                    .filter(case_id.eq(message.case_id))
+                    .first::<DisputeMessage>(conn)
+                    .optional()?;
+                if parent.is_none() {
+                    return Ok(None);
+                }
+            }
+            diesel::insert_into(dispute_messages)
+                .values(&message)
+                .execute(conn)?;
+            dispute_messages
+                .filter(case_id.eq(message.case_id))
+                .order(id.desc())
+                .first::<DisputeMessage>(conn)
+                .map(Some)
+        })
+        .expect("add_dispute_message error");
+    Ok(result)
+}
+
+fn add_dispute_evidence(
+    pool: web::Data<Pool>,
+    evidence: NewDisputeEvidence,
+) -> Result<DisputeEvidence, Error> {
+    use crate::schema::dispute_evidence::dsl::*;
+    let conn: &DbConnection = &pool.get().unwrap();
+
+    let result = conn
+        .transaction::<_, diesel::result::Error, _>(|| {
+            diesel::insert_into(dispute_evidence)
+                .values(&evidence)
+                .execute(conn)?;
+            dispute_evidence
+                .filter(case_id.eq(evidence.case_id))
+                .order(id.desc())
+                .first::<DisputeEvidence>(conn)
+        })
+        .expect("add_dispute_evidence error");
+    Ok(result)
+}
+
+fn update_dispute_status(
+    pool: web::Data<Pool>,
+    affected_case_id: i32,
+    new_status: DisputeStatus,
+) -> Result<bool, Error> {
+    use crate::schema::dispute_cases::dsl::*;
+    let conn: &DbConnection = &pool.get().unwrap();
+    diesel::update(dispute_cases.find(affected_case_id))
+        .set(status.eq(new_status))
+        .execute(conn)
+        .expect("update_dispute_status error");
+    Ok(true)
+}
+
+fn get_escrows(pool: web::Data<Pool>) -> Result<Vec<Escrow>, Error> {
+    use crate::schema::escrows::dsl::*;
+    let conn: &DbConnection = &pool.get().unwrap();
+    let result = escrows
+        .order(id)
+        .load::<Escrow>(conn)
+        .expect("get_escrows error");
+    Ok(result)
+}
+
+fn register_escrow(pool: web::Data<Pool>, escrow: NewEscrow) -> Result<Escrow, Error> {
+    use crate::schema::escrows::dsl::*;
+    let conn: &DbConnection = &pool.get().unwrap();
+
+    let result = conn
+        .transaction::<_, diesel::result::Error, _>(|| {
+            diesel::insert_into(escrows).values(&escrow).execute(conn)?;
+            escrows
+                .filter(public_key.eq(&escrow.public_key))
+                .first::<Escrow>(conn)
+        })
+        .expect("register_escrow error");
+    Ok(result)
+}
+
+fn revoke_escrow(pool: web::Data<Pool>, affected_escrow_id: i32) -> Result<bool, Error> {
+    use crate::schema::escrows::dsl::*;
+    let conn: &DbConnection = &pool.get().unwrap();
+    let updated = diesel::update(escrows.find(affected_escrow_id))
+        .set(status.eq(EscrowStatus::Revoked))
+        .execute(conn)
+        .expect("revoke_escrow error");
+    Ok(updated > 0)
+}
+
+fn get_merchant(pool: web::Data<Pool>, merchant_id: i32) -> Result<Option<Merchant>, Error> {
+    use crate::schema::merchants::dsl::*;
+    let conn: &DbConnection = &pool.get().unwrap();
+    let result = merchants
+        .find(merchant_id)
+        .first::<Merchant>(conn)
+        .optional()
+        .expect("get_merchant error");
+    Ok(result)
+}
+
+fn get_merchants(pool: web::Data<Pool>) -> Result<Vec<Merchant>, Error> {
+    use crate::schema::merchants::dsl::*;
+    let conn: &DbConnection = &pool.get().unwrap();
+    let result = merchants
+        .order(id)
+        .load::<Merchant>(conn)
+        .expect("get_merchants error");
+    Ok(result)
+}
+
+fn set_merchant_policies(
+    pool: web::Data<Pool>,
+    merchant_id: i32,
+    policies: MerchantPolicies,
+) -> Result<Merchant, Error> {
+    use crate::schema::merchants::dsl::*;
+    let conn: &DbConnection = &pool.get().unwrap();
+    diesel::update(merchants.find(merchant_id))
+        .set(&policies)
+        .execute(conn)
+        .expect("set_merchant_policies error");
+    let result = merchants
+        .find(merchant_id)
+        .first::<Merchant>(conn)
+        .expect("set_merchant_policies error");
+    Ok(result)
+}
+
+fn register_merchant(pool: web::Data<Pool>, merchant: NewMerchant) -> Result<Merchant, Error> {
+    use crate::schema::merchants::dsl::*;
+    let conn: &DbConnection = &pool.get().unwrap();
+
+    let result = conn
+        .transaction::<_, diesel::result::Error, _>(|| {
+            diesel::insert_into(merchants)
+                .values(&merchant)
+                .execute(conn)?;
+            merchants
+                .filter(name.eq(&merchant.name))
+                .first::<Merchant>(conn)
+        })
+        .expect("register_merchant error");
+    Ok(result)
+}
+
+fn get_merchant_api_key(
+    pool: web::Data<Pool>,
+    affected_key_digest: String,
+) -> Result<Option<MerchantApiKey>, Error> {
+    use crate::schema::merchant_api_keys::dsl::*;
+    let conn: &DbConnection = &pool.get().unwrap();
+    let result = merchant_api_keys
+        .filter(key_digest.eq(&affected_key_digest))
+        .first::<MerchantApiKey>(conn)
+        .optional()
+        .expect("get_merchant_api_key error");
+    Ok(result)
+}
+
+fn count_merchant_api_keys(pool: web::Data<Pool>) -> Result<i64, Error> {
+    use crate::schema::merchant_api_keys::dsl::*;
+    let conn: &DbConnection = &pool.get().unwrap();
+    let result = merchant_api_keys
+        .count()
+        .get_result(conn)
+        .expect("count_merchant_api_keys error");
+    Ok(result)
+}
+
+fn add_merchant_api_key(
+    pool: web::Data<Pool>,
+    api_key: NewMerchantApiKey,
+) -> Result<MerchantApiKey, Error> {
+    use crate::schema::merchant_api_keys::dsl::*;
+    let conn: &DbConnection = &pool.get().unwrap();
+
+    let result = conn
+        .transaction::<_, diesel::result::Error, _>(|| {
+            diesel::insert_into(merchant_api_keys)
+                .values(&api_key)
+                .execute(conn)?;
+            merchant_api_keys
+                .filter(key_digest.eq(&api_key.key_digest))
+                .first::<MerchantApiKey>(conn)
+        })
+        .expect("add_merchant_api_key error");
+    Ok(result)
+}
+
+fn revoke_merchant_api_key(pool: web::Data<Pool>, api_key_id: i32) -> Result<bool, Error> {
+    use crate::schema::merchant_api_keys::dsl::*;
+    let conn: &DbConnection = &pool.get().unwrap();
+    let deleted = diesel::delete(merchant_api_keys.find(api_key_id))
+        .execute(conn)
+        .expect("revoke_merchant_api_key error");
+    Ok(deleted > 0)
+}
+
+fn get_milestones(
+    pool: web::Data<Pool>,
+    affected_order_id: String,
+) -> Result<Vec<Milestone>, Error> {
+    use crate::schema::milestones::dsl::*;
+    let conn: &DbConnection = &pool.get().unwrap();
+    let result = milestones
+        .filter(order_id.eq(&affected_order_id))
+        .order(sequence)
+        .load::<Milestone>(conn)
+        .expect("get_milestones error");
+    Ok(result)
+}
+
+fn start_milestone_release(
+    pool: web::Data<Pool>,
+    affected_order_id: String,
+    new_milestone_amount: i64,
+    new_change_public_key: String,
+) -> Result<bool, Error> {
+    use crate::schema::orders::dsl::*;
+    let conn: &DbConnection = &pool.get().unwrap();
+    diesel::update(orders.filter(order_id.eq(&affected_order_id)))
+        .set((
+            status.eq(OrderStatus::ReleasingMilestone),
+            milestone_amount.eq(new_milestone_amount),
+            change_public_key.eq(&new_change_public_key),
+            session_id.eq(None::<String>),
+            settlement_transaction_id.eq(None::<String>),
+            encoded_settlement_transaction.eq(None::<String>),
+            settlement_network_fee.eq(None::<i64>),
+        ))
+        .execute(conn)
+        .expect("start_milestone_release error");
+    Ok(true)
+}
+
+fn release_milestone(
+    pool: web::Data<Pool>,
+    record: Order,
+    remaining_amount: Option<i64>,
+) -> Result<bool, Error> {
+    use crate::schema::{milestones, order_events, orders};
+    let conn: &DbConnection = &pool.get().unwrap();
+    let transaction_id = record.settlement_transaction_id.clone().unwrap_or_default();
+
+    conn.transaction::<_, diesel::result::Error, _>(|| {
+        let milestone = milestones::table
+            .filter(milestones::order_id.eq(&record.order_id))
+            .filter(milestones::status.eq(MilestoneStatus::Pending))
+            .order(milestones::sequence)
+            .first::<Milestone>(conn)?;
+        diesel::update(milestones::table.find(milestone.id))
+            .set((
+                milestones::status.eq(MilestoneStatus::Released),
+                milestones::settlement_transaction_id.eq(&transaction_id),
+            ))
+            .execute(conn)?;
+        match remaining_amount {
+            // The change output of the settlement funds the remaining milestones under the
+            // fresh merchant key
+            Some(remaining_amount) => diesel::update(orders::table.find(&record.order_id))
+                .set((
+                    orders::status.eq(OrderStatus::PendingResponse),
+                    orders::funding_transaction_id.eq(&transaction_id),
+                    orders::funding_amount.eq(remaining_amount),
+                    orders::merchant_public_key
+                        .eq(record.change_public_key.clone().unwrap_or_default()),
+                    orders::session_id.eq(None::<String>),
+                    orders::settlement_transaction_id.eq(None::<String>),
+                    orders::encoded_settlement_transaction.eq(None::<String>),
+                    orders::settlement_network_fee.eq(None::<i64>),
+                ))
+                .execute(conn)?,
+            None => diesel::update(orders::table.find(&record.order_id))
+                .set(orders::status.eq(OrderStatus::Completed))
+                .execute(conn)?,
+        };
+        diesel::insert_into(order_events::table)
+            .values(&NewOrderEvent {
+                order_id: record.order_id.clone(),
+                event: OrderEventKind::MilestoneReleased,
+                detail: format!(
+                    "milestone {} transaction {}",
+                    milestone.sequence, transaction_id
+                ),
+            })
+            .execute(conn)?;
+        Ok(())
+    })
+    .expect("release_milestone error");
+    Ok(true)
+}
+
+fn get_checkout(
+    pool: web::Data<Pool>,
+    affected_checkout_id: String,
+) -> Result<Option<Checkout>, Error> {
+    use crate::schema::checkouts::dsl::*;
+    let conn: &DbConnection = &pool.get().unwrap();
+    let result = checkouts
+        .find(&affected_checkout_id)
+        .first::<Checkout>(conn)
+        .optional()
+        .expect("get_checkout error");
+    Ok(result)
+}
+
+fn create_checkout(
+    pool: web::Data<Pool>,
+    checkout: NewCheckout,
+    affected_order_ids: Vec<String>,
+) -> Result<Checkout, Error> {
+    use crate::schema::{checkouts, orders};
+    let conn: &DbConnection = &pool.get().unwrap();
+
+    let result = conn
+        .transaction::<_, diesel::result::Error, _>(|| {
+            diesel::insert_into(checkouts::table)
+                .values(&checkout)
+                .execute(conn)?;
+            diesel::update(orders::table.filter(orders::order_id.eq_any(&affected_order_ids)))
+                .set(orders::checkout_id.eq(&checkout.checkout_id))
+                .execute(conn)?;
+            checkouts::table
+                .find(&checkout.checkout_id)
+                .first::<Checkout>(conn)
+        })
+        .expect("create_checkout error");
+    Ok(result)
+}
+
+fn store_checkout_payment(
+    pool: web::Data<Pool>,
+    affected_checkout_id: String,
+    transaction_id: String,
+    payment_outputs: Vec<(String, i32)>,
+) -> Result<bool, Error> {
+    use crate::schema::{checkouts, orders};
+    let conn: &DbConnection = &pool.get().unwrap();
+
+    conn.transaction::<_, diesel::result::Error, _>(|| {
+        diesel::update(checkouts::table.find(&affected_checkout_id))
+            .set((
+                checkouts::status.eq(CheckoutStatus::Paid),
+                checkouts::payment_transaction_id.eq(&transaction_id),
+            ))
+            .execute(conn)?;
+        for (affected_order_id, output_index) in payment_outputs.iter() {
+            diesel::update(orders::table.find(affected_order_id))
+                .set((
+                    orders::payment_transaction_id.eq(&transaction_id),
+                    orders::payment_output_index.eq(*output_index),
+                    orders::status.eq(OrderStatus::PendingResponse),
+                ))
+                .execute(conn)?;
+        }
+        Ok(())
+    })
+    .expect("store_checkout_payment error");
+    Ok(true)
+}
+
+fn get_orders_by_ids(
+    pool: web::Data<Pool>,
+    affected_order_ids: Vec<String>,
+) -> Result<Vec<Order>, Error> {
+    use crate::schema::orders::dsl::*;
+    let conn: &DbConnection = &pool.get().unwrap();
+    let result = orders
+        .filter(order_id.eq_any(affected_order_ids))
+        .load::<Order>(conn)
+        .expect("get_orders_by_ids error");
+    Ok(result)
+}
+
+fn get_participants_of_orders(
+    pool: web::Data<Pool>,
+    affected_order_ids: Vec<String>,
+) -> Result<Vec<OrderParticipant>, Error> {
+    use crate::schema::order_participants::dsl::*;
+    let conn: &DbConnection = &pool.get().unwrap();
+    let result = order_participants
+        .filter(order_id.eq_any(affected_order_ids))
+        .order(id)
+        .load::<OrderParticipant>(conn)
+        .expect("get_participants_of_orders error");
+    Ok(result)
+}
+
+fn get_checkout_orders(
+    pool: web::Data<Pool>,
+    affected_checkout_id: String,
+) -> Result<Vec<Order>, Error> {
+    use crate::schema::orders::dsl::*;
+    let conn: &DbConnection = &pool.get().unwrap();
+    let result = orders
+        .filter(checkout_id.eq(&affected_checkout_id))
+        .order(order_id)
+        .load::<Order>(conn)
+        .expect("get_checkout_orders error");
+    Ok(result)
+}
+
+fn create_batch(
+    pool: web::Data<Pool>,
+    batch: NewSettlementBatch,
+    affected_order_ids: Vec<String>,
+) -> Result<SettlementBatch, Error> {
+    use crate::schema::{orders, settlement_batches};
+    let conn: &DbConnection = &pool.get().unwrap();
+
+    let result = conn
+        .transaction::<_, diesel::result::Error, _>(|| {
+            diesel::insert_into(settlement_batches::table)
+                .values(&batch)
+                .execute(conn)?;
+            let inserted = settlement_batches::table
+                .order(settlement_batches::id.desc())
+                .first::<SettlementBatch>(conn)?;
+            diesel::update(orders::table.filter(orders::order_id.eq_any(&affected_order_ids)))
+                .set(orders::batch_id.eq(inserted.id))
+                .execute(conn)?;
+            Ok(inserted)
+        })
+        .expect("create_batch error");
+    Ok(result)
+}
+
+fn get_batch(
+    pool: web::Data<Pool>,
+    affected_batch_id: i32,
+) -> Result<Option<SettlementBatch>, Error> {
+    use crate::schema::settlement_batches::dsl::*;
+    let conn: &DbConnection = &pool.get().unwrap();
+    let result = settlement_batches
+        .find(affected_batch_id)
+        .first::<SettlementBatch>(conn)
+        .optional()
+        .expect("get_batch error");
+    Ok(result)
+}
+
+fn get_batch_settlement(
+    pool: web::Data<Pool>,
+    affected_batch_id: i32,
+) -> Result<Option<BatchSettlement>, Error> {
+    use crate::schema::{order_participants, orders, session_signers, settlement_batches};
+    let conn: &DbConnection = &pool.get().unwrap();
+
+    let batch = settlement_batches::table
+        .find(affected_batch_id)
+        .first::<SettlementBatch>(conn)
+        .optional()
+        .expect("get_batch_settlement error");
+    let batch = match batch {
+        Some(batch) => batch,
+        None => return Ok(None),
+    };
+    let batch_orders = orders::table
+        .filter(orders::batch_id.eq(affected_batch_id))
+        .order(orders::order_id)
+        .load::<Order>(conn)
+        .expect("get_batch_settlement error");
+    let order_ids: Vec<&String> = batch_orders.iter().map(|record| &record.order_id).collect();
+    let session_ids: Vec<&String> = batch_orders
+        .iter()
+        .filter_map(|record| record.session_id.as_ref())
+        .collect();
+    let participants = order_participants::table
+        .filter(order_participants::order_id.eq_any(order_ids))
+        .order(order_participants::id)
+        .load::<OrderParticipant>(conn)
+        .expect("get_batch_settlement error");
+    let signers = session_signers::table
+        .filter(session_signers::session_id.eq_any(session_ids))
+        .order(session_signers::id)
+        .load::<SessionSigner>(conn)
+        .expect("get_batch_settlement error");
+    Ok(Some(BatchSettlement {
+        batch,
+        orders: batch_orders,
+        participants,
+        signers,
+    }))
+}
+
+fn complete_batch(
+    pool: web::Data<Pool>,
+    affected_batch_id: i32,
+    settled: Vec<(String, OrderStatus)>,
+) -> Result<bool, Error> {
+    use crate::schema::{orders, settlement_batches};
+    let conn: &DbConnection = &pool.get().unwrap();
+
+    conn.transaction::<_, diesel::result::Error, _>(|| {
+        diesel::update(settlement_batches::table.find(affected_batch_id))
+            .set(settlement_batches::status.eq(BatchStatus::Broadcast))
+            .execute(conn)?;
+        for (affected_order_id, new_status) in settled.iter() {
+            diesel::update(orders::table.find(affected_order_id))
+                .set(orders::status.eq(*new_status))
+                .execute(conn)?;
+        }
+        Ok(())
+    })
+    .expect("complete_batch error");
+    Ok(true)
+}
+
+fn cancel_batch(pool: web::Data<Pool>, affected_batch_id: i32) -> Result<bool, Error> {
+    use crate::schema::{orders, settlement_batches};
+    let conn: &DbConnection = &pool.get().unwrap();
+
+    conn.transaction::<_, diesel::result::Error, _>(|| {
+        diesel::update(settlement_batches::table.find(affected_batch_id))
+            .set(settlement_batches::status.eq(BatchStatus::Cancelled))
+            .execute(conn)?;
+        // The sessions signed the batch transaction, each order starts over on its own
+        diesel::update(orders::table.filter(orders::batch_id.eq(affected_batch_id)))
+            .set((
+                orders::batch_id.eq(None::<i32>),
+                orders::session_id.eq(None::<String>),
+                orders::settlement_transaction_id.eq(None::<String>),
+                orders::encoded_settlement_transaction.eq(None::<String>),
+                orders::settlement_network_fee.eq(None::<i64>),
+            ))
+            .execute(conn)?;
+        Ok(())
+    })
+    .expect("cancel_batch error");
+    Ok(true)
+}
+
+fn is_participant_key(pool: web::Data<Pool>, affected_public_key: String) -> Result<bool, Error> {
+    use crate::schema::order_participants::dsl::*;
+    let conn: &DbConnection = &pool.get().unwrap();
+    let result = order_participants
+        .filter(public_key.eq(&affected_public_key))
+        .count()
+        .get_result::<i64>(conn)
+        .expect("is_participant_key error");
+    Ok(result > 0)
+}
+
+fn create_auth_challenge(
+    pool: web::Data<Pool>,
+    challenge: AuthChallenge,
+    now: i64,
+) -> Result<bool, Error> {
+    use crate::schema::auth_challenges::dsl::*;
+    let conn: &DbConnection = &pool.get().unwrap();
+
+    // Expired challenges are cleared as new ones are issued
+    diesel::delete(auth_challenges.filter(expires_at.lt(now)))
+        .execute(conn)
+        .expect("create_auth_challenge error");
+    diesel::insert_into(auth_challenges)
+        .values(&challenge)
+        .execute(conn)
+        .expect("create_auth_challenge error");
+    Ok(true)
+}
+
+fn take_auth_challenge(
+    pool: web::Data<Pool>,
+    affected_nonce: String,
+) -> Result<Option<AuthChallenge>, Error> {
+    use crate::schema::auth_challenges::dsl::*;
+    let conn: &DbConnection = &pool.get().unwrap();
+
+    let result = conn
+        .transaction::<_, diesel::result::Error, _>(|| {
+            let challenge = auth_challenges
+                .find(&affected_nonce)
+                .first::<AuthChallenge>(conn)
+                .optional()?;
+            diesel::delete(auth_challenges.find(&affected_nonce)).execute(conn)?;
+            Ok(challenge)
+        })
+        .expect("take_auth_challenge error");
+    Ok(result)
+}
+
+fn create_auth_session(
+    pool: web::Data<Pool>,
+    session: NewAuthSession,
+    now: i64,
+) -> Result<bool, Error> {
+    use crate::schema::auth_sessions::dsl::*;
+    let conn: &DbConnection = &pool.get().unwrap();
+
+    diesel::delete(auth_sessions.filter(expires_at.lt(now)))
+        .execute(conn)
+        .expect("create_auth_session error");
+    diesel::insert_into(auth_sessions)
+        .values(&session)
+        .execute(conn)
+        .expect("create_auth_session error");
+    Ok(true)
+}
+
+fn get_auth_session(
+    pool: web::Data<Pool>,
+    affected_token: String,
+) -> Result<Option<AuthSession>, Error> {
+    use crate::schema::auth_sessions::dsl::*;
+    let conn: &DbConnection = &pool.get().unwrap();
+    let result = auth_sessions
+        .find(&affected_token)
+        .first::<AuthSession>(conn)
+        .optional()
+        .expect("get_auth_session error");
+    Ok(result)
+}
+
+fn get_orders_by_status(
+    pool: web::Data<Pool>,
+    affected_merchant_id: i32,
+    order_status: Vec<OrderStatus>,
+) -> Result<Vec<Order>, Error> {
+    use crate::schema::orders::dsl::*;
+    let conn: &DbConnection = &pool.get().unwrap();
+    let result = orders
+        .filter(merchant_id.eq(affected_merchant_id))
+        .filter(status.eq_any(order_status))
+        .load::<Order>(conn)
+        .expect("Error loading orders");
+    Ok(result)
+}
+
+/// Page of the orders of a merchant matching a query, and whether more orders follow it
+fn query_orders(
+    pool: web::Data<Pool>,
+    affected_merchant_id: i32,
+    query: OrderQuery,
+) -> Result<(Vec<Order>, bool), Error> {
+    use crate::schema::orders::dsl::*;
+    let conn: &DbConnection = &pool.get().unwrap();
+    let mut page = orders
+        .filter(merchant_id.eq(affected_merchant_id))
+        .into_boxed();
+    if !query.statuses.is_empty() {
+        page = page.filter(status.eq_any(query.statuses));
+    }
+    if let Some(created_from) = query.created_from {
+        page = page.filter(created_at.ge(created_from));
+    }
+    if let Some(created_to) = query.created_to {
+        page = page.filter(created_at.lt(created_to));
+    }
+    if let Some(min_amount) = query.min_amount {
+        page = page.filter(amount.ge(min_amount));
+    }
+    if let Some(max_amount) = query.max_amount {
+        page = page.filter(amount.le(max_amount));
+    }
+    if let Some(buyer) = query.buyer {
+        page = page.filter(
+            buyer_public_key
+                .eq(buyer.clone())
+                .or(buyer_address.eq(buyer)),
+        );
+    }
+
+    // Orders strictly after the cursor in the sort order, ties broken by order ID
+    let descending = query.sort.descending;
+    page = match (query.sort.field, query.cursor) {
+        (
+            SortField::CreatedAt,
+            Some(Cursor {
+                value: CursorValue::Timestamp(value),
+                order_id: after,
+            }),
+        ) => {
+            if descending {
+                page.filter(
+                    created_at
+                        .lt(value.clone())
+                        .or(created_at.eq(value).and(order_id.lt(after))),
+                )
+            } else {
+                page.filter(
+                    created_at
+                        .gt(value.clone())
+                        .or(created_at.eq(value).and(order_id.gt(after))),
+                )
+            }
+        }
+        (
+            SortField::UpdatedAt,
+            Some(Cursor {
+                value: CursorValue::Timestamp(value),
+                order_id: after,
+            }),
+        ) => {
+            if descending {
+                page.filter(
+                    updated_at
+                        .lt(value.clone())
+                        .or(updated_at.eq(value).and(order_id.lt(after))),
+                )
+            } else {
+                page.filter(
+                    updated_at
+                        .gt(value.clone())
+                        .or(updated_at.eq(value).and(order_id.gt(after))),
+                )
+            }
+        }
+        (
+            SortField::Amount,
+            Some(Cursor {
+                value: CursorValue::Amount(value),
+                order_id: after,
+            }),
+        ) => {
+            if descending {
+                page.filter(
+                    amount
+                        .lt(value)
+                        .or(amount.eq(value).and(order_id.lt(after))),
+                )
+            } else {
+                page.filter(
+                    amount
+                        .gt(value)
+                        .or(amount.eq(value).and(order_id.gt(after))),
+                )
+            }
+        }
+        _ => page,
+    };
+    page = match (query.sort.field, descending) {
+        (SortField::CreatedAt, false) => page.order((created_at.asc(), order_id.asc())),
+        (SortField::CreatedAt, true) => page.order((created_at.desc(), order_id.desc())),
+        (SortField::UpdatedAt, false) => page.order((updated_at.asc(), order_id.asc())),
+        (SortField::UpdatedAt, true) => page.order((updated_at.desc(), order_id.desc())),
+        (SortField::Amount, false) => page.order((amount.asc(), order_id.asc())),
+        (SortField::Amount, true) => page.order((amount.desc(), order_id.desc())),
+    };
+
+    // One order more than the page tells whether another page follows
+    let mut result = page
+        .limit(query.limit + 1)
+        .load::<Order>(conn)
+        .expect("query_orders error");
+    let more = result.len() as i64 > query.limit;
+    result.truncate(query.limit as usize);
+    Ok((result, more))
+}
+
+fn count_pending_orders(pool: web::Data<Pool>, affected_public_key: String) -> Result<i64, Error> {
+    let conn: &DbConnection = &pool.get().unwrap();
+    let result =
+        pending_order_count(conn, &affected_public_key).expect("count_pending_orders error");
+    Ok(result)
+}
+
+fn pending_order_count(
+    conn: &DbConnection,
+    affected_public_key: &str,
+) -> Result<i64, diesel::result::Error> {
+    use crate::schema::orders::dsl::*;
+    orders
+        .filter(buyer_public_key.eq(affected_public_key))
+        .filter(status.eq(OrderStatus::PendingPayment))
+        .count()
+        .get_result::<i64>(conn)
+}
+
+fn get_wallet_names(
+    pool: web::Data<Pool>,
+    affected_merchant_id: i32,
+) -> Result<Vec<String>, Error> {
+    use crate::schema::orders::dsl::*;
+    let conn: &DbConnection = &pool.get().unwrap();
+    let result = orders
+        .filter(merchant_id.eq(affected_merchant_id))
+        .select(wallet_name)
+        .distinct()
+        .order(wallet_name)
+        .load::<String>(conn)
+        .expect("get_wallet_names error");
+    Ok(result)
+}
+
+fn get_order_events(
+    pool: web::Data<Pool>,
+    affected_order_id: String,
+) -> Result<Vec<OrderEvent>, Error> {
+    use crate::schema::order_events::dsl::*;
+    let conn: &DbConnection = &pool.get().unwrap();
+    let result = order_events
+        .filter(order_id.eq(&affected_order_id))
+        .order(id)
+        .load::<OrderEvent>(conn)
+        .expect("get_order_events error");
+    Ok(result)
+}
+
+fn record_order_event(
+    pool: web::Data<Pool>,
+    affected_order_id: String,
+    order_event: OrderEventKind,
+    order_event_detail: String,
+) -> Result<bool, Error> {
+    use crate::schema::order_events;
+    let conn: &DbConnection = &pool.get().unwrap();
+
+    diesel::insert_into(order_events::table)
+        .values(&NewOrderEvent {
+            order_id: affected_order_id,
+            event: order_event,
+            detail: order_event_detail,
+        })
+        .execute(conn)
+        .expect("record_order_event error");
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{OrderQueryRequest, RulingDecision, TIMESTAMP_FORMAT};
+    use crate::order_query::{encode_cursor, parse_order_query};
+    use chrono::{NaiveDate, NaiveDateTime};
+    use diesel::connection::SimpleConnection;
+    use std::fs;
+    use std::path::PathBuf;
+
+    /// Migrations of the backend under test, applied in the order of their names
+    #[cfg(feature = "sqlite")]
+    const MIGRATIONS: &str = "migrations";
+    #[cfg(feature = "postgres")]
+    const MIGRATIONS: &str = "migrations_postgres";
+
+    /// Pool of a single connection to a freshly migrated database: an in-memory SQLite database,
+    /// or a schema of its own in the PostgreSQL database of `TEST_DATABASE_URL` created in a
+    /// transaction that is never committed
+    fn test_pool() -> web::Data<Pool> {
+        let pool = r2d2::Pool::builder()
+            .max_size(1)
+            .build(manager())
+            .expect("Failed to create test pool");
+        {
+            let conn: &DbConnection = &pool.get().unwrap();
+            isolate(conn);
+            let root = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join(MIGRATIONS);
+            let mut migrations: Vec<PathBuf> = fs::read_dir(root)
+                .unwrap()
+                .map(|entry| entry.unwrap().path())
+                .collect();
+            migrations.sort();
+            for migration in migrations {
+                let sql = fs::read_to_string(migration.join("up.sql")).unwrap();
+                conn.batch_execute(&sql).expect("migration error");
+            }
+        }
+        web::Data::new(pool)
+    }
+
+    #[cfg(feature = "sqlite")]
+    fn manager() -> ConnectionManager<DbConnection> {
+        ConnectionManager::new(":memory:")
+    }
+
+    #[cfg(feature = "postgres")]
+    fn manager() -> ConnectionManager<DbConnection> {
+        let database_url =
+            std::env::var("TEST_DATABASE_URL").expect("TEST_DATABASE_URL must be set");
+        ConnectionManager::new(database_url)
+    }
+
+    #[cfg(feature = "sqlite")]
+    fn isolate(_conn: &DbConnection) {}
+
+    #[cfg(feature = "postgres")]
+    fn isolate(conn: &DbConnection) {
+        conn.begin_test_transaction().unwrap();
+        let schema = format!("test_{}", uuid::Uuid::new_v4().to_simple());
+        conn.batch_execute(&format!(
+            "CREATE SCHEMA {0}; SET LOCAL search_path TO {0};",
+            schema
+        ))
+        .unwrap();
+    }
+
+    fn new_order(order_id: &str, amount: i64) -> NewOrder {
+        NewOrder {
+            order_id: order_id.to_owned(),
+            status: OrderStatus::PendingPayment,
+            wallet_name: "wallet".to_owned(),
+            amount,
+            buyer_public_key: "buyer".to_owned(),
+            buyer_view_key: String::new(),
+            buyer_address: "buyer_address".to_owned(),
+            escrow_public_key: String::new(),
+            escrow_view_key: String::new(),
+            extra_view_keys: String::new(),
+            required_signatures: 2,
+            merchant_public_key: String::new(),
+            payout_address: String::new(),
+            escrow_id: None,
+            escrow_fee_address: "fee_address".to_owned(),
+            escrow_fee: 0,
+            escrow_fee_basis_points: 150,
+            escrow_dispute_fee: 5000,
+            escrow_dispute_fee_basis_points: 0,
+            merchant_id: 1,
+        }
+    }
+
+    #[test]
+    fn stores_typed_order() {
+        let pool = test_pool();
+        let milestones = vec![
+            NewMilestone {
+                order_id: "1".to_owned(),
+                sequence: 1,
+                amount: 4_000_000_000,
+                status: MilestoneStatus::Pending,
+            },
+            NewMilestone {
+                order_id: "1".to_owned(),
+                sequence: 2,
+                amount: 6_000_000_000,
+                status: MilestoneStatus::Pending,
+            },
+        ];
+        register_order(
+            pool.clone(),
+            new_order("1", 10_000_000_000),
+            vec![],
+            milestones,
+            None,
+        )
+        .unwrap();
+
+        let record = get_order_by_id(pool.clone(), "1".to_owned()).unwrap();
+        assert_eq!(record.amount, 10_000_000_000);
+        assert_eq!(record.session_id, None);
+        assert_eq!(record.escrow_fee_basis_points, 150);
+        assert_eq!(record.escrow_dispute_fee, 5000);
+        assert_eq!(record.created_at, record.updated_at);
+        let amounts: Vec<i64> = get_milestones(pool, "1".to_owned())
+            .unwrap()
+            .iter()
+            .map(|milestone| milestone.amount)
+            .collect();
+        assert_eq!(amounts, vec![4_000_000_000, 6_000_000_000]);
+    }
+
+    #[test]
+    fn status_update_touches_updated_at() {
+        use crate::schema::orders::dsl::*;
+        let pool = test_pool();
+        register_order(pool.clone(), new_order("1", 100), vec![], vec![], None).unwrap();
+        let earlier = NaiveDate::from_ymd(2020, 1, 1).and_hms(0, 0, 0);
+        {
+            let conn: &DbConnection = &pool.get().unwrap();
+            diesel::update(orders.find("1"))
+                .set(updated_at.eq(earlier))
+                .execute(conn)
+                .unwrap();
+        }
+        assert_eq!(
+            get_order_by_id(pool.clone(), "1".to_owned())
+                .unwrap()
+                .updated_at,
+            earlier
+        );
+
+        update_order_status(pool.clone(), "1".to_owned(), OrderStatus::PendingResponse).unwrap();
+        let record = get_order_by_id(pool, "1".to_owned()).unwrap();
+        assert!(record.updated_at > earlier);
+        assert!(record.updated_at >= record.created_at);
+    }
+
+    #[test]
+    fn register_order_keeps_pending_orders_within_cap() {
+        let pool = test_pool();
+        let register = |order_id: &str| {
+            register_order(
+                pool.clone(),
+                new_order(order_id, 100),
+                vec![],
+                vec![],
+                Some(2),
+            )
+            .unwrap()
+        };
+
+        assert!(register("1"));
+        assert!(register("2"));
+        assert!(!register("3"));
+        assert!(!is_order_exist(pool.clone(), "3".to_owned()).unwrap());
+
+        update_order_status(pool.clone(), "1".to_owned(), OrderStatus::PendingResponse).unwrap();
+        assert!(register("3"));
+    }
+
+    #[test]
+    fn escalation_after_ruling_opens_new_case() {
+        let pool = test_pool();
+        register_order(pool.clone(), new_order("1", 100), vec![], vec![], None).unwrap();
+        let escalate = |detail: &str| {
+            escalate_order(
+                pool.clone(),
+                "1".to_owned(),
+                ParticipantRole::Buyer,
+                detail.to_owned(),
+            )
+            .unwrap()
+        };
+
+        let first = escalate("Escalated from PendingResponse");
+        assert_eq!(first.status, DisputeStatus::Open);
+        assert_eq!(first.opened_by, ParticipantRole::Buyer);
+        assert_eq!(escalate("Escalated again").id, first.id);
+
+        let ruling = NewRuling {
+            order_id: "1".to_owned(),
+            escrow_public_key: "escrow".to_owned(),
+            decision: RulingDecision::Refund,
+            merchant_amount: String::new(),
+            buyer_amount: String::new(),
+            signature: "signature".to_owned(),
+            dispute_id: Some(first.id),
+        };
+        let ruling = record_ruling(pool.clone(), ruling, OrderStatus::Refunding).unwrap();
+        assert_eq!(ruling.dispute_id, Some(first.id));
+        assert_eq!(
+            get_dispute(pool.clone(), first.id).unwrap().unwrap().status,
+            DisputeStatus::Ruled
+        );
+
+        let second = escalate("Escalated from Refunding");
+        assert_ne!(second.id, first.id);
+        assert_eq!(
+            get_dispute(pool.clone(), first.id).unwrap().unwrap().status,
+            DisputeStatus::Closed
+        );
+        assert_eq!(
+            get_active_dispute(pool, "1".to_owned())
+                .unwrap()
+                .unwrap()
+                .id,
+            second.id
+        );
+    }
+
+    fn params() -> OrderQueryRequest {
+        OrderQueryRequest {
+            status: None,
+            created_from: None,
+            created_to: None,
+            min_amount: None,
+            max_amount: None,
+            buyer: None,
+            sort: None,
+            cursor: None,
+            limit: None,
+        }
+    }
+
+    /// Registers an order of the merchant created at the given time
+    fn seed_order(pool: &web::Data<Pool>, order: NewOrder, created: NaiveDateTime) {
+        use crate::schema::orders::dsl::*;
+        let affected_order_id = order.order_id.clone();
+        register_order(pool.clone(), order, vec![], vec![], None).unwrap();
+        let conn: &DbConnection = &pool.get().unwrap();
+        diesel::update(orders.find(affected_order_id))
+            .set((created_at.eq(created), updated_at.eq(created)))
+            .execute(conn)
+            .unwrap();
+    }
+
+    /// Order IDs of a page of the default merchant and the cursor of the next page, built like
+    /// the listing routes build it
+    fn query_page(
+        pool: &web::Data<Pool>,
+        params: &OrderQueryRequest,
+    ) -> (Vec<String>, Option<String>) {
+        let query = parse_order_query(params).unwrap();
+        let sort = query.sort;
+        let (records, more) = query_orders(pool.clone(), 1, query).unwrap();
+        let next_cursor = records.last().filter(|_| more).map(|record| {
+            let value = match sort.field {
+                SortField::CreatedAt => record.created_at.format(TIMESTAMP_FORMAT).to_string(),
+                SortField::UpdatedAt => record.updated_at.format(TIMESTAMP_FORMAT).to_string(),
+                SortField::Amount => record.amount.to_string(),
+            };
+            encode_cursor(sort, &value, &record.order_id)
+        });
+        let order_ids = records.into_iter().map(|record| record.order_id).collect();
+        (order_ids, next_cursor)
+    }
+
+    /// Order IDs of every page of a query
+    fn query_all(pool: &web::Data<Pool>, mut params: OrderQueryRequest) -> Vec<String> {
+        let mut order_ids = vec![];
+        loop {
+            let (page, next_cursor) = query_page(pool, &params);
+            order_ids.extend(page);
+            match next_cursor {
+                Some(cursor) => params.cursor = Some(cursor),
+                None => return order_ids,
+            }
+        }
+    }
+
+    #[test]
+    fn pages_break_ties_by_order_id() {
+        let pool = test_pool();
+        let day = NaiveDate::from_ymd(2020, 1, 1);
+        seed_order(&pool, new_order("a", 300), day.and_hms(10, 0, 0));
+        seed_order(&pool, new_order("b", 100), day.and_hms(12, 0, 0));
+        seed_order(&pool, new_order("c", 300), day.and_hms(12, 0, 0));
+        seed_order(&pool, new_order("d", 200), day.and_hms(12, 0, 0));
+        seed_order(&pool, new_order("e", 300), day.and_hms(11, 0, 0));
+
+        let mut newest = params();
+        newest.limit = Some("2".to_owned());
+        assert_eq!(query_all(&pool, newest), vec!["d", "c", "b", "e", "a"]);
+
+        let mut oldest = params();
+        oldest.sort = Some("created_at".to_owned());
+        oldest.limit = Some("2".to_owned());
+        assert_eq!(query_all(&pool, oldest), vec!["a", "e", "b", "c", "d"]);
+
+        let mut largest = params();
+        largest.sort = Some("-amount".to_owned());
+        largest.limit = Some("1".to_owned());
+        assert_eq!(query_all(&pool, largest), vec!["e", "c", "a", "d", "b"]);
+    }
+
+    #[test]
+    fn cursor_is_stable_when_orders_are_added() {
+        let pool = test_pool();
+        let day = NaiveDate::from_ymd(2020, 1, 1);
+        for (id, hour) in [("1", 10), ("2", 11), ("3", 12), ("4", 13)].iter() {
+            seed_order(&pool, new_order(id, 100), day.and_hms(*hour, 0, 0));
+        }
+
+        let mut params = params();
+        params.limit = Some("2".to_owned());
+        let (first, next_cursor) = query_page(&pool, &params);
+        assert_eq!(first, vec!["4", "3"]);
+
+        // Orders added before the cursor stay out of the following pages, those added after it
+        // take their place even when tied with it
+        seed_order(&pool, new_order("5", 100), day.and_hms(14, 0, 0));
+        seed_order(&pool, new_order("9", 100), day.and_hms(12, 0, 0));
+        seed_order(&pool, new_order("0", 100), day.and_hms(12, 0, 0));
+        params.cursor = next_cursor;
+        assert_eq!(query_all(&pool, params), vec!["0", "2", "1"]);
+    }
+
+    #[test]
+    fn filters_combine() {
+        let pool = test_pool();
+        let day = NaiveDate::from_ymd(2020, 1, 1);
+        let mut other_buyer = new_order("1", 100);
+        other_buyer.buyer_public_key = "other".to_owned();
+        other_buyer.buyer_address = "other_address".to_owned();
+        seed_order(&pool, other_buyer, day.and_hms(10, 0, 0));
+        seed_order(&pool, new_order("2", 200), day.and_hms(11, 0, 0));
+        seed_order(&pool, new_order("3", 300), day.and_hms(12, 0, 0));
+        seed_order(&pool, new_order("4", 400), day.and_hms(13, 0, 0));
+        let merchant = register_merchant(
+            pool.clone(),
+            NewMerchant {
+                name: "other".to_owned(),
+                wallet_name: String::new(),
+                payout_address: String::new(),
+                treasury_address: String::new(),
+            },
+        )
+        .unwrap();
+        let mut other_merchant = new_order("5", 300);
+        other_merchant.merchant_id = merchant.id;
+        seed_order(&pool, other_merchant, day.and_hms(12, 0, 0));
+        update_order_status(pool.clone(), "3".to_owned(), OrderStatus::Delivering).unwrap();
+        update_order_status(pool.clone(), "4".to_owned(), OrderStatus::Completed).unwrap();
+
+        assert_eq!(query_all(&pool, params()), vec!["4", "3", "2", "1"]);
+
+        let mut statuses = params();
+        statuses.status = Some("PendingPayment,Delivering".to_owned());
+        statuses.min_amount = Some("150".to_owned());
+        assert_eq!(query_all(&pool, statuses), vec!["3", "2"]);
+
+        let mut created = params();
+        created.created_from = Some("2020-01-01 11:00:00".to_owned());
+        created.created_to = Some("2020-01-01 13:00:00".to_owned());
+        created.max_amount = Some("250".to_owned());
+        assert_eq!(query_all(&pool, created), vec!["2"]);
+
+        let mut buyer = params();
+        buyer.buyer = Some("other_address".to_owned());
+        assert_eq!(query_all(&pool, buyer), vec!["1"]);
+        let mut buyer = params();
+        buyer.buyer = Some("buyer".to_owned());
+        buyer.status = Some("PendingPayment".to_owned());
+        assert_eq!(query_all(&pool, buyer), vec!["2"]);
+    }
+}
@@ -0,0 +1,49 @@
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::models::{DisputeStatus, ParticipantRole};
+
+/// Parties which can open a dispute case, post messages and attach evidence
+pub fn parse_party(party: &str) -> Result<ParticipantRole, &'static str> {
+    party.parse().map_err(|_| "Invalid Party")
+}
+
+/// Whether a case can be moved to the requested status. Cases are only marked as ruled by the
+/// escrow ruling, and closed cases stay closed.
+pub fn can_change_status(current: DisputeStatus, requested: DisputeStatus) -> bool {
+    match (current, requested) {
+        (DisputeStatus::Closed, _) | (_, DisputeStatus::Ruled) => false,
+        (DisputeStatus::Ruled, DisputeStatus::Closed) => true,
+        (DisputeStatus::Ruled, _) => false,
+        _ => true,
+    }
+}
+
+/// Stores an evidence file under the hex SHA-256 digest of its content and returns the digest.
+/// Files are spread over sub-directories named by the first byte of the digest, a file already
+/// stored is not written again.
+pub fn store_evidence(evidence_dir: &Path, content: &[u8]) -> io::Result<String> {
+    let digest = hex::encode(Sha256::digest(content));
+    let path = evidence_path(evidence_dir, &digest).unwrap();
+    if path.exists() {
+        return Ok(digest);
+    }
+
+    fs::create_dir_all(path.parent().unwrap())?;
+    // Written aside first so that a partially written file is never served under the digest
+    let partial_path = path.with_extension("partial");
+    fs::write(&partial_path, content)?;
+    fs::rename(&partial_path, &path)?;
+    Ok(digest)
+}
+
+/// Path of a stored evidence file, `None` if the digest is not a hex SHA-256 digest
+pub fn evidence_path(evidence_dir: &Path, digest: &str) -> Option<PathBuf> {
+    if digest.len() != 64 || !digest.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    let digest = digest.to_ascii_lowercase();
+    Some(evidence_dir.join(&digest[..2]).join(digest))
+}
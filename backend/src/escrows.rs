@@ -0,0 +1,136 @@
+use actix_web::web;
+use futures::Future;
+use std::str::FromStr;
+
+use chain_core::init::coin::Coin;
+use client_common::PublicKey;
+
+use crate::config::{parse_address, FeeRate, MerchantConfig};
+use crate::db;
+use crate::models::{Escrow, EscrowStatus, NewEscrow, ParticipantRole};
+use crate::participants::ParticipantSpec;
+use crate::Pool;
+
+/// Escrow of the registry chosen for a new order
+pub fn find_escrow(escrows: &[Escrow], escrow_id: i32) -> Result<&Escrow, &'static str> {
+    let escrow = escrows
+        .iter()
+        .find(|escrow| escrow.id == escrow_id)
+        .ok_or("Escrow Not Registered")?;
+    if escrow.status == EscrowStatus::Revoked {
+        return Err("Escrow Revoked");
+    }
+    Ok(escrow)
+}
+
+/// Active escrow of the registry with the given public key
+pub fn registered_escrow<'a>(escrows: &'a [Escrow], public_key: &str) -> Option<&'a Escrow> {
+    escrows
+        .iter()
+        .find(|escrow| escrow.public_key == public_key && escrow.status == EscrowStatus::Active)
+}
+
+/// Checks that every escrow among the co-signers of a new order is registered with the same
+/// view key and has not been revoked
+pub fn check_registered_escrows(
+    participants: &[ParticipantSpec],
+    escrows: &[Escrow],
+) -> Result<(), &'static str> {
+    for participant in participants
+        .iter()
+        .filter(|participant| participant.role == ParticipantRole::Escrow)
+    {
+        let public_key = participant.public_key.to_string();
+        let escrow = escrows
+            .iter()
+            .find(|escrow| escrow.public_key == public_key)
+            .ok_or("Escrow Not Registered")?;
+        if escrow.view_key != participant.view_key.to_string() {
+            return Err("Escrow Not Registered");
+        }
+        if escrow.status == EscrowStatus::Revoked {
+            return Err("Escrow Revoked");
+        }
+    }
+    Ok(())
+}
+
+/// Adds an escrow to the registry:
+/// `register-escrow <name> <public_key> <view_key> [<fee_address> <fee> <dispute_fee>]`
+pub fn register_escrow(pool: Pool, config: &MerchantConfig, args: &[String]) {
+    if args.len() != 3 && args.len() != 6 {
+        println!(
+            "Usage: register-escrow <name> <public_key> <view_key> [<fee_address> <fee> <dispute_fee>]"
+        );
+        return;
+    }
+    let public_key = PublicKey::from_str(&args[1]).expect("Invalid public key");
+    let view_key = PublicKey::from_str(&args[2]).expect("Invalid view key");
+    let (fee_address, fee, dispute_fee) = if args.len() == 6 {
+        parse_address(&args[3], config.network).expect("Invalid fee address");
+        (
+            args[3].clone(),
+            FeeRate::from_str(&args[4]).expect("Invalid fee"),
+            FeeRate::from_str(&args[5]).expect("Invalid dispute fee"),
+        )
+    } else {
+        (
+            String::from(""),
+            FeeRate::Flat(Coin::zero()),
+            FeeRate::Flat(Coin::zero()),
+        )
+    };
+    let (fee, fee_basis_points) = fee.to_columns();
+    let (dispute_fee, dispute_fee_basis_points) = dispute_fee.to_columns();
+
+    let pool = web::Data::new(pool);
+    let escrows = db::execute_get_escrows(pool.clone())
+        .wait()
+        .expect("Error loading escrows");
+    if let Some(escrow) = escrows
+        .iter()
+        .find(|escrow| escrow.public_key == public_key.to_string())
+    {
+        println!("Escrow key already registered with ID {}", escrow.id);
+        return;
+    }
+
+    let escrow = db::execute_register_escrow(
+        pool,
+        NewEscrow {
+            name: args[0].clone(),
+            public_key: public_key.to_string(),
+            view_key: view_key.to_string(),
+            fee_address,
+            fee,
+            fee_basis_points,
+            dispute_fee,
+            dispute_fee_basis_points,
+            status: EscrowStatus::Active,
+        },
+    )
+    .wait()
+    .expect("Error registering escrow");
+    println!("Registered escrow {} with ID {}", escrow.name, escrow.id);
+}
+
+/// Revokes an escrow of the registry, orders already created with it are not affected:
+/// `revoke-escrow <id>`
+pub fn revoke_escrow(pool: Pool, args: &[String]) {
+    let escrow_id = match args.get(0).map(|escrow_id| escrow_id.parse::<i32>()) {
+        Some(Ok(escrow_id)) => escrow_id,
+        _ => {
+            println!("Usage: revoke-escrow <id>");
+            return;
+        }
+    };
+
+    let revoked = db::execute_revoke_escrow(web::Data::new(pool), escrow_id)
+        .wait()
+        .expect("Error revoking escrow");
+    if revoked {
+        println!("Revoked escrow {}", escrow_id);
+    } else {
+        println!("Escrow {} not found", escrow_id);
+    }
+}
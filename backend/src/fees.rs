@@ -0,0 +1,263 @@
+use parity_scale_codec::Encode;
+use std::ops::Sub;
+
+use chain_core::init::address::CroAddress;
+use chain_core::init::coin::Coin;
+use chain_core::tx::data::access::TxAccessPolicy;
+use chain_core::tx::data::address::ExtendedAddr;
+use chain_core::tx::data::attribute::TxAttributes;
+use chain_core::tx::data::input::TxoPointer;
+use chain_core::tx::data::output::TxOut;
+use chain_core::tx::data::Tx;
+use chain_core::tx::fee::{FeeAlgorithm, LinearFee};
+
+use crate::config::{FeeConfig, FeeRate, FeeRecipient};
+use crate::models::Order;
+use crate::participants::MAX_PARTICIPANTS;
+
+/// Fee outputs of the settlement of an order: the platform fee, then the fee of the order's
+/// escrow. Settlements following an escrow ruling are charged the dispute fees.
+pub fn fee_outputs(config: &FeeConfig, record: &Order, amount: Coin) -> Vec<TxOut> {
+    let escrow = escrow_fee_recipient(record);
+    let recipients = config.platform.iter().chain(escrow.iter());
+
+    recipients
+        .map(|recipient| TxOut {
+            address: recipient.address.clone(),
+            value: fee_rate(recipient, record.arbitrated).fee(amount),
+            valid_from: None,
+        })
+        .filter(|output| output.value != Coin::zero())
+        .collect()
+}
+
+/// Fee schedule of the order's escrow, as registered when the order was created
+fn escrow_fee_recipient(record: &Order) -> Option<FeeRecipient> {
+    if record.escrow_fee_address.is_empty() {
+        return None;
+    }
+    Some(FeeRecipient {
+        address: ExtendedAddr::from_cro(&record.escrow_fee_address).unwrap(),
+        normal: FeeRate::from_columns(record.escrow_fee, record.escrow_fee_basis_points),
+        dispute: FeeRate::from_columns(
+            record.escrow_dispute_fee,
+            record.escrow_dispute_fee_basis_points,
+        ),
+    })
+}
+
+fn fee_rate(recipient: &FeeRecipient, arbitrated: bool) -> FeeRate {
+    if arbitrated {
+        recipient.dispute
+    } else {
+        recipient.normal
+    }
+}
+
+/// Bytes of a Schnorr signature, of an x-only public key and of a hash of a Merkle proof
+const SIGNATURE_SIZE: usize = 64;
+const PUBLIC_KEY_SIZE: usize = 32;
+const HASH_SIZE: usize = 32;
+/// Bytes of the envelope of an obfuscated transfer besides its inputs: the transaction ID, the
+/// key and nonce of its encryption, the number of outputs and the authentication tag of the
+/// encrypted payload
+const ENVELOPE_SIZE: usize = 32 + 8 + 12 + 2 + 16;
+
+/// Settlement of one or several orders before the network fee is deducted
+#[derive(Clone)]
+pub struct UnsignedSettlement {
+    pub inputs: Vec<TxoPointer>,
+    /// Funded amount spent by the inputs
+    pub amount: Coin,
+    /// Remaining funds returned to the order, paying no fee
+    pub change: Option<TxOut>,
+    /// Payouts of the merchant, the fees of the orders already deducted
+    pub payouts: Vec<TxOut>,
+    /// Payouts of the buyers, the fees of the orders already deducted
+    pub buyer_payouts: Vec<TxOut>,
+    pub fees: Vec<TxOut>,
+    pub access_policies: Vec<TxAccessPolicy>,
+    /// Whether the settlement merges the settlements of several orders, see `merge_settlements`
+    pub batched: bool,
+}
+
+/// Deducts the fee outputs of an order from its payouts, the merchant payouts first. Payouts
+/// reduced to zero are left out.
+pub fn deduct_fees(
+    payouts: Vec<TxOut>,
+    buyer_payouts: Vec<TxOut>,
+    fees: &[TxOut],
+) -> Result<(Vec<TxOut>, Vec<TxOut>), &'static str> {
+    let total = fees
+        .iter()
+        .try_fold(Coin::zero(), |total, output| total + output.value)
+        .map_err(|_| "Invalid Fee")?;
+    let (payouts, outstanding) = deduct(payouts, total);
+    let (buyer_payouts, outstanding) = deduct(buyer_payouts, outstanding);
+    if outstanding != Coin::zero() {
+        return Err("Fees Exceed Settlement");
+    }
+    Ok((payouts, buyer_payouts))
+}
+
+/// Transaction of a settlement with the network fee it pays, computed from the fee policy once
+/// and deducted from its payouts.
+/// The change comes first, then the merchant payouts, the buyer payouts and the fee outputs.
+///
+/// An order pays the network fee from the merchant payouts first, then from the buyer payouts.
+/// A batch pays its single network fee from the merchant payouts only, so that no buyer pays for
+/// the other orders of the batch.
+///
+/// The outputs of the result and the network fee add up to the funded amount.
+pub fn unsigned_transaction(
+    network_id: u8,
+    settlement: UnsignedSettlement,
+    fee_policy: &LinearFee,
+) -> Result<(Tx, Coin), &'static str> {
+    // Amounts are encoded with a fixed size, the fee does not change the size it is computed on
+    let draft = settlement_transaction(network_id, settlement.clone(), Coin::zero())?;
+    let network_fee = fee_policy
+        .calculate_fee(estimated_size(&draft))
+        .map_err(|_| "Invalid Fee")?
+        .to_coin();
+    let transaction = settlement_transaction(network_id, settlement, network_fee)?;
+    Ok((transaction, network_fee))
+}
+
+/// Size of a settlement transaction once signed and obfuscated. The witnesses are only known
+/// after signing, so every input is counted with the witness of the largest multi-sig address.
+pub fn estimated_size(transaction: &Tx) -> usize {
+    let inputs = transaction.inputs.len();
+    // The inputs are both in the clear and in the encrypted payload
+    transaction.encode().len()
+        + transaction.inputs.encode().len()
+        + inputs * signed_input_size()
+        + ENVELOPE_SIZE
+}
+
+/// Bytes an input adds once signed: the Schnorr signature, the combined key of the signers and
+/// its Merkle proof among the key combinations of the address, for the address with the most
+/// combinations an order can have
+fn signed_input_size() -> usize {
+    let combinations = (1..=MAX_PARTICIPANTS)
+        .map(|required| binomial(MAX_PARTICIPANTS, required))
+        .max()
+        .unwrap_or(1);
+    // Each level of the proof holds a hash and the side it is on
+    let depth = (combinations as f64).log2().ceil() as usize;
+    SIGNATURE_SIZE + PUBLIC_KEY_SIZE + depth * (HASH_SIZE + 1)
+}
+
+fn binomial(n: usize, k: usize) -> usize {
+    (0..k).fold(1, |result, i| result * (n - i) / (i + 1))
+}
+
+fn settlement_transaction(
+    network_id: u8,
+    settlement: UnsignedSettlement,
+    network_fee: Coin,
+) -> Result<Tx, &'static str> {
+    let (payouts, outstanding) = deduct(settlement.payouts, network_fee);
+    let (buyer_payouts, outstanding) = if settlement.batched {
+        (settlement.buyer_payouts, outstanding)
+    } else {
+        deduct(settlement.buyer_payouts, outstanding)
+    };
+    if outstanding != Coin::zero() {
+        return Err(if settlement.batched {
+            "Network Fee Exceeds Merchant Payouts"
+        } else {
+            "Fees Exceed Settlement"
+        });
+    }
+
+    let mut outputs: Vec<TxOut> = settlement.change.into_iter().collect();
+    outputs.extend(payouts);
+    outputs.extend(buyer_payouts);
+    outputs.extend(settlement.fees);
+
+    let total = outputs
+        .iter()
+        .try_fold(network_fee, |total, output| total + output.value)
+        .map_err(|_| "Invalid Settlement")?;
+    if total != settlement.amount {
+        return Err("Settlement Does Not Match Funded Amount");
+    }
+    Ok(Tx {
+        inputs: settlement.inputs,
+        outputs,
+        attributes: TxAttributes::new_with_access(network_id, settlement.access_policies),
+    })
+}
+
+/// Deducts an amount from payouts in their order, with the part of the amount they cannot cover.
+/// Payouts reduced to zero are left out.
+fn deduct(payouts: Vec<TxOut>, amount: Coin) -> (Vec<TxOut>, Coin) {
+    let mut outstanding = amount;
+    let mut outputs = vec![];
+    for mut payout in payouts.into_iter() {
+        let deducted = if payout.value < outstanding {
+            payout.value
+        } else {
+            outstanding
+        };
+        payout.value = payout.value.sub(deducted).unwrap();
+        outstanding = outstanding.sub(deducted).unwrap();
+        if payout.value != Coin::zero() {
+            outputs.push(payout);
+        }
+    }
+    (outputs, outstanding)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chain_core::tx::fee::Milli;
+
+    #[test]
+    fn network_fee_follows_transaction_size() {
+        let address = ExtendedAddr::OrTree([1; 32]);
+        let fee_address = ExtendedAddr::OrTree([2; 32]);
+        let fees = vec![TxOut::new(fee_address, Coin::new(10).unwrap())];
+        let payouts = vec![TxOut::new(address, Coin::new(100_000).unwrap())];
+        let (payouts, buyer_payouts) = deduct_fees(payouts, vec![], &fees).unwrap();
+        let settlement = UnsignedSettlement {
+            inputs: vec![TxoPointer::new([3; 32], 0)],
+            amount: Coin::new(100_000).unwrap(),
+            change: None,
+            payouts,
+            buyer_payouts,
+            fees,
+            access_policies: vec![],
+            batched: false,
+        };
+        let fee_policy = LinearFee::new(Milli::new(5, 0), Milli::new(2, 0));
+
+        let (transaction, network_fee) = unsigned_transaction(42, settlement, &fee_policy).unwrap();
+        let paid: u64 = transaction
+            .outputs
+            .iter()
+            .map(|output| u64::from(output.value))
+            .sum();
+        assert_eq!(u64::from(network_fee), 100_000 - paid);
+        assert_eq!(paid, 100_000 - 5 - 2 * estimated_size(&transaction) as u64);
+        assert!(estimated_size(&transaction) > transaction.encode().len() + SIGNATURE_SIZE);
+    }
+
+    #[test]
+    fn fees_of_an_order_come_from_the_merchant_first() {
+        let merchant = TxOut::new(ExtendedAddr::OrTree([1; 32]), Coin::new(30).unwrap());
+        let buyer = TxOut::new(ExtendedAddr::OrTree([2; 32]), Coin::new(50).unwrap());
+        let fee = |value| TxOut::new(ExtendedAddr::OrTree([3; 32]), Coin::new(value).unwrap());
+
+        let (payouts, buyer_payouts) =
+            deduct_fees(vec![merchant.clone()], vec![buyer.clone()], &[fee(40)]).unwrap();
+        assert!(payouts.is_empty());
+        assert_eq!(u64::from(buyer_payouts[0].value), 40);
+        assert_eq!(
+            deduct_fees(vec![merchant], vec![buyer], &[fee(90)]).err(),
+            Some("Fees Exceed Settlement")
+        );
+    }
+}
@@ -6,43 +6,92 @@ extern crate diesel;
 
 use actix_cors::Cors;
 use actix_web::{http::header, middleware, web, App, Error as AWError, HttpResponse, HttpServer};
-use diesel::prelude::*;
 use diesel::r2d2::{self, ConnectionManager};
-use futures::future::Future;
+use futures::future::{self, Either, Future};
 use listenfd::ListenFd;
+use log::{error, info};
+use parity_scale_codec::Encode;
 use secstr::SecUtf8;
 use std::ops::Sub;
 use std::str::FromStr;
-use uuid::Uuid;
 
 use chain_core::init::address::CroAddress;
 use chain_core::init::coin::Coin;
 use chain_core::tx::data::access::{TxAccess, TxAccessPolicy};
 use chain_core::tx::data::address::ExtendedAddr;
-use chain_core::tx::data::attribute::TxAttributes;
 use chain_core::tx::data::input::TxoPointer;
 use chain_core::tx::data::output::TxOut;
 use chain_core::tx::data::{Tx, TxId};
 use chain_core::tx::fee::LinearFee;
+use chain_core::tx::witness::TxWitness;
 use chain_core::tx::TransactionId;
 use client_common::storage::SledStorage;
 use client_common::tendermint::{Client, RpcClient};
-use client_common::{PublicKey, Transaction};
+use client_common::{PublicKey, SignedTransaction, Transaction};
 use client_core::signer::DefaultSigner;
 use client_core::transaction_builder::DefaultTransactionBuilder;
 use client_core::wallet::{DefaultWalletClient, MultiSigWalletClient, WalletClient};
-use client_index::cipher::MockAbciTransactionObfuscation;
+use client_index::cipher::{MockAbciTransactionObfuscation, TransactionObfuscation};
 use client_index::handler::{DefaultBlockHandler, DefaultTransactionHandler};
 use client_index::index::{DefaultIndex, Index};
 use client_index::synchronizer::ManualSynchronizer;
 
+use crate::approvals::{is_refund_approved, requires_refund_approval, requires_split_approval};
+use crate::arbitration::{can_escalate, parse_ruling, ruling_message, ruling_status};
+use crate::auth::{
+    challenge_message, random_token, unauthorized, unix_now, Identity, NonceCache, Staff,
+};
+use crate::batch::{
+    decode_transaction, encode_transaction, is_session_signed, merge_settlements, settled_status,
+};
+use crate::checkouts::{checkout_amount, is_checkout_buyer, parse_order_ids, payment_output};
+use crate::config::{parse_address, parse_view_keys, MerchantConfig};
+use crate::db::{DbConnection, Pool};
+use crate::disputes::{can_change_status, evidence_path, parse_party, store_evidence};
+use crate::escrows::{check_registered_escrows, find_escrow, registered_escrow};
+use crate::fees::{deduct_fees, fee_outputs, unsigned_transaction, UnsignedSettlement};
+use crate::merchant_wallet::{merchant_public_key, new_order_wallet, payout_address};
+use crate::merchants::{merchant_settings, MerchantSettings};
+use crate::milestones::{
+    funding_amount, funding_output_index, funding_transaction_id, next_milestone, parse_milestones,
+    remaining_amount,
+};
 use crate::models::*;
+use crate::order_query::{encode_cursor, parse_order_query, SortField};
+use crate::participants::{
+    parse_participants, select_signers, validate_participants, ParticipantSpec,
+};
+use crate::rate_limit::{OrderClient, RateLimits, SigningCaller};
+use crate::signing::{
+    add_commitment, add_partial_signature, add_verified_nonce, signed_witness, submitting_signer,
+    verify_key_signature, verify_partial_signature, verify_signature, Signer, SignerNonce,
+    SigningError, SigningSession,
+};
+use crate::split::{can_split, parse_split, parse_split_party, split_message};
+use crate::validation::Valid;
 
-type Pool = r2d2::Pool<ConnectionManager<SqliteConnection>>;
-
+mod approvals;
+mod arbitration;
+mod auth;
+mod batch;
+mod checkouts;
+mod config;
 mod db;
+mod disputes;
+mod escrows;
+mod fees;
+mod merchant_wallet;
+mod merchants;
+mod milestones;
 mod models;
+mod order_query;
+mod participants;
+mod rate_limit;
 mod schema;
+mod signing;
+mod split;
+mod sweep;
+mod validation;
 
 const NETWORK_ID: &str = "42";
 const TENDERMINT_URL: &str = "http://localhost:26657";
@@ -53,35 +102,188 @@ fn main() {
     env_logger::init();
     dotenv::dotenv().ok();
     let connspec = std::env::var("DATABASE_URL").expect("DATABASE_URL");
-    let manager = ConnectionManager::<SqliteConnection>::new(connspec);
+    let manager = ConnectionManager::<DbConnection>::new(connspec);
     let pool = r2d2::Pool::builder()
         .build(manager)
         .expect("Failed to create pool.");
+    let config = MerchantConfig::from_env();
+
+    let args: Vec<String> = std::env::args().collect();
+    match args.get(1).map(String::as_str) {
+        Some("migrate-wallets") => {
+            merchant_wallet::migrate_wallets(pool, &config);
+            return;
+        }
+        Some("register-escrow") => {
+            escrows::register_escrow(pool, &config, &args[2..]);
+            return;
+        }
+        Some("revoke-escrow") => {
+            escrows::revoke_escrow(pool, &args[2..]);
+            return;
+        }
+        Some("register-merchant") => {
+            merchants::register_merchant(pool, &config, &args[2..]);
+            return;
+        }
+        Some("set-merchant-policy") => {
+            merchants::set_merchant_policy(pool, &config, &args[2..]);
+            return;
+        }
+        Some("add-api-key") => {
+            merchants::add_api_key(pool, &args[2..]);
+            return;
+        }
+        Some("revoke-api-key") => {
+            merchants::revoke_api_key(pool, &args[2..]);
+            return;
+        }
+        _ => {}
+    }
+    merchants::check_staff_api_keys(pool.clone(), &config);
+    merchants::init_merchant_wallets(pool.clone(), &config);
+    if let Some(interval) = config.sweep.interval {
+        let pool = web::Data::new(pool.clone());
+        let config = config.clone();
+        std::thread::spawn(move || loop {
+            std::thread::sleep(interval);
+            let merchants = match db::execute_get_merchants(pool.clone()).wait() {
+                Ok(merchants) => merchants,
+                Err(err) => {
+                    error!("Sweep failed loading merchants: {}", err);
+                    continue;
+                }
+            };
+            for merchant in merchants.iter() {
+                let settings = merchant_settings(merchant, &config);
+                let treasury_address = match settings.treasury_address {
+                    Some(ref treasury_address) => treasury_address,
+                    None => continue,
+                };
+                let wallet_names =
+                    match merchant_wallet_names(pool.clone(), merchant.id, &settings).wait() {
+                        Ok(wallet_names) => wallet_names,
+                        Err(err) => {
+                            error!(
+                                "Sweep of merchant {} failed loading wallet names: {}",
+                                merchant.id, err
+                            );
+                            continue;
+                        }
+                    };
+                match sweep_payouts(&wallet_names, treasury_address, &config) {
+                    Ok(transactions) => info!(
+                        "Swept {} transactions of merchant {}",
+                        transactions.len(),
+                        merchant.id
+                    ),
+                    Err(err) => error!("Sweep of merchant {} failed: {}", merchant.id, err),
+                }
+            }
+        });
+    }
+
+    // Counters and nonces are shared by the workers, each of them builds its own app
+    let rate_limits = web::Data::new(RateLimits::new(&config.rate_limits));
+    let nonces = web::Data::new(NonceCache::default());
     let mut server = HttpServer::new(move || {
         App::new()
             .data(pool.clone())
+            .data(config.clone())
+            .register_data(rate_limits.clone())
+            .register_data(nonces.clone())
             .wrap(middleware::Logger::default())
             .wrap(
                 Cors::new()
                     .allowed_methods(vec!["GET", "POST"])
                     .allowed_headers(vec![header::AUTHORIZATION, header::ACCEPT])
                     .allowed_header(header::CONTENT_TYPE)
+                    .allowed_headers(vec![
+                        "X-API-Key",
+                        "X-Public-Key",
+                        "X-Timestamp",
+                        "X-Nonce",
+                        "X-Signature",
+                    ])
+                    .expose_headers(vec!["X-Next-Cursor"])
                     .max_age(3600),
             )
+            .service(
+                web::resource("/auth/challenge").route(web::post().to_async(new_auth_challenge)),
+            )
+            .service(
+                web::resource("/auth/verify").route(web::post().to_async(verify_auth_challenge)),
+            )
             .service(web::resource("/order/new").route(web::post().to_async(new_order)))
             .service(
                 web::resource("/order/payment-proof")
                     .route(web::post().to_async(submit_payment_proof)),
             )
+            .service(web::resource("/checkout/new").route(web::post().to_async(new_checkout)))
+            .service(web::resource("/checkout").route(web::get().to_async(get_checkout)))
+            .service(web::resource("/batch/new").route(web::post().to_async(new_batch)))
+            .service(web::resource("/batch").route(web::get().to_async(get_batch)))
+            .service(web::resource("/batch/cancel").route(web::post().to_async(cancel_batch)))
             .service(web::resource("/order").route(web::get().to_async(get_order)))
+            .service(web::resource("/order/history").route(web::get().to_async(get_order_history)))
+            .service(
+                web::resource("/order/refund-address")
+                    .route(web::post().to_async(change_refund_address)),
+            )
             .service(
                 web::resource("/order/delivering").route(web::post().to_async(mark_delivering)),
             )
             .service(web::resource("/order/refunding").route(web::post().to_async(mark_refunding)))
+            .service(
+                web::resource("/order/refund/approve").route(web::post().to_async(approve_refund)),
+            )
+            .service(
+                web::resource("/order/propose-split").route(web::post().to_async(propose_split)),
+            )
+            .service(web::resource("/order/accept-split").route(web::post().to_async(accept_split)))
+            .service(web::resource("/order/milestones").route(web::get().to_async(get_milestones)))
+            .service(
+                web::resource("/order/milestone/release")
+                    .route(web::post().to_async(release_milestone)),
+            )
+            .service(web::resource("/order/escalate").route(web::post().to_async(escalate_order)))
+            .service(
+                web::resource("/order/ruling")
+                    .route(web::get().to_async(get_ruling))
+                    .route(web::post().to_async(submit_ruling)),
+            )
+            .service(
+                web::resource("/order/disputes").route(web::get().to_async(get_order_disputes)),
+            )
+            .service(web::resource("/dispute").route(web::get().to_async(get_dispute)))
+            .service(web::resource("/dispute/open").route(web::post().to_async(open_dispute)))
+            .service(
+                web::resource("/dispute/message").route(web::post().to_async(post_dispute_message)),
+            )
+            .service(
+                web::resource("/dispute/evidence")
+                    .data(web::PayloadConfig::new(config.disputes.max_evidence_size))
+                    .route(web::get().to_async(get_evidence_file))
+                    .route(web::post().to_async(submit_dispute_evidence)),
+            )
+            .service(
+                web::resource("/dispute/status").route(web::post().to_async(change_dispute_status)),
+            )
+            .service(
+                web::resource("/order/settlement-preview")
+                    .route(web::get().to_async(get_settlement_preview)),
+            )
             .service(
                 web::resource("/order/exchange-commitment")
                     .route(web::post().to_async(exchange_commitment)),
             )
+            .service(
+                web::resource("/order/exchange-nonce").route(web::post().to_async(exchange_nonce)),
+            )
+            .service(
+                web::resource("/order/signing-session")
+                    .route(web::get().to_async(get_signing_session)),
+            )
             .service(
                 web::resource("/order/confirm/delivery")
                     .route(web::post().to_async(confirm_delivery)),
@@ -89,6 +291,14 @@ fn main() {
             .service(
                 web::resource("/order/confirm/refund").route(web::post().to_async(confirm_refund)),
             )
+            .service(
+                web::resource("/order/confirm/split").route(web::post().to_async(confirm_split)),
+            )
+            .service(
+                web::resource("/order/confirm/milestone")
+                    .route(web::post().to_async(confirm_milestone)),
+            )
+            .service(web::resource("/orders").route(web::get().to_async(query_orders)))
             .service(web::resource("/order/pending").route(web::get().to_async(get_pending_orders)))
             .service(
                 web::resource("/order/outstanding")
@@ -97,6 +307,14 @@ fn main() {
             .service(
                 web::resource("/order/completed").route(web::get().to_async(get_settled_orders)),
             )
+            .service(web::resource("/escrows").route(web::get().to_async(get_escrows)))
+            .service(
+                web::resource("/merchant/balance").route(web::get().to_async(get_merchant_balance)),
+            )
+            .service(
+                web::resource("/merchant/sweep")
+                    .route(web::post().to_async(sweep_merchant_payouts)),
+            )
     });
     server = if let Some(l) = listenfd.take_tcp_listener(0).unwrap() {
         server.listen(l).unwrap()
@@ -107,12 +325,115 @@ fn main() {
     server.run().unwrap();
 }
 
+fn new_auth_challenge(
+    pool: web::Data<Pool>,
+    config: web::Data<MerchantConfig>,
+    Valid(params): Valid<web::Form<AuthChallengeRequest>>,
+) -> impl Future<Item = HttpResponse, Error = AWError> {
+    // TODO: Consider using Arc to share resource
+    let query_pool = pool.clone();
+    let update_pool = pool.clone();
+
+    if PublicKey::from_str(&params.public_key).is_err() {
+        return Either::A(future::err(AWError::from(
+            HttpResponse::BadRequest()
+                .reason("Invalid Public Key")
+                .finish(),
+        )));
+    }
+    let now = unix_now();
+    let challenge = AuthChallenge {
+        nonce: random_token(),
+        public_key: params.public_key.to_string(),
+        expires_at: now + config.auth.challenge_ttl.as_secs() as i64,
+    };
+
+    // Only keys of the co-signers of some order have anything to authenticate for
+    Either::B(
+        db::execute_is_participant_key(query_pool, challenge.public_key.clone())
+            .and_then(|known| {
+                if !known {
+                    return Err(AWError::from(
+                        HttpResponse::NotFound()
+                            .reason("Unknown Public Key")
+                            .finish(),
+                    ));
+                }
+                Ok(())
+            })
+            .and_then(move |_| {
+                let res = AuthChallengeResponse {
+                    message: challenge_message(&challenge.nonce),
+                    nonce: challenge.nonce.clone(),
+                    expires_at: challenge.expires_at,
+                };
+                db::execute_create_auth_challenge(update_pool, challenge, now).map(move |_| res)
+            })
+            .and_then(|res| Ok(HttpResponse::Ok().json(res))),
+    )
+}
+
+fn verify_auth_challenge(
+    pool: web::Data<Pool>,
+    config: web::Data<MerchantConfig>,
+    Valid(params): Valid<web::Form<AuthVerifyRequest>>,
+) -> impl Future<Item = HttpResponse, Error = AWError> {
+    // TODO: Consider using Arc to share resource
+    let query_pool = pool.clone();
+    let update_pool = pool.clone();
+
+    let session_ttl = config.auth.session_ttl.as_secs() as i64;
+
+    // A challenge is taken by its first verification, whether it succeeds or not
+    db::execute_take_auth_challenge(query_pool, params.nonce.to_string())
+        .and_then(move |challenge| {
+            let now = unix_now();
+            match challenge {
+                Some(ref challenge)
+                    if challenge.public_key == params.public_key && challenge.expires_at > now => {}
+                _ => return Err(unauthorized("Invalid Challenge")),
+            }
+            let message = challenge_message(&params.nonce);
+            if !verify_key_signature(&params.public_key, &message, &params.signature) {
+                return Err(unauthorized("Invalid Signature"));
+            }
+            let session = NewAuthSession {
+                token: random_token(),
+                public_key: params.public_key.to_string(),
+                expires_at: now + session_ttl,
+            };
+            Ok((session, now))
+        })
+        .and_then(move |(session, now)| {
+            let res = AuthSessionResponse {
+                token: session.token.clone(),
+                public_key: session.public_key.clone(),
+                expires_at: session.expires_at,
+            };
+            db::execute_create_auth_session(update_pool, session, now).map(move |_| res)
+        })
+        .and_then(|res| Ok(HttpResponse::Ok().json(res)))
+}
+
 fn new_order(
+    OrderClient(staff): OrderClient,
     pool: web::Data<Pool>,
-    params: web::Form<NewOrderRequest>,
+    config: web::Data<MerchantConfig>,
+    Valid(params): Valid<web::Form<NewOrderRequest>>,
 ) -> impl Future<Item = HttpResponse, Error = AWError> {
+    let required_signatures = params.required_signatures.unwrap_or(2);
+    let extra_view_keys = params.extra_view_keys.clone().unwrap_or_default();
+    // Staff create orders for their own merchant only
+    let merchant_id = staff.merchant_id;
+    let buyer_public_key = params.buyer_public_key.clone();
+    let max_pending_orders = config.rate_limits.max_pending_orders_per_buyer;
+
+    let pending_pool = pool.clone();
+    let merchant_pool = pool.clone();
+    let escrows_pool = pool.clone();
+
     db::execute_is_order_exist(pool.clone(), params.order_id.to_string())
-        .and_then(|exist| {
+        .and_then(move |exist| {
             if exist {
                 return Err(AWError::from(
                     HttpResponse::BadRequest()
@@ -122,94 +443,316 @@ fn new_order(
             }
             Ok(())
         })
-        .and_then(move |_| {
-            let (wallet, _, _) = make_app();
-            let wallet_name = Uuid::new_v4().to_string();
-            let passphrase = SecUtf8::from("passphrase");
-
-            wallet
-                .new_wallet(&wallet_name, &passphrase)
-                .expect("new_wallet error");
+        // Checked before the order wallet is made, and again along with the insert
+        .and_then(move |_| db::execute_count_pending_orders(pending_pool, buyer_public_key))
+        .and_then(move |pending| match max_pending_orders {
+            Some(max_pending_orders) if pending as usize >= max_pending_orders => {
+                Err(too_many_pending_orders())
+            }
+            _ => Ok(()),
+        })
+        .and_then(move |_| db::execute_get_merchant(merchant_pool, merchant_id))
+        .and_then(|merchant| {
+            merchant.ok_or_else(|| {
+                AWError::from(
+                    HttpResponse::BadRequest()
+                        .reason("Merchant Not Found")
+                        .finish(),
+                )
+            })
+        })
+        .and_then(move |merchant| {
+            db::execute_get_escrows(escrows_pool).map(move |escrows| (merchant, escrows))
+        })
+        .and_then(move |(merchant, escrows)| {
+            let settings = merchant_settings(&merchant, &config);
+            let validated = new_order_participants(
+                &params,
+                required_signatures,
+                &escrows,
+                settings.require_registered_escrow,
+            )
+            .and_then(|participants| {
+                let extra_view_keys =
+                    parse_view_keys(&extra_view_keys).map_err(|_| "Invalid View Key")?;
+                let payout_address = match params.payout_address {
+                    Some(ref payout_address) if !payout_address.is_empty() => Some(
+                        parse_address(payout_address, config.network)
+                            .map_err(|_| "Invalid Payout Address")?,
+                    ),
+                    _ => settings.payout_address.clone(),
+                };
+                let milestones = match params.milestones {
+                    Some(ref milestones) if !milestones.is_empty() => {
+                        parse_milestones(milestones, &params.amount)?
+                    }
+                    _ => vec![],
+                };
+                Ok((participants, extra_view_keys, payout_address, milestones))
+            });
+            validated
+                .map(|validated| (params, settings, escrows, validated))
+                .map_err(|reason| AWError::from(HttpResponse::BadRequest().reason(reason).finish()))
+        })
+        .and_then(
+            move |(
+                params,
+                settings,
+                escrows,
+                (participants, extra_view_keys, payout_address, milestones),
+            )| {
+                let (wallet, _, _) = make_app();
+                let passphrase = SecUtf8::from("passphrase");
 
-            let merchant_address = wallet
-                .new_transfer_address(&wallet_name, &passphrase)
-                .unwrap();
-            let merchant_public_key = &wallet.public_keys(&wallet_name, &passphrase).unwrap()[0];
-            let merchant_public_key = merchant_public_key.to_owned();
-            let merchant_view_key = wallet.view_key(&wallet_name, &passphrase).unwrap();
+                let merchant_wallet_name = settings.wallet_name.as_ref().map(String::as_str);
+                let order_wallet = new_order_wallet(&wallet, merchant_wallet_name, &passphrase);
+                let wallet_name = order_wallet.wallet_name;
+                let merchant_address = payout_address.clone().unwrap_or(order_wallet.address);
+                let merchant_public_key = order_wallet.public_key;
+                let merchant_view_key = wallet.view_key(&wallet_name, &passphrase).unwrap();
 
-            let buyer_public_key =
-                PublicKey::from_str(&params.buyer_public_key.to_string()).unwrap();
-            let escrow_public_key =
-                PublicKey::from_str(&params.escrow_public_key.to_string()).unwrap();
+                let mut public_keys = vec![merchant_public_key.clone()];
+                public_keys.extend(
+                    participants
+                        .iter()
+                        .map(|participant| participant.public_key.clone()),
+                );
+                let total_signatures = public_keys.len();
 
-            let multisig_address = wallet
-                .new_multisig_transfer_address(
-                    &wallet_name,
-                    &passphrase,
-                    vec![
+                let multisig_address = wallet
+                    .new_multisig_transfer_address(
+                        &wallet_name,
+                        &passphrase,
+                        public_keys,
                         merchant_public_key.clone(),
-                        buyer_public_key,
-                        escrow_public_key,
-                    ],
-                    merchant_public_key.clone(),
-                    2,
-                    3,
-                )
-                .expect("new_multisig_transfer_address error");
+                        required_signatures as usize,
+                        total_signatures,
+                    )
+                    .expect("new_multisig_transfer_address error");
 
-            let order = Order {
-                order_id: params.order_id.to_string(),
-                amount: params.amount.to_string(),
-                wallet_name: wallet_name.clone(),
-                status: OrderStatus::PendingPayment,
-                buyer_public_key: params.buyer_public_key.to_string(),
-                buyer_view_key: params.buyer_view_key.to_string(),
-                buyer_address: params.buyer_address.to_string(),
-                escrow_public_key: params.escrow_public_key.to_string(),
-                escrow_view_key: params.escrow_view_key.to_string(),
-                session_id: "".to_string(),
-                payment_transaction_id: "".to_string(),
-                settlement_transaction_id: "".to_string(),
-            };
-
-            let res = NewOrderResponse {
-                public_key: merchant_public_key.to_string(),
-                address: merchant_address.to_string(),
-                view_key: merchant_view_key.to_string(),
-                multisig_address: multisig_address.to_string(),
-            };
+                // The escrow columns keep the first escrow of the order, if any
+                let escrow = participants
+                    .iter()
+                    .find(|participant| participant.role == ParticipantRole::Escrow);
+                // Registered escrows charge the fees of their schedule at the time of the order
+                let registered = escrow
+                    .and_then(|escrow| registered_escrow(&escrows, &escrow.public_key.to_string()));
+
+                let order = NewOrder {
+                    order_id: params.order_id.to_string(),
+                    amount: to_base_units(Coin::from_str(&params.amount).unwrap()),
+                    wallet_name: wallet_name.clone(),
+                    status: OrderStatus::PendingPayment,
+                    buyer_public_key: params.buyer_public_key.to_string(),
+                    buyer_view_key: params.buyer_view_key.to_string(),
+                    buyer_address: params.buyer_address.to_string(),
+                    escrow_public_key: escrow
+                        .map(|escrow| escrow.public_key.to_string())
+                        .unwrap_or_default(),
+                    escrow_view_key: escrow
+                        .map(|escrow| escrow.view_key.to_string())
+                        .unwrap_or_default(),
+                    extra_view_keys: extra_view_keys
+                        .iter()
+                        .map(|view_key| view_key.to_string())
+                        .collect::<Vec<String>>()
+                        .join(","),
+                    required_signatures,
+                    merchant_public_key: merchant_public_key.to_string(),
+                    payout_address: payout_address
+                        .map(|payout_address| payout_address.to_string())
+                        .unwrap_or_default(),
+                    escrow_id: registered.map(|escrow| escrow.id),
+                    escrow_fee_address: registered
+                        .map(|escrow| escrow.fee_address.to_string())
+                        .unwrap_or_default(),
+                    escrow_fee: registered.map(|escrow| escrow.fee).unwrap_or_default(),
+                    escrow_fee_basis_points: registered
+                        .map(|escrow| escrow.fee_basis_points)
+                        .unwrap_or_default(),
+                    escrow_dispute_fee: registered
+                        .map(|escrow| escrow.dispute_fee)
+                        .unwrap_or_default(),
+                    escrow_dispute_fee_basis_points: registered
+                        .map(|escrow| escrow.dispute_fee_basis_points)
+                        .unwrap_or_default(),
+                    merchant_id,
+                };
+                let participants = participants
+                    .iter()
+                    .map(|participant| NewOrderParticipant {
+                        order_id: params.order_id.to_string(),
+                        role: participant.role,
+                        public_key: participant.public_key.to_string(),
+                        view_key: participant.view_key.to_string(),
+                    })
+                    .collect();
+                let milestones = milestones
+                    .iter()
+                    .enumerate()
+                    .map(|(index, amount)| NewMilestone {
+                        order_id: params.order_id.to_string(),
+                        sequence: index as i32 + 1,
+                        amount: to_base_units(*amount),
+                        status: MilestoneStatus::Pending,
+                    })
+                    .collect();
 
-            db::execute_register_order(pool, order)
+                let res = NewOrderResponse {
+                    public_key: merchant_public_key.to_string(),
+                    address: merchant_address.to_string(),
+                    view_key: merchant_view_key.to_string(),
+                    multisig_address: multisig_address.to_string(),
+                };
+
+                // Concurrent orders of the buyer may have reached the cap since it was checked
+                db::execute_register_order(
+                    pool,
+                    order,
+                    participants,
+                    milestones,
+                    max_pending_orders,
+                )
                 .from_err()
-                .and_then(|_| Ok(HttpResponse::Ok().json(res)))
-        })
+                .and_then(|registered| {
+                    if !registered {
+                        return Err(too_many_pending_orders());
+                    }
+                    Ok(HttpResponse::Ok().json(res))
+                })
+            },
+        )
+}
+
+/// Response to an order of a buyer with too many orders pending payment. They are cleared as the
+/// orders get paid, so there is no time to retry after.
+fn too_many_pending_orders() -> AWError {
+    AWError::from(
+        HttpResponse::TooManyRequests()
+            .reason("Too Many Pending Orders")
+            .finish(),
+    )
+}
+
+/// Co-signers of a new order: the buyer, the escrow if given, then any additional participants.
+/// The escrow is either given by its keys or by its ID in the registry.
+fn new_order_participants(
+    params: &NewOrderRequest,
+    required_signatures: i32,
+    escrows: &[Escrow],
+    require_registered_escrow: bool,
+) -> Result<Vec<ParticipantSpec>, &'static str> {
+    let mut participants = vec![ParticipantSpec {
+        role: ParticipantRole::Buyer,
+        public_key: PublicKey::from_str(&params.buyer_public_key)
+            .map_err(|_| "Invalid Public Key")?,
+        view_key: PublicKey::from_str(&params.buyer_view_key).map_err(|_| "Invalid View Key")?,
+    }];
+
+    let registered = match params.escrow_id {
+        Some(escrow_id) => Some(find_escrow(escrows, escrow_id)?),
+        None => None,
+    };
+    let escrow_public_key = params
+        .escrow_public_key
+        .as_ref()
+        .filter(|public_key| !public_key.is_empty())
+        .or_else(|| registered.map(|escrow| &escrow.public_key));
+    let escrow_view_key = params
+        .escrow_view_key
+        .as_ref()
+        .filter(|view_key| !view_key.is_empty())
+        .or_else(|| registered.map(|escrow| &escrow.view_key));
+    if let Some(escrow) = registered {
+        if escrow_public_key != Some(&escrow.public_key)
+            || escrow_view_key != Some(&escrow.view_key)
+        {
+            return Err("Invalid Participant");
+        }
+    }
+    match (escrow_public_key, escrow_view_key) {
+        (Some(public_key), Some(view_key)) => participants.push(ParticipantSpec {
+            role: ParticipantRole::Escrow,
+            public_key: PublicKey::from_str(public_key).map_err(|_| "Invalid Public Key")?,
+            view_key: PublicKey::from_str(view_key).map_err(|_| "Invalid View Key")?,
+        }),
+        (None, None) => {}
+        _ => return Err("Invalid Participant"),
+    }
+
+    if let Some(additional) = params.participants.as_ref() {
+        participants.extend(parse_participants(additional)?);
+    }
+    validate_participants(&participants, required_signatures)?;
+    if require_registered_escrow {
+        check_registered_escrows(&participants, escrows)?;
+    }
+    Ok(participants)
+}
+
+fn get_escrows(pool: web::Data<Pool>) -> impl Future<Item = HttpResponse, Error = AWError> {
+    db::execute_get_escrows(pool.clone()).and_then(|escrows| {
+        let escrows: Vec<Escrow> = escrows
+            .into_iter()
+            .filter(|escrow| escrow.status == EscrowStatus::Active)
+            .collect();
+        Ok(HttpResponse::Ok().json(escrows))
+    })
 }
 
 fn submit_payment_proof(
+    identity: Identity,
+    pool: web::Data<Pool>,
+    Valid(params): Valid<web::Form<PaymentProof>>,
+) -> impl Future<Item = HttpResponse, Error = AWError> {
+    let transaction_id = params.transaction_id.to_string();
+    match (params.checkout_id.as_ref(), params.order_id.as_ref()) {
+        (Some(checkout_id), _) => Either::A(submit_checkout_payment_proof(
+            identity,
+            pool,
+            checkout_id.to_string(),
+            transaction_id,
+        )),
+        (None, Some(order_id)) => Either::B(Either::A(submit_order_payment_proof(
+            identity,
+            pool,
+            order_id.to_string(),
+            transaction_id,
+        ))),
+        (None, None) => Either::B(Either::B(future::err(AWError::from(
+            HttpResponse::BadRequest()
+                .reason("Missing Order Or Checkout")
+                .finish(),
+        )))),
+    }
+}
+
+fn submit_order_payment_proof(
+    identity: Identity,
     pool: web::Data<Pool>,
-    params: web::Form<PaymentProof>,
+    order_id: String,
+    transaction_id: String,
 ) -> impl Future<Item = HttpResponse, Error = AWError> {
     // TODO: Consider using Arc to share resource
-    let query_order_id = params.order_id.to_string();
-    let query_transaction_id = params.transaction_id.to_string();
+    let query_order_id = order_id.to_string();
+    let query_transaction_id = transaction_id.to_string();
     let query_pool = pool.clone();
 
-    let update_order_id = params.order_id.to_string();
-    let update_transaction_id = params.transaction_id.to_string();
+    let update_order_id = order_id.to_string();
+    let update_transaction_id = transaction_id.to_string();
     let update_pool = pool.clone();
 
-    let return_order_id = params.order_id.to_string();
+    let return_order_id = order_id.to_string();
 
-    db::execute_is_order_exist(query_pool.clone(), query_order_id.clone())
-        .and_then(|exist| {
-            if !exist {
-                return Err(AWError::from(HttpResponse::NotFound().finish()));
+    find_party_order(query_pool, identity.clone(), query_order_id)
+        .and_then(move |(record, participants)| {
+            if !identity.may_pay(&record, &participants) {
+                return Err(AWError::from(
+                    HttpResponse::Forbidden()
+                        .reason("Buyer Or Merchant Staff Only")
+                        .finish(),
+                ));
             }
-            Ok(())
-        })
-        .and_then(move |_| db::execute_get_order_by_id(query_pool, query_order_id))
-        .and_then(move |record| {
             if record.status != OrderStatus::PendingPayment {
                 return Err(AWError::from(
                     HttpResponse::BadRequest()
@@ -217,31 +760,19 @@ fn submit_payment_proof(
                         .finish(),
                 ));
             }
-
-            let transaction =
-                get_transaction_by_id(query_transaction_id, record.wallet_name.clone());
-            let transaction = match transaction {
-                None => {
-                    return Err(AWError::from(
-                        HttpResponse::NotFound()
-                            .reason("Transaction Not Found")
-                            .finish(),
-                    ))
-                }
-                Some(transaction) => transaction,
-            };
-
-            if let Transaction::TransferTransaction(tx) = transaction {
-                Ok((tx, record))
-            } else {
-                Err(AWError::from(
+            // The payment of the whole checkout is submitted at once
+            if record.checkout_id.is_some() {
+                return Err(AWError::from(
                     HttpResponse::BadRequest()
-                        .reason("Invalid Transaction Type")
+                        .reason("Order Paid By Checkout")
                         .finish(),
-                ))
+                ));
             }
+
+            let tx = get_transfer_transaction(query_transaction_id, record.wallet_name.clone())?;
+            Ok((tx, record, participants))
         })
-        .and_then(move |(tx, record)| {
+        .and_then(move |(tx, record, participants)| {
             let (wallet, _, _) = make_app();
             let wallet_name = &record.wallet_name.to_string();
             let passphrase = SecUtf8::from("passphrase");
@@ -254,47 +785,18 @@ fn submit_payment_proof(
                 ));
             }
 
-            let merchant_public_key = &wallet.public_keys(&wallet_name, &passphrase).unwrap()[0];
-            let merchant_public_key = merchant_public_key.to_owned();
-            let buyer_public_key =
-                PublicKey::from_str(&record.buyer_public_key.to_string()).unwrap();
-            let escrow_public_key =
-                PublicKey::from_str(&record.escrow_public_key.to_string()).unwrap();
-            let multisig_address = wallet
-                .new_multisig_transfer_address(
-                    &wallet_name,
-                    &passphrase,
-                    vec![
-                        merchant_public_key.clone(),
-                        buyer_public_key.clone(),
-                        escrow_public_key.clone(),
-                    ],
-                    merchant_public_key.clone(),
-                    2,
-                    3,
-                )
-                .unwrap();
-            if tx.outputs[0].address.to_cro().unwrap() != multisig_address.to_string() {
-                return Err(AWError::from(
-                    HttpResponse::BadRequest()
-                        .reason("Incorrect Transaction Output Address")
-                        .finish(),
-                ));
-            }
-            if tx.outputs[0].value != Coin::from_str(&record.amount).unwrap() {
-                return Err(AWError::from(
-                    HttpResponse::BadRequest()
-                        .reason("Incorrect Transaction Output Amount")
-                        .finish(),
-                ));
-            }
-            Ok(())
+            let multisig_address =
+                order_multisig_address(&wallet, &wallet_name, &passphrase, &record, &participants);
+            payment_output(&tx, &multisig_address.to_string(), &record)
+                .map(|index| index as i32)
+                .map_err(|reason| AWError::from(HttpResponse::BadRequest().reason(reason).finish()))
         })
-        .and_then(move |_| {
+        .and_then(move |output_index| {
             db::execute_store_payment_transaction_id(
                 update_pool,
                 update_order_id,
                 update_transaction_id,
+                output_index,
             )
         })
         .and_then(move |_| {
@@ -305,131 +807,1784 @@ fn submit_payment_proof(
         })
 }
 
-fn get_order(
+/// Links every output of a transaction paying for the orders of a checkout at once
+fn submit_checkout_payment_proof(
+    identity: Identity,
     pool: web::Data<Pool>,
-    params: web::Query<OrderRequest>,
+    checkout_id: String,
+    transaction_id: String,
 ) -> impl Future<Item = HttpResponse, Error = AWError> {
     // TODO: Consider using Arc to share resource
-    let query_order_id = params.order_id.to_string();
+    let query_checkout_id = checkout_id.to_string();
     let query_pool = pool.clone();
 
-    let return_order_id = params.order_id.to_string();
+    let orders_pool = pool.clone();
+    let participants_pool = pool.clone();
 
-    db::execute_is_order_exist(query_pool.clone(), query_order_id.clone())
-        .and_then(|exist| {
-            if !exist {
-                return Err(AWError::from(HttpResponse::NotFound().finish()));
+    let update_checkout_id = checkout_id.to_string();
+    let update_transaction_id = transaction_id.to_string();
+    let update_pool = pool.clone();
+
+    let return_checkout_id = checkout_id.to_string();
+
+    db::execute_get_checkout(query_pool, query_checkout_id)
+        .and_then(|checkout| match checkout {
+            None => Err(AWError::from(
+                HttpResponse::NotFound()
+                    .reason("Checkout Not Found")
+                    .finish(),
+            )),
+            Some(ref checkout) if checkout.status != CheckoutStatus::PendingPayment => {
+                Err(AWError::from(
+                    HttpResponse::BadRequest()
+                        .reason("Checkout Not Pending for Payment")
+                        .finish(),
+                ))
             }
-            Ok(())
+            Some(checkout) => Ok(checkout),
         })
-        .and_then(move |_| db::execute_get_order_by_id(query_pool, query_order_id))
-        .and_then(move |record| {
-            // Uncomment to return commitment and nonce in response
-            // let (wallet, _, _) = make_app();
-            // let passphrase = SecUtf8::from("passphrase");
-
-            // let nonce_commitment: String = match record.status {
-            //     OrderStatus::Delivering | OrderStatus::Refunding => {
-            //         let session_id_vec = hex::decode(record.session_id.to_string()).unwrap();
-            //         let mut session_id = [0; 32];
-            //         session_id.copy_from_slice(&session_id_vec);
-            //         let nonce_commitment = wallet.nonce_commitment(&session_id, &passphrase).unwrap();
+        .and_then(move |checkout| {
+            db::execute_get_checkout_orders(orders_pool, checkout.checkout_id)
+        })
+        .and_then(move |orders| {
+            if orders
+                .iter()
+                .any(|record| record.status != OrderStatus::PendingPayment)
+            {
+                return Either::A(future::err(AWError::from(
+                    HttpResponse::BadRequest()
+                        .reason("Transaction Not Pending for Payment")
+                        .finish(),
+                )));
+            }
+            let order_ids = orders
+                .iter()
+                .map(|record| record.order_id.clone())
+                .collect();
+            Either::B(
+                db::execute_get_participants_of_orders(participants_pool, order_ids)
+                    .map(move |participants| (orders, participants)),
+            )
+        })
+        .and_then(move |(orders, participants)| {
+            let (wallet, _, _) = make_app();
+            let passphrase = SecUtf8::from("passphrase");
 
-            //         hex::encode(nonce_commitment)
-            //     },
-            //     _ => String::from(""),
-            // };
-            // let nonce: String = match record.status {
-            //     OrderStatus::Delivering | OrderStatus::Refunding => {
-            //         let session_id_vec = hex::decode(record.session_id.to_string()).unwrap();
-            //         let mut session_id = [0; 32];
-            //         session_id.copy_from_slice(&session_id_vec);
-            //         wallet.nonce(&session_id, &passphrase).unwrap().to_string()
-            //     },
-            //     _ => String::from(""),
-            // };
+            if !is_checkout_buyer(&identity, &orders, &participants) {
+                return Err(AWError::from(
+                    HttpResponse::Forbidden()
+                        .reason("Checkout Buyer Only")
+                        .finish(),
+                ));
+            }
 
-            let res = OrderResponse {
-                order_id: return_order_id,
-                status: record.status,
-                amount: record.amount,
-                buyer_public_key: record.buyer_public_key,
-                buyer_view_key: record.buyer_view_key,
-                buyer_address: record.buyer_address,
-                escrow_public_key: record.escrow_public_key,
-                escrow_view_key: record.escrow_view_key,
-                session_id: record.session_id,
-                payment_transaction_id: record.payment_transaction_id,
-                settlement_transaction_id: record.settlement_transaction_id,
-                // nonce_commitment,
-                // nonce
+            // Any wallet of the checkout's merchants reads the payment
+            let tx = get_transfer_transaction(transaction_id, orders[0].wallet_name.clone())?;
+            let mut payment_outputs = vec![];
+            for record in orders.iter() {
+                let order_participants: Vec<OrderParticipant> = participants
+                    .iter()
+                    .filter(|participant| participant.order_id == record.order_id)
+                    .cloned()
+                    .collect();
+                let multisig_address = order_multisig_address(
+                    &wallet,
+                    &record.wallet_name,
+                    &passphrase,
+                    record,
+                    &order_participants,
+                );
+                let index = payment_output(&tx, &multisig_address.to_string(), record).map_err(
+                    |reason| AWError::from(HttpResponse::BadRequest().reason(reason).finish()),
+                )?;
+                payment_outputs.push((record.order_id.clone(), index as i32));
+            }
+            Ok(payment_outputs)
+        })
+        .and_then(move |payment_outputs| {
+            let order_ids = payment_outputs
+                .iter()
+                .map(|(order_id, _)| order_id.clone())
+                .collect();
+            db::execute_store_checkout_payment(
+                update_pool,
+                update_checkout_id,
+                update_transaction_id,
+                payment_outputs,
+            )
+            .map(move |_| order_ids)
+        })
+        .and_then(move |order_ids| {
+            let res = CheckoutPaidResponse {
+                checkout_id: return_checkout_id,
+                order_ids,
             };
             Ok(HttpResponse::Ok().json(res))
         })
 }
 
-fn mark_delivering(
-    pool: web::Data<Pool>,
-    params: web::Form<OrderRequest>,
-) -> impl Future<Item = HttpResponse, Error = AWError> {
-    mark(pool, params, OrderStatus::Delivering)
-}
-fn mark_refunding(
-    pool: web::Data<Pool>,
-    params: web::Form<OrderRequest>,
-) -> impl Future<Item = HttpResponse, Error = AWError> {
-    mark(pool, params, OrderStatus::Refunding)
+/// Transfer transaction of a payment, read with the view key of the given wallet
+fn get_transfer_transaction(transaction_id: String, wallet_name: String) -> Result<Tx, AWError> {
+    let transaction = get_transaction_by_id(transaction_id, wallet_name);
+    let transaction = match transaction {
+        None => {
+            return Err(AWError::from(
+                HttpResponse::NotFound()
+                    .reason("Transaction Not Found")
+                    .finish(),
+            ))
+        }
+        Some(transaction) => transaction,
+    };
+
+    if let Transaction::TransferTransaction(tx) = transaction {
+        Ok(tx)
+    } else {
+        Err(AWError::from(
+            HttpResponse::BadRequest()
+                .reason("Invalid Transaction Type")
+                .finish(),
+        ))
+    }
 }
-fn mark(
+
+fn new_checkout(
+    identity: Identity,
     pool: web::Data<Pool>,
-    params: web::Form<OrderRequest>,
-    status: OrderStatus,
+    Valid(params): Valid<web::Form<NewCheckoutRequest>>,
 ) -> impl Future<Item = HttpResponse, Error = AWError> {
     // TODO: Consider using Arc to share resource
-    let query_order_id = params.order_id.to_string();
+    let query_checkout_id = params.checkout_id.to_string();
     let query_pool = pool.clone();
 
-    let update_order_id = params.order_id.to_string();
+    let orders_pool = pool.clone();
+    let query_orders_pool = pool.clone();
+    let participants_pool = pool.clone();
     let update_pool = pool.clone();
 
-    let return_order_id = params.order_id.to_string();
+    db::execute_get_checkout(query_pool, query_checkout_id)
+        .and_then(move |checkout| {
+            if checkout.is_some() {
+                return Err(AWError::from(
+                    HttpResponse::BadRequest()
+                        .reason("Checkout Already Exist")
+                        .finish(),
+                ));
+            }
+            parse_order_ids(&params.order_ids)
+                .map(|order_ids| (params, order_ids))
+                .map_err(|reason| AWError::from(HttpResponse::BadRequest().reason(reason).finish()))
+        })
+        .and_then(move |(params, order_ids)| {
+            db::execute_get_orders_by_ids(orders_pool, order_ids.clone())
+                .map(move |orders| (params, order_ids, orders))
+        })
+        .and_then(move |(params, order_ids, orders)| {
+            db::execute_get_participants_of_orders(participants_pool, order_ids.clone())
+                .map(move |participants| (params, order_ids, orders, participants))
+        })
+        .and_then(move |(params, order_ids, orders, participants)| {
+            if orders.len() != order_ids.len() {
+                return Err(AWError::from(
+                    HttpResponse::NotFound().reason("Order Not Found").finish(),
+                ));
+            }
+            // The buyer signs for every order it locks in the checkout
+            if !is_checkout_buyer(&identity, &orders, &participants) {
+                return Err(AWError::from(
+                    HttpResponse::Forbidden()
+                        .reason("Checkout Buyer Only")
+                        .finish(),
+                ));
+            }
+            for record in orders.iter() {
+                if record.status != OrderStatus::PendingPayment {
+                    return Err(AWError::from(
+                        HttpResponse::BadRequest()
+                            .reason("Transaction Not Pending for Payment")
+                            .finish(),
+                    ));
+                }
+                if record.checkout_id.is_some() {
+                    return Err(AWError::from(
+                        HttpResponse::BadRequest()
+                            .reason("Order Already In Checkout")
+                            .finish(),
+                    ));
+                }
+            }
+            let checkout = NewCheckout {
+                checkout_id: params.checkout_id.to_string(),
+                status: CheckoutStatus::PendingPayment,
+            };
+            Ok((checkout, order_ids, participants))
+        })
+        .and_then(move |(checkout, order_ids, participants)| {
+            db::execute_create_checkout(update_pool, checkout, order_ids)
+                .map(move |checkout| (checkout, participants))
+        })
+        .and_then(move |(checkout, participants)| {
+            db::execute_get_checkout_orders(query_orders_pool, checkout.checkout_id.clone())
+                .map(move |orders| (checkout, orders, participants))
+        })
+        .and_then(move |(checkout, orders, participants)| {
+            Ok(HttpResponse::Ok().json(checkout_response(checkout, orders, &participants)))
+        })
+}
+
+fn get_checkout(
+    identity: Identity,
+    pool: web::Data<Pool>,
+    Valid(params): Valid<web::Query<CheckoutRequest>>,
+) -> impl Future<Item = HttpResponse, Error = AWError> {
+    // TODO: Consider using Arc to share resource
+    let query_checkout_id = params.checkout_id.to_string();
+    let query_pool = pool.clone();
+
+    let orders_pool = pool.clone();
+    let participants_pool = pool.clone();
+
+    db::execute_get_checkout(query_pool, query_checkout_id)
+        .and_then(|checkout| {
+            checkout.ok_or_else(|| {
+                AWError::from(
+                    HttpResponse::NotFound()
+                        .reason("Checkout Not Found")
+                        .finish(),
+                )
+            })
+        })
+        .and_then(move |checkout| {
+            db::execute_get_checkout_orders(orders_pool, checkout.checkout_id.clone())
+                .map(move |orders| (checkout, orders))
+        })
+        .and_then(move |(checkout, orders)| {
+            let order_ids = orders
+                .iter()
+                .map(|record| record.order_id.clone())
+                .collect();
+            db::execute_get_participants_of_orders(participants_pool, order_ids)
+                .map(move |participants| (checkout, orders, participants))
+        })
+        .and_then(move |(checkout, orders, participants)| {
+            if !is_checkout_buyer(&identity, &orders, &participants) {
+                return Err(AWError::from(
+                    HttpResponse::Forbidden()
+                        .reason("Checkout Buyer Only")
+                        .finish(),
+                ));
+            }
+            Ok(HttpResponse::Ok().json(checkout_response(checkout, orders, &participants)))
+        })
+}
+
+/// Orders of a checkout with the multi-sig address each output of the payment has to fund
+fn checkout_response(
+    checkout: Checkout,
+    orders: Vec<Order>,
+    participants: &[OrderParticipant],
+) -> CheckoutResponse {
+    let (wallet, _, _) = make_app();
+    let passphrase = SecUtf8::from("passphrase");
+
+    let amount = checkout_amount(&orders);
+    let orders = orders
+        .into_iter()
+        .map(|record| {
+            let order_participants: Vec<OrderParticipant> = participants
+                .iter()
+                .filter(|participant| participant.order_id == record.order_id)
+                .cloned()
+                .collect();
+            let multisig_address = order_multisig_address(
+                &wallet,
+                &record.wallet_name,
+                &passphrase,
+                &record,
+                &order_participants,
+            );
+            CheckoutOrderResponse {
+                order_id: record.order_id,
+                status: record.status,
+                amount: record.amount.to_string(),
+                multisig_address: multisig_address.to_string(),
+                payment_output_index: record.payment_output_index,
+            }
+        })
+        .collect();
+
+    CheckoutResponse {
+        checkout_id: checkout.checkout_id,
+        status: checkout.status,
+        amount: u64::from(amount).to_string(),
+        payment_transaction_id: checkout.payment_transaction_id,
+        orders,
+    }
+}
+
+fn new_batch(
+    staff: Staff,
+    pool: web::Data<Pool>,
+    config: web::Data<MerchantConfig>,
+    Valid(params): Valid<web::Form<NewBatchRequest>>,
+) -> impl Future<Item = HttpResponse, Error = AWError> {
+    // TODO: Consider using Arc to share resource
+    let orders_pool = pool.clone();
+    let participants_pool = pool.clone();
+    let settings_pool = pool.clone();
+    let update_pool = pool.clone();
+
+    let merchant_id = staff.merchant_id;
+    let parsed = if config.batch_settlement {
+        parse_order_ids(&params.order_ids)
+    } else {
+        Err("Batch Settlement Disabled")
+    };
+    let parsed = parsed.and_then(|order_ids| {
+        if order_ids.len() < 2 {
+            return Err("Invalid Order IDs");
+        }
+        Ok(order_ids)
+    });
+
+    future::result(parsed)
+        .map_err(|reason| AWError::from(HttpResponse::BadRequest().reason(reason).finish()))
+        .and_then(move |order_ids| {
+            db::execute_get_orders_by_ids(orders_pool, order_ids.clone())
+                .map(move |orders| (order_ids, orders))
+        })
+        .and_then(move |(order_ids, mut orders)| {
+            // Orders of the other merchants are not found by the staff
+            orders.retain(|record| record.merchant_id == merchant_id);
+            if orders.len() != order_ids.len() {
+                return Err(AWError::from(
+                    HttpResponse::NotFound().reason("Order Not Found").finish(),
+                ));
+            }
+            for record in orders.iter() {
+                if settled_status(record.status).is_none() {
+                    return Err(AWError::from(
+                        HttpResponse::BadRequest()
+                            .reason("Transaction Not Ready")
+                            .finish(),
+                    ));
+                }
+                if record.session_id.is_some() {
+                    return Err(AWError::from(
+                        HttpResponse::BadRequest()
+                            .reason("Signing Session Already Started")
+                            .finish(),
+                    ));
+                }
+                if record.batch_id.is_some() {
+                    return Err(AWError::from(
+                        HttpResponse::BadRequest()
+                            .reason("Order Already In Batch")
+                            .finish(),
+                    ));
+                }
+            }
+            orders.sort_by(|a, b| a.order_id.cmp(&b.order_id));
+            Ok((order_ids, orders))
+        })
+        .and_then(move |(order_ids, orders)| {
+            db::execute_get_participants_of_orders(participants_pool, order_ids.clone())
+                .map(move |participants| (order_ids, orders, participants))
+        })
+        .and_then(move |(order_ids, orders, participants)| {
+            find_merchant_settings(settings_pool, config, merchant_id)
+                .map(move |settings| (order_ids, orders, participants, settings))
+        })
+        .and_then(move |(order_ids, orders, participants, settings)| {
+            let (wallet, _, _) = make_app();
+            let passphrase = SecUtf8::from("passphrase");
+
+            // The settlement each order would have on its own, merged into one transaction
+            // paying a single network fee
+            let bad_request =
+                |reason| AWError::from(HttpResponse::BadRequest().reason(reason).finish());
+            let mut settlements = vec![];
+            for record in orders.iter() {
+                let order_participants: Vec<OrderParticipant> = participants
+                    .iter()
+                    .filter(|participant| participant.order_id == record.order_id)
+                    .cloned()
+                    .collect();
+                let settlement = unsigned_settlement(
+                    record.wallet_name.clone(),
+                    passphrase.clone(),
+                    &wallet,
+                    record,
+                    &order_participants,
+                    &settings,
+                )
+                .map_err(bad_request)?;
+                settlements.push(settlement);
+            }
+            let settlement = merge_settlements(settlements).map_err(bad_request)?;
+            let network_id = hex::decode(NETWORK_ID).unwrap()[0];
+            let (transaction, network_fee) = fee_policy()
+                .and_then(|fee_policy| unsigned_transaction(network_id, settlement, &fee_policy))
+                .map_err(bad_request)?;
+
+            let batch = NewSettlementBatch {
+                status: BatchStatus::Signing,
+                transaction_id: hex::encode(transaction.id()),
+                encoded_transaction: encode_transaction(&transaction),
+                merchant_id,
+                network_fee: Some(to_base_units(network_fee)),
+            };
+            Ok((batch, order_ids, orders))
+        })
+        .and_then(move |(batch, order_ids, orders)| {
+            db::execute_create_batch(update_pool, batch, order_ids)
+                .map(move |batch| (batch, orders))
+        })
+        .and_then(
+            move |(batch, orders)| Ok(HttpResponse::Ok().json(batch_response(batch, &orders))),
+        )
+}
+
+fn get_batch(
+    staff: Staff,
+    pool: web::Data<Pool>,
+    params: web::Query<BatchRequest>,
+) -> impl Future<Item = HttpResponse, Error = AWError> {
+    db::execute_get_batch_settlement(pool, params.batch_id)
+        .and_then(move |settlement| {
+            settlement
+                .filter(|settlement| settlement.batch.merchant_id == staff.merchant_id)
+                .ok_or_else(|| {
+                    AWError::from(HttpResponse::NotFound().reason("Batch Not Found").finish())
+                })
+        })
+        .and_then(|settlement| {
+            Ok(HttpResponse::Ok().json(batch_response(settlement.batch, &settlement.orders)))
+        })
+}
+
+fn cancel_batch(
+    staff: Staff,
+    pool: web::Data<Pool>,
+    params: web::Form<BatchRequest>,
+) -> impl Future<Item = HttpResponse, Error = AWError> {
+    // TODO: Consider using Arc to share resource
+    let query_pool = pool.clone();
+    let update_pool = pool.clone();
+
+    db::execute_get_batch_settlement(query_pool, params.batch_id)
+        .and_then(move |settlement| {
+            let settlement = settlement
+                .filter(|settlement| settlement.batch.merchant_id == staff.merchant_id)
+                .ok_or_else(|| {
+                    AWError::from(HttpResponse::NotFound().reason("Batch Not Found").finish())
+                })?;
+            if settlement.batch.status != BatchStatus::Signing {
+                return Err(AWError::from(
+                    HttpResponse::BadRequest()
+                        .reason("Batch Not Signing")
+                        .finish(),
+                ));
+            }
+            Ok(settlement)
+        })
+        .and_then(move |settlement| {
+            db::execute_cancel_batch(update_pool, settlement.batch.id).map(move |_| settlement)
+        })
+        .and_then(|mut settlement| {
+            settlement.batch.status = BatchStatus::Cancelled;
+            Ok(HttpResponse::Ok().json(batch_response(settlement.batch, &settlement.orders)))
+        })
+}
+
+/// Orders settled by a batch with the aggregated outputs of the batch transaction
+fn batch_response(batch: SettlementBatch, orders: &[Order]) -> BatchResponse {
+    let transaction = decode_transaction(&batch.encoded_transaction);
+    let outputs = transaction
+        .outputs
+        .iter()
+        .map(|output| SettlementOutput {
+            address: output.address.to_cro().unwrap(),
+            value: u64::from(output.value).to_string(),
+        })
+        .collect();
+
+    BatchResponse {
+        batch_id: batch.id,
+        status: batch.status,
+        transaction_id: batch.transaction_id,
+        order_ids: orders
+            .iter()
+            .map(|record| record.order_id.clone())
+            .collect(),
+        outputs,
+        network_fee: batch.network_fee.map(|network_fee| network_fee.to_string()),
+    }
+}
+
+/// Loads an order with its co-signers, failing with Not Found if there is none or Forbidden if
+/// the caller is neither the merchant staff nor a co-signer of the order
+fn find_party_order(
+    pool: web::Data<Pool>,
+    identity: Identity,
+    order_id: String,
+) -> impl Future<Item = (Order, Vec<OrderParticipant>), Error = AWError> {
+    // TODO: Consider using Arc to share resource
+    let query_pool = pool.clone();
+    let participants_pool = pool.clone();
+
+    db::execute_is_order_exist(pool, order_id.clone())
+        .and_then(|exist| {
+            if !exist {
+                return Err(AWError::from(HttpResponse::NotFound().finish()));
+            }
+            Ok(())
+        })
+        .and_then(move |_| db::execute_get_order_by_id(query_pool, order_id))
+        .and_then(move |record| {
+            db::execute_get_order_participants(participants_pool, record.order_id.clone())
+                .map(move |participants| (record, participants))
+        })
+        .and_then(move |(record, participants)| {
+            if !identity.is_order_party(&record, &participants) {
+                return Err(AWError::from(
+                    HttpResponse::Forbidden()
+                        .reason("Not An Order Party")
+                        .finish(),
+                ));
+            }
+            Ok((record, participants))
+        })
+}
+
+/// Checks that the caller holds the key of the co-signer submitting a signing step, the buyer
+/// when no public key is given
+fn authorize_signer(
+    identity: &Identity,
+    record: &Order,
+    public_key: &Option<String>,
+) -> Result<(), AWError> {
+    let submitter = public_key.as_ref().unwrap_or(&record.buyer_public_key);
+    if !identity.holds_key(submitter) {
+        return Err(AWError::from(
+            HttpResponse::Forbidden()
+                .reason("Not The Submitting Signer")
+                .finish(),
+        ));
+    }
+    Ok(())
+}
+
+fn get_order(
+    identity: Identity,
+    pool: web::Data<Pool>,
+    Valid(params): Valid<web::Query<OrderRequest>>,
+) -> impl Future<Item = HttpResponse, Error = AWError> {
+    // TODO: Consider using Arc to share resource
+    let query_order_id = params.order_id.to_string();
+    let query_pool = pool.clone();
+
+    find_party_order(query_pool, identity, query_order_id).and_then(
+        move |(record, participants)| {
+            // Uncomment to return commitment and nonce in response
+            // let (wallet, _, _) = make_app();
+            // let passphrase = SecUtf8::from("passphrase");
+
+            // let nonce_commitment: String = match record.status {
+            //     OrderStatus::Delivering | OrderStatus::Refunding => {
+            //         let session_id_vec = hex::decode(record.session_id.to_string()).unwrap();
+            //         let mut session_id = [0; 32];
+            //         session_id.copy_from_slice(&session_id_vec);
+            //         let nonce_commitment = wallet.nonce_commitment(&session_id, &passphrase).unwrap();
+
+            //         hex::encode(nonce_commitment)
+            //     },
+            //     _ => String::from(""),
+            // };
+            // let nonce: String = match record.status {
+            //     OrderStatus::Delivering | OrderStatus::Refunding => {
+            //         let session_id_vec = hex::decode(record.session_id.to_string()).unwrap();
+            //         let mut session_id = [0; 32];
+            //         session_id.copy_from_slice(&session_id_vec);
+            //         wallet.nonce(&session_id, &passphrase).unwrap().to_string()
+            //     },
+            //     _ => String::from(""),
+            // };
+
+            let res = order_response(record, participants);
+            Ok(HttpResponse::Ok().json(res))
+        },
+    )
+}
+
+/// Order as returned by the API, amounts as decimal strings and the fields not set yet empty
+fn order_response(record: Order, participants: Vec<OrderParticipant>) -> OrderResponse {
+    OrderResponse {
+        order_id: record.order_id,
+        status: record.status,
+        amount: record.amount.to_string(),
+        buyer_public_key: record.buyer_public_key,
+        buyer_view_key: record.buyer_view_key,
+        buyer_address: record.buyer_address,
+        escrow_public_key: record.escrow_public_key,
+        escrow_view_key: record.escrow_view_key,
+        session_id: record.session_id.unwrap_or_default(),
+        payment_transaction_id: record.payment_transaction_id.unwrap_or_default(),
+        settlement_transaction_id: record.settlement_transaction_id.unwrap_or_default(),
+        extra_view_keys: record.extra_view_keys,
+        required_signatures: record.required_signatures,
+        merchant_public_key: record.merchant_public_key,
+        payout_address: record.payout_address,
+        refund_address: record.refund_address.unwrap_or_default(),
+        split_merchant_amount: record
+            .split_merchant_amount
+            .map(|amount| amount.to_string())
+            .unwrap_or_default(),
+        split_buyer_amount: record
+            .split_buyer_amount
+            .map(|amount| amount.to_string())
+            .unwrap_or_default(),
+        arbitrated: record.arbitrated,
+        escrow_id: record.escrow_id,
+        funding_transaction_id: record.funding_transaction_id.unwrap_or_default(),
+        funding_amount: record
+            .funding_amount
+            .map(|amount| amount.to_string())
+            .unwrap_or_default(),
+        milestone_amount: record
+            .milestone_amount
+            .map(|amount| amount.to_string())
+            .unwrap_or_default(),
+        checkout_id: record.checkout_id.unwrap_or_default(),
+        payment_output_index: record.payment_output_index,
+        batch_id: record.batch_id,
+        merchant_id: record.merchant_id,
+        created_at: record.created_at.format(TIMESTAMP_FORMAT).to_string(),
+        updated_at: record.updated_at.format(TIMESTAMP_FORMAT).to_string(),
+        participants,
+    }
+}
+
+fn get_order_history(
+    identity: Identity,
+    pool: web::Data<Pool>,
+    Valid(params): Valid<web::Query<OrderRequest>>,
+) -> impl Future<Item = HttpResponse, Error = AWError> {
+    let query_order_id = params.order_id.to_string();
+    let query_pool = pool.clone();
+
+    find_party_order(query_pool.clone(), identity, query_order_id.clone())
+        .and_then(move |_| db::execute_get_order_events(query_pool, query_order_id))
+        .and_then(|events| Ok(HttpResponse::Ok().json(events)))
+}
+
+fn change_refund_address(
+    pool: web::Data<Pool>,
+    config: web::Data<MerchantConfig>,
+    Valid(params): Valid<web::Form<RefundAddressRequest>>,
+) -> impl Future<Item = HttpResponse, Error = AWError> {
+    // TODO: Consider using Arc to share resource
+    let query_order_id = params.order_id.to_string();
+    let query_pool = pool.clone();
+
+    let update_order_id = params.order_id.to_string();
+    let update_pool = pool.clone();
+
+    let return_order_id = params.order_id.to_string();
+
+    db::execute_is_order_exist(query_pool.clone(), query_order_id.clone())
+        .and_then(|exist| {
+            if !exist {
+                return Err(AWError::from(HttpResponse::NotFound().finish()));
+            }
+            Ok(())
+        })
+        .and_then(move |_| db::execute_get_order_by_id(query_pool, query_order_id))
+        .and_then(move |record| {
+            // The refund address is part of the settlement transaction once a session starts
+            if record.status.is_settled()
+                || (record.status.is_settling() && record.session_id.is_some())
+            {
+                return Err(AWError::from(
+                    HttpResponse::BadRequest()
+                        .reason("Settlement Already Started")
+                        .finish(),
+                ));
+            }
+            if parse_address(&params.refund_address, config.network).is_err() {
+                return Err(AWError::from(
+                    HttpResponse::BadRequest()
+                        .reason("Invalid Refund Address")
+                        .finish(),
+                ));
+            }
+
+            let message = refund_address_message(&record, &params.refund_address);
+            if !verify_key_signature(&record.buyer_public_key, &message, &params.signature) {
+                return Err(AWError::from(
+                    HttpResponse::BadRequest()
+                        .reason("Invalid Buyer Signature")
+                        .finish(),
+                ));
+            }
+
+            let detail = format!(
+                "{} -> {} signature {}",
+                current_refund_address(&record),
+                params.refund_address,
+                params.signature
+            );
+            Ok((params.refund_address.to_string(), detail))
+        })
+        .and_then(move |(refund_address, detail)| {
+            db::execute_change_refund_address(update_pool, update_order_id, refund_address, detail)
+        })
+        .and_then(move |_| {
+            let res = OrderUpdatedResponse {
+                order_id: return_order_id,
+            };
+            Ok(HttpResponse::Ok().json(res))
+        })
+}
+
+/// Address receiving refunds and deposit returns of an order
+fn current_refund_address(record: &Order) -> &str {
+    record
+        .refund_address
+        .as_ref()
+        .unwrap_or(&record.buyer_address)
+}
+
+/// Message the buyer signs to change the refund address of an order. It names the address being
+/// replaced so that an earlier change request cannot be replayed.
+fn refund_address_message(record: &Order, refund_address: &str) -> String {
+    format!(
+        "refund-address:{}:{}:{}",
+        record.order_id,
+        current_refund_address(record),
+        refund_address
+    )
+}
+
+fn mark_delivering(
+    staff: Staff,
+    pool: web::Data<Pool>,
+    config: web::Data<MerchantConfig>,
+    Valid(params): Valid<web::Form<OrderRequest>>,
+) -> impl Future<Item = HttpResponse, Error = AWError> {
+    mark(staff, pool, config, params, OrderStatus::Delivering)
+}
+fn mark_refunding(
+    staff: Staff,
+    pool: web::Data<Pool>,
+    config: web::Data<MerchantConfig>,
+    Valid(params): Valid<web::Form<OrderRequest>>,
+) -> impl Future<Item = HttpResponse, Error = AWError> {
+    mark(staff, pool, config, params, OrderStatus::Refunding)
+}
+fn mark(
+    staff: Staff,
+    pool: web::Data<Pool>,
+    config: web::Data<MerchantConfig>,
+    params: web::Form<OrderRequest>,
+    status: OrderStatus,
+) -> impl Future<Item = HttpResponse, Error = AWError> {
+    // TODO: Consider using Arc to share resource
+    let query_order_id = params.order_id.to_string();
+    let query_pool = pool.clone();
+    let staff_name = staff.name.clone();
+
+    let settings_pool = pool.clone();
+    let update_order_id = params.order_id.to_string();
+    let update_pool = pool.clone();
+
+    let return_order_id = params.order_id.to_string();
+
+    find_party_order(query_pool, Identity::Staff(staff), query_order_id)
+        .and_then(move |(record, _)| {
+            find_merchant_settings(settings_pool, config, record.merchant_id)
+                .map(move |settings| (record, settings))
+        })
+        .and_then(move |(record, settings)| {
+            // Only a paid order waiting for the merchant starts its settlement here, a started one
+            // keeps its approvals and signing session
+            let rejected = match record.status {
+                OrderStatus::PendingResponse => None,
+                OrderStatus::PendingPayment => Some("Order Not Paid"),
+                OrderStatus::RefundPendingApproval | OrderStatus::SplitPendingApproval => {
+                    Some("Settlement Pending Approval")
+                }
+                OrderStatus::Disputed => Some("Order Disputed"),
+                status if status.is_settled() => Some("Order Already Settled"),
+                _ => Some("Settlement Already Started"),
+            };
+            if let Some(reason) = rejected {
+                return Either::A(future::err(AWError::from(
+                    HttpResponse::BadRequest().reason(reason).finish(),
+                )));
+            }
+            // Refunds needing approval wait for the other staff accounts, the requesting staff
+            // approving first. Any signing session of a previous settlement is abandoned.
+            if status == OrderStatus::Refunding
+                && requires_refund_approval(&record, &settings.refund_approval)
+            {
+                let approval = NewRefundApproval {
+                    order_id: update_order_id,
+                    staff_name,
+                };
+                Either::B(Either::A(db::execute_request_refund_approval(
+                    update_pool,
+                    approval,
+                )))
+            } else {
+                Either::B(Either::B(db::execute_start_settlement(
+                    update_pool,
+                    update_order_id.clone(),
+                    status,
+                )))
+            }
+        })
+        .and_then(move |_| {
+            let res = OrderUpdatedResponse {
+                order_id: return_order_id,
+            };
+            Ok(HttpResponse::Ok().json(res))
+        })
+}
+
+fn approve_refund(
+    staff: Staff,
+    pool: web::Data<Pool>,
+    config: web::Data<MerchantConfig>,
+    Valid(params): Valid<web::Form<OrderRequest>>,
+) -> impl Future<Item = HttpResponse, Error = AWError> {
+    // TODO: Consider using Arc to share resource
+    let query_order_id = params.order_id.to_string();
+    let query_pool = pool.clone();
+    let staff_name = staff.name.clone();
+
+    let approvals_pool = pool.clone();
+    let update_pool = pool.clone();
+
+    let required_approvals = config.refund_approval.required_approvals;
+
+    find_party_order(query_pool, Identity::Staff(staff), query_order_id)
+        .and_then(|(record, _)| {
+            if record.status != OrderStatus::RefundPendingApproval
+                && record.status != OrderStatus::SplitPendingApproval
+            {
+                return Err(AWError::from(
+                    HttpResponse::BadRequest()
+                        .reason("Refund Not Pending Approval")
+                        .finish(),
+                ));
+            }
+            Ok(record)
+        })
+        .and_then(move |record| {
+            db::execute_get_refund_approvals(approvals_pool, record.order_id.clone())
+                .map(move |approvals| (record, approvals))
+        })
+        .and_then(move |(record, approvals)| {
+            // Each staff account signs off a refund once
+            if approvals
+                .iter()
+                .any(|approval| approval.staff_name == staff_name)
+            {
+                return Either::A(future::err(AWError::from(
+                    HttpResponse::BadRequest()
+                        .reason("Refund Already Approved")
+                        .finish(),
+                )));
+            }
+            let approval = NewRefundApproval {
+                order_id: record.order_id.clone(),
+                staff_name,
+            };
+            Either::B(
+                db::execute_approve_refund(update_pool, approval, required_approvals)
+                    .map(move |approvals| (record, approvals)),
+            )
+        })
+        .and_then(move |(record, approvals)| {
+            let status = match record.status {
+                OrderStatus::RefundPendingApproval
+                    if is_refund_approved(&approvals, &config.refund_approval) =>
+                {
+                    OrderStatus::Refunding
+                }
+                OrderStatus::SplitPendingApproval
+                    if is_refund_approved(&approvals, &config.refund_approval) =>
+                {
+                    OrderStatus::Splitting
+                }
+                status => status,
+            };
+            let res = RefundApprovalResponse {
+                order_id: record.order_id,
+                status,
+                approved_by: approvals
+                    .into_iter()
+                    .map(|approval| approval.staff_name)
+                    .collect(),
+                required_approvals,
+            };
+            Ok(HttpResponse::Ok().json(res))
+        })
+}
+
+fn propose_split(
+    identity: Identity,
+    pool: web::Data<Pool>,
+    Valid(params): Valid<web::Form<ProposeSplitRequest>>,
+) -> impl Future<Item = HttpResponse, Error = AWError> {
+    // TODO: Consider using Arc to share resource
+    let query_order_id = params.order_id.to_string();
+    let query_pool = pool.clone();
+
+    let latest_pool = pool.clone();
+    let proposal_pool = pool.clone();
+
+    find_party_order(query_pool, identity.clone(), query_order_id)
+        .and_then(move |(record, participants)| {
+            db::execute_get_latest_split(latest_pool, record.order_id.clone())
+                .map(move |latest| (record, participants, latest))
+        })
+        .and_then(move |(record, participants, latest)| {
+            if !can_split(&record) {
+                return Err(AWError::from(
+                    HttpResponse::BadRequest()
+                        .reason("Order Cannot Be Split")
+                        .finish(),
+                ));
+            }
+            let bad_request =
+                |reason| AWError::from(HttpResponse::BadRequest().reason(reason).finish());
+            let proposed_by = parse_split_party(&params.proposed_by).map_err(bad_request)?;
+            if !identity.acts_as(&record, &participants, proposed_by) {
+                return Err(AWError::from(
+                    HttpResponse::Forbidden()
+                        .reason("Not Authorized For Party")
+                        .finish(),
+                ));
+            }
+            let (merchant_amount, buyer_amount) =
+                parse_split(&record, &params.merchant_amount, &params.buyer_amount)
+                    .map_err(bad_request)?;
+
+            let signature = params.signature.clone().unwrap_or_default();
+            let latest_id = latest.map_or(0, |proposal| proposal.id);
+            let message = split_message(&record.order_id, latest_id, merchant_amount, buyer_amount);
+            if proposed_by == ParticipantRole::Buyer
+                && !verify_key_signature(&record.buyer_public_key, &message, &signature)
+            {
+                return Err(bad_request("Invalid Buyer Signature"));
+            }
+
+            Ok(NewSplitProposal {
+                order_id: record.order_id,
+                proposed_by,
+                merchant_amount: to_base_units(merchant_amount),
+                buyer_amount: to_base_units(buyer_amount),
+                signature,
+                status: SplitProposalStatus::Proposed,
+            })
+        })
+        .and_then(move |proposal| db::execute_propose_split(proposal_pool, proposal))
+        .and_then(|proposal| {
+            let res = SplitProposalResponse {
+                order_id: proposal.order_id,
+                split_id: proposal.id,
+                proposed_by: proposal.proposed_by,
+                merchant_amount: proposal.merchant_amount.to_string(),
+                buyer_amount: proposal.buyer_amount.to_string(),
+            };
+            Ok(HttpResponse::Ok().json(res))
+        })
+}
+
+fn accept_split(
+    identity: Identity,
+    pool: web::Data<Pool>,
+    config: web::Data<MerchantConfig>,
+    Valid(params): Valid<web::Form<AcceptSplitRequest>>,
+) -> impl Future<Item = HttpResponse, Error = AWError> {
+    // TODO: Consider using Arc to share resource
+    let query_order_id = params.order_id.to_string();
+    let query_pool = pool.clone();
+
+    let settings_pool = pool.clone();
+    let proposal_pool = pool.clone();
+    let update_pool = pool.clone();
+
+    let return_order_id = params.order_id.to_string();
+
+    find_party_order(query_pool, identity.clone(), query_order_id)
+        .and_then(move |(record, participants)| {
+            find_merchant_settings(settings_pool, config, record.merchant_id)
+                .map(move |settings| (record, participants, settings))
+        })
+        .and_then(move |(record, participants, settings)| {
+            db::execute_get_proposed_split(proposal_pool, record.order_id.clone())
+                .map(move |proposal| (record, participants, settings, proposal))
+        })
+        .and_then(move |(record, participants, settings, proposal)| {
+            // Only the proposal still waiting for the counterparty can be accepted
+            let proposal = match proposal.filter(|proposal| proposal.id == params.split_id) {
+                Some(proposal) => proposal,
+                None => {
+                    return Err(AWError::from(
+                        HttpResponse::NotFound()
+                            .reason("Split Proposal Not Found")
+                            .finish(),
+                    ))
+                }
+            };
+            if !can_split(&record) {
+                return Err(AWError::from(
+                    HttpResponse::BadRequest()
+                        .reason("Order Cannot Be Split")
+                        .finish(),
+                ));
+            }
+            let bad_request =
+                |reason| AWError::from(HttpResponse::BadRequest().reason(reason).finish());
+            let accepted_by = parse_split_party(&params.accepted_by).map_err(bad_request)?;
+            if !identity.acts_as(&record, &participants, accepted_by) {
+                return Err(AWError::from(
+                    HttpResponse::Forbidden()
+                        .reason("Not Authorized For Party")
+                        .finish(),
+                ));
+            }
+            if accepted_by == proposal.proposed_by {
+                return Err(bad_request("Split Must Be Accepted By Counterparty"));
+            }
+
+            let signature = params.signature.clone().unwrap_or_default();
+            let buyer_amount = to_coin(proposal.buyer_amount);
+            let message = split_message(
+                &record.order_id,
+                proposal.id,
+                to_coin(proposal.merchant_amount),
+                buyer_amount,
+            );
+            if accepted_by == ParticipantRole::Buyer
+                && !verify_key_signature(&record.buyer_public_key, &message, &signature)
+            {
+                return Err(bad_request("Invalid Buyer Signature"));
+            }
+            // A split paying the buyer as much as a refund needing approval waits for the same
+            // approvals
+            let status =
+                if requires_split_approval(&record, buyer_amount, &settings.refund_approval) {
+                    OrderStatus::SplitPendingApproval
+                } else {
+                    OrderStatus::Splitting
+                };
+            Ok((proposal, signature, status))
+        })
+        .and_then(move |(proposal, signature, status)| {
+            db::execute_accept_split(update_pool, proposal, signature, status)
+        })
+        .and_then(move |_| {
+            let res = OrderUpdatedResponse {
+                order_id: return_order_id,
+            };
+            Ok(HttpResponse::Ok().json(res))
+        })
+}
+
+fn get_milestones(
+    identity: Identity,
+    pool: web::Data<Pool>,
+    Valid(params): Valid<web::Query<OrderRequest>>,
+) -> impl Future<Item = HttpResponse, Error = AWError> {
+    // TODO: Consider using Arc to share resource
+    let query_order_id = params.order_id.to_string();
+    let query_pool = pool.clone();
+
+    find_party_order(query_pool.clone(), identity, query_order_id.clone())
+        .and_then(move |_| db::execute_get_milestones(query_pool, query_order_id))
+        .and_then(|milestones| Ok(HttpResponse::Ok().json(milestones)))
+}
+
+fn release_milestone(
+    staff: Staff,
+    pool: web::Data<Pool>,
+    Valid(params): Valid<web::Form<OrderRequest>>,
+) -> impl Future<Item = HttpResponse, Error = AWError> {
+    // TODO: Consider using Arc to share resource
+    let query_order_id = params.order_id.to_string();
+    let query_pool = pool.clone();
+
+    let milestones_pool = pool.clone();
+    let update_pool = pool.clone();
+
+    find_party_order(query_pool, Identity::Staff(staff), query_order_id)
+        .and_then(move |(record, participants)| {
+            // Milestones are released one at a time from a paid order with no other settlement
+            if record.status != OrderStatus::PendingResponse {
+                return Err(AWError::from(
+                    HttpResponse::BadRequest()
+                        .reason("Order Not Awaiting Settlement")
+                        .finish(),
+                ));
+            }
+            Ok((record, participants))
+        })
+        .and_then(move |(record, participants)| {
+            db::execute_get_milestones(milestones_pool, record.order_id.clone())
+                .map(move |milestones| (record, participants, milestones))
+        })
+        .and_then(move |(record, participants, milestones)| {
+            let milestone = match next_milestone(&milestones) {
+                Some(milestone) => milestone,
+                None => {
+                    return Either::A(future::err(AWError::from(
+                        HttpResponse::BadRequest()
+                            .reason("No Milestone Pending")
+                            .finish(),
+                    )));
+                }
+            };
+
+            let (wallet, _, _) = make_app();
+            let passphrase = SecUtf8::from("passphrase");
+
+            // Funds of the remaining milestones move to a multi-sig address of a fresh merchant
+            // key with the same co-signers
+            let milestone_amount = to_coin(milestone.amount);
+            let remaining = (funding_amount(&record) - milestone_amount).unwrap();
+            let (change_public_key, change_address) = if remaining == Coin::zero() {
+                (String::from(""), String::from(""))
+            } else {
+                let change_public_key = wallet
+                    .new_public_key(&record.wallet_name, &passphrase)
+                    .expect("new_public_key error");
+                let change_address = multisig_address(
+                    &wallet,
+                    &record.wallet_name,
+                    &passphrase,
+                    change_public_key.clone(),
+                    &record,
+                    &participants,
+                );
+                (change_public_key.to_string(), change_address.to_string())
+            };
+
+            let res = MilestoneReleaseResponse {
+                order_id: record.order_id.clone(),
+                milestone_id: milestone.id,
+                sequence: milestone.sequence,
+                amount: milestone.amount.to_string(),
+                change_address,
+            };
+            Either::B(
+                db::execute_start_milestone_release(
+                    update_pool,
+                    record.order_id,
+                    to_base_units(milestone_amount),
+                    change_public_key,
+                )
+                .and_then(move |_| Ok(HttpResponse::Ok().json(res))),
+            )
+        })
+}
+
+fn escalate_order(
+    identity: Identity,
+    pool: web::Data<Pool>,
+    Valid(params): Valid<web::Form<OrderRequest>>,
+) -> impl Future<Item = HttpResponse, Error = AWError> {
+    // TODO: Consider using Arc to share resource
+    let query_order_id = params.order_id.to_string();
+    let query_pool = pool.clone();
+
+    let update_order_id = params.order_id.to_string();
+    let update_pool = pool.clone();
+
+    find_party_order(query_pool, identity.clone(), query_order_id)
+        .and_then(move |(record, participants)| {
+            if !can_escalate(&record) {
+                return Err(AWError::from(
+                    HttpResponse::BadRequest()
+                        .reason("Order Cannot Be Escalated")
+                        .finish(),
+                ));
+            }
+            let opened_by = identity.party_role(&record, &participants).ok_or_else(|| {
+                AWError::from(
+                    HttpResponse::Forbidden()
+                        .reason("Not An Order Party")
+                        .finish(),
+                )
+            })?;
+            Ok((opened_by, format!("Escalated from {:?}", record.status)))
+        })
+        .and_then(move |(opened_by, detail)| {
+            db::execute_escalate_order(update_pool, update_order_id, opened_by, detail)
+        })
+        .and_then(|dispute| {
+            let res = EscalationResponse {
+                order_id: dispute.order_id,
+                dispute_id: dispute.id,
+            };
+            Ok(HttpResponse::Ok().json(res))
+        })
+}
+
+fn submit_ruling(
+    pool: web::Data<Pool>,
+    Valid(params): Valid<web::Form<RulingRequest>>,
+) -> impl Future<Item = HttpResponse, Error = AWError> {
+    // TODO: Consider using Arc to share resource
+    let query_order_id = params.order_id.to_string();
+    let query_pool = pool.clone();
+
+    let dispute_order_id = params.order_id.to_string();
+    let dispute_pool = pool.clone();
+    let participants_pool = pool.clone();
+
+    let update_pool = pool.clone();
+
+    db::execute_is_order_exist(query_pool.clone(), query_order_id.clone())
+        .and_then(|exist| {
+            if !exist {
+                return Err(AWError::from(HttpResponse::NotFound().finish()));
+            }
+            Ok(())
+        })
+        .and_then(move |_| db::execute_get_order_by_id(query_pool, query_order_id))
+        .and_then(move |record| {
+            db::execute_get_active_dispute(dispute_pool, dispute_order_id)
+                .map(move |dispute| (record, dispute))
+        })
+        .and_then(move |(record, dispute)| {
+            db::execute_get_order_participants(participants_pool, record.order_id.clone())
+                .map(move |participants| (record, dispute, participants))
+        })
+        .and_then(move |(record, dispute, participants)| {
+            if record.status != OrderStatus::Disputed {
+                return Err(AWError::from(
+                    HttpResponse::BadRequest()
+                        .reason("Order Not Disputed")
+                        .finish(),
+                ));
+            }
+            let bad_request =
+                |reason| AWError::from(HttpResponse::BadRequest().reason(reason).finish());
+            // Only the case of the pending escalation can be ruled on
+            let dispute_id = match dispute {
+                Some(ref dispute)
+                    if dispute.id == params.dispute_id
+                        && dispute.status != DisputeStatus::Ruled =>
+                {
+                    dispute.id
+                }
+                _ => return Err(bad_request("Dispute Not Found")),
+            };
+            let (decision, shares) = parse_ruling(
+                &record,
+                &params.decision,
+                params.merchant_amount.as_ref().map(String::as_str),
+                params.buyer_amount.as_ref().map(String::as_str),
+            )
+            .map_err(bad_request)?;
+
+            let message = ruling_message(&record.order_id, dispute_id, decision, shares);
+            // Any escrow co-signing the order rules on its disputes
+            let escrow_public_key = participants
+                .iter()
+                .filter(|participant| participant.role == ParticipantRole::Escrow)
+                .map(|participant| participant.public_key.clone())
+                .find(|public_key| verify_key_signature(public_key, &message, &params.signature))
+                .ok_or_else(|| bad_request("Invalid Escrow Signature"))?;
+
+            let (merchant_amount, buyer_amount) = match shares {
+                Some((merchant_amount, buyer_amount)) => (
+                    u64::from(merchant_amount).to_string(),
+                    u64::from(buyer_amount).to_string(),
+                ),
+                None => (String::from(""), String::from("")),
+            };
+            Ok(NewRuling {
+                order_id: record.order_id,
+                escrow_public_key,
+                decision,
+                merchant_amount,
+                buyer_amount,
+                signature: params.signature.to_string(),
+                dispute_id: Some(dispute_id),
+            })
+        })
+        .and_then(move |ruling| {
+            let status = ruling_status(ruling.decision);
+            db::execute_record_ruling(update_pool, ruling, status)
+                .map(move |ruling| (ruling, status))
+        })
+        .and_then(|(ruling, status)| {
+            let res = RulingResponse {
+                order_id: ruling.order_id,
+                ruling_id: ruling.id,
+                dispute_id: ruling.dispute_id,
+                decision: ruling.decision,
+                merchant_amount: ruling.merchant_amount,
+                buyer_amount: ruling.buyer_amount,
+                status,
+            };
+            Ok(HttpResponse::Ok().json(res))
+        })
+}
+
+fn get_ruling(
+    identity: Identity,
+    pool: web::Data<Pool>,
+    Valid(params): Valid<web::Query<OrderRequest>>,
+) -> impl Future<Item = HttpResponse, Error = AWError> {
+    // TODO: Consider using Arc to share resource
+    let query_order_id = params.order_id.to_string();
+    let query_pool = pool.clone();
+
+    find_party_order(query_pool.clone(), identity, query_order_id.clone())
+        .and_then(move |_| db::execute_get_latest_ruling(query_pool, query_order_id))
+        .and_then(|ruling| match ruling {
+            Some(ruling) => Ok(HttpResponse::Ok().json(ruling)),
+            None => Err(AWError::from(
+                HttpResponse::NotFound().reason("Ruling Not Found").finish(),
+            )),
+        })
+}
+
+fn open_dispute(
+    identity: Identity,
+    pool: web::Data<Pool>,
+    Valid(params): Valid<web::Form<OpenDisputeRequest>>,
+) -> impl Future<Item = HttpResponse, Error = AWError> {
+    // TODO: Consider using Arc to share resource
+    let query_order_id = params.order_id.to_string();
+    let query_pool = pool.clone();
+
+    let update_pool = pool.clone();
+
+    find_party_order(query_pool.clone(), identity.clone(), query_order_id.clone())
+        .and_then(move |(record, participants)| {
+            db::execute_get_active_dispute(query_pool, query_order_id)
+                .map(move |active| (record, participants, active))
+        })
+        .and_then(move |(record, participants, active)| {
+            if active.is_some() {
+                return Err(AWError::from(
+                    HttpResponse::BadRequest()
+                        .reason("Dispute Already Open")
+                        .finish(),
+                ));
+            }
+            let opened_by = parse_party(&params.opened_by).map_err(|reason| {
+                AWError::from(HttpResponse::BadRequest().reason(reason).finish())
+            })?;
+            if !identity.acts_as(&record, &participants, opened_by) {
+                return Err(AWError::from(
+                    HttpResponse::Forbidden()
+                        .reason("Not Authorized For Party")
+                        .finish(),
+                ));
+            }
+            let dispute = NewDisputeCase {
+                order_id: params.order_id.to_string(),
+                opened_by,
+                status: DisputeStatus::Open,
+            };
+            let message = params.message.clone().filter(|message| !message.is_empty());
+            Ok((dispute, message))
+        })
+        .and_then(move |(dispute, message)| db::execute_open_dispute(update_pool, dispute, message))
+        .and_then(|dispute| Ok(HttpResponse::Ok().json(dispute)))
+}
+
+fn get_order_disputes(
+    identity: Identity,
+    pool: web::Data<Pool>,
+    Valid(params): Valid<web::Query<OrderRequest>>,
+) -> impl Future<Item = HttpResponse, Error = AWError> {
+    let query_order_id = params.order_id.to_string();
+    let query_pool = pool.clone();
+
+    find_party_order(query_pool.clone(), identity, query_order_id.clone())
+        .and_then(move |_| db::execute_get_order_disputes(query_pool, query_order_id))
+        .and_then(|disputes| Ok(HttpResponse::Ok().json(disputes)))
+}
 
-    db::execute_is_order_exist(query_pool.clone(), query_order_id.clone())
-        .and_then(|exist| {
-            if !exist {
-                return Err(AWError::from(HttpResponse::NotFound().finish()));
+/// Loads a dispute case, failing with Not Found if there is none
+fn find_dispute(
+    pool: web::Data<Pool>,
+    case_id: i32,
+) -> impl Future<Item = DisputeCase, Error = AWError> {
+    db::execute_get_dispute(pool, case_id).and_then(|dispute| {
+        dispute.ok_or_else(|| {
+            AWError::from(
+                HttpResponse::NotFound()
+                    .reason("Dispute Not Found")
+                    .finish(),
+            )
+        })
+    })
+}
+
+fn get_dispute(
+    identity: Identity,
+    pool: web::Data<Pool>,
+    Valid(params): Valid<web::Query<DisputeRequest>>,
+) -> impl Future<Item = HttpResponse, Error = AWError> {
+    // TODO: Consider using Arc to share resource
+    let query_pool = pool.clone();
+    let order_pool = pool.clone();
+    let log_pool = pool.clone();
+
+    find_dispute(query_pool, params.case_id)
+        .and_then(move |dispute| {
+            find_party_order(order_pool, identity, dispute.order_id.clone()).map(move |_| dispute)
+        })
+        .and_then(move |dispute| {
+            db::execute_get_dispute_log(log_pool, dispute.id)
+                .map(move |(evidence, messages)| (dispute, evidence, messages))
+        })
+        .and_then(|(dispute, evidence, messages)| {
+            let res = DisputeCaseResponse {
+                id: dispute.id,
+                order_id: dispute.order_id,
+                opened_by: dispute.opened_by,
+                status: dispute.status,
+                created_at: dispute.created_at,
+                evidence,
+                messages,
+            };
+            Ok(HttpResponse::Ok().json(res))
+        })
+}
+
+fn post_dispute_message(
+    identity: Identity,
+    pool: web::Data<Pool>,
+    Valid(params): Valid<web::Form<DisputeMessageRequest>>,
+) -> impl Future<Item = HttpResponse, Error = AWError> {
+    // TODO: Consider using Arc to share resource
+    let query_pool = pool.clone();
+    let query_identity = identity.clone();
+    let order_pool = pool.clone();
+    let update_pool = pool.clone();
+
+    find_dispute(query_pool, params.case_id)
+        .and_then(move |dispute| {
+            find_party_order(order_pool, query_identity, dispute.order_id.clone())
+                .map(move |(record, participants)| (dispute, record, participants))
+        })
+        .and_then(move |(dispute, record, participants)| {
+            if dispute.status == DisputeStatus::Closed {
+                return Err(AWError::from(
+                    HttpResponse::BadRequest().reason("Dispute Closed").finish(),
+                ));
             }
-            Ok(())
+            let bad_request =
+                |reason| AWError::from(HttpResponse::BadRequest().reason(reason).finish());
+            let author = parse_party(&params.author).map_err(bad_request)?;
+            if !identity.acts_as(&record, &participants, author) {
+                return Err(AWError::from(
+                    HttpResponse::Forbidden()
+                        .reason("Not Authorized For Party")
+                        .finish(),
+                ));
+            }
+            if params.body.is_empty() {
+                return Err(bad_request("Empty Message"));
+            }
+            Ok(NewDisputeMessage {
+                case_id: dispute.id,
+                reply_to: params.reply_to,
+                author,
+                body: params.body.to_string(),
+            })
         })
-        .and_then(move |_| db::execute_get_order_by_id(query_pool, query_order_id))
-        .and_then(move |_| {
-            // TODO: Check status
-            db::execute_update_order_status(update_pool, update_order_id.clone(), status)
+        .and_then(move |message| db::execute_add_dispute_message(update_pool, message))
+        .and_then(|message| match message {
+            Some(message) => Ok(HttpResponse::Ok().json(message)),
+            None => Err(AWError::from(
+                HttpResponse::BadRequest().reason("Invalid Reply").finish(),
+            )),
         })
-        .and_then(move |_| {
-            let res = OrderUpdatedResponse {
+}
+
+fn submit_dispute_evidence(
+    identity: Identity,
+    pool: web::Data<Pool>,
+    config: web::Data<MerchantConfig>,
+    Valid(params): Valid<web::Query<DisputeEvidenceRequest>>,
+    body: web::Bytes,
+) -> impl Future<Item = HttpResponse, Error = AWError> {
+    // TODO: Consider using Arc to share resource
+    let query_pool = pool.clone();
+    let query_identity = identity.clone();
+    let order_pool = pool.clone();
+    let update_pool = pool.clone();
+
+    let evidence_dir = config.disputes.evidence_dir.clone();
+
+    find_dispute(query_pool, params.case_id)
+        .and_then(move |dispute| {
+            find_party_order(order_pool, query_identity, dispute.order_id.clone())
+                .map(move |(record, participants)| (dispute, record, participants))
+        })
+        .and_then(move |(dispute, record, participants)| {
+            if dispute.status == DisputeStatus::Closed {
+                return Err(AWError::from(
+                    HttpResponse::BadRequest().reason("Dispute Closed").finish(),
+                ));
+            }
+            let bad_request =
+                |reason| AWError::from(HttpResponse::BadRequest().reason(reason).finish());
+            let submitted_by = parse_party(&params.submitted_by).map_err(bad_request)?;
+            if !identity.acts_as(&record, &participants, submitted_by) {
+                return Err(AWError::from(
+                    HttpResponse::Forbidden()
+                        .reason("Not Authorized For Party")
+                        .finish(),
+                ));
+            }
+            if body.is_empty() {
+                return Err(bad_request("Empty Evidence"));
+            }
+            Ok((dispute, submitted_by, params.file_name.to_string(), body))
+        })
+        .and_then(move |(dispute, submitted_by, file_name, body)| {
+            web::block(move || {
+                store_evidence(&evidence_dir, &body).map(|digest| (digest, body.len()))
+            })
+            .from_err()
+            .map(move |(digest, size)| NewDisputeEvidence {
+                case_id: dispute.id,
+                submitted_by,
+                file_name,
+                digest,
+                size: size as i32,
+            })
+        })
+        .and_then(move |evidence| db::execute_add_dispute_evidence(update_pool, evidence))
+        .and_then(|evidence| Ok(HttpResponse::Ok().json(evidence)))
+}
+
+fn get_evidence_file(
+    identity: Identity,
+    pool: web::Data<Pool>,
+    config: web::Data<MerchantConfig>,
+    Valid(params): Valid<web::Query<EvidenceFileRequest>>,
+) -> impl Future<Item = HttpResponse, Error = AWError> {
+    let path = match evidence_path(&config.disputes.evidence_dir, &params.digest) {
+        Some(path) => path,
+        None => {
+            return Either::A(future::err(AWError::from(
+                HttpResponse::BadRequest().reason("Invalid Digest").finish(),
+            )))
+        }
+    };
+
+    // Evidence is served to the parties of the disputes it was submitted to
+    Either::B(
+        db::execute_get_evidence_orders(pool, params.digest.to_string())
+            .and_then(move |(orders, participants)| {
+                let is_party = orders.iter().any(|record| {
+                    let order_participants: Vec<OrderParticipant> = participants
+                        .iter()
+                        .filter(|participant| participant.order_id == record.order_id)
+                        .cloned()
+                        .collect();
+                    identity.is_order_party(record, &order_participants)
+                });
+                if !is_party {
+                    return Err(AWError::from(
+                        HttpResponse::Forbidden()
+                            .reason("Not An Order Party")
+                            .finish(),
+                    ));
+                }
+                Ok(())
+            })
+            .and_then(move |_| {
+                web::block(move || {
+                    if !path.exists() {
+                        return Ok(None);
+                    }
+                    std::fs::read(path).map(Some)
+                })
+                .from_err()
+            })
+            .and_then(|content| match content {
+                Some(content) => Ok(HttpResponse::Ok()
+                    .content_type("application/octet-stream")
+                    .body(content)),
+                None => Err(AWError::from(HttpResponse::NotFound().finish())),
+            }),
+    )
+}
+
+fn change_dispute_status(
+    identity: Identity,
+    pool: web::Data<Pool>,
+    Valid(params): Valid<web::Form<DisputeStatusRequest>>,
+) -> impl Future<Item = HttpResponse, Error = AWError> {
+    // TODO: Consider using Arc to share resource
+    let query_pool = pool.clone();
+    let query_identity = identity.clone();
+    let order_pool = pool.clone();
+    let update_pool = pool.clone();
+
+    find_dispute(query_pool, params.case_id)
+        .and_then(move |dispute| {
+            find_party_order(order_pool, query_identity, dispute.order_id.clone())
+                .map(move |(record, participants)| (dispute, record, participants))
+        })
+        .and_then(move |(dispute, record, participants)| {
+            if !identity.acts_as(&record, &participants, ParticipantRole::Merchant)
+                && !identity.acts_as(&record, &participants, ParticipantRole::Escrow)
+            {
+                return Err(AWError::from(
+                    HttpResponse::Forbidden()
+                        .reason("Not Authorized For Party")
+                        .finish(),
+                ));
+            }
+            let status = params.status.parse::<DisputeStatus>().map_err(|_| {
+                AWError::from(
+                    HttpResponse::BadRequest()
+                        .reason("Invalid Dispute Status")
+                        .finish(),
+                )
+            })?;
+            if !can_change_status(dispute.status, status) {
+                return Err(AWError::from(
+                    HttpResponse::BadRequest()
+                        .reason("Dispute Status Cannot Be Changed")
+                        .finish(),
+                ));
+            }
+            Ok((dispute, status))
+        })
+        .and_then(move |(dispute, status)| {
+            db::execute_update_dispute_status(update_pool, dispute.id, status)
+                .map(move |_| (dispute, status))
+        })
+        .and_then(|(dispute, status)| {
+            let res = DisputeCase { status, ..dispute };
+            Ok(HttpResponse::Ok().json(res))
+        })
+}
+
+fn get_settlement_preview(
+    identity: Identity,
+    pool: web::Data<Pool>,
+    config: web::Data<MerchantConfig>,
+    Valid(params): Valid<web::Query<OrderRequest>>,
+) -> impl Future<Item = HttpResponse, Error = AWError> {
+    // TODO: Consider using Arc to share resource
+    let query_order_id = params.order_id.to_string();
+    let query_pool = pool.clone();
+
+    let settings_pool = pool.clone();
+    let batch_pool = pool.clone();
+
+    let return_order_id = params.order_id.to_string();
+
+    find_party_order(query_pool, identity, query_order_id)
+        .and_then(move |(record, participants)| {
+            find_merchant_settings(settings_pool, config, record.merchant_id)
+                .map(move |settings| (record, participants, settings))
+        })
+        .and_then(
+            move |(record, participants, settings)| match record.batch_id {
+                Some(batch_id) => Either::A(
+                    db::execute_get_batch(batch_pool, batch_id)
+                        .map(move |batch| (record, participants, settings, batch)),
+                ),
+                None => Either::B(future::ok((record, participants, settings, None))),
+            },
+        )
+        .and_then(move |(record, participants, settings, batch)| {
+            if !record.status.is_settling() {
+                return Err(AWError::from(
+                    HttpResponse::BadRequest()
+                        .reason("Transaction Not Ready")
+                        .finish(),
+                ));
+            }
+
+            let (wallet, _, _) = make_app();
+            let passphrase = SecUtf8::from("passphrase");
+
+            // Same transaction as the one proposed in exchange_commitment, the one the signing
+            // session started with once it runs
+            let (transaction, network_fee) = settlement_transaction(
+                &wallet,
+                &passphrase,
+                &record,
+                &participants,
+                batch.as_ref(),
+                &settings,
+            )
+            .map_err(|reason| AWError::from(HttpResponse::BadRequest().reason(reason).finish()))?;
+
+            let outputs = transaction
+                .outputs
+                .iter()
+                .map(|output| SettlementOutput {
+                    address: output.address.to_cro().unwrap(),
+                    value: u64::from(output.value).to_string(),
+                })
+                .collect();
+            let access_policies = transaction
+                .attributes
+                .allowed_view
+                .iter()
+                .map(|policy| SettlementAccessPolicy {
+                    view_key: hex::encode(&policy.view_key.serialize()[..]),
+                    access: format!("{:?}", policy.access),
+                })
+                .collect();
+
+            let res = SettlementPreviewResponse {
                 order_id: return_order_id,
+                status: record.status,
+                transaction_id: hex::encode(transaction.id()),
+                outputs,
+                access_policies,
+                encoded_transaction: hex::encode(transaction.encode()),
+                transaction,
+                network_fee: network_fee.map(|network_fee| u64::from(network_fee).to_string()),
             };
             Ok(HttpResponse::Ok().json(res))
         })
 }
 
 fn exchange_commitment(
+    SigningCaller(identity): SigningCaller,
     pool: web::Data<Pool>,
-    params: web::Form<ExchangeCommitmentRequest>,
+    config: web::Data<MerchantConfig>,
+    Valid(params): Valid<web::Form<ExchangeCommitmentRequest>>,
 ) -> impl Future<Item = HttpResponse, Error = AWError> {
-    let buyer_commitment_vec = hex::decode(params.commitment.to_string()).unwrap();
-    let mut buyer_commitment = [0; 32];
-    buyer_commitment.copy_from_slice(&buyer_commitment_vec);
-
     let (wallet, _, _) = make_app();
     let passphrase = SecUtf8::from("passphrase");
 
     // TODO: Consider using Arc to share resource
     let query_order_id = params.order_id.to_string();
     let query_pool = pool.clone();
+    let signer_public_key = params.public_key.clone();
+
+    let settings_pool = pool.clone();
+    let participants_pool = pool.clone();
+    let signers_pool = pool.clone();
+    let batch_pool = pool.clone();
+    let event_pool = pool.clone();
 
     let update_order_id = params.order_id.to_string();
+    let session_pool = pool.clone();
     let update_pool = pool.clone();
 
     let return_order_id = params.order_id.to_string();
@@ -443,97 +2598,463 @@ fn exchange_commitment(
         })
         .and_then(move |_| db::execute_get_order_by_id(query_pool, query_order_id))
         .and_then(move |record| {
-            if record.status != OrderStatus::Delivering && record.status != OrderStatus::Refunding {
+            authorize_signer(&identity, &record, &signer_public_key)?;
+            Ok(record)
+        })
+        .and_then(move |record| {
+            if !record.status.is_settling() {
                 return Err(AWError::from(
                     HttpResponse::BadRequest()
                         .reason("Transaction Not Ready")
                         .finish(),
                 ));
             }
-            // TODO: Check order hasn't exchanged commitment before
 
             Ok(record)
         })
         .and_then(move |record| {
-            let wallet_name = record.wallet_name.clone();
+            find_merchant_settings(settings_pool, config, record.merchant_id)
+                .map(move |settings| (record, settings))
+        })
+        .and_then(move |(record, settings)| {
+            db::execute_get_order_participants(participants_pool, record.order_id.clone())
+                .map(move |participants| (record, settings, participants))
+        })
+        .and_then(move |(record, settings, participants)| {
+            db::execute_get_session_signers(
+                signers_pool,
+                record.session_id.clone().unwrap_or_default(),
+            )
+            .map(move |signers| (record, settings, participants, signers))
+        })
+        .and_then(
+            move |(record, settings, participants, signers)| match record.batch_id {
+                Some(batch_id) => Either::A(
+                    db::execute_get_batch(batch_pool, batch_id)
+                        .map(move |batch| (record, settings, participants, signers, batch)),
+                ),
+                None => Either::B(future::ok((record, settings, participants, signers, None))),
+            },
+        )
+        .and_then(move |(record, settings, participants, signers, batch)| {
+            let exchange = exchange_nonce_commitment(
+                &wallet,
+                &passphrase,
+                &record,
+                &participants,
+                &signers,
+                batch.as_ref(),
+                &params,
+                &settings,
+            );
+            let exchange = match exchange {
+                Ok(exchange) => exchange,
+                Err(err) => return Either::A(reject_signing(event_pool, record.order_id, err)),
+            };
+
+            let session_id = hex::encode(&exchange.session_id);
+            let transaction_id = hex::encode(&exchange.transaction.id());
+            let encoded_transaction = encode_transaction(&exchange.transaction);
+            let network_fee = exchange.network_fee.map(to_base_units);
+            let public_key = exchange.public_key;
+            let res = ExchangeCommitmentResponse {
+                order_id: return_order_id,
+                commitment: exchange.commitment,
+                nonce: exchange.nonce,
+                transaction_id: transaction_id.clone(),
+                transaction: exchange.transaction,
+            };
+
+            // The first commitment of a session starts it
+            let started = match exchange.new_signers {
+                Some(new_signers) => Either::A(db::execute_start_signing_session(
+                    session_pool,
+                    update_order_id,
+                    session_id.clone(),
+                    transaction_id,
+                    encoded_transaction,
+                    network_fee,
+                    new_signers,
+                )),
+                None => Either::B(future::ok(true)),
+            };
+            Either::B(
+                started
+                    .and_then(move |_| {
+                        db::execute_store_signer_commitment(
+                            update_pool,
+                            session_id,
+                            public_key,
+                            params.commitment.to_string(),
+                        )
+                    })
+                    .and_then(|_| Ok(HttpResponse::Ok().json(res))),
+            )
+        })
+}
 
-            let merchant_public_key =
-                wallet.public_keys(&wallet_name, &passphrase).unwrap()[0].clone();
-            let buyer_public_key =
-                PublicKey::from_str(&record.buyer_public_key.to_string()).unwrap();
+struct CommitmentExchange {
+    session_id: [u8; 32],
+    transaction: Tx,
+    network_fee: Option<Coin>,
+    // Signers of the session, when it is started by this exchange
+    new_signers: Option<Vec<NewSessionSigner>>,
+    public_key: String,
+    commitment: String,
+    nonce: String,
+}
 
-            let transaction =
-                construct_tx(wallet_name.clone(), passphrase.clone(), &wallet, &record);
+/// Adds a co-signer's nonce commitment to the signing session of the order, starting the session
+/// if this is its first commitment. The merchant's nonce is only revealed once every co-signer
+/// has committed.
+#[allow(clippy::too_many_arguments)]
+fn exchange_nonce_commitment(
+    wallet: &AppWalletClient,
+    passphrase: &SecUtf8,
+    record: &Order,
+    participants: &[OrderParticipant],
+    signers: &[SessionSigner],
+    batch: Option<&SettlementBatch>,
+    params: &ExchangeCommitmentRequest,
+    settings: &MerchantSettings,
+) -> Result<CommitmentExchange, SigningError> {
+    let wallet_name = record.wallet_name.clone();
+    let submitter = params
+        .public_key
+        .clone()
+        .unwrap_or_else(|| record.buyer_public_key.clone());
 
-            let session_id = wallet
-                .new_multi_sig_session(
-                    &wallet_name,
-                    &passphrase,
-                    transaction.id(),
-                    vec![merchant_public_key.clone(), buyer_public_key.clone()],
-                    merchant_public_key.clone(),
-                )
-                .expect("new_multi_sig_session error");
+    let (transaction, network_fee) =
+        settlement_transaction(wallet, passphrase, record, participants, batch, settings)
+            .map_err(SigningError::Rejected)?;
 
-            // TODO: Handle duplicate add error
-            wallet
-                .add_nonce_commitment(
-                    &session_id,
-                    &passphrase,
-                    buyer_commitment,
-                    &buyer_public_key,
+    let (session_id, signer, new_signers, all_committed) = if record.session_id.is_none() {
+        let selected = select_signers(
+            participants,
+            record.required_signatures,
+            params.signers.as_ref().map(String::as_str),
+        )
+        .map_err(SigningError::Rejected)?;
+        let signer = selected
+            .iter()
+            .find(|participant| participant.public_key == submitter)
+            .ok_or(SigningError::Rejected("Not A Session Signer"))?;
+        let signer = Signer {
+            role: signer.role,
+            public_key: PublicKey::from_str(&signer.public_key).unwrap(),
+        };
+
+        let merchant_public_key = merchant_public_key(wallet, passphrase, record);
+        let mut public_keys = vec![merchant_public_key.clone()];
+        public_keys.extend(
+            selected
+                .iter()
+                .map(|participant| PublicKey::from_str(&participant.public_key).unwrap()),
+        );
+
+        let session_id = wallet
+            .new_multi_sig_session(
+                &wallet_name,
+                passphrase,
+                transaction.id(),
+                public_keys,
+                merchant_public_key,
+            )
+            .map_err(|err| SigningError::Session(err.to_string()))?;
+        let new_signers = selected
+            .iter()
+            .map(|participant| NewSessionSigner {
+                session_id: hex::encode(&session_id),
+                order_id: record.order_id.clone(),
+                role: participant.role,
+                public_key: participant.public_key.clone(),
+            })
+            .collect();
+
+        (session_id, signer, Some(new_signers), selected.len() == 1)
+    } else {
+        let signer = signers
+            .iter()
+            .find(|signer| signer.public_key == submitter)
+            .ok_or(SigningError::Rejected("Not A Session Signer"))?;
+        if !signer.commitment.is_empty() {
+            return Err(SigningError::Rejected("Commitment Already Exchanged"));
+        }
+        let all_committed = signers
+            .iter()
+            .all(|signer| signer.public_key == submitter || !signer.commitment.is_empty());
+
+        (
+            decode_hash(record.session_id.as_ref().unwrap()),
+            Signer::from_session_signer(signer),
+            None,
+            all_committed,
+        )
+    };
+
+    add_commitment(wallet, passphrase, &session_id, &signer, &params.commitment)?;
+
+    let merchant_commitment = wallet
+        .nonce_commitment(&session_id, passphrase)
+        .map_err(|err| SigningError::Session(err.to_string()))?;
+    let merchant_nonce = if all_committed {
+        wallet
+            .nonce(&session_id, passphrase)
+            .map_err(|err| SigningError::Session(err.to_string()))?
+            .to_string()
+    } else {
+        String::from("")
+    };
+
+    Ok(CommitmentExchange {
+        session_id,
+        transaction,
+        network_fee,
+        new_signers,
+        public_key: submitter,
+        commitment: hex::encode(merchant_commitment),
+        nonce: merchant_nonce,
+    })
+}
+
+fn exchange_nonce(
+    SigningCaller(identity): SigningCaller,
+    pool: web::Data<Pool>,
+    Valid(params): Valid<web::Form<ExchangeNonceRequest>>,
+) -> impl Future<Item = HttpResponse, Error = AWError> {
+    let (wallet, _, _) = make_app();
+    let passphrase = SecUtf8::from("passphrase");
+
+    // TODO: Consider using Arc to share resource
+    let query_order_id = params.order_id.to_string();
+    let query_pool = pool.clone();
+    let signer_public_key = params.public_key.clone();
+
+    let signers_pool = pool.clone();
+    let event_pool = pool.clone();
+
+    let update_pool = pool.clone();
+    let return_pool = pool.clone();
+
+    db::execute_is_order_exist(query_pool.clone(), query_order_id.clone())
+        .and_then(|exist| {
+            if !exist {
+                return Err(AWError::from(HttpResponse::NotFound().finish()));
+            }
+            Ok(())
+        })
+        .and_then(move |_| db::execute_get_order_by_id(query_pool, query_order_id))
+        .and_then(move |record| {
+            authorize_signer(&identity, &record, &signer_public_key)?;
+            Ok(record)
+        })
+        .and_then(move |record| {
+            db::execute_get_session_signers(
+                signers_pool,
+                record.session_id.clone().unwrap_or_default(),
+            )
+            .map(move |signers| (record, signers))
+        })
+        .and_then(move |(record, signers)| {
+            let submitter = params
+                .public_key
+                .clone()
+                .unwrap_or_else(|| record.buyer_public_key.clone());
+            let accepted = accept_nonce(
+                &wallet,
+                &passphrase,
+                &record,
+                &signers,
+                &submitter,
+                &params.nonce,
+            );
+            if let Err(err) = accepted {
+                return Either::A(reject_signing(event_pool, record.order_id, err));
+            }
+
+            Either::B(
+                db::execute_store_signer_nonce(
+                    update_pool,
+                    record.session_id.clone().unwrap(),
+                    submitter,
+                    params.nonce.to_string(),
                 )
-                .expect("add_nonce_commitment error");
+                .and_then(move |_| {
+                    db::execute_get_session_signers(
+                        return_pool,
+                        record.session_id.clone().unwrap_or_default(),
+                    )
+                    .map(move |signers| (record, signers))
+                })
+                .and_then(move |(record, signers)| {
+                    let res = signing_session_response(&wallet, &passphrase, &record, &signers);
+                    Ok(HttpResponse::Ok().json(res))
+                }),
+            )
+        })
+}
+
+fn accept_nonce(
+    wallet: &AppWalletClient,
+    passphrase: &SecUtf8,
+    record: &Order,
+    signers: &[SessionSigner],
+    submitter: &str,
+    nonce: &str,
+) -> Result<(), SigningError> {
+    if record.session_id.is_none() {
+        return Err(SigningError::Rejected("Signing Session Not Started"));
+    }
+    let signer = signers
+        .iter()
+        .find(|signer| signer.public_key == submitter)
+        .ok_or(SigningError::Rejected("Not A Session Signer"))?;
+    if !signer.nonce.is_empty() {
+        return Err(SigningError::Rejected("Nonce Already Exchanged"));
+    }
+    if signers.iter().any(|signer| signer.commitment.is_empty()) {
+        return Err(SigningError::Rejected("Nonce Commitments Incomplete"));
+    }
 
-            let merchant_nonce_commitment = wallet
-                .nonce_commitment(&session_id, &passphrase)
-                .expect("nonce_commitment error");
-            let merchant_nonce = wallet.nonce(&session_id, &passphrase).expect("nonce error");
+    add_verified_nonce(
+        wallet,
+        passphrase,
+        &decode_hash(record.session_id.as_ref().unwrap()),
+        &Signer::from_session_signer(signer),
+        nonce,
+    )
+}
 
-            let res = ExchangeCommitmentResponse {
-                order_id: return_order_id,
-                commitment: hex::encode(merchant_nonce_commitment),
-                nonce: merchant_nonce.to_string(),
-                transaction_id: hex::encode(transaction.id()),
-                transaction: transaction.clone(),
-            };
+fn get_signing_session(
+    identity: Identity,
+    pool: web::Data<Pool>,
+    Valid(params): Valid<web::Query<OrderRequest>>,
+) -> impl Future<Item = HttpResponse, Error = AWError> {
+    // TODO: Consider using Arc to share resource
+    let query_order_id = params.order_id.to_string();
+    let query_pool = pool.clone();
 
-            db::execute_store_exchanged_data(
-                update_pool,
-                update_order_id,
-                hex::encode(&session_id),
-                hex::encode(&transaction.id()),
+    let signers_pool = pool.clone();
+
+    find_party_order(query_pool, identity, query_order_id)
+        .map(|(record, _)| record)
+        .and_then(move |record| {
+            db::execute_get_session_signers(
+                signers_pool,
+                record.session_id.clone().unwrap_or_default(),
             )
-            .from_err()
-            .and_then(|_| Ok(HttpResponse::Ok().json(res)))
+            .map(move |signers| (record, signers))
+        })
+        .and_then(move |(record, signers)| {
+            let (wallet, _, _) = make_app();
+            let passphrase = SecUtf8::from("passphrase");
+
+            let res = signing_session_response(&wallet, &passphrase, &record, &signers);
+            Ok(HttpResponse::Ok().json(res))
         })
 }
 
+/// State of the order's signing session as seen by the co-signers, the merchant first. Nonces
+/// are only shared once every signer has committed.
+fn signing_session_response(
+    wallet: &AppWalletClient,
+    passphrase: &SecUtf8,
+    record: &Order,
+    signers: &[SessionSigner],
+) -> SigningSessionResponse {
+    if record.session_id.is_none() {
+        return SigningSessionResponse {
+            order_id: record.order_id.clone(),
+            session_id: String::from(""),
+            transaction_id: String::from(""),
+            signers: vec![],
+        };
+    }
+
+    let session_id = decode_hash(record.session_id.as_ref().unwrap());
+    let all_committed = signers.iter().all(|signer| !signer.commitment.is_empty());
+    let all_nonces = signers.iter().all(|signer| !signer.nonce.is_empty());
+
+    let merchant_public_key = merchant_public_key(wallet, passphrase, record);
+    let merchant_commitment = wallet.nonce_commitment(&session_id, passphrase).unwrap();
+    let merchant_nonce = if all_committed {
+        wallet.nonce(&session_id, passphrase).unwrap().to_string()
+    } else {
+        String::from("")
+    };
+
+    let mut session_signers = vec![SigningSessionSignerResponse {
+        role: ParticipantRole::Merchant,
+        public_key: merchant_public_key.to_string(),
+        commitment: hex::encode(merchant_commitment),
+        nonce: merchant_nonce,
+        partial_signature_submitted: all_nonces,
+    }];
+    session_signers.extend(signers.iter().map(|signer| SigningSessionSignerResponse {
+        role: signer.role,
+        public_key: signer.public_key.clone(),
+        commitment: signer.commitment.clone(),
+        nonce: if all_committed {
+            signer.nonce.clone()
+        } else {
+            String::from("")
+        },
+        partial_signature_submitted: !signer.partial_signature.is_empty(),
+    }));
+
+    SigningSessionResponse {
+        order_id: record.order_id.clone(),
+        session_id: record.session_id.clone().unwrap_or_default(),
+        transaction_id: record.settlement_transaction_id.clone().unwrap_or_default(),
+        signers: session_signers,
+    }
+}
+
 fn confirm_delivery(
+    SigningCaller(identity): SigningCaller,
     pool: web::Data<Pool>,
-    params: web::Form<ConfirmRequest>,
+    config: web::Data<MerchantConfig>,
+    Valid(params): Valid<web::Form<ConfirmRequest>>,
 ) -> impl Future<Item = HttpResponse, Error = AWError> {
-    // TODO: Should check order is in delivering status
-    confirm(pool, params, OrderStatus::Completed)
+    confirm(identity, pool, config, params, OrderStatus::Completed)
 }
 fn confirm_refund(
+    SigningCaller(identity): SigningCaller,
     pool: web::Data<Pool>,
-    params: web::Form<ConfirmRequest>,
+    config: web::Data<MerchantConfig>,
+    Valid(params): Valid<web::Form<ConfirmRequest>>,
+) -> impl Future<Item = HttpResponse, Error = AWError> {
+    confirm(identity, pool, config, params, OrderStatus::Refunded)
+}
+fn confirm_split(
+    SigningCaller(identity): SigningCaller,
+    pool: web::Data<Pool>,
+    config: web::Data<MerchantConfig>,
+    Valid(params): Valid<web::Form<ConfirmRequest>>,
+) -> impl Future<Item = HttpResponse, Error = AWError> {
+    confirm(identity, pool, config, params, OrderStatus::Split)
+}
+fn confirm_milestone(
+    SigningCaller(identity): SigningCaller,
+    pool: web::Data<Pool>,
+    config: web::Data<MerchantConfig>,
+    Valid(params): Valid<web::Form<ConfirmRequest>>,
 ) -> impl Future<Item = HttpResponse, Error = AWError> {
-    // TODO: Should check order is in refunding status
-    confirm(pool, params, OrderStatus::Refunded)
+    confirm(
+        identity,
+        pool,
+        config,
+        params,
+        OrderStatus::ReleasingMilestone,
+    )
 }
 fn confirm(
+    identity: Identity,
     pool: web::Data<Pool>,
+    config: web::Data<MerchantConfig>,
     params: web::Form<ConfirmRequest>,
     status: OrderStatus,
-) -> impl Future<Item = HttpResponse, Error = AWError> {
-    let buyer_partial_signature_vec = hex::decode(params.partial_signature.to_string()).unwrap();
-    let mut buyer_partial_signature = [0; 32];
-    buyer_partial_signature.copy_from_slice(&buyer_partial_signature_vec);
-
-    let buyer_nonce = PublicKey::from_str(&params.nonce.to_string()).unwrap();
-
+) -> impl Future<Item = HttpResponse, Error = AWError> {
     let (wallet, _, synchronizer) = make_app();
     let passphrase = SecUtf8::from("passphrase");
 
@@ -541,8 +3062,17 @@ fn confirm(
 
     let query_order_id = params.order_id.to_string();
     let query_pool = pool.clone();
+    let signer_public_key = params.public_key.clone();
 
-    let update_order_id = params.order_id.to_string();
+    let settings_pool = pool.clone();
+    let approval_pool = pool.clone();
+    let participants_pool = pool.clone();
+    let signers_pool = pool.clone();
+    let batch_pool = pool.clone();
+    let event_pool = pool.clone();
+
+    let nonce_pool = pool.clone();
+    let partial_signature_pool = pool.clone();
     let update_pool = pool.clone();
 
     let return_order_id = params.order_id.to_string();
@@ -555,6 +3085,10 @@ fn confirm(
             Ok(())
         })
         .and_then(move |_| db::execute_get_order_by_id(query_pool, query_order_id))
+        .and_then(move |record| {
+            authorize_signer(&identity, &record, &signer_public_key)?;
+            Ok(record)
+        })
         .and_then(move |record| {
             match status {
                 OrderStatus::Completed => {
@@ -567,6 +3101,13 @@ fn confirm(
                     }
                 }
                 OrderStatus::Refunded => {
+                    if record.status == OrderStatus::RefundPendingApproval {
+                        return Err(AWError::from(
+                            HttpResponse::BadRequest()
+                                .reason("Refund Pending Approval")
+                                .finish(),
+                        ));
+                    }
                     if record.status != OrderStatus::Refunding {
                         return Err(AWError::from(
                             HttpResponse::BadRequest()
@@ -575,131 +3116,754 @@ fn confirm(
                         ));
                     }
                 }
+                OrderStatus::Split => {
+                    if record.status == OrderStatus::SplitPendingApproval {
+                        return Err(AWError::from(
+                            HttpResponse::BadRequest()
+                                .reason("Split Pending Approval")
+                                .finish(),
+                        ));
+                    }
+                    if record.status != OrderStatus::Splitting {
+                        return Err(AWError::from(
+                            HttpResponse::BadRequest()
+                                .reason("Transaction Not Splitting")
+                                .finish(),
+                        ));
+                    }
+                }
+                OrderStatus::ReleasingMilestone => {
+                    if record.status != OrderStatus::ReleasingMilestone {
+                        return Err(AWError::from(
+                            HttpResponse::BadRequest()
+                                .reason("Transaction Not Releasing Milestone")
+                                .finish(),
+                        ));
+                    }
+                }
                 _ => {
                     return Err(AWError::from(HttpResponse::InternalServerError().finish()));
                 }
             }
+            Ok(record)
+        })
+        .and_then(move |record| {
+            find_merchant_settings(settings_pool, config, record.merchant_id)
+                .map(move |settings| (record, settings))
+        })
+        .and_then(move |(record, settings)| {
+            // Refunds needing approval are not signed until the policy of the merchant is satisfied
+            if status != OrderStatus::Refunded
+                || !requires_refund_approval(&record, &settings.refund_approval)
+            {
+                return Either::A(future::ok((record, settings)));
+            }
+            Either::B(
+                db::execute_get_refund_approvals(approval_pool, record.order_id.clone()).and_then(
+                    move |approvals| {
+                        if !is_refund_approved(&approvals, &settings.refund_approval) {
+                            return Err(AWError::from(
+                                HttpResponse::Forbidden()
+                                    .reason("Refund Not Approved")
+                                    .finish(),
+                            ));
+                        }
+                        Ok((record, settings))
+                    },
+                ),
+            )
+        })
+        .and_then(move |(record, settings)| {
+            db::execute_get_order_participants(participants_pool, record.order_id.clone())
+                .map(move |participants| (record, settings, participants))
+        })
+        .and_then(move |(record, settings, participants)| {
+            db::execute_get_session_signers(
+                signers_pool,
+                record.session_id.clone().unwrap_or_default(),
+            )
+            .map(move |signers| (record, settings, participants, signers))
+        })
+        .and_then(
+            move |(record, settings, participants, signers)| match record.batch_id {
+                Some(batch_id) => Either::A(
+                    db::execute_get_batch_settlement(batch_pool, batch_id)
+                        .map(move |batch| (record, settings, participants, signers, batch)),
+                ),
+                None => Either::B(future::ok((record, settings, participants, signers, None))),
+            },
+        )
+        .and_then(move |(record, settings, participants, signers, batch)| {
+            let accepted = accept_partial_signature(
+                &wallet,
+                &passphrase,
+                &record,
+                &participants,
+                &signers,
+                &params,
+            );
+            let accepted = match accepted {
+                Ok(accepted) => accepted,
+                Err(err) => return Either::A(reject_signing(event_pool, record.order_id, err)),
+            };
 
-            let wallet_name = record.wallet_name.clone();
-
-            // Complete multi-sig session
-            let session_id_vec = hex::decode(record.session_id.to_string()).unwrap();
-            let mut session_id = [0; 32];
-            session_id.copy_from_slice(&session_id_vec);
-            let buyer_public_key =
-                PublicKey::from_str(&record.buyer_public_key.to_string()).unwrap();
-
-            // TODO: Handle duplicate add error
-            wallet
-                .add_nonce(&session_id, &passphrase, &buyer_nonce, &buyer_public_key)
-                .expect("add_nonce error");
+            // Settle once the last co-signer has submitted its partial signature, orders of a
+            // batch once the sessions of every order of the batch are signed
+            let settlement = match (accepted.complete, batch.as_ref()) {
+                (false, _) => None,
+                (true, Some(batch)) => Some(settle_batch(&wallet, &passphrase, &record, batch)),
+                (true, None) => Some(
+                    settle(
+                        &wallet,
+                        &synchronizer,
+                        &passphrase,
+                        &record,
+                        &participants,
+                        &signers,
+                        &settings,
+                    )
+                    .map(|_| true),
+                ),
+            };
 
-            wallet
-                .partial_signature(&session_id, &passphrase)
-                .expect("partial_signature error");
+            // Nothing of a co-signer whose partial signature completed the session is stored
+            // unless the settlement succeeded, so it submits it again once the failure is resolved
+            let settled = match settlement {
+                None => false,
+                Some(Ok(settled)) => settled,
+                Some(Err(err)) => {
+                    return Either::A(reject_signing(event_pool, record.order_id, err))
+                }
+            };
 
-            // TODO: Handle duplicate add error
-            wallet
-                .add_partial_signature(
-                    &session_id,
-                    &passphrase,
-                    buyer_partial_signature,
-                    &buyer_public_key,
-                )
-                .expect("add_partial_signature error");
-
-            wallet
-                .signature(&session_id, &passphrase)
-                .expect("signature error");
-
-            let merchant_view_key = wallet.view_key(&wallet_name, &passphrase).unwrap();
-
-            let transaction_id_vec = hex::decode(&record.payment_transaction_id).unwrap();
-            let mut transaction_id = [0; 32];
-            transaction_id.copy_from_slice(&transaction_id_vec);
-
-            let merchant_private_key = wallet
-                .private_key(&passphrase, &merchant_view_key)
-                .unwrap()
-                .unwrap();
-            let merchant_staking_addresses =
-                wallet.staking_addresses(&wallet_name, &passphrase).unwrap();
-            // TODO: Create separate thread to sync in background
-            synchronizer
-                .sync(
-                    &merchant_staking_addresses,
-                    &merchant_view_key,
-                    &merchant_private_key,
-                    None,
-                    None,
+            let session_id = record.session_id.clone().unwrap();
+            let public_key = accepted.public_key.clone();
+            Either::B(
+                db::execute_store_signer_nonce(
+                    nonce_pool,
+                    session_id.clone(),
+                    public_key.clone(),
+                    accepted.nonce,
                 )
-                .expect("sync error");
-
-            let transaction =
-                construct_tx(wallet_name.clone(), passphrase.clone(), &wallet, &record);
-
-            let tx_aux = wallet
-                .transaction(&wallet_name, &session_id, &passphrase, transaction)
-                .expect("transaction error");
-
-            wallet
-                .broadcast_transaction(&tx_aux)
-                .expect("broadcast_transaction error");
-            Ok(record)
-        })
-        .and_then(move |record| {
-            db::execute_update_order_status(update_pool, update_order_id.clone(), status).and_then(
-                move |_| {
+                .and_then(move |_| {
+                    db::execute_store_signer_partial_signature(
+                        partial_signature_pool,
+                        session_id,
+                        public_key,
+                        accepted.partial_signature,
+                    )
+                })
+                .and_then(move |_| {
                     let res = ConfirmResponse {
                         order_id: return_order_id,
-                        transaction_id: record.settlement_transaction_id.to_string(),
+                        transaction_id: record.settlement_transaction_id.clone().unwrap(),
                     };
-                    Ok(HttpResponse::Ok().json(res))
-                },
+                    if !settled {
+                        return Either::A(future::ok(HttpResponse::Accepted().json(res)));
+                    }
+                    let update = if let Some(batch) = batch {
+                        let statuses = batch
+                            .orders
+                            .iter()
+                            .map(|batched| {
+                                let status = settled_status(batched.status).unwrap_or(status);
+                                (batched.order_id.clone(), status)
+                            })
+                            .collect();
+                        Either::A(db::execute_complete_batch(
+                            update_pool,
+                            batch.batch.id,
+                            statuses,
+                        ))
+                    } else if status == OrderStatus::ReleasingMilestone {
+                        let remaining = remaining_amount(&record).map(to_base_units);
+                        Either::B(Either::A(db::execute_release_milestone(
+                            update_pool,
+                            record,
+                            remaining,
+                        )))
+                    } else {
+                        Either::B(Either::B(db::execute_update_order_status(
+                            update_pool,
+                            record.order_id.clone(),
+                            status,
+                        )))
+                    };
+                    Either::B(update.and_then(move |_| Ok(HttpResponse::Ok().json(res))))
+                }),
             )
         })
 }
 
-fn get_pending_orders(pool: web::Data<Pool>) -> impl Future<Item = HttpResponse, Error = AWError> {
-    db::execute_get_orders_by_status(
-        pool.clone(),
-        vec![
+struct PartialSignatureAcceptance {
+    public_key: String,
+    nonce: String,
+    partial_signature: String,
+    // Whether every co-signer of the session has now submitted its partial signature
+    complete: bool,
+}
+
+/// Adds a co-signer's partial signature, and its nonce if not exchanged before, to the signing
+/// session of the order, once the partial signature is verified against the co-signer's key.
+fn accept_partial_signature(
+    wallet: &AppWalletClient,
+    passphrase: &SecUtf8,
+    record: &Order,
+    participants: &[OrderParticipant],
+    signers: &[SessionSigner],
+    params: &ConfirmRequest,
+) -> Result<PartialSignatureAcceptance, SigningError> {
+    if record.session_id.is_none() {
+        return Err(SigningError::Rejected("Signing Session Not Started"));
+    }
+    let submitter = params
+        .public_key
+        .clone()
+        .unwrap_or_else(|| record.buyer_public_key.clone());
+    let signer = submitting_signer(signers, &submitter)?;
+
+    let session_id = decode_hash(record.session_id.as_ref().unwrap());
+    let session_signer = Signer::from_session_signer(signer);
+    let nonce = if signer.nonce.is_empty() {
+        params
+            .nonce
+            .clone()
+            .ok_or(SigningError::Rejected("Nonce Required"))?
+    } else {
+        signer.nonce.clone()
+    };
+
+    // Nothing of the co-signer is added to the session unless its partial signature is valid, so
+    // a rejected co-signer retries with the same nonce
+    let (session, _) = signing_session(wallet, passphrase, record, participants, signers);
+    let nonces = session_nonces(wallet, passphrase, &session_id, signers, &submitter, &nonce)?;
+    let index = signers
+        .iter()
+        .position(|signer| signer.public_key == submitter)
+        .unwrap();
+    verify_partial_signature(&session, &nonces, index + 1, &params.partial_signature)?;
+
+    // A co-signer retrying after its partial signature completed the session but the settlement
+    // failed was added to the session already; its verified nonce and partial signature are the
+    // only ones completing the session, so they are not added again
+    let retried = wallet.signature(&session_id, passphrase).is_ok();
+    if signer.nonce.is_empty() && !retried {
+        add_verified_nonce(wallet, passphrase, &session_id, &session_signer, &nonce)?;
+    }
+    if !retried {
+        add_partial_signature(
+            wallet,
+            passphrase,
+            &session_id,
+            &session_signer,
+            &params.partial_signature,
+        )?;
+    }
+
+    Ok(PartialSignatureAcceptance {
+        complete: signers
+            .iter()
+            .all(|signer| signer.public_key == submitter || !signer.partial_signature.is_empty()),
+        public_key: submitter,
+        nonce,
+        partial_signature: params.partial_signature.to_string(),
+    })
+}
+
+/// Nonce commitments and nonces of the signers of the order's session, the merchant first, with
+/// the nonce the submitting co-signer sends along its partial signature
+fn session_nonces(
+    wallet: &AppWalletClient,
+    passphrase: &SecUtf8,
+    session_id: &[u8; 32],
+    signers: &[SessionSigner],
+    submitter: &str,
+    submitter_nonce: &str,
+) -> Result<Vec<SignerNonce>, SigningError> {
+    let mut nonces = vec![SignerNonce {
+        role: ParticipantRole::Merchant,
+        commitment: wallet
+            .nonce_commitment(session_id, passphrase)
+            .map_err(|err| SigningError::Session(err.to_string()))?,
+        nonce: wallet
+            .nonce(session_id, passphrase)
+            .map_err(|err| SigningError::Session(err.to_string()))?,
+    }];
+    for signer in signers.iter() {
+        let role = signer.role;
+        let commitment = hex::decode(&signer.commitment)
+            .ok()
+            .filter(|commitment| commitment.len() == 32)
+            .map(|commitment| {
+                let mut bytes = [0; 32];
+                bytes.copy_from_slice(&commitment);
+                bytes
+            })
+            .ok_or(SigningError::InvalidCommitment(role))?;
+        let nonce = if signer.public_key == submitter {
+            submitter_nonce
+        } else {
+            &signer.nonce
+        };
+        nonces.push(SignerNonce {
+            role,
+            commitment,
+            nonce: PublicKey::from_str(nonce).map_err(|_| SigningError::InvalidNonce(role))?,
+        });
+    }
+    Ok(nonces)
+}
+
+/// Verifies the combined signature of the order's signing session, then signs and broadcasts
+/// the transaction the session started with.
+fn settle(
+    wallet: &AppWalletClient,
+    synchronizer: &AppSynchronizer,
+    passphrase: &SecUtf8,
+    record: &Order,
+    participants: &[OrderParticipant],
+    signers: &[SessionSigner],
+    settings: &MerchantSettings,
+) -> Result<(), SigningError> {
+    let wallet_name = record.wallet_name.clone();
+
+    // Complete multi-sig session
+    let (session, co_signers) = signing_session(wallet, passphrase, record, participants, signers);
+    let session_id = session.id;
+    verify_signature(wallet, &wallet_name, passphrase, &session, &co_signers)?;
+
+    sync_wallet(wallet, synchronizer, &wallet_name, passphrase);
+
+    // The signatures only hold for the transaction the session signed
+    let (transaction, _) =
+        settlement_transaction(wallet, passphrase, record, participants, None, settings)
+            .map_err(SigningError::Rejected)?;
+    if Some(hex::encode(transaction.id())) != record.settlement_transaction_id {
+        return Err(SigningError::Rejected("Settlement Transaction Changed"));
+    }
+
+    let tx_aux = wallet
+        .transaction(&wallet_name, &session_id, passphrase, transaction)
+        .map_err(|err| SigningError::Session(err.to_string()))?;
+
+    wallet
+        .broadcast_transaction(&tx_aux)
+        .map_err(|err| SigningError::Session(err.to_string()))?;
+    Ok(())
+}
+
+/// Multi-sig session of the order with the co-signers among the given session signers
+fn signing_session(
+    wallet: &AppWalletClient,
+    passphrase: &SecUtf8,
+    record: &Order,
+    participants: &[OrderParticipant],
+    signers: &[SessionSigner],
+) -> (SigningSession, Vec<Signer>) {
+    let merchant_public_key = merchant_public_key(wallet, passphrase, record);
+    let co_signers: Vec<Signer> = signers
+        .iter()
+        .filter(|signer| Some(&signer.session_id) == record.session_id.as_ref())
+        .map(Signer::from_session_signer)
+        .collect();
+    let mut public_keys = vec![merchant_public_key];
+    public_keys.extend(co_signers.iter().map(|signer| signer.public_key.clone()));
+
+    let session = SigningSession {
+        id: decode_hash(record.session_id.as_ref().unwrap()),
+        message: decode_hash(record.settlement_transaction_id.as_ref().unwrap()),
+        multisig_address: order_multisig_address(
+            wallet,
+            &record.wallet_name,
+            passphrase,
+            record,
+            participants,
+        ),
+        public_keys,
+    };
+    (session, co_signers)
+}
+
+/// Broadcasts the batch transaction once the session of every order of the batch is signed, the
+/// session of the given order being complete with the partial signature just accepted. Returns
+/// whether the batch was broadcast.
+fn settle_batch(
+    wallet: &AppWalletClient,
+    passphrase: &SecUtf8,
+    record: &Order,
+    settlement: &BatchSettlement,
+) -> Result<bool, SigningError> {
+    let transaction = decode_transaction(&settlement.batch.encoded_transaction);
+    let signed = settlement.orders.iter().all(|batched| {
+        batched.order_id == record.order_id || is_session_signed(batched, &settlement.signers)
+    });
+    if !signed || settlement.orders.len() != transaction.inputs.len() {
+        return Ok(false);
+    }
+
+    // One witness per input, from the session of the order spending it
+    let mut witnesses = vec![];
+    for input in transaction.inputs.iter() {
+        let batched = settlement
+            .orders
+            .iter()
+            .find(|batched| {
+                decode_hash(funding_transaction_id(batched)) == input.id
+                    && funding_output_index(batched) == input.index
+            })
+            .ok_or(SigningError::Rejected("Batch Out Of Date"))?;
+        if batched.settlement_transaction_id.as_ref() != Some(&settlement.batch.transaction_id) {
+            return Err(SigningError::Rejected("Batch Out Of Date"));
+        }
+        let participants: Vec<OrderParticipant> = settlement
+            .participants
+            .iter()
+            .filter(|participant| participant.order_id == batched.order_id)
+            .cloned()
+            .collect();
+        let (session, co_signers) = signing_session(
+            wallet,
+            passphrase,
+            batched,
+            &participants,
+            &settlement.signers,
+        );
+        witnesses.push(signed_witness(
+            wallet,
+            &batched.wallet_name,
+            passphrase,
+            &session,
+            &co_signers,
+        )?);
+    }
+
+    let transaction_cipher = MockAbciTransactionObfuscation::new(RpcClient::new(TENDERMINT_URL));
+    let tx_aux = transaction_cipher
+        .encrypt(SignedTransaction::TransferTransaction(
+            transaction,
+            TxWitness::from(witnesses),
+        ))
+        .map_err(|err| SigningError::Session(err.to_string()))?;
+    wallet
+        .broadcast_transaction(&tx_aux)
+        .map_err(|err| SigningError::Session(err.to_string()))?;
+    Ok(true)
+}
+
+/// Records a rejected signing step in the order events before responding with the error
+fn reject_signing<T>(
+    pool: web::Data<Pool>,
+    order_id: String,
+    err: SigningError,
+) -> impl Future<Item = T, Error = AWError> {
+    db::execute_record_order_event(
+        pool,
+        order_id,
+        OrderEventKind::SignatureRejected,
+        err.to_string(),
+    )
+    .then(move |_| Err(AWError::from(err)))
+}
+
+fn query_orders(
+    staff: Staff,
+    pool: web::Data<Pool>,
+    Valid(params): Valid<web::Query<OrderQueryRequest>>,
+) -> impl Future<Item = HttpResponse, Error = AWError> {
+    find_order_page(pool, staff.merchant_id, &params, None)
+        .and_then(|page| Ok(HttpResponse::Ok().json(page)))
+}
+
+fn get_pending_orders(
+    staff: Staff,
+    pool: web::Data<Pool>,
+    Valid(params): Valid<web::Query<OrderQueryRequest>>,
+) -> impl Future<Item = HttpResponse, Error = AWError> {
+    find_order_page(
+        pool,
+        staff.merchant_id,
+        &params,
+        Some(vec![
             OrderStatus::PendingPayment,
             OrderStatus::Delivering,
             OrderStatus::Refunding,
-        ],
+            OrderStatus::Splitting,
+            OrderStatus::Disputed,
+            OrderStatus::ReleasingMilestone,
+            OrderStatus::RefundPendingApproval,
+            OrderStatus::SplitPendingApproval,
+        ]),
     )
-    .from_err()
-    .and_then(move |res| Ok(HttpResponse::Ok().json(res)))
+    .map(order_list_response)
 }
 
 fn get_pending_response_orders(
+    staff: Staff,
+    pool: web::Data<Pool>,
+    Valid(params): Valid<web::Query<OrderQueryRequest>>,
+) -> impl Future<Item = HttpResponse, Error = AWError> {
+    find_order_page(
+        pool,
+        staff.merchant_id,
+        &params,
+        Some(vec![OrderStatus::PendingResponse]),
+    )
+    .map(order_list_response)
+}
+
+fn get_settled_orders(
+    staff: Staff,
+    pool: web::Data<Pool>,
+    Valid(params): Valid<web::Query<OrderQueryRequest>>,
+) -> impl Future<Item = HttpResponse, Error = AWError> {
+    find_order_page(
+        pool,
+        staff.merchant_id,
+        &params,
+        Some(vec![
+            OrderStatus::Completed,
+            OrderStatus::Refunded,
+            OrderStatus::Split,
+        ]),
+    )
+    .map(order_list_response)
+}
+
+/// Page of the orders of a merchant matching a query, restricted to the given statuses by the
+/// listing routes, which keep the statuses of the query among them
+fn find_order_page(
+    pool: web::Data<Pool>,
+    merchant_id: i32,
+    params: &OrderQueryRequest,
+    statuses: Option<Vec<OrderStatus>>,
+) -> impl Future<Item = OrderPageResponse, Error = AWError> {
+    future::result(
+        parse_order_query(params)
+            .map_err(|reason| AWError::from(HttpResponse::BadRequest().reason(reason).finish())),
+    )
+    .and_then(move |mut query| {
+        if let Some(statuses) = statuses {
+            if !query.restrict_statuses(&statuses) {
+                return Either::A(future::ok(OrderPageResponse {
+                    orders: vec![],
+                    next_cursor: None,
+                }));
+            }
+        }
+        let sort = query.sort;
+        Either::B(
+            db::execute_query_orders(pool, merchant_id, query).map(move |(records, more)| {
+                let next_cursor = records.last().filter(|_| more).map(|record| {
+                    let value = match sort.field {
+                        SortField::CreatedAt => {
+                            record.created_at.format(TIMESTAMP_FORMAT).to_string()
+                        }
+                        SortField::UpdatedAt => {
+                            record.updated_at.format(TIMESTAMP_FORMAT).to_string()
+                        }
+                        SortField::Amount => record.amount.to_string(),
+                    };
+                    encode_cursor(sort, &value, &record.order_id)
+                });
+                OrderPageResponse {
+                    orders: records
+                        .into_iter()
+                        .map(|record| order_response(record, vec![]))
+                        .collect(),
+                    next_cursor,
+                }
+            }),
+        )
+    })
+}
+
+/// Orders of a page as a plain list, as returned by the listing routes, with the cursor of the
+/// next page in a header
+fn order_list_response(page: OrderPageResponse) -> HttpResponse {
+    let mut res = HttpResponse::Ok();
+    if let Some(ref next_cursor) = page.next_cursor {
+        res.header("X-Next-Cursor", next_cursor.as_str());
+    }
+    res.json(page.orders)
+}
+
+fn get_merchant_balance(
+    staff: Staff,
+    pool: web::Data<Pool>,
+    config: web::Data<MerchantConfig>,
+) -> impl Future<Item = HttpResponse, Error = AWError> {
+    // TODO: Consider using Arc to share resource
+    let query_pool = pool.clone();
+    let wallets_pool = pool.clone();
+
+    find_merchant(query_pool, staff.merchant_id)
+        .and_then(move |merchant| {
+            let settings = merchant_settings(&merchant, &config);
+            merchant_wallet_names(wallets_pool, merchant.id, &settings)
+        })
+        .and_then(|wallet_names| {
+            web::block(move || sweep::wallet_balances(&wallet_names, &SecUtf8::from("passphrase")))
+                .from_err()
+        })
+        .and_then(|balances| {
+            let mut total = Coin::zero();
+            let mut wallets = vec![];
+            for wallet_balance in balances.iter() {
+                if wallet_balance.unspent_transactions.is_empty() {
+                    continue;
+                }
+                let balance = wallet_balance.balance().unwrap();
+                total = (total + balance).unwrap();
+                wallets.push(WalletBalanceResponse {
+                    wallet_name: wallet_balance.wallet_name.clone(),
+                    address: wallet_balance.address.to_cro().unwrap(),
+                    balance: u64::from(balance).to_string(),
+                    unspent_outputs: wallet_balance.unspent_transactions.len(),
+                });
+            }
+
+            let res = MerchantBalanceResponse {
+                balance: u64::from(total).to_string(),
+                wallets,
+            };
+            Ok(HttpResponse::Ok().json(res))
+        })
+}
+
+fn sweep_merchant_payouts(
+    staff: Staff,
     pool: web::Data<Pool>,
+    config: web::Data<MerchantConfig>,
 ) -> impl Future<Item = HttpResponse, Error = AWError> {
-    db::execute_get_orders_by_status(pool.clone(), vec![OrderStatus::PendingResponse])
-        .from_err()
-        .and_then(move |res| Ok(HttpResponse::Ok().json(res)))
+    // TODO: Consider using Arc to share resource
+    let query_pool = pool.clone();
+    let wallets_pool = pool.clone();
+
+    find_merchant(query_pool, staff.merchant_id)
+        .and_then(move |merchant| {
+            let settings = merchant_settings(&merchant, &config);
+            let treasury_address = match settings.treasury_address {
+                Some(ref treasury_address) => treasury_address.clone(),
+                None => {
+                    return Either::A(future::err(AWError::from(
+                        HttpResponse::BadRequest()
+                            .reason("Treasury Address Not Configured")
+                            .finish(),
+                    )))
+                }
+            };
+            Either::B(
+                merchant_wallet_names(wallets_pool, merchant.id, &settings).and_then(
+                    move |wallet_names| {
+                        web::block(move || {
+                            sweep_payouts(&wallet_names, &treasury_address, &config)
+                                .map(|transactions| (treasury_address, transactions))
+                        })
+                        .from_err()
+                    },
+                ),
+            )
+        })
+        .and_then(move |(treasury_address, transactions)| {
+            let res = SweepResponse {
+                treasury_address: treasury_address.to_cro().unwrap(),
+                transactions: transactions
+                    .iter()
+                    .map(|transaction| SweepTransactionResponse {
+                        wallet_name: transaction.wallet_name.clone(),
+                        transaction_id: hex::encode(transaction.transaction_id),
+                        amount: u64::from(transaction.amount).to_string(),
+                        inputs: transaction.inputs,
+                    })
+                    .collect(),
+            };
+            Ok(HttpResponse::Ok().json(res))
+        })
+}
+
+fn find_merchant(
+    pool: web::Data<Pool>,
+    merchant_id: i32,
+) -> impl Future<Item = Merchant, Error = AWError> {
+    db::execute_get_merchant(pool, merchant_id).and_then(|merchant| {
+        merchant.ok_or_else(|| {
+            AWError::from(
+                HttpResponse::NotFound()
+                    .reason("Merchant Not Found")
+                    .finish(),
+            )
+        })
+    })
 }
 
-fn get_settled_orders(pool: web::Data<Pool>) -> impl Future<Item = HttpResponse, Error = AWError> {
-    db::execute_get_orders_by_status(
-        pool.clone(),
-        vec![OrderStatus::Completed, OrderStatus::Refunded],
+/// Settings of a merchant, e.g. the merchant of an order being settled
+fn find_merchant_settings(
+    pool: web::Data<Pool>,
+    config: web::Data<MerchantConfig>,
+    merchant_id: i32,
+) -> impl Future<Item = MerchantSettings, Error = AWError> {
+    find_merchant(pool, merchant_id).map(move |merchant| merchant_settings(&merchant, &config))
+}
+
+/// Wallets holding the payouts of a merchant: the wallets of its orders and its long-lived wallet
+fn merchant_wallet_names(
+    pool: web::Data<Pool>,
+    merchant_id: i32,
+    settings: &MerchantSettings,
+) -> impl Future<Item = Vec<String>, Error = AWError> {
+    let merchant_wallet_name = settings.wallet_name.clone();
+    db::execute_get_wallet_names(pool, merchant_id).map(move |mut wallet_names| {
+        if let Some(wallet_name) = merchant_wallet_name {
+            if !wallet_names.contains(&wallet_name) {
+                wallet_names.push(wallet_name);
+            }
+        }
+        wallet_names
+    })
+}
+
+fn sweep_payouts(
+    wallet_names: &[String],
+    treasury_address: &ExtendedAddr,
+    config: &MerchantConfig,
+) -> Result<Vec<sweep::SweepTransaction>, failure::Error> {
+    sweep::sweep(
+        wallet_names,
+        &SecUtf8::from("passphrase"),
+        &config.sweep,
+        treasury_address,
     )
-    .from_err()
-    .and_then(move |res| Ok(HttpResponse::Ok().json(res)))
 }
 
 fn get_transaction_by_id(transaction_id: String, wallet_name: String) -> Option<Transaction> {
     let (wallet, index, synchronizer) = make_app();
     let passphrase = SecUtf8::from("passphrase");
 
-    let merchant_view_key = wallet.view_key(&wallet_name, &passphrase).unwrap();
+    sync_wallet(&wallet, &synchronizer, &wallet_name, &passphrase);
+
+    let transaction_id_vec = hex::decode(transaction_id).unwrap();
+    let mut transaction_id = [0; 32];
+    transaction_id.copy_from_slice(&transaction_id_vec);
+
+    let transaction_id: &TxId = &transaction_id;
+    index
+        .transaction(transaction_id)
+        .expect("transaction error")
+}
+
+/// Fetches the transactions readable with the view key of a wallet into the index
+fn sync_wallet(
+    wallet: &AppWalletClient,
+    synchronizer: &AppSynchronizer,
+    wallet_name: &str,
+    passphrase: &SecUtf8,
+) {
+    let merchant_view_key = wallet.view_key(wallet_name, passphrase).unwrap();
     let merchant_private_key = wallet
-        .private_key(&passphrase, &merchant_view_key)
+        .private_key(passphrase, &merchant_view_key)
         .unwrap()
         .unwrap();
-    let merchant_staking_addresses = wallet.staking_addresses(&wallet_name, &passphrase).unwrap();
+    let merchant_staking_addresses = wallet.staking_addresses(wallet_name, passphrase).unwrap();
 
     // TODO: Create separate thread to sync in background
     synchronizer
@@ -711,15 +3875,6 @@ fn get_transaction_by_id(transaction_id: String, wallet_name: String) -> Option<
             None,
         )
         .expect("sync error");
-
-    let transaction_id_vec = hex::decode(transaction_id).unwrap();
-    let mut transaction_id = [0; 32];
-    transaction_id.copy_from_slice(&transaction_id_vec);
-
-    let transaction_id: &TxId = &transaction_id;
-    index
-        .transaction(transaction_id)
-        .expect("transaction error")
 }
 
 type AppSigner = DefaultSigner<SledStorage>;
@@ -732,9 +3887,10 @@ type AppBlockHandler =
     DefaultBlockHandler<AppTransactionCipher, AppTransactionHandler, SledStorage>;
 type AppSynchronizer = ManualSynchronizer<SledStorage, RpcClient, AppBlockHandler>;
 fn make_app() -> (AppWalletClient, AppIndex, AppSynchronizer) {
+    make_app_with_storage(SledStorage::new(".client-storage").unwrap())
+}
+fn make_app_with_storage(storage: SledStorage) -> (AppWalletClient, AppIndex, AppSynchronizer) {
     let tendermint_client = RpcClient::new(TENDERMINT_URL);
-    let storage = SledStorage::new(".client-storage").unwrap();
-    let signer = DefaultSigner::new(storage.clone());
     let transaction_cipher = MockAbciTransactionObfuscation::new(tendermint_client.clone());
     let transaction_handler = DefaultTransactionHandler::new(storage.clone());
     let block_handler = DefaultBlockHandler::new(
@@ -744,11 +3900,7 @@ fn make_app() -> (AppWalletClient, AppIndex, AppSynchronizer) {
     );
 
     let index = DefaultIndex::new(storage.clone(), tendermint_client.clone());
-    let transaction_builder = DefaultTransactionBuilder::new(
-        signer,
-        tendermint_client.genesis().unwrap().fee_policy(),
-        transaction_cipher.clone(),
-    );
+    let transaction_builder = make_transaction_builder(storage.clone(), tendermint_client.clone());
     let wallet = DefaultWalletClient::builder()
         .with_wallet(storage.clone())
         .with_transaction_read(index.clone())
@@ -761,60 +3913,246 @@ fn make_app() -> (AppWalletClient, AppIndex, AppSynchronizer) {
     (wallet, index, synchronizer)
 }
 
+/// Fee policy of the chain, as set by its genesis
+fn fee_policy() -> Result<LinearFee, &'static str> {
+    RpcClient::new(TENDERMINT_URL)
+        .genesis()
+        .map(|genesis| genesis.fee_policy())
+        .map_err(|_| "Fee Policy Unavailable")
+}
+
+fn make_transaction_builder(storage: SledStorage, tendermint_client: RpcClient) -> AppTxBuilder {
+    let signer = DefaultSigner::new(storage);
+    let transaction_cipher = MockAbciTransactionObfuscation::new(tendermint_client.clone());
+
+    DefaultTransactionBuilder::new(
+        signer,
+        tendermint_client.genesis().unwrap().fee_policy(),
+        transaction_cipher,
+    )
+}
+
+fn order_multisig_address(
+    wallet: &AppWalletClient,
+    wallet_name: &str,
+    passphrase: &SecUtf8,
+    record: &Order,
+    participants: &[OrderParticipant],
+) -> ExtendedAddr {
+    let merchant_public_key = merchant_public_key(wallet, passphrase, record);
+    multisig_address(
+        wallet,
+        wallet_name,
+        passphrase,
+        merchant_public_key,
+        record,
+        participants,
+    )
+}
+
+/// Multi-sig address of the order's co-signers with the given merchant key
+fn multisig_address(
+    wallet: &AppWalletClient,
+    wallet_name: &str,
+    passphrase: &SecUtf8,
+    merchant_public_key: PublicKey,
+    record: &Order,
+    participants: &[OrderParticipant],
+) -> ExtendedAddr {
+    let mut public_keys = vec![merchant_public_key.clone()];
+    public_keys.extend(
+        participants
+            .iter()
+            .map(|participant| PublicKey::from_str(&participant.public_key).unwrap()),
+    );
+    let total_signatures = public_keys.len();
+
+    wallet
+        .new_multisig_transfer_address(
+            wallet_name,
+            passphrase,
+            public_keys,
+            merchant_public_key,
+            record.required_signatures as usize,
+            total_signatures,
+        )
+        .expect("new_multisig_transfer_address error")
+}
+
+fn decode_hash(value: &str) -> [u8; 32] {
+    let value_vec = hex::decode(value).unwrap();
+    let mut hash = [0; 32];
+    hash.copy_from_slice(&value_vec);
+    hash
+}
+
+/// Transaction settling the order with the network fee it pays: the transaction its signing
+/// session started with, else the batch transaction when the order is part of a batch. The fee
+/// is computed when the transaction is built, and is unknown for the sessions and batches
+/// started before it was stored.
+fn settlement_transaction(
+    wallet: &AppWalletClient,
+    passphrase: &SecUtf8,
+    record: &Order,
+    participants: &[OrderParticipant],
+    batch: Option<&SettlementBatch>,
+    settings: &MerchantSettings,
+) -> Result<(Tx, Option<Coin>), &'static str> {
+    if let Some(ref encoded_transaction) = record.encoded_settlement_transaction {
+        return Ok((
+            decode_transaction(encoded_transaction),
+            record.settlement_network_fee.map(to_coin),
+        ));
+    }
+    match batch {
+        Some(batch) => Ok((
+            decode_transaction(&batch.encoded_transaction),
+            batch.network_fee.map(to_coin),
+        )),
+        None => construct_tx(
+            record.wallet_name.clone(),
+            passphrase.clone(),
+            wallet,
+            record,
+            participants,
+            settings,
+        )
+        .map(|(transaction, network_fee)| (transaction, Some(network_fee))),
+    }
+}
+
 fn construct_tx(
     wallet_name: String,
     passphrase: SecUtf8,
     wallet: &AppWalletClient,
     record: &Order,
-) -> Tx {
-    let merchant_address = wallet
-        .transfer_addresses(&wallet_name, &passphrase)
-        .unwrap()[0]
-        .clone();
+    participants: &[OrderParticipant],
+    settings: &MerchantSettings,
+) -> Result<(Tx, Coin), &'static str> {
+    let settlement = unsigned_settlement(
+        wallet_name,
+        passphrase,
+        wallet,
+        record,
+        participants,
+        settings,
+    )?;
+    let network_id = hex::decode(NETWORK_ID).unwrap()[0];
+    unsigned_transaction(network_id, settlement, &fee_policy()?)
+}
+
+/// Settlement of the order before the network fee is deducted, its fees already deducted from
+/// its payouts
+fn unsigned_settlement(
+    wallet_name: String,
+    passphrase: SecUtf8,
+    wallet: &AppWalletClient,
+    record: &Order,
+    participants: &[OrderParticipant],
+    settings: &MerchantSettings,
+) -> Result<UnsignedSettlement, &'static str> {
+    let merchant_address = if record.payout_address.is_empty() {
+        payout_address(wallet, &wallet_name, &passphrase)
+    } else {
+        ExtendedAddr::from_cro(&record.payout_address).map_err(|_| "Invalid Payout Address")?
+    };
     let merchant_view_key = wallet.view_key(&wallet_name, &passphrase).unwrap();
 
-    let buyer_address = ExtendedAddr::from_cro(&record.buyer_address[..]).unwrap();
+    let buyer_address = ExtendedAddr::from_cro(current_refund_address(record))
+        .map_err(|_| "Invalid Refund Address")?;
 
-    let transaction_id_vec = hex::decode(&record.payment_transaction_id).unwrap();
+    let transaction_id_vec = hex::decode(funding_transaction_id(record)).unwrap();
     let mut transaction_id = [0; 32];
     transaction_id.copy_from_slice(&transaction_id_vec);
 
     let inputs = vec![TxoPointer {
         id: transaction_id,
-        index: 0,
+        index: funding_output_index(record),
     }];
 
-    let outputs = match record.status {
-        OrderStatus::Delivering => vec![
-            TxOut {
-                address: merchant_address,
-                value: Coin::from_str(&record.amount[..])
-                    .unwrap()
-                    .sub(Coin::from(10 * 1_0000_0000))
-                    .unwrap(),
-                valid_from: None,
-            },
-            TxOut {
-                address: buyer_address,
-                value: Coin::from(10 * 1_0000_0000),
+    let amount = funding_amount(record);
+    // A milestone settles its tranche and returns the rest to the order as the first output
+    let (settled_amount, change) = match record.status {
+        OrderStatus::ReleasingMilestone => (
+            to_coin(record.milestone_amount.ok_or("Milestone Not Releasing")?),
+            remaining_amount(record),
+        ),
+        _ => (amount, None),
+    };
+    // Payouts of the merchant and of the buyer, the merchant paying the fees first
+    let merchant_payout = |value| TxOut {
+        address: merchant_address.clone(),
+        value,
+        valid_from: None,
+    };
+    let buyer_payout = |value| TxOut {
+        address: buyer_address.clone(),
+        value,
+        valid_from: None,
+    };
+    let (payouts, buyer_payouts) = match record.status {
+        OrderStatus::Delivering => {
+            let deposit = Coin::from(BUYER_DEPOSIT);
+            (
+                vec![merchant_payout(
+                    amount.sub(deposit).map_err(|_| "Amount Below Deposit")?,
+                )],
+                vec![buyer_payout(deposit)],
+            )
+        }
+        OrderStatus::Refunding => (vec![], vec![buyer_payout(amount)]),
+        OrderStatus::Splitting => {
+            let (merchant_amount, buyer_amount) =
+                match (record.split_merchant_amount, record.split_buyer_amount) {
+                    (Some(merchant_amount), Some(buyer_amount)) => (merchant_amount, buyer_amount),
+                    _ => return Err("Split Not Accepted"),
+                };
+            (
+                vec![merchant_payout(to_coin(merchant_amount))],
+                vec![buyer_payout(to_coin(buyer_amount))],
+            )
+        }
+        OrderStatus::ReleasingMilestone => (vec![merchant_payout(settled_amount)], vec![]),
+        _ => (vec![], vec![]),
+    };
+    let change = match change {
+        Some(change) => {
+            let change_public_key = record
+                .change_public_key
+                .as_ref()
+                .and_then(|public_key| PublicKey::from_str(public_key).ok())
+                .ok_or("Invalid Change Public Key")?;
+            Some(TxOut {
+                address: multisig_address(
+                    wallet,
+                    &wallet_name,
+                    &passphrase,
+                    change_public_key,
+                    record,
+                    participants,
+                ),
+                value: change,
                 valid_from: None,
-            },
-        ],
-        OrderStatus::Refunding => vec![TxOut {
-            address: buyer_address,
-            value: Coin::from_str(&record.amount[..])
-                .unwrap(),
-            valid_from: None,
-        }],
-        _ => vec![],
+            })
+        }
+        None => None,
     };
+    let fees = fee_outputs(&settings.fees, record, settled_amount);
+    let (payouts, buyer_payouts) = deduct_fees(payouts, buyer_payouts, &fees)?;
 
     let mut access_policies: Vec<TxAccessPolicy> = vec![];
-    let view_keys = vec![
-        merchant_view_key,
-        PublicKey::from_str(&record.buyer_view_key[..]).unwrap(),
-        PublicKey::from_str(&record.escrow_view_key[..]).unwrap(),
-    ];
+    let mut view_keys = vec![merchant_view_key];
+    for participant in participants.iter() {
+        if participant.role == ParticipantRole::Escrow
+            && record.status == OrderStatus::Refunding
+            && !settings.access_policy.escrow_access_on_refund
+        {
+            continue;
+        }
+        view_keys.push(PublicKey::from_str(&participant.view_key[..]).unwrap());
+    }
+    view_keys.extend(settings.access_policy.auditor_view_keys.iter().cloned());
+    view_keys.extend(parse_view_keys(&record.extra_view_keys).unwrap());
     for key in view_keys.iter() {
         access_policies.push(TxAccessPolicy {
             view_key: key.into(),
@@ -822,11 +4160,14 @@ fn construct_tx(
         });
     }
 
-    let network_id = hex::decode(NETWORK_ID).unwrap()[0];
-    let attributes = TxAttributes::new_with_access(network_id, access_policies);
-    Tx {
+    Ok(UnsignedSettlement {
         inputs,
-        outputs,
-        attributes,
-    }
+        amount,
+        change,
+        payouts,
+        buyer_payouts,
+        fees,
+        access_policies,
+        batched: false,
+    })
 }
@@ -0,0 +1,176 @@
+use actix_web::web;
+use futures::Future;
+use secstr::SecUtf8;
+use std::str::FromStr;
+use uuid::Uuid;
+
+use chain_core::tx::data::address::ExtendedAddr;
+use client_common::PublicKey;
+use client_core::wallet::WalletClient;
+
+use crate::config::MerchantConfig;
+use crate::db;
+use crate::merchants::DEFAULT_MERCHANT_ID;
+use crate::models::{Order, OrderStatus};
+use crate::{make_app, AppWalletClient, Pool};
+
+/// Merchant side of a new order
+pub struct OrderWallet {
+    /// Wallet holding the merchant key of the order
+    pub wallet_name: String,
+    pub public_key: PublicKey,
+    /// Address receiving the merchant share of the settlement
+    pub address: ExtendedAddr,
+}
+
+/// Creates the configured merchant wallet and its payout address if they do not exist yet
+pub fn init_merchant_wallet(wallet: &AppWalletClient, wallet_name: &str, passphrase: &SecUtf8) {
+    let wallets = wallet.wallets().expect("wallets error");
+    if !wallets.iter().any(|name| name == wallet_name) {
+        wallet
+            .new_wallet(wallet_name, passphrase)
+            .expect("new_wallet error");
+    }
+    if wallet
+        .transfer_addresses(wallet_name, passphrase)
+        .unwrap()
+        .is_empty()
+    {
+        wallet
+            .new_transfer_address(wallet_name, passphrase)
+            .expect("new_transfer_address error");
+    }
+}
+
+/// Derives the merchant key of a new order. With a merchant wallet set a fresh key is added to it
+/// and the wallet's payout address is reused, otherwise a wallet is created for the order.
+pub fn new_order_wallet(
+    wallet: &AppWalletClient,
+    merchant_wallet_name: Option<&str>,
+    passphrase: &SecUtf8,
+) -> OrderWallet {
+    match merchant_wallet_name {
+        Some(wallet_name) => {
+            let public_key = wallet
+                .new_public_key(wallet_name, passphrase)
+                .expect("new_public_key error");
+            let address = payout_address(wallet, wallet_name, passphrase);
+
+            OrderWallet {
+                wallet_name: wallet_name.to_owned(),
+                public_key,
+                address,
+            }
+        }
+        None => {
+            let wallet_name = Uuid::new_v4().to_string();
+            wallet
+                .new_wallet(&wallet_name, passphrase)
+                .expect("new_wallet error");
+            let address = wallet
+                .new_transfer_address(&wallet_name, passphrase)
+                .unwrap();
+            let public_key = wallet.public_keys(&wallet_name, passphrase).unwrap()[0].clone();
+
+            OrderWallet {
+                wallet_name,
+                public_key,
+                address,
+            }
+        }
+    }
+}
+
+/// Address of a wallet receiving the merchant share of settlements
+pub fn payout_address(
+    wallet: &AppWalletClient,
+    wallet_name: &str,
+    passphrase: &SecUtf8,
+) -> ExtendedAddr {
+    wallet.transfer_addresses(wallet_name, passphrase).unwrap()[0].clone()
+}
+
+/// Merchant key of an order. Orders created before the key was recorded with the order use the
+/// only key of their own wallet.
+pub fn merchant_public_key(
+    wallet: &AppWalletClient,
+    passphrase: &SecUtf8,
+    record: &Order,
+) -> PublicKey {
+    if record.merchant_public_key.is_empty() {
+        wallet.public_keys(&record.wallet_name, passphrase).unwrap()[0].clone()
+    } else {
+        PublicKey::from_str(&record.merchant_public_key).unwrap()
+    }
+}
+
+/// Records the merchant key of every order created with a wallet of its own, so that each order
+/// row names the wallet and the key signing for it. The per-order wallets are kept since they
+/// hold the keys of the multi-sig addresses already handed out to buyers.
+pub fn migrate_wallets(pool: Pool, config: &MerchantConfig) {
+    let (wallet, _, _) = make_app();
+    let passphrase = SecUtf8::from("passphrase");
+    let pool = web::Data::new(pool);
+
+    if let Some(ref wallet_name) = config.wallet_name {
+        init_merchant_wallet(&wallet, wallet_name, &passphrase);
+    }
+
+    // Orders created with a wallet of their own predate the merchants
+    let orders = db::execute_get_orders_by_status(
+        pool.clone(),
+        DEFAULT_MERCHANT_ID,
+        vec![
+            OrderStatus::PendingPayment,
+            OrderStatus::PendingResponse,
+            OrderStatus::Delivering,
+            OrderStatus::Refunding,
+            OrderStatus::Completed,
+            OrderStatus::Refunded,
+            OrderStatus::Splitting,
+            OrderStatus::Split,
+            OrderStatus::Disputed,
+            OrderStatus::ReleasingMilestone,
+            OrderStatus::RefundPendingApproval,
+            OrderStatus::SplitPendingApproval,
+        ],
+    )
+    .wait()
+    .expect("Error loading orders");
+
+    let mut migrated = 0;
+    for record in orders
+        .iter()
+        .filter(|record| record.merchant_public_key.is_empty())
+    {
+        let public_keys = match wallet.public_keys(&record.wallet_name, &passphrase) {
+            Ok(public_keys) => public_keys,
+            Err(err) => {
+                println!(
+                    "Skipping order {}: wallet {} not found ({})",
+                    record.order_id, record.wallet_name, err
+                );
+                continue;
+            }
+        };
+        if public_keys.len() != 1 {
+            println!(
+                "Skipping order {}: wallet {} has {} keys",
+                record.order_id,
+                record.wallet_name,
+                public_keys.len()
+            );
+            continue;
+        }
+
+        db::execute_store_merchant_public_key(
+            pool.clone(),
+            record.order_id.clone(),
+            public_keys[0].to_string(),
+        )
+        .wait()
+        .expect("Error storing merchant public key");
+        migrated += 1;
+    }
+    println!("Recorded merchant key of {} orders", migrated);
+}
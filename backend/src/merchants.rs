@@ -0,0 +1,350 @@
+use actix_web::web;
+use futures::Future;
+use secstr::SecUtf8;
+use std::str::FromStr;
+
+use chain_core::init::address::CroAddress;
+use chain_core::init::coin::Coin;
+use chain_core::tx::data::address::ExtendedAddr;
+
+use crate::auth::{api_key_digest, random_token};
+use crate::config::{
+    parse_address, parse_view_keys, AccessPolicyConfig, FeeConfig, FeeRate, FeeRecipient,
+    MerchantConfig, RefundApprovalConfig,
+};
+use crate::db;
+use crate::merchant_wallet::init_merchant_wallet;
+use crate::models::{
+    to_base_units, to_coin, Merchant, MerchantPolicies, NewMerchant, NewMerchantApiKey,
+};
+use crate::{make_app, Pool};
+
+/// Merchant which ran the backend before merchants were added. It owns the orders created
+/// without a merchant and the staff accounts of the configuration.
+pub const DEFAULT_MERCHANT_ID: i32 = 1;
+
+/// Settings of a merchant. Those left empty on the default merchant are taken from the
+/// configuration, the other merchants go without them.
+pub struct MerchantSettings {
+    /// Long-lived wallet holding the merchant keys of new orders, a wallet per order when unset
+    pub wallet_name: Option<String>,
+    /// Address receiving the merchant share of settlements, unless set per order
+    pub payout_address: Option<ExtendedAddr>,
+    /// Address receiving the swept payouts, sweeping is disabled when unset
+    pub treasury_address: Option<ExtendedAddr>,
+    pub access_policy: AccessPolicyConfig,
+    /// Platform fee of the merchant's orders, the network fee being the same for every merchant
+    pub fees: FeeConfig,
+    /// Refund threshold of the merchant, the number of approvals being the same for every merchant
+    pub refund_approval: RefundApprovalConfig,
+    /// Whether new orders only accept escrows of the registry which have not been revoked
+    pub require_registered_escrow: bool,
+}
+
+pub fn merchant_settings(merchant: &Merchant, config: &MerchantConfig) -> MerchantSettings {
+    let is_default = merchant.id == DEFAULT_MERCHANT_ID;
+    let address = |address: &str, fallback: &Option<ExtendedAddr>| {
+        if !address.is_empty() {
+            Some(ExtendedAddr::from_cro(address).unwrap())
+        } else if is_default {
+            fallback.clone()
+        } else {
+            None
+        }
+    };
+    let flag = |flag: Option<bool>, fallback: bool, unset: bool| match flag {
+        Some(flag) => flag,
+        None if is_default => fallback,
+        None => unset,
+    };
+
+    let auditor_view_keys = if !merchant.auditor_view_keys.is_empty() {
+        parse_view_keys(&merchant.auditor_view_keys).unwrap()
+    } else if is_default {
+        config.access_policy.auditor_view_keys.clone()
+    } else {
+        vec![]
+    };
+    let platform_fee = if !merchant.platform_fee_address.is_empty() {
+        Some(FeeRecipient {
+            address: ExtendedAddr::from_cro(&merchant.platform_fee_address).unwrap(),
+            normal: FeeRate::from_columns(
+                merchant.platform_fee,
+                merchant.platform_fee_basis_points,
+            ),
+            dispute: FeeRate::from_columns(
+                merchant.platform_dispute_fee,
+                merchant.platform_dispute_fee_basis_points,
+            ),
+        })
+    } else if is_default {
+        config.fees.platform.clone()
+    } else {
+        None
+    };
+    let refund_approval_threshold = match merchant.refund_approval_threshold {
+        Some(threshold) => Some(to_coin(threshold)),
+        None if is_default => config.refund_approval.threshold,
+        None => None,
+    };
+
+    MerchantSettings {
+        wallet_name: if !merchant.wallet_name.is_empty() {
+            Some(merchant.wallet_name.clone())
+        } else if is_default {
+            config.wallet_name.clone()
+        } else {
+            None
+        },
+        payout_address: address(&merchant.payout_address, &config.payout_address),
+        treasury_address: address(&merchant.treasury_address, &config.sweep.treasury_address),
+        access_policy: AccessPolicyConfig {
+            auditor_view_keys,
+            escrow_access_on_refund: flag(
+                merchant.escrow_access_on_refund,
+                config.access_policy.escrow_access_on_refund,
+                true,
+            ),
+        },
+        fees: FeeConfig {
+            platform: platform_fee,
+        },
+        refund_approval: RefundApprovalConfig {
+            threshold: refund_approval_threshold,
+            required_approvals: config.refund_approval.required_approvals,
+        },
+        require_registered_escrow: flag(
+            merchant.require_registered_escrow,
+            config.require_registered_escrow,
+            false,
+        ),
+    }
+}
+
+/// Creates the wallet of every merchant with a long-lived wallet, if it does not exist yet
+pub fn init_merchant_wallets(pool: Pool, config: &MerchantConfig) {
+    let merchants = db::execute_get_merchants(web::Data::new(pool))
+        .wait()
+        .expect("Error loading merchants");
+    let (wallet, _, _) = make_app();
+    for merchant in merchants.iter() {
+        if let Some(ref wallet_name) = merchant_settings(merchant, config).wallet_name {
+            init_merchant_wallet(&wallet, wallet_name, &SecUtf8::from("passphrase"));
+        }
+    }
+}
+
+/// Refuses to serve without a staff API key, in the configuration or issued with `add-api-key`
+pub fn check_staff_api_keys(pool: Pool, config: &MerchantConfig) {
+    if !config.auth.staff_api_keys.is_empty() {
+        return;
+    }
+    let api_keys = db::execute_count_merchant_api_keys(web::Data::new(pool))
+        .wait()
+        .expect("Error loading API keys");
+    if api_keys == 0 {
+        panic!("No staff API key, set STAFF_API_KEYS or issue one with add-api-key");
+    }
+}
+
+/// Adds a merchant with its own wallet, payout and treasury addresses, each of them left to the
+/// defaults when empty:
+/// `register-merchant <name> [<wallet_name> [<payout_address> [<treasury_address>]]]`
+pub fn register_merchant(pool: Pool, config: &MerchantConfig, args: &[String]) {
+    if args.is_empty() || args.len() > 4 || args[0].is_empty() {
+        println!(
+            "Usage: register-merchant <name> [<wallet_name> [<payout_address> [<treasury_address>]]]"
+        );
+        return;
+    }
+    let setting = |index: usize| args.get(index).cloned().unwrap_or_default();
+    let (wallet_name, payout_address, treasury_address) = (setting(1), setting(2), setting(3));
+    if !payout_address.is_empty() {
+        parse_address(&payout_address, config.network).expect("Invalid payout address");
+    }
+    if !treasury_address.is_empty() {
+        parse_address(&treasury_address, config.network).expect("Invalid treasury address");
+    }
+
+    let pool = web::Data::new(pool);
+    let merchants = db::execute_get_merchants(pool.clone())
+        .wait()
+        .expect("Error loading merchants");
+    if let Some(merchant) = merchants.iter().find(|merchant| merchant.name == args[0]) {
+        println!("Merchant name already registered with ID {}", merchant.id);
+        return;
+    }
+
+    if !wallet_name.is_empty() {
+        let (wallet, _, _) = make_app();
+        init_merchant_wallet(&wallet, &wallet_name, &SecUtf8::from("passphrase"));
+    }
+    let merchant = db::execute_register_merchant(
+        pool,
+        NewMerchant {
+            name: args[0].clone(),
+            wallet_name,
+            payout_address,
+            treasury_address,
+        },
+    )
+    .wait()
+    .expect("Error registering merchant");
+    println!(
+        "Registered merchant {} with ID {}",
+        merchant.name, merchant.id
+    );
+}
+
+/// Sets a policy of a merchant, or unsets it when no value is given:
+/// `set-merchant-policy <merchant_id> <policy> [<value>]`. The policies are
+/// `auditor-view-keys <view_key>,...`, `escrow-access-on-refund <true|false>`,
+/// `platform-fee <fee_address> <fee> <dispute_fee>`, `refund-approval-threshold <amount>` and
+/// `require-registered-escrow <true|false>`.
+pub fn set_merchant_policy(pool: Pool, config: &MerchantConfig, args: &[String]) {
+    let merchant_id = match args.get(0).map(|merchant_id| merchant_id.parse::<i32>()) {
+        Some(Ok(merchant_id)) if args.len() >= 2 => merchant_id,
+        _ => {
+            println!("Usage: set-merchant-policy <merchant_id> <policy> [<value>]");
+            return;
+        }
+    };
+
+    let pool = web::Data::new(pool);
+    let merchant = match db::execute_get_merchant(pool.clone(), merchant_id)
+        .wait()
+        .expect("Error loading merchant")
+    {
+        Some(merchant) => merchant,
+        None => {
+            println!("Merchant {} not found", merchant_id);
+            return;
+        }
+    };
+
+    let mut policies = merchant_policies(&merchant);
+    let values = &args[2..];
+    let flag = |values: &[String]| {
+        values
+            .get(0)
+            .map(|value| value.parse::<bool>().expect("Invalid flag"))
+    };
+    match (args[1].as_str(), values.len()) {
+        ("auditor-view-keys", 0..=1) => {
+            let view_keys = values.get(0).cloned().unwrap_or_default();
+            parse_view_keys(&view_keys).expect("Invalid view key");
+            policies.auditor_view_keys = view_keys;
+        }
+        ("escrow-access-on-refund", 0..=1) => policies.escrow_access_on_refund = flag(values),
+        ("platform-fee", 0) => {
+            policies.platform_fee_address = String::from("");
+            policies.platform_fee = 0;
+            policies.platform_fee_basis_points = 0;
+            policies.platform_dispute_fee = 0;
+            policies.platform_dispute_fee_basis_points = 0;
+        }
+        ("platform-fee", 3) => {
+            parse_address(&values[0], config.network).expect("Invalid fee address");
+            let fee = FeeRate::from_str(&values[1]).expect("Invalid fee");
+            let dispute_fee = FeeRate::from_str(&values[2]).expect("Invalid dispute fee");
+            policies.platform_fee_address = values[0].clone();
+            let (fee, fee_basis_points) = fee.to_columns();
+            policies.platform_fee = fee;
+            policies.platform_fee_basis_points = fee_basis_points;
+            let (dispute_fee, dispute_fee_basis_points) = dispute_fee.to_columns();
+            policies.platform_dispute_fee = dispute_fee;
+            policies.platform_dispute_fee_basis_points = dispute_fee_basis_points;
+        }
+        ("refund-approval-threshold", 0..=1) => {
+            policies.refund_approval_threshold = values.get(0).map(|amount| {
+                to_base_units(Coin::from_str(amount).expect("Invalid refund approval threshold"))
+            })
+        }
+        ("require-registered-escrow", 0..=1) => policies.require_registered_escrow = flag(values),
+        _ => {
+            println!(
+                "Usage: set-merchant-policy <merchant_id> auditor-view-keys [<view_key>,...] | \
+                 escrow-access-on-refund [<true|false>] | \
+                 platform-fee [<fee_address> <fee> <dispute_fee>] | \
+                 refund-approval-threshold [<amount>] | require-registered-escrow [<true|false>]"
+            );
+            return;
+        }
+    }
+
+    let merchant = db::execute_set_merchant_policies(pool, merchant_id, policies)
+        .wait()
+        .expect("Error setting merchant policy");
+    println!("Set {} of merchant {}", args[1], merchant.name);
+}
+
+fn merchant_policies(merchant: &Merchant) -> MerchantPolicies {
+    MerchantPolicies {
+        auditor_view_keys: merchant.auditor_view_keys.clone(),
+        escrow_access_on_refund: merchant.escrow_access_on_refund,
+        platform_fee_address: merchant.platform_fee_address.clone(),
+        platform_fee: merchant.platform_fee,
+        platform_fee_basis_points: merchant.platform_fee_basis_points,
+        platform_dispute_fee: merchant.platform_dispute_fee,
+        platform_dispute_fee_basis_points: merchant.platform_dispute_fee_basis_points,
+        refund_approval_threshold: merchant.refund_approval_threshold,
+        require_registered_escrow: merchant.require_registered_escrow,
+    }
+}
+
+/// Issues an API key to a staff account of a merchant, the key is only printed once:
+/// `add-api-key <merchant_id> <name>`
+pub fn add_api_key(pool: Pool, args: &[String]) {
+    let merchant_id = match args.get(0).map(|merchant_id| merchant_id.parse::<i32>()) {
+        Some(Ok(merchant_id)) if args.len() == 2 && !args[1].is_empty() => merchant_id,
+        _ => {
+            println!("Usage: add-api-key <merchant_id> <name>");
+            return;
+        }
+    };
+
+    let pool = web::Data::new(pool);
+    let merchant = db::execute_get_merchant(pool.clone(), merchant_id)
+        .wait()
+        .expect("Error loading merchant");
+    if merchant.is_none() {
+        println!("Merchant {} not found", merchant_id);
+        return;
+    }
+
+    let api_key = random_token();
+    let merchant_api_key = db::execute_add_merchant_api_key(
+        pool,
+        NewMerchantApiKey {
+            merchant_id,
+            name: args[1].clone(),
+            key_digest: api_key_digest(&api_key),
+        },
+    )
+    .wait()
+    .expect("Error adding API key");
+    println!(
+        "Added API key {} of {} with ID {}: {}",
+        merchant_api_key.name, merchant_id, merchant_api_key.id, api_key
+    );
+}
+
+/// Revokes an API key issued with `add-api-key`: `revoke-api-key <id>`
+pub fn revoke_api_key(pool: Pool, args: &[String]) {
+    let api_key_id = match args.get(0).map(|api_key_id| api_key_id.parse::<i32>()) {
+        Some(Ok(api_key_id)) => api_key_id,
+        _ => {
+            println!("Usage: revoke-api-key <id>");
+            return;
+        }
+    };
+
+    let revoked = db::execute_revoke_merchant_api_key(web::Data::new(pool), api_key_id)
+        .wait()
+        .expect("Error revoking API key");
+    if revoked {
+        println!("Revoked API key {}", api_key_id);
+    } else {
+        println!("API key {} not found", api_key_id);
+    }
+}
@@ -0,0 +1,77 @@
+use std::str::FromStr;
+
+use chain_core::init::coin::Coin;
+use chain_core::tx::data::input::TxoIndex;
+
+use crate::models::{to_coin, Milestone, MilestoneStatus, Order};
+
+/// Parses the comma separated milestone amounts of a new order in base units. An order released
+/// in milestones has at least two of them and they have to add up to the order amount.
+pub fn parse_milestones(milestones: &str, amount: &str) -> Result<Vec<Coin>, &'static str> {
+    let amounts = milestones
+        .split(',')
+        .map(|milestone| match Coin::from_str(milestone.trim()) {
+            Ok(amount) if amount != Coin::zero() => Ok(amount),
+            _ => Err("Invalid Milestone Amount"),
+        })
+        .collect::<Result<Vec<Coin>, &'static str>>()?;
+    if amounts.len() < 2 {
+        return Err("Invalid Milestone Amount");
+    }
+
+    let total = amounts
+        .iter()
+        .try_fold(Coin::zero(), |total, amount| total + *amount)
+        .map_err(|_| "Invalid Milestone Amount")?;
+    let amount = Coin::from_str(amount).map_err(|_| "Invalid Amount")?;
+    if total != amount {
+        return Err("Milestones Do Not Match Amount");
+    }
+    Ok(amounts)
+}
+
+/// Milestone to be released next, `None` once every milestone is released or if the order is not
+/// released in milestones
+pub fn next_milestone(milestones: &[Milestone]) -> Option<&Milestone> {
+    milestones
+        .iter()
+        .filter(|milestone| milestone.status == MilestoneStatus::Pending)
+        .min_by_key(|milestone| milestone.sequence)
+}
+
+/// Transaction holding the funds of the order's multi-sig address still to be settled, the
+/// payment until a milestone is released
+pub fn funding_transaction_id(record: &Order) -> &str {
+    record
+        .funding_transaction_id
+        .as_ref()
+        .or_else(|| record.payment_transaction_id.as_ref())
+        .map(String::as_str)
+        .unwrap_or_default()
+}
+
+/// Output of the funding transaction holding the funds, the change of a milestone settlement is
+/// always its first output
+pub fn funding_output_index(record: &Order) -> TxoIndex {
+    if record.funding_transaction_id.is_none() {
+        record.payment_output_index as TxoIndex
+    } else {
+        0
+    }
+}
+
+/// Funds of the order's multi-sig address still to be settled
+pub fn funding_amount(record: &Order) -> Coin {
+    to_coin(record.funding_amount.unwrap_or(record.amount))
+}
+
+/// Funds left once the milestone being released is paid out, `None` for the last milestone
+pub fn remaining_amount(record: &Order) -> Option<Coin> {
+    let milestone_amount = to_coin(record.milestone_amount.unwrap());
+    let remaining = (funding_amount(record) - milestone_amount).unwrap();
+    if remaining == Coin::zero() {
+        None
+    } else {
+        Some(remaining)
+    }
+}
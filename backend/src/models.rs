@@ -1,29 +1,107 @@
+use chrono::NaiveDateTime;
 use diesel::backend::Backend;
 use diesel::deserialize::{self, FromSql};
 use diesel::serialize::{self, Output, ToSql};
 use diesel::sql_types::*;
 use serde::{Deserialize, Serialize};
+use std::fmt;
 use std::io;
+use std::str::FromStr;
 
+use chain_core::init::coin::Coin;
 use chain_core::tx::data::Tx;
 
-use crate::schema::orders;
+use crate::schema::{
+    auth_challenges, auth_sessions, checkouts, dispute_cases, dispute_evidence, dispute_messages,
+    escrows, merchant_api_keys, merchants, milestones, order_events, order_participants, orders,
+    refund_approvals, rulings, session_signers, settlement_batches, split_proposals,
+};
 
-#[derive(Debug, Serialize, Deserialize, Queryable, Insertable)]
-#[table_name = "orders"]
+#[derive(Debug, Serialize, Deserialize, Queryable)]
 pub struct Order {
     pub order_id: String,
     pub status: OrderStatus,
     pub wallet_name: String,
-    pub amount: String,
+    // Amounts in base units
+    pub amount: i64,
     pub buyer_public_key: String,
     pub buyer_view_key: String,
     pub buyer_address: String,
     pub escrow_public_key: String,
     pub escrow_view_key: String,
-    pub session_id: String,
-    pub payment_transaction_id: String,
-    pub settlement_transaction_id: String,
+    pub session_id: Option<String>,
+    pub payment_transaction_id: Option<String>,
+    pub settlement_transaction_id: Option<String>,
+    pub extra_view_keys: String,
+    pub required_signatures: i32,
+    pub merchant_public_key: String,
+    pub payout_address: String,
+    pub refund_address: Option<String>,
+    pub split_merchant_amount: Option<i64>,
+    pub split_buyer_amount: Option<i64>,
+    pub arbitrated: bool,
+    pub escrow_id: Option<i32>,
+    pub escrow_fee_address: String,
+    // Flat fees in base units or basis points, as set by FeeRate::to_columns
+    pub escrow_fee: i64,
+    pub escrow_fee_basis_points: i32,
+    pub escrow_dispute_fee: i64,
+    pub escrow_dispute_fee_basis_points: i32,
+    pub funding_transaction_id: Option<String>,
+    pub funding_amount: Option<i64>,
+    pub milestone_amount: Option<i64>,
+    pub change_public_key: Option<String>,
+    pub checkout_id: Option<String>,
+    pub payment_output_index: i32,
+    pub batch_id: Option<i32>,
+    pub merchant_id: i32,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+    // Hex of the SCALE encoded transaction the signing session signs
+    pub encoded_settlement_transaction: Option<String>,
+    // Network fee in base units the transaction of the signing session pays
+    pub settlement_network_fee: Option<i64>,
+}
+#[derive(Debug, Insertable)]
+#[table_name = "orders"]
+pub struct NewOrder {
+    pub order_id: String,
+    pub status: OrderStatus,
+    pub wallet_name: String,
+    pub amount: i64,
+    pub buyer_public_key: String,
+    pub buyer_view_key: String,
+    pub buyer_address: String,
+    pub escrow_public_key: String,
+    pub escrow_view_key: String,
+    pub extra_view_keys: String,
+    pub required_signatures: i32,
+    pub merchant_public_key: String,
+    pub payout_address: String,
+    pub escrow_id: Option<i32>,
+    pub escrow_fee_address: String,
+    pub escrow_fee: i64,
+    pub escrow_fee_basis_points: i32,
+    pub escrow_dispute_fee: i64,
+    pub escrow_dispute_fee_basis_points: i32,
+    pub merchant_id: i32,
+}
+/// Format of the timestamps of the API and the cursors, in UTC and with the fraction of a second
+/// only when there is one
+pub const TIMESTAMP_FORMAT: &str = "%Y-%m-%d %H:%M:%S%.f";
+/// Deposit in base units of CRO paid back to the buyer when an order is delivered
+pub const BUYER_DEPOSIT: u64 = 10 * 1_0000_0000;
+/// Coin of an amount column, stored in base units
+pub fn to_coin(amount: i64) -> Coin {
+    Coin::new(amount as u64).unwrap()
+}
+/// Base units of a coin, as stored in an amount column
+pub fn to_base_units(amount: Coin) -> i64 {
+    u64::from(amount) as i64
+}
+/// Base units of an amount given as text, `None` for an empty or invalid one
+pub fn parse_base_units(amount: &str) -> Option<i64> {
+    Coin::from_str(amount).ok().map(to_base_units)
 }
 #[derive(Debug, Serialize, Deserialize, AsExpression, FromSqlRow, PartialEq, Clone, Copy)]
 #[sql_type = "Text"]
@@ -34,6 +112,31 @@ pub enum OrderStatus {
     Refunding,
     Completed,
     Refunded,
+    Splitting,
+    Split,
+    Disputed,
+    ReleasingMilestone,
+    RefundPendingApproval,
+    SplitPendingApproval,
+}
+impl OrderStatus {
+    /// Whether a settlement transaction of the order is being signed
+    pub fn is_settling(self) -> bool {
+        match self {
+            OrderStatus::Delivering
+            | OrderStatus::Refunding
+            | OrderStatus::Splitting
+            | OrderStatus::ReleasingMilestone => true,
+            _ => false,
+        }
+    }
+    /// Whether the settlement transaction of the order has been broadcast
+    pub fn is_settled(self) -> bool {
+        match self {
+            OrderStatus::Completed | OrderStatus::Refunded | OrderStatus::Split => true,
+            _ => false,
+        }
+    }
 }
 impl<DB: Backend> ToSql<Text, DB> for OrderStatus
 where
@@ -50,6 +153,12 @@ where
             OrderStatus::Refunding => String::from("Refunding"),
             OrderStatus::Completed => String::from("Completed"),
             OrderStatus::Refunded => String::from("Refunded"),
+            OrderStatus::Splitting => String::from("Splitting"),
+            OrderStatus::Split => String::from("Split"),
+            OrderStatus::Disputed => String::from("Disputed"),
+            OrderStatus::ReleasingMilestone => String::from("ReleasingMilestone"),
+            OrderStatus::RefundPendingApproval => String::from("RefundPendingApproval"),
+            OrderStatus::SplitPendingApproval => String::from("SplitPendingApproval"),
         };
         v.to_sql(out)
     }
@@ -67,10 +176,707 @@ where
             "Refunding" => OrderStatus::Refunding,
             "Completed" => OrderStatus::Completed,
             "Refunded" => OrderStatus::Refunded,
+            "Splitting" => OrderStatus::Splitting,
+            "Split" => OrderStatus::Split,
+            "Disputed" => OrderStatus::Disputed,
+            "ReleasingMilestone" => OrderStatus::ReleasingMilestone,
+            "RefundPendingApproval" => OrderStatus::RefundPendingApproval,
+            "SplitPendingApproval" => OrderStatus::SplitPendingApproval,
             _ => return Err("Unsupported order status".into()),
         })
     }
 }
+#[derive(Debug, Clone, Serialize, Queryable)]
+pub struct OrderParticipant {
+    pub id: i32,
+    pub order_id: String,
+    pub role: ParticipantRole,
+    pub public_key: String,
+    pub view_key: String,
+}
+#[derive(Debug, Insertable)]
+#[table_name = "order_participants"]
+pub struct NewOrderParticipant {
+    pub order_id: String,
+    pub role: ParticipantRole,
+    pub public_key: String,
+    pub view_key: String,
+}
+#[derive(Debug, Serialize, Deserialize, AsExpression, FromSqlRow, PartialEq, Clone, Copy)]
+#[sql_type = "Text"]
+pub enum ParticipantRole {
+    Merchant,
+    Buyer,
+    Escrow,
+    Operator,
+}
+impl<DB: Backend> ToSql<Text, DB> for ParticipantRole
+where
+    String: ToSql<Text, DB>,
+{
+    fn to_sql<W>(&self, out: &mut Output<W, DB>) -> serialize::Result
+    where
+        W: io::Write,
+    {
+        let v = match *self {
+            ParticipantRole::Merchant => String::from("Merchant"),
+            ParticipantRole::Buyer => String::from("Buyer"),
+            ParticipantRole::Escrow => String::from("Escrow"),
+            ParticipantRole::Operator => String::from("Operator"),
+        };
+        v.to_sql(out)
+    }
+}
+impl<DB: Backend> FromSql<Text, DB> for ParticipantRole
+where
+    String: FromSql<Text, DB>,
+{
+    fn from_sql(bytes: Option<&DB::RawValue>) -> deserialize::Result<Self> {
+        let v = String::from_sql(bytes)?;
+        v.parse().map_err(|_| "Unsupported participant role".into())
+    }
+}
+impl FromStr for ParticipantRole {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "Merchant" => ParticipantRole::Merchant,
+            "Buyer" => ParticipantRole::Buyer,
+            "Escrow" => ParticipantRole::Escrow,
+            "Operator" => ParticipantRole::Operator,
+            _ => return Err(()),
+        })
+    }
+}
+impl fmt::Display for ParticipantRole {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ParticipantRole::Merchant => write!(f, "merchant"),
+            ParticipantRole::Buyer => write!(f, "buyer"),
+            ParticipantRole::Escrow => write!(f, "escrow"),
+            ParticipantRole::Operator => write!(f, "operator"),
+        }
+    }
+}
+#[derive(Debug, Serialize, Queryable)]
+pub struct SessionSigner {
+    pub id: i32,
+    pub session_id: String,
+    pub order_id: String,
+    pub role: ParticipantRole,
+    pub public_key: String,
+    pub commitment: String,
+    pub nonce: String,
+    pub partial_signature: String,
+}
+#[derive(Debug, Insertable)]
+#[table_name = "session_signers"]
+pub struct NewSessionSigner {
+    pub session_id: String,
+    pub order_id: String,
+    pub role: ParticipantRole,
+    pub public_key: String,
+}
+#[derive(Debug, Serialize, Queryable)]
+pub struct OrderEvent {
+    pub id: i32,
+    pub order_id: String,
+    pub event: OrderEventKind,
+    pub detail: String,
+    pub created_at: String,
+}
+#[derive(Debug, Insertable)]
+#[table_name = "order_events"]
+pub struct NewOrderEvent {
+    pub order_id: String,
+    pub event: OrderEventKind,
+    pub detail: String,
+}
+#[derive(Debug, Serialize, Deserialize, AsExpression, FromSqlRow, PartialEq, Clone, Copy)]
+#[sql_type = "Text"]
+pub enum OrderEventKind {
+    SignatureRejected,
+    RefundAddressChanged,
+    Escalated,
+    Ruled,
+    MilestoneReleased,
+}
+impl<DB: Backend> ToSql<Text, DB> for OrderEventKind
+where
+    String: ToSql<Text, DB>,
+{
+    fn to_sql<W>(&self, out: &mut Output<W, DB>) -> serialize::Result
+    where
+        W: io::Write,
+    {
+        let v = match *self {
+            OrderEventKind::SignatureRejected => String::from("SignatureRejected"),
+            OrderEventKind::RefundAddressChanged => String::from("RefundAddressChanged"),
+            OrderEventKind::Escalated => String::from("Escalated"),
+            OrderEventKind::Ruled => String::from("Ruled"),
+            OrderEventKind::MilestoneReleased => String::from("MilestoneReleased"),
+        };
+        v.to_sql(out)
+    }
+}
+impl<DB: Backend> FromSql<Text, DB> for OrderEventKind
+where
+    String: FromSql<Text, DB>,
+{
+    fn from_sql(bytes: Option<&DB::RawValue>) -> deserialize::Result<Self> {
+        let v = String::from_sql(bytes)?;
+        Ok(match &v[..] {
+            "SignatureRejected" => OrderEventKind::SignatureRejected,
+            "RefundAddressChanged" => OrderEventKind::RefundAddressChanged,
+            "Escalated" => OrderEventKind::Escalated,
+            "Ruled" => OrderEventKind::Ruled,
+            "MilestoneReleased" => OrderEventKind::MilestoneReleased,
+            _ => return Err("Unsupported order event".into()),
+        })
+    }
+}
+#[derive(Debug, Serialize, Queryable)]
+pub struct RefundApproval {
+    pub id: i32,
+    pub order_id: String,
+    pub staff_name: String,
+    pub created_at: String,
+}
+#[derive(Debug, Insertable)]
+#[table_name = "refund_approvals"]
+pub struct NewRefundApproval {
+    pub order_id: String,
+    pub staff_name: String,
+}
+#[derive(Debug, Clone, Serialize, Queryable)]
+pub struct Merchant {
+    pub id: i32,
+    pub name: String,
+    pub wallet_name: String,
+    pub payout_address: String,
+    pub treasury_address: String,
+    pub created_at: String,
+    // Policies, unset when empty or None
+    pub auditor_view_keys: String,
+    pub escrow_access_on_refund: Option<bool>,
+    pub platform_fee_address: String,
+    pub platform_fee: i64,
+    pub platform_fee_basis_points: i32,
+    pub platform_dispute_fee: i64,
+    pub platform_dispute_fee_basis_points: i32,
+    pub refund_approval_threshold: Option<i64>,
+    pub require_registered_escrow: Option<bool>,
+}
+#[derive(Debug, Insertable)]
+#[table_name = "merchants"]
+pub struct NewMerchant {
+    pub name: String,
+    pub wallet_name: String,
+    pub payout_address: String,
+    pub treasury_address: String,
+}
+/// Policies of a merchant, unset when empty or `None`
+#[derive(Debug, AsChangeset)]
+#[table_name = "merchants"]
+#[changeset_options(treat_none_as_null = "true")]
+pub struct MerchantPolicies {
+    // Comma separated view keys added to every settlement transaction
+    pub auditor_view_keys: String,
+    pub escrow_access_on_refund: Option<bool>,
+    // Flat fees in base units or basis points, as set by FeeRate::to_columns
+    pub platform_fee_address: String,
+    pub platform_fee: i64,
+    pub platform_fee_basis_points: i32,
+    pub platform_dispute_fee: i64,
+    pub platform_dispute_fee_basis_points: i32,
+    pub refund_approval_threshold: Option<i64>,
+    pub require_registered_escrow: Option<bool>,
+}
+#[derive(Debug, Queryable)]
+pub struct MerchantApiKey {
+    pub id: i32,
+    pub merchant_id: i32,
+    pub name: String,
+    pub key_digest: String,
+    pub created_at: String,
+}
+#[derive(Debug, Insertable)]
+#[table_name = "merchant_api_keys"]
+pub struct NewMerchantApiKey {
+    pub merchant_id: i32,
+    pub name: String,
+    pub key_digest: String,
+}
+#[derive(Debug, Queryable, Insertable)]
+#[table_name = "auth_challenges"]
+pub struct AuthChallenge {
+    pub nonce: String,
+    pub public_key: String,
+    pub expires_at: i64,
+}
+#[derive(Debug, Queryable)]
+pub struct AuthSession {
+    pub token: String,
+    pub public_key: String,
+    pub expires_at: i64,
+    pub created_at: String,
+}
+#[derive(Debug, Insertable)]
+#[table_name = "auth_sessions"]
+pub struct NewAuthSession {
+    pub token: String,
+    pub public_key: String,
+    pub expires_at: i64,
+}
+#[derive(Debug, Serialize, Queryable)]
+pub struct Checkout {
+    pub checkout_id: String,
+    pub status: CheckoutStatus,
+    pub payment_transaction_id: String,
+    pub created_at: String,
+}
+#[derive(Debug, Insertable)]
+#[table_name = "checkouts"]
+pub struct NewCheckout {
+    pub checkout_id: String,
+    pub status: CheckoutStatus,
+}
+#[derive(Debug, Serialize, Deserialize, AsExpression, FromSqlRow, PartialEq, Clone, Copy)]
+#[sql_type = "Text"]
+pub enum CheckoutStatus {
+    PendingPayment,
+    Paid,
+}
+impl<DB: Backend> ToSql<Text, DB> for CheckoutStatus
+where
+    String: ToSql<Text, DB>,
+{
+    fn to_sql<W>(&self, out: &mut Output<W, DB>) -> serialize::Result
+    where
+        W: io::Write,
+    {
+        let v = match *self {
+            CheckoutStatus::PendingPayment => String::from("PendingPayment"),
+            CheckoutStatus::Paid => String::from("Paid"),
+        };
+        v.to_sql(out)
+    }
+}
+impl<DB: Backend> FromSql<Text, DB> for CheckoutStatus
+where
+    String: FromSql<Text, DB>,
+{
+    fn from_sql(bytes: Option<&DB::RawValue>) -> deserialize::Result<Self> {
+        let v = String::from_sql(bytes)?;
+        Ok(match &v[..] {
+            "PendingPayment" => CheckoutStatus::PendingPayment,
+            "Paid" => CheckoutStatus::Paid,
+            _ => return Err("Unsupported checkout status".into()),
+        })
+    }
+}
+#[derive(Debug, Serialize, Queryable)]
+pub struct SettlementBatch {
+    pub id: i32,
+    pub status: BatchStatus,
+    pub transaction_id: String,
+    pub encoded_transaction: String,
+    pub created_at: String,
+    pub merchant_id: i32,
+    // Network fee in base units the batch transaction pays
+    pub network_fee: Option<i64>,
+}
+#[derive(Debug, Insertable)]
+#[table_name = "settlement_batches"]
+pub struct NewSettlementBatch {
+    pub status: BatchStatus,
+    pub transaction_id: String,
+    pub encoded_transaction: String,
+    pub merchant_id: i32,
+    pub network_fee: Option<i64>,
+}
+#[derive(Debug, Serialize, Deserialize, AsExpression, FromSqlRow, PartialEq, Clone, Copy)]
+#[sql_type = "Text"]
+pub enum BatchStatus {
+    Signing,
+    Broadcast,
+    Cancelled,
+}
+impl<DB: Backend> ToSql<Text, DB> for BatchStatus
+where
+    String: ToSql<Text, DB>,
+{
+    fn to_sql<W>(&self, out: &mut Output<W, DB>) -> serialize::Result
+    where
+        W: io::Write,
+    {
+        let v = match *self {
+            BatchStatus::Signing => String::from("Signing"),
+            BatchStatus::Broadcast => String::from("Broadcast"),
+            BatchStatus::Cancelled => String::from("Cancelled"),
+        };
+        v.to_sql(out)
+    }
+}
+impl<DB: Backend> FromSql<Text, DB> for BatchStatus
+where
+    String: FromSql<Text, DB>,
+{
+    fn from_sql(bytes: Option<&DB::RawValue>) -> deserialize::Result<Self> {
+        let v = String::from_sql(bytes)?;
+        Ok(match &v[..] {
+            "Signing" => BatchStatus::Signing,
+            "Broadcast" => BatchStatus::Broadcast,
+            "Cancelled" => BatchStatus::Cancelled,
+            _ => return Err("Unsupported batch status".into()),
+        })
+    }
+}
+/// Batch with the orders it settles, their co-signers and the signers of their signing sessions
+pub struct BatchSettlement {
+    pub batch: SettlementBatch,
+    pub orders: Vec<Order>,
+    pub participants: Vec<OrderParticipant>,
+    pub signers: Vec<SessionSigner>,
+}
+#[derive(Debug, Serialize, Queryable)]
+pub struct Milestone {
+    pub id: i32,
+    pub order_id: String,
+    pub sequence: i32,
+    pub amount: i64,
+    pub status: MilestoneStatus,
+    pub settlement_transaction_id: String,
+    pub created_at: String,
+}
+#[derive(Debug, Insertable)]
+#[table_name = "milestones"]
+pub struct NewMilestone {
+    pub order_id: String,
+    pub sequence: i32,
+    pub amount: i64,
+    pub status: MilestoneStatus,
+}
+#[derive(Debug, Serialize, Deserialize, AsExpression, FromSqlRow, PartialEq, Clone, Copy)]
+#[sql_type = "Text"]
+pub enum MilestoneStatus {
+    Pending,
+    Released,
+}
+impl<DB: Backend> ToSql<Text, DB> for MilestoneStatus
+where
+    String: ToSql<Text, DB>,
+{
+    fn to_sql<W>(&self, out: &mut Output<W, DB>) -> serialize::Result
+    where
+        W: io::Write,
+    {
+        let v = match *self {
+            MilestoneStatus::Pending => String::from("Pending"),
+            MilestoneStatus::Released => String::from("Released"),
+        };
+        v.to_sql(out)
+    }
+}
+impl<DB: Backend> FromSql<Text, DB> for MilestoneStatus
+where
+    String: FromSql<Text, DB>,
+{
+    fn from_sql(bytes: Option<&DB::RawValue>) -> deserialize::Result<Self> {
+        let v = String::from_sql(bytes)?;
+        Ok(match &v[..] {
+            "Pending" => MilestoneStatus::Pending,
+            "Released" => MilestoneStatus::Released,
+            _ => return Err("Unsupported milestone status".into()),
+        })
+    }
+}
+#[derive(Debug, Serialize, Queryable)]
+pub struct SplitProposal {
+    pub id: i32,
+    pub order_id: String,
+    pub proposed_by: ParticipantRole,
+    pub merchant_amount: i64,
+    pub buyer_amount: i64,
+    pub signature: String,
+    pub status: SplitProposalStatus,
+    pub acceptance_signature: String,
+    pub created_at: String,
+}
+#[derive(Debug, Insertable)]
+#[table_name = "split_proposals"]
+pub struct NewSplitProposal {
+    pub order_id: String,
+    pub proposed_by: ParticipantRole,
+    pub merchant_amount: i64,
+    pub buyer_amount: i64,
+    pub signature: String,
+    pub status: SplitProposalStatus,
+}
+#[derive(Debug, Serialize, Deserialize, AsExpression, FromSqlRow, PartialEq, Clone, Copy)]
+#[sql_type = "Text"]
+pub enum SplitProposalStatus {
+    Proposed,
+    Accepted,
+    Superseded,
+}
+impl<DB: Backend> ToSql<Text, DB> for SplitProposalStatus
+where
+    String: ToSql<Text, DB>,
+{
+    fn to_sql<W>(&self, out: &mut Output<W, DB>) -> serialize::Result
+    where
+        W: io::Write,
+    {
+        let v = match *self {
+            SplitProposalStatus::Proposed => String::from("Proposed"),
+            SplitProposalStatus::Accepted => String::from("Accepted"),
+            SplitProposalStatus::Superseded => String::from("Superseded"),
+        };
+        v.to_sql(out)
+    }
+}
+impl<DB: Backend> FromSql<Text, DB> for SplitProposalStatus
+where
+    String: FromSql<Text, DB>,
+{
+    fn from_sql(bytes: Option<&DB::RawValue>) -> deserialize::Result<Self> {
+        let v = String::from_sql(bytes)?;
+        Ok(match &v[..] {
+            "Proposed" => SplitProposalStatus::Proposed,
+            "Accepted" => SplitProposalStatus::Accepted,
+            "Superseded" => SplitProposalStatus::Superseded,
+            _ => return Err("Unsupported split proposal status".into()),
+        })
+    }
+}
+#[derive(Debug, Serialize, Queryable)]
+pub struct Ruling {
+    pub id: i32,
+    pub order_id: String,
+    pub escrow_public_key: String,
+    pub decision: RulingDecision,
+    pub merchant_amount: String,
+    pub buyer_amount: String,
+    pub signature: String,
+    pub created_at: String,
+    pub dispute_id: Option<i32>,
+}
+#[derive(Debug, Insertable)]
+#[table_name = "rulings"]
+pub struct NewRuling {
+    pub order_id: String,
+    pub escrow_public_key: String,
+    pub decision: RulingDecision,
+    pub merchant_amount: String,
+    pub buyer_amount: String,
+    pub signature: String,
+    pub dispute_id: Option<i32>,
+}
+#[derive(Debug, Serialize, Deserialize, AsExpression, FromSqlRow, PartialEq, Clone, Copy)]
+#[sql_type = "Text"]
+pub enum RulingDecision {
+    Release,
+    Refund,
+    Split,
+}
+impl<DB: Backend> ToSql<Text, DB> for RulingDecision
+where
+    String: ToSql<Text, DB>,
+{
+    fn to_sql<W>(&self, out: &mut Output<W, DB>) -> serialize::Result
+    where
+        W: io::Write,
+    {
+        let v = match *self {
+            RulingDecision::Release => String::from("Release"),
+            RulingDecision::Refund => String::from("Refund"),
+            RulingDecision::Split => String::from("Split"),
+        };
+        v.to_sql(out)
+    }
+}
+impl<DB: Backend> FromSql<Text, DB> for RulingDecision
+where
+    String: FromSql<Text, DB>,
+{
+    fn from_sql(bytes: Option<&DB::RawValue>) -> deserialize::Result<Self> {
+        let v = String::from_sql(bytes)?;
+        v.parse().map_err(|_| "Unsupported ruling decision".into())
+    }
+}
+impl FromStr for RulingDecision {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "Release" => RulingDecision::Release,
+            "Refund" => RulingDecision::Refund,
+            "Split" => RulingDecision::Split,
+            _ => return Err(()),
+        })
+    }
+}
+#[derive(Debug, Serialize, Queryable)]
+pub struct Escrow {
+    pub id: i32,
+    pub name: String,
+    pub public_key: String,
+    pub view_key: String,
+    pub fee_address: String,
+    pub fee: i64,
+    pub fee_basis_points: i32,
+    pub dispute_fee: i64,
+    pub dispute_fee_basis_points: i32,
+    pub status: EscrowStatus,
+    pub created_at: String,
+}
+#[derive(Debug, Insertable)]
+#[table_name = "escrows"]
+pub struct NewEscrow {
+    pub name: String,
+    pub public_key: String,
+    pub view_key: String,
+    pub fee_address: String,
+    pub fee: i64,
+    pub fee_basis_points: i32,
+    pub dispute_fee: i64,
+    pub dispute_fee_basis_points: i32,
+    pub status: EscrowStatus,
+}
+#[derive(Debug, Serialize, Deserialize, AsExpression, FromSqlRow, PartialEq, Clone, Copy)]
+#[sql_type = "Text"]
+pub enum EscrowStatus {
+    Active,
+    Revoked,
+}
+impl<DB: Backend> ToSql<Text, DB> for EscrowStatus
+where
+    String: ToSql<Text, DB>,
+{
+    fn to_sql<W>(&self, out: &mut Output<W, DB>) -> serialize::Result
+    where
+        W: io::Write,
+    {
+        let v = match *self {
+            EscrowStatus::Active => String::from("Active"),
+            EscrowStatus::Revoked => String::from("Revoked"),
+        };
+        v.to_sql(out)
+    }
+}
+impl<DB: Backend> FromSql<Text, DB> for EscrowStatus
+where
+    String: FromSql<Text, DB>,
+{
+    fn from_sql(bytes: Option<&DB::RawValue>) -> deserialize::Result<Self> {
+        let v = String::from_sql(bytes)?;
+        Ok(match &v[..] {
+            "Active" => EscrowStatus::Active,
+            "Revoked" => EscrowStatus::Revoked,
+            _ => return Err("Unsupported escrow status".into()),
+        })
+    }
+}
+#[derive(Debug, Serialize, Queryable)]
+pub struct DisputeCase {
+    pub id: i32,
+    pub order_id: String,
+    pub opened_by: ParticipantRole,
+    pub status: DisputeStatus,
+    pub created_at: String,
+}
+#[derive(Debug, Insertable)]
+#[table_name = "dispute_cases"]
+pub struct NewDisputeCase {
+    pub order_id: String,
+    pub opened_by: ParticipantRole,
+    pub status: DisputeStatus,
+}
+#[derive(Debug, Serialize, Deserialize, AsExpression, FromSqlRow, PartialEq, Clone, Copy)]
+#[sql_type = "Text"]
+pub enum DisputeStatus {
+    Open,
+    AwaitingResponse,
+    Ruled,
+    Closed,
+}
+impl<DB: Backend> ToSql<Text, DB> for DisputeStatus
+where
+    String: ToSql<Text, DB>,
+{
+    fn to_sql<W>(&self, out: &mut Output<W, DB>) -> serialize::Result
+    where
+        W: io::Write,
+    {
+        let v = match *self {
+            DisputeStatus::Open => String::from("Open"),
+            DisputeStatus::AwaitingResponse => String::from("AwaitingResponse"),
+            DisputeStatus::Ruled => String::from("Ruled"),
+            DisputeStatus::Closed => String::from("Closed"),
+        };
+        v.to_sql(out)
+    }
+}
+impl<DB: Backend> FromSql<Text, DB> for DisputeStatus
+where
+    String: FromSql<Text, DB>,
+{
+    fn from_sql(bytes: Option<&DB::RawValue>) -> deserialize::Result<Self> {
+        let v = String::from_sql(bytes)?;
+        v.parse().map_err(|_| "Unsupported dispute status".into())
+    }
+}
+impl FromStr for DisputeStatus {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "Open" => DisputeStatus::Open,
+            "AwaitingResponse" => DisputeStatus::AwaitingResponse,
+            "Ruled" => DisputeStatus::Ruled,
+            "Closed" => DisputeStatus::Closed,
+            _ => return Err(()),
+        })
+    }
+}
+#[derive(Debug, Serialize, Queryable)]
+pub struct DisputeEvidence {
+    pub id: i32,
+    pub case_id: i32,
+    pub submitted_by: ParticipantRole,
+    pub file_name: String,
+    pub digest: String,
+    pub size: i32,
+    pub created_at: String,
+}
+#[derive(Debug, Insertable)]
+#[table_name = "dispute_evidence"]
+pub struct NewDisputeEvidence {
+    pub case_id: i32,
+    pub submitted_by: ParticipantRole,
+    pub file_name: String,
+    pub digest: String,
+    pub size: i32,
+}
+#[derive(Debug, Serialize, Queryable)]
+pub struct DisputeMessage {
+    pub id: i32,
+    pub case_id: i32,
+    pub reply_to: Option<i32>,
+    pub author: ParticipantRole,
+    pub body: String,
+    pub created_at: String,
+}
+#[derive(Debug, Insertable)]
+#[table_name = "dispute_messages"]
+pub struct NewDisputeMessage {
+    pub case_id: i32,
+    pub reply_to: Option<i32>,
+    pub author: ParticipantRole,
+    pub body: String,
+}
 #[derive(Deserialize)]
 pub struct NewOrderRequest {
     pub order_id: String,
@@ -78,8 +884,18 @@ pub struct NewOrderRequest {
     pub buyer_public_key: String,
     pub buyer_view_key: String,
     pub buyer_address: String,
-    pub escrow_public_key: String,
-    pub escrow_view_key: String,
+    pub escrow_public_key: Option<String>,
+    pub escrow_view_key: Option<String>,
+    pub extra_view_keys: Option<String>,
+    // Additional co-signers as comma separated `Role:public_key:view_key` entries
+    pub participants: Option<String>,
+    pub required_signatures: Option<i32>,
+    // Address receiving the merchant share of the settlement
+    pub payout_address: Option<String>,
+    // Escrow of the registry, in place of escrow_public_key and escrow_view_key
+    pub escrow_id: Option<i32>,
+    // Comma separated amounts in base units released one after the other, adding up to the amount
+    pub milestones: Option<String>,
 }
 #[derive(Serialize)]
 pub struct NewOrderResponse {
@@ -90,18 +906,250 @@ pub struct NewOrderResponse {
 }
 #[derive(Deserialize)]
 pub struct PaymentProof {
+    // Either the order paid or the checkout paying for several orders at once
+    pub order_id: Option<String>,
+    pub checkout_id: Option<String>,
+    pub transaction_id: String,
+}
+#[derive(Serialize)]
+pub struct CheckoutPaidResponse {
+    pub checkout_id: String,
+    pub order_ids: Vec<String>,
+}
+#[derive(Deserialize)]
+pub struct AuthChallengeRequest {
+    pub public_key: String,
+}
+#[derive(Serialize)]
+pub struct AuthChallengeResponse {
+    pub nonce: String,
+    // Message to sign with the key, see `challenge_message`
+    pub message: String,
+    pub expires_at: i64,
+}
+#[derive(Deserialize)]
+pub struct AuthVerifyRequest {
+    pub public_key: String,
+    pub nonce: String,
+    // Signature over the challenge message
+    pub signature: String,
+}
+#[derive(Serialize)]
+pub struct AuthSessionResponse {
+    // Bearer token of the session
+    pub token: String,
+    pub public_key: String,
+    pub expires_at: i64,
+}
+#[derive(Deserialize)]
+pub struct NewCheckoutRequest {
+    pub checkout_id: String,
+    // Comma separated IDs of the orders paid by the checkout
+    pub order_ids: String,
+}
+#[derive(Deserialize)]
+pub struct CheckoutRequest {
+    pub checkout_id: String,
+}
+#[derive(Serialize)]
+pub struct CheckoutOrderResponse {
     pub order_id: String,
+    pub status: OrderStatus,
+    pub amount: String,
+    pub multisig_address: String,
+    pub payment_output_index: i32,
+}
+#[derive(Serialize)]
+pub struct CheckoutResponse {
+    pub checkout_id: String,
+    pub status: CheckoutStatus,
+    // Total of the orders in base units
+    pub amount: String,
+    pub payment_transaction_id: String,
+    pub orders: Vec<CheckoutOrderResponse>,
+}
+#[derive(Deserialize)]
+pub struct RefundAddressRequest {
+    pub order_id: String,
+    pub refund_address: String,
+    // Buyer signature over the refund address change, see `refund_address_message`
+    pub signature: String,
+}
+#[derive(Deserialize)]
+pub struct ProposeSplitRequest {
+    pub order_id: String,
+    // Merchant or Buyer
+    pub proposed_by: String,
+    pub merchant_amount: String,
+    pub buyer_amount: String,
+    // Buyer signature over the split, see `split_message`
+    pub signature: Option<String>,
+}
+#[derive(Deserialize)]
+pub struct AcceptSplitRequest {
+    pub order_id: String,
+    // Proposal being accepted, returned to its proposer as split_id
+    pub split_id: i32,
+    // Merchant or Buyer, the counterparty of the proposal
+    pub accepted_by: String,
+    // Buyer signature over the split, see `split_message`
+    pub signature: Option<String>,
+}
+#[derive(Serialize)]
+pub struct SplitProposalResponse {
+    pub order_id: String,
+    pub split_id: i32,
+    pub proposed_by: ParticipantRole,
+    pub merchant_amount: String,
+    pub buyer_amount: String,
+}
+#[derive(Deserialize)]
+pub struct RulingRequest {
+    pub order_id: String,
+    // Dispute case opened by the escalation of the order
+    pub dispute_id: i32,
+    // Release, Refund or Split
+    pub decision: String,
+    // Shares of a Split ruling in base units
+    pub merchant_amount: Option<String>,
+    pub buyer_amount: Option<String>,
+    // Escrow signature over the ruling, see `ruling_message`
+    pub signature: String,
+}
+#[derive(Serialize)]
+pub struct RulingResponse {
+    pub order_id: String,
+    pub ruling_id: i32,
+    pub dispute_id: Option<i32>,
+    pub decision: RulingDecision,
+    pub merchant_amount: String,
+    pub buyer_amount: String,
+    pub status: OrderStatus,
+}
+#[derive(Deserialize)]
+pub struct OpenDisputeRequest {
+    pub order_id: String,
+    // Merchant, Buyer, Escrow or Operator
+    pub opened_by: String,
+    // First message of the case
+    pub message: Option<String>,
+}
+#[derive(Deserialize)]
+pub struct DisputeRequest {
+    pub case_id: i32,
+}
+#[derive(Deserialize)]
+pub struct DisputeMessageRequest {
+    pub case_id: i32,
+    pub author: String,
+    pub body: String,
+    // Message answered by this one, the message starts a new thread if not given
+    pub reply_to: Option<i32>,
+}
+#[derive(Deserialize)]
+pub struct DisputeEvidenceRequest {
+    pub case_id: i32,
+    pub submitted_by: String,
+    pub file_name: String,
+}
+#[derive(Deserialize)]
+pub struct EvidenceFileRequest {
+    pub digest: String,
+}
+#[derive(Deserialize)]
+pub struct DisputeStatusRequest {
+    pub case_id: i32,
+    // Open, AwaitingResponse or Closed, Ruled is set by the escrow ruling
+    pub status: String,
+}
+#[derive(Serialize)]
+pub struct DisputeCaseResponse {
+    pub id: i32,
+    pub order_id: String,
+    pub opened_by: ParticipantRole,
+    pub status: DisputeStatus,
+    pub created_at: String,
+    pub evidence: Vec<DisputeEvidence>,
+    pub messages: Vec<DisputeMessage>,
+}
+#[derive(Serialize)]
+pub struct MilestoneReleaseResponse {
+    pub order_id: String,
+    pub milestone_id: i32,
+    pub sequence: i32,
+    pub amount: String,
+    // Multi-sig address receiving the funds of the remaining milestones, empty for the last one
+    pub change_address: String,
+}
+#[derive(Deserialize)]
+pub struct NewBatchRequest {
+    // Comma separated IDs of the orders settled by the batch
+    pub order_ids: String,
+}
+#[derive(Deserialize)]
+pub struct BatchRequest {
+    pub batch_id: i32,
+}
+#[derive(Serialize)]
+pub struct BatchResponse {
+    pub batch_id: i32,
+    pub status: BatchStatus,
     pub transaction_id: String,
+    pub order_ids: Vec<String>,
+    pub outputs: Vec<SettlementOutput>,
+    /// Network fee in base units, unknown for the batches created before it was stored
+    pub network_fee: Option<String>,
 }
 #[derive(Deserialize)]
 pub struct OrderRequest {
     pub order_id: String,
 }
+#[derive(Deserialize)]
+pub struct OrderQueryRequest {
+    pub status: Option<String>,
+    pub created_from: Option<String>,
+    pub created_to: Option<String>,
+    pub min_amount: Option<String>,
+    pub max_amount: Option<String>,
+    pub buyer: Option<String>,
+    pub sort: Option<String>,
+    pub cursor: Option<String>,
+    pub limit: Option<String>,
+}
+#[derive(Serialize)]
+pub struct OrderPageResponse {
+    pub orders: Vec<OrderResponse>,
+    // Cursor of the next page, null on the last page
+    pub next_cursor: Option<String>,
+}
+#[derive(Serialize)]
+pub struct FieldError {
+    pub field: &'static str,
+    pub reason: &'static str,
+}
+#[derive(Serialize)]
+pub struct ValidationErrorResponse {
+    // Every failing field of the request
+    pub errors: Vec<FieldError>,
+}
 #[derive(Serialize)]
 pub struct OrderUpdatedResponse {
     pub order_id: String,
 }
 #[derive(Serialize)]
+pub struct EscalationResponse {
+    pub order_id: String,
+    pub dispute_id: i32,
+}
+#[derive(Serialize)]
+pub struct RefundApprovalResponse {
+    pub order_id: String,
+    pub status: OrderStatus,
+    // Staff accounts which signed off the refund, the one requesting it first
+    pub approved_by: Vec<String>,
+    pub required_approvals: usize,
+}
+#[derive(Serialize)]
 pub struct OrderResponse {
     pub order_id: String,
     pub amount: String,
@@ -114,13 +1162,61 @@ pub struct OrderResponse {
     pub session_id: String,
     pub payment_transaction_id: String,
     pub settlement_transaction_id: String,
+    pub extra_view_keys: String,
+    pub required_signatures: i32,
+    pub merchant_public_key: String,
+    pub payout_address: String,
+    pub refund_address: String,
+    pub split_merchant_amount: String,
+    pub split_buyer_amount: String,
+    pub arbitrated: bool,
+    pub escrow_id: Option<i32>,
+    pub funding_transaction_id: String,
+    pub funding_amount: String,
+    pub milestone_amount: String,
+    pub checkout_id: String,
+    pub payment_output_index: i32,
+    pub batch_id: Option<i32>,
+    pub merchant_id: i32,
+    pub created_at: String,
+    pub updated_at: String,
+    // Only returned with a single order
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub participants: Vec<OrderParticipant>,
     // pub nonce_commitment: String,
     // pub nonce: String,
 }
+#[derive(Serialize)]
+pub struct SettlementOutput {
+    pub address: String,
+    pub value: String,
+}
+#[derive(Serialize)]
+pub struct SettlementAccessPolicy {
+    pub view_key: String,
+    pub access: String,
+}
+#[derive(Serialize)]
+pub struct SettlementPreviewResponse {
+    pub order_id: String,
+    pub status: OrderStatus,
+    pub transaction_id: String,
+    pub outputs: Vec<SettlementOutput>,
+    pub access_policies: Vec<SettlementAccessPolicy>,
+    /// Hex of the SCALE encoded transaction, the transaction ID is its blake2s hash
+    pub encoded_transaction: String,
+    pub transaction: Tx,
+    /// Network fee in base units, unknown for the sessions started before it was stored
+    pub network_fee: Option<String>,
+}
 #[derive(Deserialize)]
 pub struct ExchangeCommitmentRequest {
     pub order_id: String,
     pub commitment: String,
+    // Co-signer submitting the commitment, the buyer if not given
+    pub public_key: Option<String>,
+    // Comma separated public keys of the co-signers, only used by the call starting the session
+    pub signers: Option<String>,
 }
 #[derive(Serialize)]
 pub struct ExchangeCommitmentResponse {
@@ -131,9 +1227,31 @@ pub struct ExchangeCommitmentResponse {
     pub transaction: Tx,
 }
 #[derive(Deserialize)]
-pub struct ConfirmRequest {
+pub struct ExchangeNonceRequest {
     pub order_id: String,
+    pub public_key: Option<String>,
     pub nonce: String,
+}
+#[derive(Serialize)]
+pub struct SigningSessionSignerResponse {
+    pub role: ParticipantRole,
+    pub public_key: String,
+    pub commitment: String,
+    pub nonce: String,
+    pub partial_signature_submitted: bool,
+}
+#[derive(Serialize)]
+pub struct SigningSessionResponse {
+    pub order_id: String,
+    pub session_id: String,
+    pub transaction_id: String,
+    pub signers: Vec<SigningSessionSignerResponse>,
+}
+#[derive(Deserialize)]
+pub struct ConfirmRequest {
+    pub order_id: String,
+    pub public_key: Option<String>,
+    pub nonce: Option<String>,
     pub partial_signature: String,
 }
 #[derive(Serialize)]
@@ -147,3 +1265,27 @@ pub struct AfterReceived {
     pub partial_signature: String,
     pub nonce: String,
 }
+#[derive(Serialize)]
+pub struct WalletBalanceResponse {
+    pub wallet_name: String,
+    pub address: String,
+    pub balance: String,
+    pub unspent_outputs: usize,
+}
+#[derive(Serialize)]
+pub struct MerchantBalanceResponse {
+    pub balance: String,
+    pub wallets: Vec<WalletBalanceResponse>,
+}
+#[derive(Serialize)]
+pub struct SweepTransactionResponse {
+    pub wallet_name: String,
+    pub transaction_id: String,
+    pub amount: String,
+    pub inputs: usize,
+}
+#[derive(Serialize)]
+pub struct SweepResponse {
+    pub treasury_address: String,
+    pub transactions: Vec<SweepTransactionResponse>,
+}
@@ -0,0 +1,321 @@
+use chrono::{NaiveDate, NaiveDateTime};
+use std::str::FromStr;
+
+use chain_core::init::coin::Coin;
+
+use crate::models::{to_base_units, OrderQueryRequest, OrderStatus, TIMESTAMP_FORMAT};
+use crate::validation::given;
+
+/// Orders of a page when the request sets no limit
+pub const DEFAULT_PAGE_SIZE: i64 = 50;
+/// Largest page a request can ask for
+pub const MAX_PAGE_SIZE: i64 = 200;
+
+/// Filters, sort and page of a query of the orders of a merchant
+pub struct OrderQuery {
+    pub statuses: Vec<OrderStatus>,
+    pub created_from: Option<NaiveDateTime>,
+    pub created_to: Option<NaiveDateTime>,
+    pub min_amount: Option<i64>,
+    pub max_amount: Option<i64>,
+    pub buyer: Option<String>,
+    pub sort: Sort,
+    pub cursor: Option<Cursor>,
+    pub limit: i64,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum SortField {
+    CreatedAt,
+    UpdatedAt,
+    Amount,
+}
+
+/// Order of the orders of a query, ties broken by order ID in the same direction
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Sort {
+    pub field: SortField,
+    pub descending: bool,
+}
+
+/// Position after the last order of a page, the sort value and ID of that order
+#[derive(Debug)]
+pub struct Cursor {
+    pub value: CursorValue,
+    pub order_id: String,
+}
+
+#[derive(Debug)]
+pub enum CursorValue {
+    Timestamp(NaiveDateTime),
+    Amount(i64),
+}
+
+impl OrderQuery {
+    /// Restricts the query to the statuses of a listing route, keeping the statuses the caller
+    /// filters on among them. Returns false when none of them is one of the route, the query then
+    /// matching no order.
+    pub fn restrict_statuses(&mut self, statuses: &[OrderStatus]) -> bool {
+        if self.statuses.is_empty() {
+            self.statuses = statuses.to_vec();
+        } else {
+            self.statuses.retain(|status| statuses.contains(status));
+        }
+        !self.statuses.is_empty()
+    }
+}
+
+impl Sort {
+    fn name(self) -> &'static str {
+        match (self.field, self.descending) {
+            (SortField::CreatedAt, false) => "created_at",
+            (SortField::CreatedAt, true) => "-created_at",
+            (SortField::UpdatedAt, false) => "updated_at",
+            (SortField::UpdatedAt, true) => "-updated_at",
+            (SortField::Amount, false) => "amount",
+            (SortField::Amount, true) => "-amount",
+        }
+    }
+}
+
+impl Default for Sort {
+    /// Newest orders first
+    fn default() -> Self {
+        Sort {
+            field: SortField::CreatedAt,
+            descending: true,
+        }
+    }
+}
+
+/// Parses the filters, sort and page of an order query
+pub fn parse_order_query(params: &OrderQueryRequest) -> Result<OrderQuery, &'static str> {
+    let sort = match given(&params.sort) {
+        Some(sort) => parse_sort(sort)?,
+        None => Sort::default(),
+    };
+    Ok(OrderQuery {
+        statuses: match given(&params.status) {
+            Some(statuses) => parse_statuses(statuses)?,
+            None => vec![],
+        },
+        created_from: given(&params.created_from)
+            .map(parse_timestamp)
+            .transpose()?,
+        created_to: given(&params.created_to).map(parse_timestamp).transpose()?,
+        min_amount: given(&params.min_amount).map(parse_amount).transpose()?,
+        max_amount: given(&params.max_amount).map(parse_amount).transpose()?,
+        buyer: given(&params.buyer).map(str::to_owned),
+        sort,
+        cursor: given(&params.cursor)
+            .map(|cursor| parse_cursor(cursor, sort))
+            .transpose()?,
+        limit: match given(&params.limit) {
+            Some(limit) => parse_limit(limit)?,
+            None => DEFAULT_PAGE_SIZE,
+        },
+    })
+}
+
+/// Parses comma separated order statuses
+pub fn parse_statuses(statuses: &str) -> Result<Vec<OrderStatus>, &'static str> {
+    statuses
+        .split(',')
+        .map(|status| OrderStatus::from_str(status.trim()).map_err(|_| "Invalid Order Status"))
+        .collect()
+}
+
+/// Parses a date or a time in UTC, `YYYY-MM-DD` or `YYYY-MM-DD HH:MM:SS`, a date standing for
+/// its midnight
+pub fn parse_timestamp(timestamp: &str) -> Result<NaiveDateTime, &'static str> {
+    let timestamp = timestamp.trim().replacen('T', " ", 1);
+    NaiveDateTime::parse_from_str(&timestamp, TIMESTAMP_FORMAT)
+        .or_else(|_| {
+            NaiveDate::parse_from_str(&timestamp, "%Y-%m-%d").map(|date| date.and_hms(0, 0, 0))
+        })
+        .map_err(|_| "Invalid Timestamp")
+}
+
+/// Parses an amount in base units
+pub fn parse_amount(amount: &str) -> Result<i64, &'static str> {
+    Coin::from_str(amount)
+        .map(to_base_units)
+        .map_err(|_| "Invalid Amount")
+}
+
+/// Parses a sort field, prefixed with a dash for a descending sort
+pub fn parse_sort(sort: &str) -> Result<Sort, &'static str> {
+    let (field, descending) = if sort.starts_with('-') {
+        (&sort[1..], true)
+    } else {
+        (sort, false)
+    };
+    let field = match field {
+        "created_at" => SortField::CreatedAt,
+        "updated_at" => SortField::UpdatedAt,
+        "amount" => SortField::Amount,
+        _ => return Err("Invalid Sort"),
+    };
+    Ok(Sort { field, descending })
+}
+
+pub fn parse_limit(limit: &str) -> Result<i64, &'static str> {
+    match limit.parse::<i64>() {
+        Ok(limit) if limit > 0 && limit <= MAX_PAGE_SIZE => Ok(limit),
+        _ => Err("Invalid Limit"),
+    }
+}
+
+/// Opaque cursor of the page following the given order, bound to the sort of the query
+pub fn encode_cursor(sort: Sort, value: &str, order_id: &str) -> String {
+    hex::encode(format!("{}\n{}\n{}", sort.name(), value, order_id))
+}
+
+/// Parses a cursor returned with a page of a query with the same sort
+pub fn parse_cursor(cursor: &str, sort: Sort) -> Result<Cursor, &'static str> {
+    let decoded = hex::decode(cursor)
+        .ok()
+        .and_then(|decoded| String::from_utf8(decoded).ok())
+        .ok_or("Invalid Cursor")?;
+    let mut parts = decoded.splitn(3, '\n');
+    let (name, value, order_id) = match (parts.next(), parts.next(), parts.next()) {
+        (Some(name), Some(value), Some(order_id)) => (name, value, order_id),
+        _ => return Err("Invalid Cursor"),
+    };
+    if name != sort.name() {
+        return Err("Cursor Of Another Sort");
+    }
+    let value = match sort.field {
+        SortField::CreatedAt | SortField::UpdatedAt => CursorValue::Timestamp(
+            NaiveDateTime::parse_from_str(value, TIMESTAMP_FORMAT).map_err(|_| "Invalid Cursor")?,
+        ),
+        SortField::Amount => CursorValue::Amount(value.parse().map_err(|_| "Invalid Cursor")?),
+    };
+    Ok(Cursor {
+        value,
+        order_id: order_id.to_owned(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(
+        status: Option<&str>,
+        sort: Option<&str>,
+        cursor: Option<String>,
+    ) -> OrderQueryRequest {
+        OrderQueryRequest {
+            status: status.map(str::to_owned),
+            created_from: None,
+            created_to: None,
+            min_amount: None,
+            max_amount: None,
+            buyer: None,
+            sort: sort.map(str::to_owned),
+            cursor,
+            limit: None,
+        }
+    }
+
+    #[test]
+    fn cursor_round_trips() {
+        let sort = parse_sort("-updated_at").unwrap();
+        let cursor = encode_cursor(sort, "2020-01-02 11:30:00.250", "order\n1");
+        let parsed = parse_cursor(&cursor, sort).unwrap();
+        match parsed.value {
+            CursorValue::Timestamp(value) => assert_eq!(
+                value,
+                NaiveDate::from_ymd(2020, 1, 2).and_hms_milli(11, 30, 0, 250)
+            ),
+            value => panic!("unexpected cursor value {:?}", value),
+        }
+        assert_eq!(parsed.order_id, "order\n1");
+
+        let sort = parse_sort("amount").unwrap();
+        let parsed = parse_cursor(&encode_cursor(sort, "1500", "2"), sort).unwrap();
+        match parsed.value {
+            CursorValue::Amount(value) => assert_eq!(value, 1500),
+            value => panic!("unexpected cursor value {:?}", value),
+        }
+    }
+
+    #[test]
+    fn cursor_is_bound_to_its_sort() {
+        let ascending = parse_sort("created_at").unwrap();
+        let descending = parse_sort("-created_at").unwrap();
+        let cursor = encode_cursor(ascending, "2020-01-01 00:00:00", "1");
+        assert_eq!(
+            parse_cursor(&cursor, descending).unwrap_err(),
+            "Cursor Of Another Sort"
+        );
+        let params = request(None, None, Some(cursor));
+        assert_eq!(
+            parse_order_query(&params).err(),
+            Some("Cursor Of Another Sort")
+        );
+        assert_eq!(
+            parse_cursor("not hex", ascending).unwrap_err(),
+            "Invalid Cursor"
+        );
+        assert_eq!(
+            parse_cursor(&hex::encode("created_at\n1"), ascending).unwrap_err(),
+            "Invalid Cursor"
+        );
+        assert_eq!(
+            parse_cursor(&encode_cursor(ascending, "yesterday", "1"), ascending).unwrap_err(),
+            "Invalid Cursor"
+        );
+    }
+
+    #[test]
+    fn parses_filters() {
+        assert_eq!(
+            parse_statuses("Delivering, Refunding").unwrap(),
+            vec![OrderStatus::Delivering, OrderStatus::Refunding]
+        );
+        assert_eq!(
+            parse_statuses("Delivering,Lost").unwrap_err(),
+            "Invalid Order Status"
+        );
+        assert_eq!(
+            parse_timestamp("2020-01-02").unwrap(),
+            NaiveDate::from_ymd(2020, 1, 2).and_hms(0, 0, 0)
+        );
+        assert_eq!(
+            parse_timestamp("2020-01-02T10:00:00").unwrap(),
+            NaiveDate::from_ymd(2020, 1, 2).and_hms(10, 0, 0)
+        );
+        assert_eq!(parse_sort("-name").unwrap_err(), "Invalid Sort");
+        assert_eq!(parse_limit("200").unwrap(), MAX_PAGE_SIZE);
+        assert_eq!(parse_limit("0").unwrap_err(), "Invalid Limit");
+        assert_eq!(parse_limit("201").unwrap_err(), "Invalid Limit");
+
+        let query = parse_order_query(&request(None, None, None)).unwrap();
+        assert!(query.statuses.is_empty());
+        assert_eq!(query.sort, Sort::default());
+        assert_eq!(query.limit, DEFAULT_PAGE_SIZE);
+    }
+
+    #[test]
+    fn restricts_statuses_to_the_route() {
+        let route = [
+            OrderStatus::Completed,
+            OrderStatus::Refunded,
+            OrderStatus::Split,
+        ];
+
+        let mut query = parse_order_query(&request(None, None, None)).unwrap();
+        assert!(query.restrict_statuses(&route));
+        assert_eq!(query.statuses, route.to_vec());
+
+        let mut query =
+            parse_order_query(&request(Some("Refunded,Delivering"), None, None)).unwrap();
+        assert!(query.restrict_statuses(&route));
+        assert_eq!(query.statuses, vec![OrderStatus::Refunded]);
+
+        let mut query = parse_order_query(&request(Some("Delivering"), None, None)).unwrap();
+        assert!(!query.restrict_statuses(&route));
+    }
+}
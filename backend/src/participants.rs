@@ -0,0 +1,123 @@
+use std::collections::HashSet;
+use std::str::FromStr;
+
+use client_common::PublicKey;
+
+use crate::models::{OrderParticipant, ParticipantRole};
+
+/// Maximum number of keys in an order's multi-sig address, including the merchant
+pub const MAX_PARTICIPANTS: usize = 8;
+
+/// Co-signer declared in a new order request
+pub struct ParticipantSpec {
+    pub role: ParticipantRole,
+    pub public_key: PublicKey,
+    pub view_key: PublicKey,
+}
+
+/// Parses comma separated `Role:public_key:view_key` entries. The merchant always participates
+/// through its own wallet and cannot be declared.
+pub fn parse_participants(participants: &str) -> Result<Vec<ParticipantSpec>, &'static str> {
+    participants
+        .split(',')
+        .map(str::trim)
+        .filter(|participant| !participant.is_empty())
+        .map(|participant| {
+            let fields: Vec<&str> = participant.split(':').map(str::trim).collect();
+            if fields.len() != 3 {
+                return Err("Invalid Participant");
+            }
+            let role = ParticipantRole::from_str(fields[0]).map_err(|_| "Invalid Participant")?;
+            if role == ParticipantRole::Merchant {
+                return Err("Invalid Participant");
+            }
+            Ok(ParticipantSpec {
+                role,
+                public_key: PublicKey::from_str(fields[1]).map_err(|_| "Invalid Public Key")?,
+                view_key: PublicKey::from_str(fields[2]).map_err(|_| "Invalid View Key")?,
+            })
+        })
+        .collect()
+}
+
+/// Checks the participants and threshold of a new order. The merchant counts as one of the
+/// signers, so a threshold of 2 over a single co-signer is a 2-of-2 order.
+pub fn validate_participants(
+    participants: &[ParticipantSpec],
+    required_signatures: i32,
+) -> Result<(), &'static str> {
+    let total = participants.len() + 1;
+    if total > MAX_PARTICIPANTS {
+        return Err("Too Many Participants");
+    }
+    if required_signatures < 2 || required_signatures as usize > total {
+        return Err("Invalid Required Signatures");
+    }
+    if participants
+        .iter()
+        .filter(|participant| participant.role == ParticipantRole::Buyer)
+        .count()
+        != 1
+    {
+        return Err("Invalid Participant");
+    }
+
+    let mut public_keys = HashSet::new();
+    for participant in participants.iter() {
+        if !public_keys.insert(participant.public_key.to_string()) {
+            return Err("Duplicate Participant");
+        }
+    }
+    Ok(())
+}
+
+/// Selects the co-signers of a signing session, the merchant signs every session.
+///
+/// When the caller does not name the co-signers, the buyer is selected first followed by the
+/// other participants in the order they were declared.
+pub fn select_signers<'a>(
+    participants: &'a [OrderParticipant],
+    required_signatures: i32,
+    requested: Option<&str>,
+) -> Result<Vec<&'a OrderParticipant>, &'static str> {
+    let count = required_signatures as usize - 1;
+
+    let signers: Vec<&OrderParticipant> = match requested {
+        Some(requested) => {
+            let mut signers = vec![];
+            for public_key in requested
+                .split(',')
+                .map(str::trim)
+                .filter(|public_key| !public_key.is_empty())
+            {
+                let signer = participants
+                    .iter()
+                    .find(|participant| participant.public_key == public_key)
+                    .ok_or("Not A Participant")?;
+                if signers
+                    .iter()
+                    .any(|selected: &&OrderParticipant| selected.id == signer.id)
+                {
+                    return Err("Duplicate Participant");
+                }
+                signers.push(signer);
+            }
+            signers
+        }
+        None => participants
+            .iter()
+            .filter(|participant| participant.role == ParticipantRole::Buyer)
+            .chain(
+                participants
+                    .iter()
+                    .filter(|participant| participant.role != ParticipantRole::Buyer),
+            )
+            .take(count)
+            .collect(),
+    };
+
+    if signers.len() != count {
+        return Err("Invalid Number Of Signers");
+    }
+    Ok(signers)
+}
@@ -0,0 +1,238 @@
+use actix_web::dev::Payload;
+use actix_web::http::header;
+use actix_web::{web, Error as AWError, FromRequest, HttpRequest, HttpResponse};
+use futures::future::{self, Future};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::auth::{Identity, Staff};
+use crate::config::{RateLimit, RateLimitConfig};
+
+/// Number of tracked clients above which the expired windows are dropped
+const PRUNE_THRESHOLD: usize = 1024;
+
+/// Fixed window counter of the requests of each client
+pub struct RateLimiter {
+    limit: RateLimit,
+    windows: Mutex<HashMap<String, Window>>,
+}
+
+struct Window {
+    started: Instant,
+    requests: u32,
+}
+
+impl RateLimiter {
+    pub fn new(limit: RateLimit) -> Self {
+        RateLimiter {
+            limit,
+            windows: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Counts a request of the client, returning how long it has to wait when over the limit
+    pub fn check(&self, client: &str) -> Result<(), Duration> {
+        let now = Instant::now();
+        let window = self.limit.window;
+        let mut windows = self.windows.lock().unwrap();
+        if !windows.contains_key(client) && windows.len() >= PRUNE_THRESHOLD {
+            windows.retain(|_, current| now.duration_since(current.started) < window);
+        }
+
+        let current = windows.entry(client.to_owned()).or_insert(Window {
+            started: now,
+            requests: 0,
+        });
+        let elapsed = now.duration_since(current.started);
+        if elapsed >= window {
+            current.started = now;
+            current.requests = 0;
+        } else if current.requests >= self.limit.requests {
+            return Err(window - elapsed);
+        }
+        current.requests += 1;
+        Ok(())
+    }
+}
+
+/// Limiters of the order creation and signing routes, shared by every worker
+pub struct RateLimits {
+    order_per_ip: Option<RateLimiter>,
+    order_per_key: Option<RateLimiter>,
+    signing_per_ip: Option<RateLimiter>,
+    signing_per_key: Option<RateLimiter>,
+    trusted_proxies: usize,
+}
+
+impl RateLimits {
+    pub fn new(config: &RateLimitConfig) -> Self {
+        RateLimits {
+            order_per_ip: config.order_per_ip.map(RateLimiter::new),
+            order_per_key: config.order_per_key.map(RateLimiter::new),
+            signing_per_ip: config.signing_per_ip.map(RateLimiter::new),
+            signing_per_key: config.signing_per_key.map(RateLimiter::new),
+            trusted_proxies: config.trusted_proxies,
+        }
+    }
+
+    fn check(
+        &self,
+        req: &HttpRequest,
+        identity: Option<&Identity>,
+        per_ip: &Option<RateLimiter>,
+        per_key: &Option<RateLimiter>,
+    ) -> Result<(), AWError> {
+        if let Some(limiter) = per_ip {
+            let client = client_ip(req, self.trusted_proxies);
+            limiter.check(&client).map_err(too_many_requests)?;
+        }
+        if let (Some(limiter), Some(identity)) = (per_key, identity) {
+            limiter
+                .check(&identity_key(identity))
+                .map_err(too_many_requests)?;
+        }
+        Ok(())
+    }
+}
+
+/// Merchant staff creating an order, within the limits of its IP and of its account
+pub struct OrderClient(pub Staff);
+impl FromRequest for OrderClient {
+    type Config = ();
+    type Error = AWError;
+    type Future = Box<dyn Future<Item = Self, Error = Self::Error>>;
+
+    fn from_request(req: &HttpRequest, payload: &mut Payload) -> Self::Future {
+        let limits = match web::Data::<RateLimits>::extract(req) {
+            Ok(limits) => limits,
+            Err(err) => return Box::new(future::err(err)),
+        };
+        let req = req.clone();
+        Box::new(Staff::from_request(&req, payload).and_then(move |staff| {
+            limits
+                .check(
+                    &req,
+                    Some(&Identity::Staff(staff.clone())),
+                    &limits.order_per_ip,
+                    &limits.order_per_key,
+                )
+                .map(|_| OrderClient(staff))
+        }))
+    }
+}
+
+/// Caller of a signing route, within the limits of its IP and of its key
+pub struct SigningCaller(pub Identity);
+impl FromRequest for SigningCaller {
+    type Config = ();
+    type Error = AWError;
+    type Future = Box<dyn Future<Item = Self, Error = Self::Error>>;
+
+    fn from_request(req: &HttpRequest, payload: &mut Payload) -> Self::Future {
+        let limits = match web::Data::<RateLimits>::extract(req) {
+            Ok(limits) => limits,
+            Err(err) => return Box::new(future::err(err)),
+        };
+        let req = req.clone();
+        Box::new(
+            Identity::from_request(&req, payload).and_then(move |identity| {
+                limits
+                    .check(
+                        &req,
+                        Some(&identity),
+                        &limits.signing_per_ip,
+                        &limits.signing_per_key,
+                    )
+                    .map(|_| SigningCaller(identity))
+            }),
+        )
+    }
+}
+
+/// Response to a client over its limit, telling it when to retry
+fn too_many_requests(retry_after: Duration) -> AWError {
+    let seconds = retry_after.as_secs() + u64::from(retry_after.subsec_nanos() > 0);
+    AWError::from(
+        HttpResponse::TooManyRequests()
+            .header(header::RETRY_AFTER, seconds.max(1).to_string())
+            .reason("Too Many Requests")
+            .finish(),
+    )
+}
+
+/// IP address of the client, as reported by the trusted reverse proxies or of the peer otherwise
+fn client_ip(req: &HttpRequest, trusted_proxies: usize) -> String {
+    let forwarded_for: Vec<&str> = req
+        .headers()
+        .get_all("x-forwarded-for")
+        .filter_map(|value| value.to_str().ok())
+        .collect();
+    let address = forwarded_client(&forwarded_for, trusted_proxies)
+        .map(str::to_owned)
+        .or_else(|| req.peer_addr().map(|address| address.to_string()));
+    match address {
+        Some(address) => address
+            .parse::<SocketAddr>()
+            .map(|address| address.ip().to_string())
+            .unwrap_or(address),
+        None => String::new(),
+    }
+}
+
+/// Client address in the `X-Forwarded-For` values of a request behind the given number of
+/// trusted proxies. Each proxy appends the address it received the request from, so the address
+/// added by the outermost one is counted from the right: the entries on its left are sent by the
+/// client and cannot be trusted.
+fn forwarded_client<'a>(values: &[&'a str], trusted_proxies: usize) -> Option<&'a str> {
+    if trusted_proxies == 0 {
+        return None;
+    }
+    let addresses: Vec<&str> = values
+        .iter()
+        .flat_map(|value| value.split(','))
+        .map(str::trim)
+        .filter(|address| !address.is_empty())
+        .collect();
+    let index = addresses.len().saturating_sub(trusted_proxies);
+    addresses.get(index).cloned()
+}
+
+/// Key the requests of an authenticated caller are counted under
+fn identity_key(identity: &Identity) -> String {
+    match identity {
+        Identity::Staff(staff) => format!("staff:{}:{}", staff.merchant_id, staff.name),
+        Identity::Key(public_key) => format!("key:{}", public_key),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ignores_forwarded_for_without_trusted_proxies() {
+        assert_eq!(forwarded_client(&["203.0.113.7"], 0), None);
+    }
+
+    #[test]
+    fn takes_address_added_by_outermost_proxy() {
+        let values = ["198.51.100.1, 203.0.113.7", "10.0.0.2"];
+        assert_eq!(forwarded_client(&values, 1), Some("10.0.0.2"));
+        assert_eq!(forwarded_client(&values, 2), Some("203.0.113.7"));
+    }
+
+    #[test]
+    fn spoofed_entries_are_not_trusted() {
+        // The client sent the leftmost entry, the proxy appended the address it connected from
+        let values = ["1.2.3.4, 203.0.113.7"];
+        assert_eq!(forwarded_client(&values, 1), Some("203.0.113.7"));
+    }
+
+    #[test]
+    fn falls_back_to_first_address_of_shorter_chain() {
+        assert_eq!(forwarded_client(&["203.0.113.7"], 2), Some("203.0.113.7"));
+        assert_eq!(forwarded_client(&[], 1), None);
+    }
+}
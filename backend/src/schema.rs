@@ -1,16 +1,279 @@
+table! {
+    auth_challenges (nonce) {
+        nonce -> Text,
+        public_key -> Text,
+        expires_at -> BigInt,
+    }
+}
+
+table! {
+    auth_sessions (token) {
+        token -> Text,
+        public_key -> Text,
+        expires_at -> BigInt,
+        created_at -> Text,
+    }
+}
+
+table! {
+    checkouts (checkout_id) {
+        checkout_id -> Text,
+        status -> Text,
+        payment_transaction_id -> Text,
+        created_at -> Text,
+    }
+}
+
+table! {
+    dispute_cases (id) {
+        id -> Integer,
+        order_id -> Text,
+        opened_by -> Text,
+        status -> Text,
+        created_at -> Text,
+    }
+}
+
+table! {
+    dispute_evidence (id) {
+        id -> Integer,
+        case_id -> Integer,
+        submitted_by -> Text,
+        file_name -> Text,
+        digest -> Text,
+        size -> Integer,
+        created_at -> Text,
+    }
+}
+
+table! {
+    dispute_messages (id) {
+        id -> Integer,
+        case_id -> Integer,
+        reply_to -> Nullable<Integer>,
+        author -> Text,
+        body -> Text,
+        created_at -> Text,
+    }
+}
+
+table! {
+    escrows (id) {
+        id -> Integer,
+        name -> Text,
+        public_key -> Text,
+        view_key -> Text,
+        fee_address -> Text,
+        fee -> BigInt,
+        fee_basis_points -> Integer,
+        dispute_fee -> BigInt,
+        dispute_fee_basis_points -> Integer,
+        status -> Text,
+        created_at -> Text,
+    }
+}
+
+table! {
+    merchant_api_keys (id) {
+        id -> Integer,
+        merchant_id -> Integer,
+        name -> Text,
+        key_digest -> Text,
+        created_at -> Text,
+    }
+}
+
+table! {
+    merchants (id) {
+        id -> Integer,
+        name -> Text,
+        wallet_name -> Text,
+        payout_address -> Text,
+        treasury_address -> Text,
+        created_at -> Text,
+        auditor_view_keys -> Text,
+        escrow_access_on_refund -> Nullable<Bool>,
+        platform_fee_address -> Text,
+        platform_fee -> BigInt,
+        platform_fee_basis_points -> Integer,
+        platform_dispute_fee -> BigInt,
+        platform_dispute_fee_basis_points -> Integer,
+        refund_approval_threshold -> Nullable<BigInt>,
+        require_registered_escrow -> Nullable<Bool>,
+    }
+}
+
+table! {
+    milestones (id) {
+        id -> Integer,
+        order_id -> Text,
+        sequence -> Integer,
+        amount -> BigInt,
+        status -> Text,
+        settlement_transaction_id -> Text,
+        created_at -> Text,
+    }
+}
+
+table! {
+    order_events (id) {
+        id -> Integer,
+        order_id -> Text,
+        event -> Text,
+        detail -> Text,
+        created_at -> Text,
+    }
+}
+
+table! {
+    order_participants (id) {
+        id -> Integer,
+        order_id -> Text,
+        role -> Text,
+        public_key -> Text,
+        view_key -> Text,
+    }
+}
+
 table! {
     orders (order_id) {
         order_id -> Text,
         status -> Text,
         wallet_name -> Text,
-        amount -> Text,
+        amount -> BigInt,
         buyer_public_key -> Text,
         buyer_view_key -> Text,
         buyer_address -> Text,
         escrow_public_key -> Text,
         escrow_view_key -> Text,
+        session_id -> Nullable<Text>,
+        payment_transaction_id -> Nullable<Text>,
+        settlement_transaction_id -> Nullable<Text>,
+        extra_view_keys -> Text,
+        required_signatures -> Integer,
+        merchant_public_key -> Text,
+        payout_address -> Text,
+        refund_address -> Nullable<Text>,
+        split_merchant_amount -> Nullable<BigInt>,
+        split_buyer_amount -> Nullable<BigInt>,
+        arbitrated -> Bool,
+        escrow_id -> Nullable<Integer>,
+        escrow_fee_address -> Text,
+        escrow_fee -> BigInt,
+        escrow_fee_basis_points -> Integer,
+        escrow_dispute_fee -> BigInt,
+        escrow_dispute_fee_basis_points -> Integer,
+        funding_transaction_id -> Nullable<Text>,
+        funding_amount -> Nullable<BigInt>,
+        milestone_amount -> Nullable<BigInt>,
+        change_public_key -> Nullable<Text>,
+        checkout_id -> Nullable<Text>,
+        payment_output_index -> Integer,
+        batch_id -> Nullable<Integer>,
+        merchant_id -> Integer,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+        encoded_settlement_transaction -> Nullable<Text>,
+        settlement_network_fee -> Nullable<BigInt>,
+    }
+}
+
+table! {
+    refund_approvals (id) {
+        id -> Integer,
+        order_id -> Text,
+        staff_name -> Text,
+        created_at -> Text,
+    }
+}
+
+table! {
+    rulings (id) {
+        id -> Integer,
+        order_id -> Text,
+        escrow_public_key -> Text,
+        decision -> Text,
+        merchant_amount -> Text,
+        buyer_amount -> Text,
+        signature -> Text,
+        created_at -> Text,
+        dispute_id -> Nullable<Integer>,
+    }
+}
+
+table! {
+    session_signers (id) {
+        id -> Integer,
         session_id -> Text,
-        payment_transaction_id -> Text,
-        settlement_transaction_id -> Text,
+        order_id -> Text,
+        role -> Text,
+        public_key -> Text,
+        commitment -> Text,
+        nonce -> Text,
+        partial_signature -> Text,
+    }
+}
+
+table! {
+    settlement_batches (id) {
+        id -> Integer,
+        status -> Text,
+        transaction_id -> Text,
+        encoded_transaction -> Text,
+        created_at -> Text,
+        merchant_id -> Integer,
+        network_fee -> Nullable<BigInt>,
+    }
+}
+
+table! {
+    split_proposals (id) {
+        id -> Integer,
+        order_id -> Text,
+        proposed_by -> Text,
+        merchant_amount -> BigInt,
+        buyer_amount -> BigInt,
+        signature -> Text,
+        status -> Text,
+        acceptance_signature -> Text,
+        created_at -> Text,
     }
 }
+
+joinable!(dispute_cases -> orders (order_id));
+joinable!(dispute_evidence -> dispute_cases (case_id));
+joinable!(dispute_messages -> dispute_cases (case_id));
+joinable!(merchant_api_keys -> merchants (merchant_id));
+joinable!(milestones -> orders (order_id));
+joinable!(order_events -> orders (order_id));
+joinable!(orders -> escrows (escrow_id));
+joinable!(orders -> merchants (merchant_id));
+joinable!(orders -> settlement_batches (batch_id));
+joinable!(order_participants -> orders (order_id));
+joinable!(refund_approvals -> orders (order_id));
+joinable!(rulings -> dispute_cases (dispute_id));
+joinable!(rulings -> orders (order_id));
+joinable!(session_signers -> orders (order_id));
+joinable!(settlement_batches -> merchants (merchant_id));
+joinable!(split_proposals -> orders (order_id));
+
+allow_tables_to_appear_in_same_query!(
+    auth_challenges,
+    auth_sessions,
+    checkouts,
+    dispute_cases,
+    dispute_evidence,
+    dispute_messages,
+    escrows,
+    merchant_api_keys,
+    merchants,
+    milestones,
+    order_events,
+    order_participants,
+    orders,
+    refund_approvals,
+    rulings,
+    session_signers,
+    settlement_batches,
+    split_proposals,
+);
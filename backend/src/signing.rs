@@ -0,0 +1,422 @@
+use actix_web::{HttpResponse, ResponseError};
+use failure::Fail;
+use secp256k1::musig::{
+    musig_pubkey_combine, MuSigNonceCommitment, MuSigPartialSignature, MuSigSession,
+};
+use secp256k1::schnorrsig::{schnorr_verify, SchnorrSignature};
+use secp256k1::{Message, PublicKey as SecpPublicKey, Secp256k1};
+use secstr::SecUtf8;
+use sha2::{Digest, Sha256};
+use std::str::FromStr;
+
+use chain_core::tx::data::address::ExtendedAddr;
+use chain_core::tx::data::TxId;
+use chain_core::tx::witness::TxInWitness;
+use chain_tx_validation::witness::verify_tx_address;
+use client_common::PublicKey;
+use client_core::wallet::{MultiSigWalletClient, WalletClient};
+
+use crate::models::{ParticipantRole, SessionSigner};
+use crate::AppWalletClient;
+
+#[derive(Debug, Fail)]
+pub enum SigningError {
+    #[fail(display = "Invalid nonce commitment from {}", _0)]
+    InvalidCommitment(ParticipantRole),
+    #[fail(display = "Invalid nonce from {}", _0)]
+    InvalidNonce(ParticipantRole),
+    #[fail(display = "Invalid partial signature from {}", _0)]
+    InvalidPartialSignature(ParticipantRole),
+    #[fail(display = "Invalid partial signature from one of the co-signers")]
+    InvalidCombinedSignature,
+    #[fail(display = "Signing request rejected: {}", _0)]
+    Rejected(&'static str),
+    #[fail(display = "Signing session error: {}", _0)]
+    Session(String),
+}
+impl SigningError {
+    fn reason(&self) -> &'static str {
+        match *self {
+            SigningError::InvalidCommitment(role) => by_role(
+                role,
+                [
+                    "Invalid Commitment From Merchant",
+                    "Invalid Commitment From Buyer",
+                    "Invalid Commitment From Escrow",
+                    "Invalid Commitment From Operator",
+                ],
+            ),
+            SigningError::InvalidNonce(role) => by_role(
+                role,
+                [
+                    "Invalid Nonce From Merchant",
+                    "Invalid Nonce From Buyer",
+                    "Invalid Nonce From Escrow",
+                    "Invalid Nonce From Operator",
+                ],
+            ),
+            SigningError::InvalidPartialSignature(role) => by_role(
+                role,
+                [
+                    "Invalid Partial Signature From Merchant",
+                    "Invalid Partial Signature From Buyer",
+                    "Invalid Partial Signature From Escrow",
+                    "Invalid Partial Signature From Operator",
+                ],
+            ),
+            SigningError::InvalidCombinedSignature => "Invalid Partial Signature From Co-Signers",
+            SigningError::Rejected(reason) => reason,
+            SigningError::Session(_) => "Signing Session Error",
+        }
+    }
+}
+impl ResponseError for SigningError {
+    fn error_response(&self) -> HttpResponse {
+        match *self {
+            SigningError::Rejected(_) => HttpResponse::BadRequest().reason(self.reason()).finish(),
+            SigningError::Session(_) => HttpResponse::InternalServerError()
+                .reason(self.reason())
+                .finish(),
+            _ => HttpResponse::UnprocessableEntity()
+                .reason(self.reason())
+                .finish(),
+        }
+    }
+}
+
+fn by_role(role: ParticipantRole, reasons: [&'static str; 4]) -> &'static str {
+    match role {
+        ParticipantRole::Merchant => reasons[0],
+        ParticipantRole::Buyer => reasons[1],
+        ParticipantRole::Escrow => reasons[2],
+        ParticipantRole::Operator => reasons[3],
+    }
+}
+
+/// Co-signer of a multi-sig session, identified by the role it plays in the order.
+pub struct Signer {
+    pub role: ParticipantRole,
+    pub public_key: PublicKey,
+}
+impl Signer {
+    pub fn from_session_signer(signer: &SessionSigner) -> Signer {
+        Signer {
+            role: signer.role,
+            public_key: PublicKey::from_str(&signer.public_key).unwrap(),
+        }
+    }
+}
+
+/// Multi-sig session over a settlement transaction spending the order's multi-sig address.
+pub struct SigningSession {
+    pub id: [u8; 32],
+    pub message: TxId,
+    pub multisig_address: ExtendedAddr,
+    pub public_keys: Vec<PublicKey>,
+}
+
+/// Adds a co-signer's nonce commitment to the merchant's signing session.
+pub fn add_commitment(
+    wallet: &AppWalletClient,
+    passphrase: &SecUtf8,
+    session_id: &[u8; 32],
+    signer: &Signer,
+    commitment: &str,
+) -> Result<(), SigningError> {
+    let role = signer.role;
+
+    let commitment_vec =
+        hex::decode(commitment).map_err(|_| SigningError::InvalidCommitment(role))?;
+    if commitment_vec.len() != 32 {
+        return Err(SigningError::InvalidCommitment(role));
+    }
+    let mut commitment = [0; 32];
+    commitment.copy_from_slice(&commitment_vec);
+
+    wallet
+        .add_nonce_commitment(session_id, passphrase, commitment, &signer.public_key)
+        .map_err(|_| SigningError::InvalidCommitment(role))
+}
+
+/// Adds a co-signer's nonce to the merchant's signing session. The nonce is checked against the
+/// commitment the co-signer exchanged earlier in the session.
+pub fn add_verified_nonce(
+    wallet: &AppWalletClient,
+    passphrase: &SecUtf8,
+    session_id: &[u8; 32],
+    signer: &Signer,
+    nonce: &str,
+) -> Result<(), SigningError> {
+    let role = signer.role;
+
+    let nonce = PublicKey::from_str(nonce).map_err(|_| SigningError::InvalidNonce(role))?;
+    wallet
+        .add_nonce(session_id, passphrase, &nonce, &signer.public_key)
+        .map_err(|_| SigningError::InvalidNonce(role))
+}
+
+/// Adds a co-signer's partial signature to the merchant's signing session, once the nonces of
+/// every signer are known.
+pub fn add_partial_signature(
+    wallet: &AppWalletClient,
+    passphrase: &SecUtf8,
+    session_id: &[u8; 32],
+    signer: &Signer,
+    partial_signature: &str,
+) -> Result<(), SigningError> {
+    let role = signer.role;
+
+    let partial_signature_vec =
+        hex::decode(partial_signature).map_err(|_| SigningError::InvalidPartialSignature(role))?;
+    if partial_signature_vec.len() != 32 {
+        return Err(SigningError::InvalidPartialSignature(role));
+    }
+    let mut partial_signature = [0; 32];
+    partial_signature.copy_from_slice(&partial_signature_vec);
+
+    wallet
+        .partial_signature(session_id, passphrase)
+        .map_err(|err| SigningError::Session(err.to_string()))?;
+    wallet
+        .add_partial_signature(
+            session_id,
+            passphrase,
+            partial_signature,
+            &signer.public_key,
+        )
+        .map_err(|_| SigningError::InvalidPartialSignature(role))
+}
+
+/// Session signer submitting its partial signature, once every signer has committed to its nonce
+/// and every other signer has revealed it. A signer whose partial signature completed the session
+/// but whose settlement failed has nothing stored, and submits it again.
+pub fn submitting_signer<'a>(
+    signers: &'a [SessionSigner],
+    submitter: &str,
+) -> Result<&'a SessionSigner, SigningError> {
+    let signer = signers
+        .iter()
+        .find(|signer| signer.public_key == submitter)
+        .ok_or(SigningError::Rejected("Not A Session Signer"))?;
+    if !signer.partial_signature.is_empty() {
+        return Err(SigningError::Rejected(
+            "Partial Signature Already Submitted",
+        ));
+    }
+    if signers.iter().any(|signer| signer.commitment.is_empty()) {
+        return Err(SigningError::Rejected("Nonce Commitments Incomplete"));
+    }
+    if signers
+        .iter()
+        .any(|signer| signer.public_key != submitter && signer.nonce.is_empty())
+    {
+        return Err(SigningError::Rejected("Nonces Incomplete"));
+    }
+    Ok(signer)
+}
+
+/// Nonce commitment and nonce of a signer of a session, in the order of the session public keys
+pub struct SignerNonce {
+    pub role: ParticipantRole,
+    pub commitment: [u8; 32],
+    pub nonce: PublicKey,
+}
+
+/// Verifies the partial signature of the co-signer at the given index of the session public keys
+/// against its key and the nonces of every signer, before it is added to the session. An invalid
+/// partial signature is rejected naming its co-signer, and leaves the session untouched.
+pub fn verify_partial_signature(
+    session: &SigningSession,
+    nonces: &[SignerNonce],
+    index: usize,
+    partial_signature: &str,
+) -> Result<(), SigningError> {
+    let role = nonces[index].role;
+    let invalid = || SigningError::InvalidPartialSignature(role);
+    let session_error = |err: secp256k1::Error| SigningError::Session(err.to_string());
+
+    let partial_signature = hex::decode(partial_signature)
+        .ok()
+        .filter(|partial_signature| partial_signature.len() == 32)
+        .and_then(|partial_signature| MuSigPartialSignature::from_slice(&partial_signature).ok())
+        .ok_or_else(invalid)?;
+
+    let secp = Secp256k1::new();
+    let public_keys = session
+        .public_keys
+        .iter()
+        .map(|public_key| SecpPublicKey::from_slice(&public_key.serialize()))
+        .collect::<Result<Vec<SecpPublicKey>, _>>()
+        .map_err(session_error)?;
+    let (combined_public_key, public_key_hash) =
+        musig_pubkey_combine(&secp, &public_keys).map_err(session_error)?;
+    let message = Message::from_slice(&session.message).map_err(session_error)?;
+    let commitments: Vec<MuSigNonceCommitment> = nonces
+        .iter()
+        .map(|signer| MuSigNonceCommitment::from(signer.commitment))
+        .collect();
+
+    let (mut verifier, mut signers) = MuSigSession::new_verifier(
+        &secp,
+        &message,
+        &combined_public_key,
+        &public_key_hash,
+        &commitments,
+    )
+    .map_err(session_error)?;
+    // Nonces are checked against the commitments of their signer
+    for (signer, signer_nonce) in signers.iter_mut().zip(nonces.iter()) {
+        let invalid_nonce = || SigningError::InvalidNonce(signer_nonce.role);
+        let nonce = SecpPublicKey::from_slice(&signer_nonce.nonce.serialize())
+            .map_err(|_| invalid_nonce())?;
+        signer
+            .set_nonce(&secp, &nonce)
+            .map_err(|_| invalid_nonce())?;
+    }
+    verifier
+        .combine_nonces(&secp, &mut signers, None)
+        .map_err(session_error)?;
+
+    if !verifier.partial_sig_verify(
+        &secp,
+        &signers[index],
+        &partial_signature,
+        &public_keys[index],
+    ) {
+        return Err(invalid());
+    }
+    Ok(())
+}
+
+/// Combines the partial signatures of a signing session and verifies the result against the
+/// session message and the order's multi-sig address before it is used in a transaction.
+///
+/// The merchant's own partial signature is produced locally, so when there is a single co-signer
+/// a combined signature which fails verification can only be caused by that co-signer.
+pub fn verify_signature(
+    wallet: &AppWalletClient,
+    wallet_name: &str,
+    passphrase: &SecUtf8,
+    session: &SigningSession,
+    co_signers: &[Signer],
+) -> Result<(), SigningError> {
+    signed_witness(wallet, wallet_name, passphrase, session, co_signers).map(|_| ())
+}
+
+/// Witness spending the order's multi-sig address with the verified signature of a session, see
+/// `verify_signature`
+pub fn signed_witness(
+    wallet: &AppWalletClient,
+    wallet_name: &str,
+    passphrase: &SecUtf8,
+    session: &SigningSession,
+    co_signers: &[Signer],
+) -> Result<TxInWitness, SigningError> {
+    let invalid = || match co_signers {
+        [signer] => SigningError::InvalidPartialSignature(signer.role),
+        _ => SigningError::InvalidCombinedSignature,
+    };
+
+    let signature = wallet
+        .signature(&session.id, passphrase)
+        .map_err(|_| invalid())?;
+    let proof = wallet
+        .generate_proof(
+            wallet_name,
+            passphrase,
+            &session.multisig_address,
+            session.public_keys.clone(),
+        )
+        .map_err(|err| SigningError::Session(err.to_string()))?;
+
+    let witness = TxInWitness::TreeSig(signature, proof);
+    verify_tx_address(&witness, &session.message, &session.multisig_address)
+        .map_err(|_| invalid())?;
+    Ok(witness)
+}
+
+/// Verifies a Schnorr signature made with a single key, e.g. the buyer key of an order, over the
+/// SHA-256 digest of a message
+pub fn verify_key_signature(public_key: &str, message: &str, signature: &str) -> bool {
+    let public_key = match hex::decode(public_key)
+        .ok()
+        .and_then(|public_key| SecpPublicKey::from_slice(&public_key).ok())
+    {
+        Some(public_key) => public_key,
+        None => return false,
+    };
+    let signature = match hex::decode(signature)
+        .ok()
+        .and_then(|signature| SchnorrSignature::from_default(&signature).ok())
+    {
+        Some(signature) => signature,
+        None => return false,
+    };
+    let message = Message::from_slice(&Sha256::digest(message.as_bytes())).unwrap();
+
+    schnorr_verify(
+        &Secp256k1::verification_only(),
+        &message,
+        &signature,
+        &public_key,
+    )
+    .is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn signer(public_key: &str, nonce: &str, partial_signature: &str) -> SessionSigner {
+        SessionSigner {
+            id: 0,
+            session_id: "session".to_owned(),
+            order_id: "1".to_owned(),
+            role: ParticipantRole::Buyer,
+            public_key: public_key.to_owned(),
+            commitment: "commitment".to_owned(),
+            nonce: nonce.to_owned(),
+            partial_signature: partial_signature.to_owned(),
+        }
+    }
+
+    fn rejection(signers: &[SessionSigner], submitter: &str) -> Option<String> {
+        submitting_signer(signers, submitter)
+            .err()
+            .map(|err| err.to_string())
+    }
+
+    #[test]
+    fn failed_settlement_leaves_signer_to_retry() {
+        let mut signers = vec![
+            signer("merchant", "nonce", "signature"),
+            signer("buyer", "", ""),
+        ];
+        assert_eq!(rejection(&signers, "buyer"), None);
+
+        // the settlement completed by the buyer's partial signature failed: nothing is stored,
+        // so the buyer submits it again
+        assert_eq!(rejection(&signers, "buyer"), None);
+
+        // the retried settlement succeeded and its nonce and partial signature are stored
+        signers[1].nonce = "nonce".to_owned();
+        signers[1].partial_signature = "signature".to_owned();
+        assert_eq!(
+            rejection(&signers, "buyer"),
+            Some(SigningError::Rejected("Partial Signature Already Submitted").to_string())
+        );
+    }
+
+    #[test]
+    fn partial_signature_waits_for_other_nonces() {
+        let signers = vec![signer("merchant", "", ""), signer("buyer", "", "")];
+        assert_eq!(
+            rejection(&signers, "buyer"),
+            Some(SigningError::Rejected("Nonces Incomplete").to_string())
+        );
+        assert_eq!(
+            rejection(&signers, "arbiter"),
+            Some(SigningError::Rejected("Not A Session Signer").to_string())
+        );
+    }
+}
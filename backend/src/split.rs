@@ -0,0 +1,58 @@
+use std::str::FromStr;
+
+use chain_core::init::coin::Coin;
+
+use crate::milestones::funding_amount;
+use crate::models::{Order, OrderStatus, ParticipantRole};
+
+/// Parties which can propose a split and accept the proposal of the other
+pub fn parse_split_party(party: &str) -> Result<ParticipantRole, &'static str> {
+    match ParticipantRole::from_str(party) {
+        Ok(ParticipantRole::Merchant) => Ok(ParticipantRole::Merchant),
+        Ok(ParticipantRole::Buyer) => Ok(ParticipantRole::Buyer),
+        _ => Err("Invalid Split Party"),
+    }
+}
+
+/// Parses the amounts of a split in base units. The amounts have to add up to the funds of the
+/// order's multi-sig address, less any milestones already released.
+pub fn parse_split(
+    record: &Order,
+    merchant_amount: &str,
+    buyer_amount: &str,
+) -> Result<(Coin, Coin), &'static str> {
+    let merchant_amount = Coin::from_str(merchant_amount).map_err(|_| "Invalid Split Amount")?;
+    let buyer_amount = Coin::from_str(buyer_amount).map_err(|_| "Invalid Split Amount")?;
+    let total = (merchant_amount + buyer_amount).map_err(|_| "Invalid Split Amount")?;
+    if total != funding_amount(record) {
+        return Err("Split Does Not Match Funded Amount");
+    }
+    Ok((merchant_amount, buyer_amount))
+}
+
+/// Whether the settlement of an order can still be changed to a split
+pub fn can_split(record: &Order) -> bool {
+    match record.status {
+        OrderStatus::PendingResponse => true,
+        status => status.is_settling() && record.session_id.is_none(),
+    }
+}
+
+/// Message the buyer signs to propose or accept a split, with the amounts in base units. A
+/// proposal is signed over the ID of the latest proposal of the order, 0 for the first one, and an
+/// acceptance over the ID of the accepted proposal, so that neither signature can be replayed on
+/// another proposal.
+pub fn split_message(
+    order_id: &str,
+    split_id: i32,
+    merchant_amount: Coin,
+    buyer_amount: Coin,
+) -> String {
+    format!(
+        "split:{}:{}:{}:{}",
+        order_id,
+        split_id,
+        u64::from(merchant_amount),
+        u64::from(buyer_amount)
+    )
+}
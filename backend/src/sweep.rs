@@ -0,0 +1,141 @@
+use failure::{format_err, Error};
+use secstr::SecUtf8;
+use std::ops::Sub;
+
+use chain_core::init::coin::Coin;
+use chain_core::tx::data::access::{TxAccess, TxAccessPolicy};
+use chain_core::tx::data::address::ExtendedAddr;
+use chain_core::tx::data::attribute::TxAttributes;
+use chain_core::tx::data::input::TxoPointer;
+use chain_core::tx::data::output::TxOut;
+use chain_core::tx::data::TxId;
+use client_common::storage::SledStorage;
+use client_common::tendermint::RpcClient;
+use client_core::wallet::WalletClient;
+use client_core::{TransactionBuilder, UnspentTransactions};
+use client_index::index::Index;
+
+use crate::config::SweepConfig;
+use crate::merchant_wallet::payout_address;
+use crate::{
+    make_app_with_storage, make_transaction_builder, sync_wallet, AppIndex, AppSynchronizer,
+    AppWalletClient, NETWORK_ID, TENDERMINT_URL,
+};
+
+/// Unspent outputs of the payout address of a merchant wallet
+pub struct WalletBalance {
+    pub wallet_name: String,
+    pub address: ExtendedAddr,
+    pub unspent_transactions: Vec<(TxoPointer, TxOut)>,
+}
+impl WalletBalance {
+    pub fn balance(&self) -> Result<Coin, Error> {
+        sum_outputs(&self.unspent_transactions)
+    }
+}
+
+/// Transaction moving a batch of payout outputs to the treasury address
+pub struct SweepTransaction {
+    pub wallet_name: String,
+    pub transaction_id: TxId,
+    pub amount: Coin,
+    pub inputs: usize,
+}
+
+/// Collects the unspent payout outputs of the given merchant wallets from the index, after
+/// syncing each wallet
+pub fn wallet_balances(
+    wallet_names: &[String],
+    passphrase: &SecUtf8,
+) -> Result<Vec<WalletBalance>, Error> {
+    let (wallet, index, synchronizer) = make_app_with_storage(SledStorage::new(".client-storage")?);
+    balances(&wallet, &index, &synchronizer, wallet_names, passphrase)
+}
+
+/// Moves the payout outputs of the given merchant wallets to the treasury address, spending at
+/// most `batch_size` outputs per transaction
+pub fn sweep(
+    wallet_names: &[String],
+    passphrase: &SecUtf8,
+    config: &SweepConfig,
+    treasury_address: &ExtendedAddr,
+) -> Result<Vec<SweepTransaction>, Error> {
+    let storage = SledStorage::new(".client-storage")?;
+    let (wallet, index, synchronizer) = make_app_with_storage(storage.clone());
+    let transaction_builder = make_transaction_builder(storage, RpcClient::new(TENDERMINT_URL));
+    let network_id = hex::decode(NETWORK_ID).unwrap()[0];
+
+    let mut transactions = vec![];
+    for balance in balances(&wallet, &index, &synchronizer, wallet_names, passphrase)? {
+        let merchant_view_key = wallet.view_key(&balance.wallet_name, passphrase)?;
+
+        for batch in balance.unspent_transactions.chunks(config.batch_size) {
+            let amount = sum_outputs(batch)?;
+            if amount <= config.fee_allowance {
+                continue;
+            }
+
+            // The builder pays the fee out of the allowance and returns the rest as change
+            let outputs = vec![TxOut {
+                address: treasury_address.clone(),
+                value: amount
+                    .sub(config.fee_allowance)
+                    .map_err(|err| format_err!("{:?}", err))?,
+                valid_from: None,
+            }];
+            let attributes = TxAttributes::new_with_access(
+                network_id,
+                vec![TxAccessPolicy {
+                    view_key: (&merchant_view_key).into(),
+                    access: TxAccess::AllData,
+                }],
+            );
+            let tx_aux = transaction_builder.build(
+                &balance.wallet_name,
+                passphrase,
+                outputs,
+                attributes,
+                UnspentTransactions::new(batch.to_vec()),
+                treasury_address.clone(),
+            )?;
+            wallet.broadcast_transaction(&tx_aux)?;
+
+            transactions.push(SweepTransaction {
+                wallet_name: balance.wallet_name.clone(),
+                transaction_id: tx_aux.tx_id(),
+                amount,
+                inputs: batch.len(),
+            });
+        }
+    }
+    Ok(transactions)
+}
+
+fn balances(
+    wallet: &AppWalletClient,
+    index: &AppIndex,
+    synchronizer: &AppSynchronizer,
+    wallet_names: &[String],
+    passphrase: &SecUtf8,
+) -> Result<Vec<WalletBalance>, Error> {
+    let mut balances = vec![];
+    for wallet_name in wallet_names.iter() {
+        sync_wallet(wallet, synchronizer, wallet_name, passphrase);
+
+        let address = payout_address(wallet, wallet_name, passphrase);
+        let unspent_transactions = index.unspent_transactions(&address)?;
+        balances.push(WalletBalance {
+            wallet_name: wallet_name.to_owned(),
+            address,
+            unspent_transactions,
+        });
+    }
+    Ok(balances)
+}
+
+fn sum_outputs(unspent_transactions: &[(TxoPointer, TxOut)]) -> Result<Coin, Error> {
+    unspent_transactions
+        .iter()
+        .try_fold(Coin::zero(), |total, (_, output)| total + output.value)
+        .map_err(|err| format_err!("{:?}", err))
+}
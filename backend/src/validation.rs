@@ -0,0 +1,419 @@
+use actix_web::dev::Payload;
+use actix_web::{web, Error as AWError, FromRequest, HttpRequest, HttpResponse};
+use futures::future::{self, Future};
+use std::ops::Deref;
+use std::str::FromStr;
+
+use chain_core::init::coin::Coin;
+use client_common::PublicKey;
+
+use crate::checkouts::parse_order_ids;
+use crate::config::{parse_address, parse_view_keys, MerchantConfig, Network};
+use crate::disputes::parse_party;
+use crate::milestones::parse_milestones;
+use crate::models::*;
+use crate::order_query::{
+    parse_cursor, parse_limit, parse_sort, parse_statuses, parse_timestamp, Sort,
+};
+use crate::participants::parse_participants;
+use crate::split::parse_split_party;
+
+/// Parameters of a request, `web::Form` or `web::Query`, whose fields were all checked before
+/// the handler runs. Every failing field is reported at once with `400 Invalid Request`.
+pub struct Valid<T>(pub T);
+impl<T> FromRequest for Valid<T>
+where
+    T: FromRequest + Deref + 'static,
+    T::Target: Validate,
+    T::Future: 'static,
+{
+    type Config = T::Config;
+    type Error = AWError;
+    type Future = Box<dyn Future<Item = Self, Error = Self::Error>>;
+
+    fn from_request(req: &HttpRequest, payload: &mut Payload) -> Self::Future {
+        let config = match web::Data::<MerchantConfig>::extract(req) {
+            Ok(config) => config,
+            Err(err) => return Box::new(future::err(err)),
+        };
+        Box::new(
+            T::from_request(req, payload)
+                .map_err(Into::into)
+                .and_then(move |params| {
+                    let mut validator = Validator::new(config.network);
+                    params.validate(&mut validator);
+                    if validator.errors.is_empty() {
+                        return Ok(Valid(params));
+                    }
+                    Err(AWError::from(
+                        HttpResponse::BadRequest().reason("Invalid Request").json(
+                            ValidationErrorResponse {
+                                errors: validator.errors,
+                            },
+                        ),
+                    ))
+                }),
+        )
+    }
+}
+
+/// Request parameters checked field by field
+pub trait Validate {
+    fn validate(&self, validator: &mut Validator);
+}
+
+/// Collects the failing fields of a request
+pub struct Validator {
+    network: Network,
+    errors: Vec<FieldError>,
+}
+impl Validator {
+    fn new(network: Network) -> Self {
+        Validator {
+            network,
+            errors: vec![],
+        }
+    }
+
+    fn check(&mut self, field: &'static str, valid: bool, reason: &'static str) {
+        if !valid {
+            self.errors.push(FieldError { field, reason });
+        }
+    }
+
+    /// Reports the reason of a failing parser of the field
+    fn parsed<T>(&mut self, field: &'static str, parsed: Result<T, &'static str>) {
+        if let Err(reason) = parsed {
+            self.errors.push(FieldError { field, reason });
+        }
+    }
+
+    fn required(&mut self, field: &'static str, value: &str) {
+        self.check(field, !value.trim().is_empty(), "Missing Value");
+    }
+
+    fn public_key(&mut self, field: &'static str, value: &str) {
+        self.check(
+            field,
+            PublicKey::from_str(value).is_ok(),
+            "Invalid Public Key",
+        );
+    }
+
+    fn view_key(&mut self, field: &'static str, value: &str) {
+        self.check(
+            field,
+            PublicKey::from_str(value).is_ok(),
+            "Invalid View Key",
+        );
+    }
+
+    /// Transfer address of the configured network
+    fn address(&mut self, field: &'static str, value: &str) {
+        self.check(
+            field,
+            parse_address(value, self.network).is_ok(),
+            "Invalid Address",
+        );
+    }
+
+    /// Amount in base units, up to the maximum supply
+    fn amount(&mut self, field: &'static str, value: &str) {
+        self.check(field, Coin::from_str(value).is_ok(), "Invalid Amount");
+    }
+
+    /// Hex encoding of exactly `length` bytes
+    fn hex(&mut self, field: &'static str, value: &str, length: usize, reason: &'static str) {
+        let valid = hex::decode(value)
+            .map(|bytes| bytes.len() == length)
+            .unwrap_or(false);
+        self.check(field, valid, reason);
+    }
+
+    /// Transaction IDs, digests and nonces of authentication challenges
+    fn id(&mut self, field: &'static str, value: &str) {
+        self.hex(field, value, 32, "Invalid ID");
+    }
+
+    /// Schnorr signature made with a single key
+    fn signature(&mut self, field: &'static str, value: &str) {
+        self.hex(field, value, 64, "Invalid Signature");
+    }
+}
+
+/// Optional field, where an empty value stands for a missing one
+pub fn given(value: &Option<String>) -> Option<&str> {
+    value
+        .as_ref()
+        .map(String::as_str)
+        .filter(|value| !value.is_empty())
+}
+
+impl Validate for AuthChallengeRequest {
+    fn validate(&self, validator: &mut Validator) {
+        validator.public_key("public_key", &self.public_key);
+    }
+}
+
+impl Validate for AuthVerifyRequest {
+    fn validate(&self, validator: &mut Validator) {
+        validator.public_key("public_key", &self.public_key);
+        validator.id("nonce", &self.nonce);
+        validator.signature("signature", &self.signature);
+    }
+}
+
+impl Validate for NewOrderRequest {
+    fn validate(&self, validator: &mut Validator) {
+        validator.required("order_id", &self.order_id);
+        let amount = Coin::from_str(&self.amount);
+        validator.check("amount", amount.is_ok(), "Invalid Amount");
+        if let Ok(amount) = amount.as_ref() {
+            // Delivering the order pays the deposit back to the buyer out of the amount
+            validator.check(
+                "amount",
+                u64::from(*amount) >= BUYER_DEPOSIT,
+                "Amount Below Deposit",
+            );
+        }
+        validator.public_key("buyer_public_key", &self.buyer_public_key);
+        validator.view_key("buyer_view_key", &self.buyer_view_key);
+        validator.address("buyer_address", &self.buyer_address);
+        if let Some(public_key) = given(&self.escrow_public_key) {
+            validator.public_key("escrow_public_key", public_key);
+        }
+        if let Some(view_key) = given(&self.escrow_view_key) {
+            validator.view_key("escrow_view_key", view_key);
+        }
+        if let Some(view_keys) = given(&self.extra_view_keys) {
+            validator.check(
+                "extra_view_keys",
+                parse_view_keys(view_keys).is_ok(),
+                "Invalid View Key",
+            );
+        }
+        if let Some(participants) = given(&self.participants) {
+            validator.parsed("participants", parse_participants(participants));
+        }
+        if let Some(payout_address) = given(&self.payout_address) {
+            validator.address("payout_address", payout_address);
+        }
+        if let Some(milestones) = given(&self.milestones) {
+            // Checked against the amount, whose own failure is already reported
+            if amount.is_ok() {
+                validator.parsed("milestones", parse_milestones(milestones, &self.amount));
+            }
+        }
+    }
+}
+
+impl Validate for PaymentProof {
+    fn validate(&self, validator: &mut Validator) {
+        validator.check(
+            "order_id",
+            given(&self.order_id).is_some() || given(&self.checkout_id).is_some(),
+            "Missing Order Or Checkout",
+        );
+        validator.id("transaction_id", &self.transaction_id);
+    }
+}
+
+impl Validate for NewCheckoutRequest {
+    fn validate(&self, validator: &mut Validator) {
+        validator.required("checkout_id", &self.checkout_id);
+        validator.parsed("order_ids", parse_order_ids(&self.order_ids));
+    }
+}
+
+impl Validate for CheckoutRequest {
+    fn validate(&self, validator: &mut Validator) {
+        validator.required("checkout_id", &self.checkout_id);
+    }
+}
+
+impl Validate for NewBatchRequest {
+    fn validate(&self, validator: &mut Validator) {
+        validator.parsed("order_ids", parse_order_ids(&self.order_ids));
+    }
+}
+
+impl Validate for OrderRequest {
+    fn validate(&self, validator: &mut Validator) {
+        validator.required("order_id", &self.order_id);
+    }
+}
+
+impl Validate for OrderQueryRequest {
+    fn validate(&self, validator: &mut Validator) {
+        if let Some(statuses) = given(&self.status) {
+            validator.parsed("status", parse_statuses(statuses));
+        }
+        if let Some(created_from) = given(&self.created_from) {
+            validator.parsed("created_from", parse_timestamp(created_from));
+        }
+        if let Some(created_to) = given(&self.created_to) {
+            validator.parsed("created_to", parse_timestamp(created_to));
+        }
+        if let Some(min_amount) = given(&self.min_amount) {
+            validator.amount("min_amount", min_amount);
+        }
+        if let Some(max_amount) = given(&self.max_amount) {
+            validator.amount("max_amount", max_amount);
+        }
+        let sort = given(&self.sort).map_or(Ok(Sort::default()), parse_sort);
+        validator.parsed("sort", sort);
+        // A cursor is checked against the sort, whose own failure is already reported
+        if let (Some(cursor), Ok(sort)) = (given(&self.cursor), sort) {
+            validator.parsed("cursor", parse_cursor(cursor, sort));
+        }
+        if let Some(limit) = given(&self.limit) {
+            validator.parsed("limit", parse_limit(limit));
+        }
+    }
+}
+
+impl Validate for RefundAddressRequest {
+    fn validate(&self, validator: &mut Validator) {
+        validator.required("order_id", &self.order_id);
+        validator.address("refund_address", &self.refund_address);
+        validator.signature("signature", &self.signature);
+    }
+}
+
+impl Validate for ProposeSplitRequest {
+    fn validate(&self, validator: &mut Validator) {
+        validator.required("order_id", &self.order_id);
+        validator.parsed("proposed_by", parse_split_party(&self.proposed_by));
+        validator.amount("merchant_amount", &self.merchant_amount);
+        validator.amount("buyer_amount", &self.buyer_amount);
+        if let Some(signature) = given(&self.signature) {
+            validator.signature("signature", signature);
+        }
+    }
+}
+
+impl Validate for AcceptSplitRequest {
+    fn validate(&self, validator: &mut Validator) {
+        validator.required("order_id", &self.order_id);
+        validator.parsed("accepted_by", parse_split_party(&self.accepted_by));
+        if let Some(signature) = given(&self.signature) {
+            validator.signature("signature", signature);
+        }
+    }
+}
+
+impl Validate for RulingRequest {
+    fn validate(&self, validator: &mut Validator) {
+        validator.required("order_id", &self.order_id);
+        validator.check(
+            "decision",
+            self.decision.parse::<RulingDecision>().is_ok(),
+            "Invalid Ruling Decision",
+        );
+        if let Some(merchant_amount) = given(&self.merchant_amount) {
+            validator.amount("merchant_amount", merchant_amount);
+        }
+        if let Some(buyer_amount) = given(&self.buyer_amount) {
+            validator.amount("buyer_amount", buyer_amount);
+        }
+        validator.signature("signature", &self.signature);
+    }
+}
+
+impl Validate for OpenDisputeRequest {
+    fn validate(&self, validator: &mut Validator) {
+        validator.required("order_id", &self.order_id);
+        validator.parsed("opened_by", parse_party(&self.opened_by));
+    }
+}
+
+impl Validate for DisputeRequest {
+    fn validate(&self, validator: &mut Validator) {
+        validator.check("case_id", self.case_id > 0, "Invalid Case ID");
+    }
+}
+
+impl Validate for DisputeMessageRequest {
+    fn validate(&self, validator: &mut Validator) {
+        validator.check("case_id", self.case_id > 0, "Invalid Case ID");
+        validator.parsed("author", parse_party(&self.author));
+        validator.required("body", &self.body);
+    }
+}
+
+impl Validate for DisputeEvidenceRequest {
+    fn validate(&self, validator: &mut Validator) {
+        validator.check("case_id", self.case_id > 0, "Invalid Case ID");
+        validator.parsed("submitted_by", parse_party(&self.submitted_by));
+        validator.required("file_name", &self.file_name);
+    }
+}
+
+impl Validate for EvidenceFileRequest {
+    fn validate(&self, validator: &mut Validator) {
+        validator.id("digest", &self.digest);
+    }
+}
+
+impl Validate for DisputeStatusRequest {
+    fn validate(&self, validator: &mut Validator) {
+        validator.check(
+            "status",
+            self.status.parse::<DisputeStatus>().is_ok(),
+            "Invalid Dispute Status",
+        );
+    }
+}
+
+impl Validate for ExchangeCommitmentRequest {
+    fn validate(&self, validator: &mut Validator) {
+        validator.required("order_id", &self.order_id);
+        validator.hex("commitment", &self.commitment, 32, "Invalid Commitment");
+        if let Some(ref public_key) = self.public_key {
+            validator.public_key("public_key", public_key);
+        }
+        if let Some(signers) = given(&self.signers) {
+            validator.check(
+                "signers",
+                signers
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|signer| !signer.is_empty())
+                    .all(|signer| PublicKey::from_str(signer).is_ok()),
+                "Invalid Public Key",
+            );
+        }
+    }
+}
+
+impl Validate for ExchangeNonceRequest {
+    fn validate(&self, validator: &mut Validator) {
+        validator.required("order_id", &self.order_id);
+        if let Some(ref public_key) = self.public_key {
+            validator.public_key("public_key", public_key);
+        }
+        validator.check(
+            "nonce",
+            PublicKey::from_str(&self.nonce).is_ok(),
+            "Invalid Nonce",
+        );
+    }
+}
+
+impl Validate for ConfirmRequest {
+    fn validate(&self, validator: &mut Validator) {
+        validator.required("order_id", &self.order_id);
+        if let Some(ref public_key) = self.public_key {
+            validator.public_key("public_key", public_key);
+        }
+        if let Some(ref nonce) = self.nonce {
+            validator.check("nonce", PublicKey::from_str(nonce).is_ok(), "Invalid Nonce");
+        }
+        validator.hex(
+            "partial_signature",
+            &self.partial_signature,
+            32,
+            "Invalid Partial Signature",
+        );
+    }
+}
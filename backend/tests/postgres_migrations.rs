@@ -0,0 +1,177 @@
+#![cfg(feature = "postgres")]
+
+#[macro_use]
+extern crate diesel;
+
+use diesel::connection::SimpleConnection;
+use diesel::pg::PgConnection;
+use diesel::prelude::*;
+use diesel::sql_query;
+use diesel::sql_types::{BigInt, Text};
+use rusqlite::NO_PARAMS;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::PathBuf;
+use uuid::Uuid;
+
+/// Type and nullability of the columns of a schema, by table and column name
+type Columns = BTreeMap<(String, String), (String, bool)>;
+
+#[derive(QueryableByName)]
+struct Column {
+    #[sql_type = "Text"]
+    table_name: String,
+    #[sql_type = "Text"]
+    column_name: String,
+    #[sql_type = "Text"]
+    data_type: String,
+    #[sql_type = "Text"]
+    is_nullable: String,
+}
+
+#[derive(QueryableByName)]
+struct Count {
+    #[sql_type = "BigInt"]
+    count: i64,
+}
+
+fn migrations(dir: &str) -> Vec<PathBuf> {
+    let root = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join(dir);
+    let mut migrations: Vec<PathBuf> = fs::read_dir(root)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.is_dir())
+        .collect();
+    migrations.sort();
+    migrations
+}
+
+fn read(migration: &PathBuf, direction: &str) -> String {
+    fs::read_to_string(migration.join(direction)).unwrap()
+}
+
+/// Connection to the database of `TEST_DATABASE_URL` in a schema of its own, created in a
+/// transaction that is never committed
+fn connection() -> PgConnection {
+    let database_url = std::env::var("TEST_DATABASE_URL").expect("TEST_DATABASE_URL must be set");
+    let conn = PgConnection::establish(&database_url).unwrap();
+    conn.begin_test_transaction().unwrap();
+    let schema = format!("test_{}", Uuid::new_v4().to_simple());
+    conn.batch_execute(&format!(
+        "CREATE SCHEMA {0}; SET LOCAL search_path TO {0};",
+        schema
+    ))
+    .unwrap();
+    conn
+}
+
+fn postgres_columns(conn: &PgConnection) -> Columns {
+    sql_query(
+        "SELECT table_name::TEXT, column_name::TEXT, data_type::TEXT, is_nullable::TEXT \
+         FROM information_schema.columns WHERE table_schema = current_schema()",
+    )
+    .load::<Column>(conn)
+    .unwrap()
+    .into_iter()
+    .map(|column| {
+        let data_type = match &column.data_type[..] {
+            "timestamp without time zone" => "TIMESTAMP".to_owned(),
+            data_type => data_type.to_uppercase(),
+        };
+        (
+            (column.table_name, column.column_name),
+            (data_type, column.is_nullable == "NO"),
+        )
+    })
+    .collect()
+}
+
+fn sqlite_columns() -> Columns {
+    let conn = rusqlite::Connection::open_in_memory().unwrap();
+    for migration in migrations("migrations") {
+        conn.execute_batch(&read(&migration, "up.sql")).unwrap();
+    }
+    let mut statement = conn
+        .prepare("SELECT name FROM sqlite_master WHERE type = 'table' AND name NOT LIKE 'sqlite_%'")
+        .unwrap();
+    let tables: Vec<String> = statement
+        .query_map(NO_PARAMS, |row| row.get(0))
+        .unwrap()
+        .map(Result::unwrap)
+        .collect();
+
+    let mut columns = Columns::new();
+    for table in tables {
+        let mut info = conn
+            .prepare(&format!("PRAGMA table_info({})", table))
+            .unwrap();
+        let rows = info
+            .query_map(NO_PARAMS, |row| {
+                let name: String = row.get(1);
+                let data_type: String = row.get(2);
+                let not_null: bool = row.get(3);
+                (name, data_type.to_uppercase(), not_null)
+            })
+            .unwrap();
+        for row in rows {
+            let (name, data_type, not_null) = row.unwrap();
+            columns.insert((table.clone(), name), (data_type, not_null));
+        }
+    }
+    columns
+}
+
+fn table_count(conn: &PgConnection) -> i64 {
+    sql_query(
+        "SELECT COUNT(*) AS count FROM information_schema.tables \
+         WHERE table_schema = current_schema()",
+    )
+    .get_result::<Count>(conn)
+    .unwrap()
+    .count
+}
+
+#[test]
+fn init_reverts() {
+    let conn = connection();
+    let migrations = migrations("migrations_postgres");
+
+    for migration in migrations.iter() {
+        conn.batch_execute(&read(migration, "up.sql")).unwrap();
+    }
+    assert!(table_count(&conn) > 0);
+    for migration in migrations.iter().rev() {
+        conn.batch_execute(&read(migration, "down.sql")).unwrap();
+    }
+    assert_eq!(table_count(&conn), 0);
+    for migration in migrations.iter() {
+        conn.batch_execute(&read(migration, "up.sql")).unwrap();
+    }
+}
+
+/// The squashed PostgreSQL migrations create the schema of the SQLite migrations
+#[test]
+fn schema_matches_sqlite_migrations() {
+    let conn = connection();
+    for migration in migrations("migrations_postgres") {
+        conn.batch_execute(&read(&migration, "up.sql")).unwrap();
+    }
+
+    let postgres = postgres_columns(&conn);
+    let sqlite = sqlite_columns();
+    let names: BTreeSet<&(String, String)> = postgres.keys().chain(sqlite.keys()).collect();
+    let differences: Vec<String> = names
+        .into_iter()
+        .filter(|name| postgres.get(*name) != sqlite.get(*name))
+        .map(|name| {
+            format!(
+                "{}.{}: postgres {:?}, sqlite {:?}",
+                name.0,
+                name.1,
+                postgres.get(name),
+                sqlite.get(name)
+            )
+        })
+        .collect();
+    assert!(differences.is_empty(), "{:#?}", differences);
+}
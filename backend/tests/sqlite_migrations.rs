@@ -0,0 +1,177 @@
+#![cfg(feature = "sqlite")]
+
+use rusqlite::{Connection, NO_PARAMS};
+use std::fs;
+use std::path::PathBuf;
+
+const TYPED_ORDERS: &str = "2020-01-28-000000_typed_orders";
+
+fn migrations() -> Vec<PathBuf> {
+    let root = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("migrations");
+    let mut migrations: Vec<PathBuf> = fs::read_dir(root)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.is_dir())
+        .collect();
+    migrations.sort();
+    migrations
+}
+
+fn run(conn: &Connection, migration: &PathBuf, direction: &str) {
+    let sql = fs::read_to_string(migration.join(direction)).unwrap();
+    conn.execute_batch(&sql)
+        .unwrap_or_else(|err| panic!("{} {}: {}", migration.display(), direction, err));
+}
+
+/// Database migrated up to the one before typed_orders, with an order of every untyped column
+fn seeded() -> (Connection, PathBuf) {
+    let conn = Connection::open_in_memory().unwrap();
+    let mut migrations = migrations().into_iter();
+    let typed_orders = loop {
+        let migration = migrations.next().expect("typed_orders migration");
+        if migration.ends_with(TYPED_ORDERS) {
+            break migration;
+        }
+        run(&conn, &migration, "up.sql");
+    };
+    conn.execute_batch(
+        "
+        INSERT INTO escrows (name, public_key, view_key, fee_address, fee, dispute_fee, status)
+        VALUES ('escrow', 'pk', 'vk', 'fee_address', '150bps', '5000', 'Active');
+        INSERT INTO orders (order_id, status, wallet_name, amount, buyer_public_key,
+            buyer_view_key, buyer_address, escrow_public_key, escrow_view_key, session_id,
+            payment_transaction_id, settlement_transaction_id, split_merchant_amount,
+            split_buyer_amount, escrow_id, escrow_fee_address, escrow_fee, escrow_dispute_fee,
+            milestone_amount)
+        VALUES ('1', 'Splitting', 'wallet', '10000000000', 'bpk', 'bvk', 'baddr', 'epk', 'evk',
+            '', 'tx', '', '6000000000', '4000000000', 1, 'fee_address', '150bps', '5000', '');
+        INSERT INTO order_events (order_id, event, detail, created_at)
+        VALUES ('1', 'Created', '', '2020-01-01 10:00:00'),
+            ('1', 'Splitting', '', '2020-01-02 11:30:00');
+        INSERT INTO milestones (order_id, sequence, amount, status)
+        VALUES ('1', 1, '4000000000', 'Pending'), ('1', 2, '6000000000', 'Pending');
+        INSERT INTO split_proposals (order_id, proposed_by, merchant_amount, buyer_amount,
+            signature, status)
+        VALUES ('1', 'Buyer', '6000000000', '4000000000', 'signature', 'Accepted');
+        ",
+    )
+    .unwrap();
+    (conn, typed_orders)
+}
+
+fn text(conn: &Connection, sql: &str) -> String {
+    conn.query_row(sql, NO_PARAMS, |row| row.get(0)).unwrap()
+}
+
+fn integer(conn: &Connection, sql: &str) -> i64 {
+    conn.query_row(sql, NO_PARAMS, |row| row.get(0)).unwrap()
+}
+
+fn assert_typed(conn: &Connection) {
+    assert_eq!(integer(conn, "SELECT amount FROM orders"), 10_000_000_000);
+    assert_eq!(
+        text(conn, "SELECT COALESCE(session_id, 'NULL') FROM orders"),
+        "NULL"
+    );
+    assert_eq!(
+        integer(conn, "SELECT split_buyer_amount FROM orders"),
+        4_000_000_000
+    );
+    assert_eq!(integer(conn, "SELECT escrow_fee FROM orders"), 0);
+    assert_eq!(
+        integer(conn, "SELECT escrow_fee_basis_points FROM orders"),
+        150
+    );
+    assert_eq!(integer(conn, "SELECT escrow_dispute_fee FROM orders"), 5000);
+    assert_eq!(
+        integer(conn, "SELECT escrow_dispute_fee_basis_points FROM orders"),
+        0
+    );
+    assert_eq!(
+        text(conn, "SELECT created_at FROM orders"),
+        "2020-01-01 10:00:00"
+    );
+    assert_eq!(
+        text(conn, "SELECT updated_at FROM orders"),
+        "2020-01-02 11:30:00"
+    );
+    assert_eq!(integer(conn, "SELECT fee_basis_points FROM escrows"), 150);
+    assert_eq!(integer(conn, "SELECT dispute_fee FROM escrows"), 5000);
+    assert_eq!(
+        integer(conn, "SELECT SUM(amount) FROM milestones"),
+        10_000_000_000
+    );
+    assert_eq!(
+        text(conn, "SELECT typeof(amount) FROM milestones LIMIT 1"),
+        "integer"
+    );
+    assert_eq!(
+        integer(conn, "SELECT merchant_amount FROM split_proposals"),
+        6_000_000_000
+    );
+}
+
+fn assert_untyped(conn: &Connection) {
+    assert_eq!(text(conn, "SELECT amount FROM orders"), "10000000000");
+    assert_eq!(text(conn, "SELECT session_id FROM orders"), "");
+    assert_eq!(
+        text(conn, "SELECT split_buyer_amount FROM orders"),
+        "4000000000"
+    );
+    assert_eq!(text(conn, "SELECT escrow_fee FROM orders"), "150bps");
+    assert_eq!(text(conn, "SELECT escrow_dispute_fee FROM orders"), "5000");
+    assert_eq!(text(conn, "SELECT fee FROM escrows"), "150bps");
+    assert_eq!(text(conn, "SELECT dispute_fee FROM escrows"), "5000");
+    assert_eq!(
+        text(conn, "SELECT amount FROM milestones WHERE sequence = 2"),
+        "6000000000"
+    );
+    assert_eq!(
+        text(conn, "SELECT buyer_amount FROM split_proposals"),
+        "4000000000"
+    );
+}
+
+#[test]
+fn typed_orders_round_trips() {
+    let (conn, typed_orders) = seeded();
+
+    run(&conn, &typed_orders, "up.sql");
+    assert_typed(&conn);
+    run(&conn, &typed_orders, "down.sql");
+    assert_untyped(&conn);
+    run(&conn, &typed_orders, "up.sql");
+    assert_typed(&conn);
+}
+
+#[test]
+fn typed_orders_touches_updated_at() {
+    let (conn, typed_orders) = seeded();
+    run(&conn, &typed_orders, "up.sql");
+
+    conn.execute("UPDATE orders SET status = 'Split'", NO_PARAMS)
+        .unwrap();
+    assert_ne!(
+        text(&conn, "SELECT updated_at FROM orders"),
+        "2020-01-02 11:30:00"
+    );
+}
+
+#[test]
+fn every_migration_reverts() {
+    let conn = Connection::open_in_memory().unwrap();
+    let migrations = migrations();
+    for migration in migrations.iter() {
+        run(&conn, migration, "up.sql");
+    }
+    for migration in migrations.iter().rev() {
+        run(&conn, migration, "down.sql");
+    }
+    assert_eq!(
+        integer(
+            &conn,
+            "SELECT COUNT(*) FROM sqlite_master WHERE name NOT LIKE 'sqlite_%'"
+        ),
+        0
+    );
+}
@@ -1,6 +1,8 @@
 import { Injectable } from '@angular/core';
 import { HttpClient, HttpHeaders, HttpParams } from '@angular/common/http';
-import { Observable, BehaviorSubject, interval } from 'rxjs';
+import { Observable, BehaviorSubject, EMPTY, interval } from 'rxjs';
+import { expand, reduce } from 'rxjs/operators';
+import { environment } from '../environments/environment';
 
 @Injectable({
   providedIn: 'root'
@@ -10,6 +12,7 @@ export class ApiService {
   public $outstandingOrders: BehaviorSubject<Order[]>;
   public $completedOrders: BehaviorSubject<Order[]>;
   private baseUrl = 'http://localhost:8080';
+  private authHeaders = { 'X-API-Key': environment.apiKey };
 
   constructor(private http: HttpClient) {
     this.$pendingOrders = new BehaviorSubject([]);
@@ -39,18 +42,32 @@ export class ApiService {
   }
 
   getPendingOrders(): Observable<Order[]> {
-    const url = `${this.baseUrl}/order/pending`;
-    return this.http.get<Order[]>(url);
+    return this.getAllOrders(`${this.baseUrl}/order/pending`);
   }
 
   getOutstandingOrders(): Observable<Order[]> {
-    const url = `${this.baseUrl}/order/outstanding`;
-    return this.http.get<Order[]>(url);
+    return this.getAllOrders(`${this.baseUrl}/order/outstanding`);
   }
 
   getCompletedOrders(): Observable<Order[]> {
-    const url = `${this.baseUrl}/order/completed`;
-    return this.http.get<Order[]>(url);
+    return this.getAllOrders(`${this.baseUrl}/order/completed`);
+  }
+
+  // Listing routes return a page at a time, followed through X-Next-Cursor
+  private getAllOrders(url: string): Observable<Order[]> {
+    const getPage = (cursor?: string) =>
+      this.http.get<Order[]>(url, {
+        headers: new HttpHeaders(this.authHeaders),
+        params: cursor ? { cursor } : {},
+        observe: 'response'
+      });
+    return getPage().pipe(
+      expand(res => {
+        const cursor = res.headers.get('X-Next-Cursor');
+        return cursor ? getPage(cursor) : EMPTY;
+      }),
+      reduce((orders: Order[], res) => orders.concat(res.body), [])
+    );
   }
 
   markDelivering(orderId: string): Observable<void> {
@@ -64,6 +81,7 @@ export class ApiService {
       }),
       {
         headers: new HttpHeaders({
+          ...this.authHeaders,
           'Content-Type': 'application/x-www-form-urlencoded'
         })
       }
@@ -81,6 +99,7 @@ export class ApiService {
       }),
       {
         headers: new HttpHeaders({
+          ...this.authHeaders,
           'Content-Type': 'application/x-www-form-urlencoded'
         })
       }
@@ -3,7 +3,9 @@
 // The list of file replacements can be found in `angular.json`.
 
 export const environment = {
-  production: false
+  production: false,
+  // Merchant staff API key of the backend, see STAFF_API_KEYS, set locally and never committed
+  apiKey: ''
 };
 
 /*